@@ -198,6 +198,19 @@ pub enum ValidationError {
     },
 }
 
+/// Position of a remote `ForkId` relative to the local fork, see [`ForkFilter::relation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ForkRelation {
+    /// Remote node is on the same fork as the local node.
+    Same,
+    /// Remote node already passed forks that are still upcoming locally.
+    RemoteAhead,
+    /// Remote node did not pass all forks that are already active locally.
+    RemoteBehind,
+    /// Remote fork hash is not part of the local fork schedule.
+    Unknown,
+}
+
 /// Filter that describes the state of blockchain and can be used to check incoming `ForkId`s for
 /// compatibility.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -384,6 +397,21 @@ impl ForkFilter {
         // 4) Reject in all other cases.
         Err(ValidationError::LocalIncompatibleOrStale { local: self.current(), remote: fork_id })
     }
+
+    /// Returns where the fork of the provided `ForkId` lies relative to the local fork.
+    ///
+    /// This does not check compatibility, see [`ForkFilter::validate`].
+    pub fn relation(&self, fork_id: ForkId) -> ForkRelation {
+        if self.current().hash == fork_id.hash {
+            ForkRelation::Same
+        } else if self.cache.past.iter().any(|(_, hash)| *hash == fork_id.hash) {
+            ForkRelation::RemoteBehind
+        } else if self.cache.future.contains(&fork_id.hash) {
+            ForkRelation::RemoteAhead
+        } else {
+            ForkRelation::Unknown
+        }
+    }
 }
 
 /// Represents a transition from one fork to another
@@ -676,6 +704,44 @@ mod tests {
         assert_eq!(filter.validate(remote), Ok(()));
     }
 
+    #[test]
+    fn fork_relation() {
+        let filter = ForkFilter::new(
+            Head { number: 4_370_000, ..Default::default() },
+            GENESIS_HASH,
+            0,
+            vec![
+                ForkFilterKey::Block(1_150_000),
+                ForkFilterKey::Block(1_920_000),
+                ForkFilterKey::Block(2_463_000),
+                ForkFilterKey::Block(2_675_000),
+                ForkFilterKey::Block(4_370_000),
+                ForkFilterKey::Block(7_280_000),
+            ],
+        );
+
+        // Local is mainnet Byzantium.
+        assert_eq!(
+            filter.relation(ForkId { hash: ForkHash(hex!("a00bc324")), next: 7_280_000 }),
+            ForkRelation::Same
+        );
+        // Remote is in Homestead.
+        assert_eq!(
+            filter.relation(ForkId { hash: ForkHash(hex!("97c2c34c")), next: 1_920_000 }),
+            ForkRelation::RemoteBehind
+        );
+        // Remote is in Petersburg.
+        assert_eq!(
+            filter.relation(ForkId { hash: ForkHash(hex!("668db0af")), next: 0 }),
+            ForkRelation::RemoteAhead
+        );
+        // Remote is on another chain.
+        assert_eq!(
+            filter.relation(ForkId { hash: ForkHash(hex!("afec6b27")), next: 0 }),
+            ForkRelation::Unknown
+        );
+    }
+
     #[test]
     fn forkid_serialization() {
         assert_eq!(
@@ -724,6 +790,50 @@ mod tests {
         assert_eq!(alloy_rlp::encode(id), &val[..]);
     }
 
+    #[test]
+    fn mainnet_fork_id_rlp_roundtrip() {
+        // mainnet fork ids of EIP-2124
+        let frontier = ForkId { hash: ForkHash(hex!("fc64ec04")), next: 1_150_000 };
+        let homestead = ForkId { hash: ForkHash(hex!("97c2c34c")), next: 1_920_000 };
+        let dao = ForkId { hash: ForkHash(hex!("91d1f948")), next: 2_463_000 };
+        let tangerine = ForkId { hash: ForkHash(hex!("7a64da13")), next: 2_675_000 };
+
+        assert_eq!(ForkHash::from(GENESIS_HASH), frontier.hash);
+        assert_eq!(frontier.hash + frontier.next, homestead.hash);
+        assert_eq!(homestead.hash + homestead.next, dao.hash);
+        assert_eq!(dao.hash + dao.next, tangerine.hash);
+
+        for id in [frontier, homestead, dao, tangerine] {
+            let encoded = alloy_rlp::encode(id);
+            assert_eq!(encoded.len(), id.length());
+            assert_eq!(ForkId::decode(&mut &encoded[..]).unwrap(), id);
+        }
+
+        assert_eq!(alloy_rlp::encode(homestead), hex!("c98497c2c34c831d4c00"));
+    }
+
+    #[test]
+    fn fork_id_rlp_rejects_malformed() {
+        let malformed: [&[u8]; 6] = [
+            // not a list
+            &hex!("8400000000"),
+            // 3 byte hash
+            &hex!("c58300000080"),
+            // 5 byte hash
+            &hex!("c785000000000080"),
+            // next exceeds u64
+            &hex!("cf84ffffffff89ffffffffffffffffff"),
+            // next with leading zero
+            &hex!("c884000000008200ff"),
+            // trailing list element
+            &hex!("c784000000008080"),
+        ];
+
+        for buf in malformed {
+            assert!(ForkId::decode(&mut &buf[..]).is_err(), "decoded {}", hex::encode(buf));
+        }
+    }
+
     #[test]
     fn compute_cache() {
         let b1 = 1_150_000;
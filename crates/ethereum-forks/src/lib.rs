@@ -25,7 +25,8 @@ mod hardforks;
 mod head;
 
 pub use forkid::{
-    EnrForkIdEntry, ForkFilter, ForkFilterKey, ForkHash, ForkId, ForkTransition, ValidationError,
+    EnrForkIdEntry, ForkFilter, ForkFilterKey, ForkHash, ForkId, ForkRelation, ForkTransition,
+    ValidationError,
 };
 pub use hardfork::{EthereumHardfork, Hardfork, DEV_HARDFORKS};
 pub use head::Head;
@@ -5,6 +5,7 @@
 
 use alloy_primitives::bytes::Bytes;
 use alloy_rlp::Encodable;
+use discv5::kbucket::MAX_NODES_PER_BUCKET;
 use reth_net_banlist::BanList;
 use reth_net_nat::{NatResolver, ResolveNatInterval};
 use reth_network_peers::NodeRecord;
@@ -27,6 +28,9 @@ pub struct Discv4Config {
     pub udp_ingress_message_buffer: usize,
     /// The number of allowed failures for `FindNode` requests. Default: 5.
     pub max_find_node_failures: u8,
+    /// The number of consecutive re-pings a node may fail to answer before it's evicted from the
+    /// table. Default: 3.
+    pub max_ping_failures: u8,
     /// The interval to use when checking for expired nodes that need to be re-pinged. Default:
     /// 10min.
     pub ping_interval: Duration,
@@ -68,6 +72,12 @@ pub struct Discv4Config {
     pub resolve_external_ip_interval: Option<Duration>,
     /// The duration after which we consider a bond expired.
     pub bond_expiration: Duration,
+    /// The maximum number of nodes kept in a single k-bucket of the routing table. Values larger
+    /// than [`MAX_NODES_PER_BUCKET`] are capped. Default: 16.
+    pub bucket_size: usize,
+    /// The maximum number of replacement candidates cached per k-bucket while the bucket is full.
+    /// Default: 10.
+    pub replacement_cache_size: usize,
 }
 
 impl Discv4Config {
@@ -118,6 +128,7 @@ impl Default for Discv4Config {
             // Every outgoing request will eventually lead to an incoming response
             udp_ingress_message_buffer: 1024,
             max_find_node_failures: 5,
+            max_ping_failures: 3,
             ping_interval: Duration::from_secs(60 * 10),
             // Unified expiration and timeout durations, mirrors geth's `expiration` duration
             ping_expiration: Duration::from_secs(20),
@@ -138,6 +149,9 @@ impl Default for Discv4Config {
             external_ip_resolver: Some(Default::default()),
             // By default retry public IP using a 5min interval
             resolve_external_ip_interval: Some(Duration::from_secs(60 * 5)),
+            bucket_size: MAX_NODES_PER_BUCKET,
+            // mirrors geth's `maxReplacements`
+            replacement_cache_size: 10,
         }
     }
 }
@@ -174,6 +188,12 @@ impl Discv4ConfigBuilder {
         self
     }
 
+    /// The number of consecutive re-pings a node may fail to answer before it's evicted.
+    pub fn max_ping_failures(&mut self, max_ping_failures: u8) -> &mut Self {
+        self.config.max_ping_failures = max_ping_failures;
+        self
+    }
+
     /// The time between pings to ensure connectivity amongst connected nodes.
     pub fn ping_interval(&mut self, interval: Duration) -> &mut Self {
         self.config.ping_interval = interval;
@@ -307,6 +327,20 @@ impl Discv4ConfigBuilder {
         self
     }
 
+    /// Sets the maximum number of nodes per k-bucket.
+    ///
+    /// This is capped at [`MAX_NODES_PER_BUCKET`].
+    pub fn bucket_size(&mut self, bucket_size: usize) -> &mut Self {
+        self.config.bucket_size = bucket_size.min(MAX_NODES_PER_BUCKET);
+        self
+    }
+
+    /// Sets the maximum number of replacement candidates cached per full k-bucket.
+    pub fn replacement_cache_size(&mut self, replacement_cache_size: usize) -> &mut Self {
+        self.config.replacement_cache_size = replacement_cache_size;
+        self
+    }
+
     /// Returns the configured [`Discv4Config`]
     pub fn build(&self) -> Discv4Config {
         self.config.clone()
@@ -327,6 +361,8 @@ mod tests {
             .ban_duration(None)
             .lookup_interval(Duration::from_secs(3))
             .enable_lookup(true)
+            .bucket_size(8)
+            .replacement_cache_size(4)
             .build();
     }
 }
@@ -82,7 +82,7 @@ pub use reth_network_peers::NodeRecord;
 #[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
 
-use crate::table::PongTable;
+use crate::table::{PongTable, ReplacementCache};
 use reth_net_nat::ResolveNatInterval;
 /// reexport to get public ip.
 pub use reth_net_nat::{external_ip, NatResolver};
@@ -410,6 +410,17 @@ impl Discv4 {
         Ok(rx.await?)
     }
 
+    /// Returns the EIP-868 [`Enr`] of the local node.
+    ///
+    /// Its sequence number is incremented whenever one of its pairs is updated, e.g. via
+    /// [`Discv4::set_eip868_rlp`].
+    pub async fn local_enr(&self) -> Result<Enr<SecretKey>, Discv4Error> {
+        let (tx, rx) = oneshot::channel();
+        let cmd = Discv4Command::LocalEnr(tx);
+        self.to_service.send(cmd)?;
+        Ok(rx.await?)
+    }
+
     /// Terminates the spawned [`Discv4Service`].
     pub fn terminate(&self) {
         self.send_to_service(Discv4Command::Terminated);
@@ -488,6 +499,8 @@ pub struct Discv4Service {
     queued_events: VecDeque<Discv4Event>,
     /// Keeps track of nodes from which we have received a `Pong` message.
     received_pongs: PongTable,
+    /// Candidates for k-buckets that were full when the candidate was discovered.
+    replacements: ReplacementCache,
     /// Interval used to expire additionally tracked nodes
     expire_interval: Interval,
 }
@@ -586,6 +599,7 @@ impl Discv4Service {
             evict_expired_requests_interval,
             lookup_rotator,
             resolve_external_ip_interval: config.resolve_external_ip_interval(),
+            replacements: ReplacementCache::new(config.replacement_cache_size),
             config,
             queued_events: Default::default(),
             received_pongs: Default::default(),
@@ -842,6 +856,50 @@ impl Discv4Service {
         self.kbuckets.buckets_iter().fold(0, |count, bucket| count + bucket.num_connected())
     }
 
+    /// Returns the log2 distance of the node to the local node, which identifies its k-bucket.
+    fn bucket_index(&self, key: &discv5::Key<NodeKey>) -> Option<u64> {
+        kad_key(self.local_node_record.id).log2_distance(key)
+    }
+
+    /// Returns true if the k-bucket of the given node holds [`Discv4Config::bucket_size`] nodes.
+    fn is_bucket_full(&self, key: &discv5::Key<NodeKey>) -> bool {
+        self.kbuckets
+            .get_bucket(key)
+            .is_some_and(|bucket| bucket.num_entries() >= self.config.bucket_size)
+    }
+
+    /// Invoked when a new node doesn't fit into its k-bucket.
+    ///
+    /// Following the Kademlia replacement policy, the node is kept as a replacement candidate and
+    /// the least-recently seen node of the bucket is pinged. If that node responds the candidate
+    /// is discarded, otherwise the unresponsive node is evicted and replaced by the candidate.
+    fn on_bucket_full(&mut self, record: NodeRecord) {
+        let key = kad_key(record.id);
+        let Some(bucket) = self.bucket_index(&key) else { return };
+        let Some(least_recently_seen) = self
+            .kbuckets
+            .get_bucket(&key)
+            .and_then(|bucket| bucket.iter().next())
+            .map(|node| node.value.record)
+        else {
+            return
+        };
+
+        self.replacements.insert(bucket, record);
+        trace!(target: "discv4", ?record, ?least_recently_seen, candidates=%self.replacements.len(bucket), "bucket is full, checking least-recently seen node");
+        self.try_ping(least_recently_seen, PingReason::EvictionCheck(record));
+    }
+
+    /// Fills the slot of a node that was evicted from its k-bucket with the most recently
+    /// discovered replacement candidate of that bucket, if any.
+    fn insert_replacement(&mut self, evicted: PeerId) {
+        let Some(bucket) = self.bucket_index(&kad_key(evicted)) else { return };
+        if let Some(candidate) = self.replacements.pop(bucket) {
+            trace!(target: "discv4", ?evicted, ?candidate, "replacing evicted node");
+            self.add_node(candidate);
+        }
+    }
+
     /// Check if the peer has a bond
     fn has_bond(&self, remote_id: PeerId, remote_ip: IpAddr) -> bool {
         if let Some(timestamp) = self.received_pongs.last_pong(remote_id, remote_ip) {
@@ -960,7 +1018,12 @@ impl Discv4Service {
     /// already in the table or the record's bucket is full.
     pub fn add_node(&mut self, record: NodeRecord) -> bool {
         let key = kad_key(record.id);
+        let bucket_full = self.is_bucket_full(&key);
         match self.kbuckets.entry(&key) {
+            kbucket::Entry::Absent(_) if bucket_full => {
+                self.on_bucket_full(record);
+                return false
+            }
             kbucket::Entry::Absent(entry) => {
                 let node = NodeEntry::new(record);
                 match entry.insert(
@@ -973,6 +1036,10 @@ impl Discv4Service {
                     BucketInsertResult::Inserted | BucketInsertResult::Pending { .. } => {
                         trace!(target: "discv4", ?record, "inserted new record");
                     }
+                    BucketInsertResult::Full => {
+                        self.on_bucket_full(record);
+                        return false
+                    }
                     _ => return false,
                 }
             }
@@ -1025,6 +1092,7 @@ impl Discv4Service {
         let mut is_new_insert = false;
         let mut needs_bond = false;
         let mut is_proven = false;
+        let bucket_full = self.is_bucket_full(&key);
 
         let old_enr = match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, _) => {
@@ -1039,14 +1107,19 @@ impl Discv4Service {
                 let mut node = NodeEntry::new(record);
                 node.last_enr_seq = ping.enr_sq;
 
-                match entry.insert(
-                    node,
-                    NodeStatus {
-                        direction: ConnectionDirection::Incoming,
-                        // mark as disconnected until endpoint proof established on pong
-                        state: ConnectionState::Disconnected,
-                    },
-                ) {
+                let res = if bucket_full {
+                    BucketInsertResult::Full
+                } else {
+                    entry.insert(
+                        node,
+                        NodeStatus {
+                            direction: ConnectionDirection::Incoming,
+                            // mark as disconnected until endpoint proof established on pong
+                            state: ConnectionState::Disconnected,
+                        },
+                    )
+                };
+                match res {
                     BucketInsertResult::Inserted | BucketInsertResult::Pending { .. } => {
                         // mark as new insert if insert was successful
                         is_new_insert = true;
@@ -1057,6 +1130,7 @@ impl Discv4Service {
                         // to emit an event that we discovered the node
                         trace!(target: "discv4", ?record, "discovered new record but bucket is full");
                         self.notify(DiscoveryUpdate::DiscoveredAtCapacity(record));
+                        self.on_bucket_full(record);
                         needs_bond = true;
                     }
                     BucketInsertResult::TooManyIncoming | BucketInsertResult::NodeExists => {
@@ -1210,6 +1284,17 @@ impl Discv4Service {
             PingReason::RePing => {
                 self.update_on_reping(node, pong.enr_sq);
             }
+            PingReason::EvictionCheck(candidate) => {
+                // the least-recently seen node is still alive: it is moved to the tail of its
+                // bucket and the candidate is discarded
+                self.update_on_pong(node, pong.enr_sq);
+                let key = kad_key(node.id);
+                let _ = self.kbuckets.update_node_status(&key, ConnectionState::Connected, None);
+                if let Some(bucket) = self.bucket_index(&key) {
+                    self.replacements.remove(bucket, &candidate.id);
+                }
+                trace!(target: "discv4", ?node, ?candidate, "discarded replacement candidate");
+            }
             PingReason::Lookup(node, ctx) => {
                 self.update_on_pong(node, pong.enr_sq);
                 // insert node and assoc. lookup_context into the pending_lookup table to complete
@@ -1356,6 +1441,7 @@ impl Discv4Service {
 
         for closest in closest {
             let key = kad_key(closest.id);
+            let bucket_full = self.is_bucket_full(&key);
             match self.kbuckets.entry(&key) {
                 BucketEntry::Absent(entry) => {
                     // the node's endpoint is not proven yet, so we need to ping it first, on
@@ -1365,20 +1451,26 @@ impl Discv4Service {
                     // while the ping is still active, we always mark it as queried.
                     ctx.mark_queried(closest.id);
                     let node = NodeEntry::new(closest);
-                    match entry.insert(
-                        node,
-                        NodeStatus {
-                            direction: ConnectionDirection::Outgoing,
-                            state: ConnectionState::Disconnected,
-                        },
-                    ) {
+                    let res = if bucket_full {
+                        BucketInsertResult::Full
+                    } else {
+                        entry.insert(
+                            node,
+                            NodeStatus {
+                                direction: ConnectionDirection::Outgoing,
+                                state: ConnectionState::Disconnected,
+                            },
+                        )
+                    };
+                    match res {
                         BucketInsertResult::Inserted | BucketInsertResult::Pending { .. } => {
                             // only ping if the node was added to the table
                             self.try_ping(closest, PingReason::Lookup(closest, ctx.clone()))
                         }
                         BucketInsertResult::Full => {
                             // new node but the node's bucket is already full
-                            self.notify(DiscoveryUpdate::DiscoveredAtCapacity(closest))
+                            self.notify(DiscoveryUpdate::DiscoveredAtCapacity(closest));
+                            self.on_bucket_full(closest);
                         }
                         _ => {}
                     }
@@ -1423,19 +1515,46 @@ impl Discv4Service {
         });
 
         let mut failed_pings = Vec::new();
+        let mut failed_re_pings = Vec::new();
         self.pending_pings.retain(|node_id, ping_request| {
             if now.duration_since(ping_request.sent_at) > self.config.ping_expiration {
-                failed_pings.push(*node_id);
+                if matches!(ping_request.reason, PingReason::RePing) {
+                    failed_re_pings.push(*node_id);
+                } else {
+                    failed_pings.push(*node_id);
+                }
                 return false
             }
             true
         });
 
+        // nodes that were already proven are only evicted once they failed to answer multiple
+        // consecutive re-pings
+        for node_id in failed_re_pings {
+            let key = kad_key(node_id);
+            let failures = match self.kbuckets.entry(&key) {
+                kbucket::Entry::Present(mut entry, _) => {
+                    entry.value_mut().inc_failed_ping();
+                    entry.value().ping_failures
+                }
+                kbucket::Entry::Pending(mut entry, _) => {
+                    entry.value().inc_failed_ping();
+                    entry.value().ping_failures
+                }
+                _ => continue,
+            };
+            if failures >= self.config.max_ping_failures as usize {
+                failed_pings.push(node_id);
+            }
+        }
+
         trace!(target: "discv4", num=%failed_pings.len(), "evicting nodes due to failed pong");
 
-        // remove nodes that failed to pong
+        // remove nodes that failed to pong and fill their slots with replacement candidates
         for node_id in failed_pings {
-            self.remove_node(node_id);
+            if self.remove_node(node_id) {
+                self.insert_replacement(node_id);
+            }
         }
 
         let mut failed_lookups = Vec::new();
@@ -1492,7 +1611,7 @@ impl Discv4Service {
             // least half full)
             if failures > (self.config.max_find_node_failures as usize) {
                 if let Some(bucket) = self.kbuckets.get_bucket(&key) {
-                    if bucket.num_entries() < MAX_NODES_PER_BUCKET / 2 {
+                    if bucket.num_entries() < self.config.bucket_size / 2 {
                         // skip half empty bucket
                         continue
                     }
@@ -1505,7 +1624,8 @@ impl Discv4Service {
     /// Re-pings all nodes which endpoint proofs are considered expired: [`NodeEntry::is_expired`]
     ///
     /// This will send a `Ping` to the nodes, if a node fails to respond with a `Pong` to renew the
-    /// endpoint proof it will be removed from the table.
+    /// endpoint proof [`Discv4Config::max_ping_failures`] times in a row, it will be removed from
+    /// the table.
     fn re_ping_oldest(&mut self) {
         let mut nodes = self
             .kbuckets
@@ -1624,6 +1744,9 @@ impl Discv4Service {
                         let rx = self.update_stream();
                         let _ = tx.send(rx);
                     }
+                    Discv4Command::LocalEnr(tx) => {
+                        let _ = tx.send(self.local_eip_868_enr.clone());
+                    }
                     Discv4Command::BanPeer(node_id) => self.ban_node(node_id),
                     Discv4Command::Remove(node_id) => {
                         self.remove_node(node_id);
@@ -1938,6 +2061,7 @@ enum Discv4Command {
     Lookup { node_id: Option<PeerId>, tx: Option<NodeRecordSender> },
     SetLookupInterval(Duration),
     Updates(OneshotSender<ReceiverStream<DiscoveryUpdate>>),
+    LocalEnr(OneshotSender<Enr<SecretKey>>),
     Terminated,
 }
 
@@ -2191,6 +2315,8 @@ struct NodeEntry {
     fork_id: Option<ForkId>,
     /// Counter for failed findNode requests.
     find_node_failures: usize,
+    /// Counter for consecutive re-pings that weren't answered.
+    ping_failures: usize,
     /// Whether the endpoint of the peer is proven.
     has_endpoint_proof: bool,
 }
@@ -2206,6 +2332,7 @@ impl NodeEntry {
             last_enr_seq: None,
             fork_id: None,
             find_node_failures: 0,
+            ping_failures: 0,
             has_endpoint_proof: false,
         }
     }
@@ -2227,17 +2354,23 @@ impl NodeEntry {
         self.find_node_failures += 1;
     }
 
+    /// Increases the failed re-ping counter
+    fn inc_failed_ping(&mut self) {
+        self.ping_failures += 1;
+    }
+
     /// Updates the last timestamp and sets the enr seq
     fn update_with_fork_id(&mut self, fork_id: Option<ForkId>) -> Option<ForkId> {
         self.update_now(|s| std::mem::replace(&mut s.fork_id, fork_id))
     }
 
-    /// Updates the `last_seen` timestamp and calls the closure
+    /// Updates the `last_seen` timestamp, resets the failed re-ping counter and calls the closure
     fn update_now<F, R>(&mut self, f: F) -> R
     where
         F: FnOnce(&mut Self) -> R,
     {
         self.last_seen = Instant::now();
+        self.ping_failures = 0;
         f(self)
     }
 }
@@ -2263,6 +2396,9 @@ enum PingReason {
     RePing,
     /// Part of a lookup to ensure endpoint is proven before we can send a `FindNode` request.
     Lookup(NodeRecord, LookupContext),
+    /// Ping the least-recently seen node of a full bucket to check whether it should be replaced
+    /// by the given candidate.
+    EvictionCheck(NodeRecord),
 }
 
 /// Represents node related updates state changes in the underlying node table
@@ -2283,13 +2419,15 @@ pub enum DiscoveryUpdate {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::{create_discv4, create_discv4_with_config, rng_endpoint, rng_record};
+    use crate::test_utils::{
+        create_discv4, create_discv4_with_config, rng_endpoint, rng_ipv6_record, rng_record,
+    };
     use alloy_primitives::hex;
     use alloy_rlp::{Decodable, Encodable};
     use rand::{thread_rng, Rng};
     use reth_ethereum_forks::{EnrForkIdEntry, ForkHash};
     use reth_network_peers::mainnet_nodes;
-    use std::future::poll_fn;
+    use std::{collections::HashSet, future::poll_fn};
 
     #[tokio::test]
     async fn test_configured_enr_forkid_entry() {
@@ -2567,6 +2705,82 @@ mod tests {
         assert_eq!(service.pending_find_nodes.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_neighbours_split_across_packets() {
+        reth_tracing::init_test_tracing();
+
+        let config = Discv4Config::builder().build();
+        let (_discv4, mut responder) = create_discv4_with_config(config.clone()).await;
+        let (_discv4, mut requester) = create_discv4_with_config(config).await;
+
+        // a full bucket of IPv6 records doesn't fit into a single packet
+        let mut rng = thread_rng();
+        while responder.kbuckets.iter().count() < MAX_NODES_PER_BUCKET {
+            let record = rng_ipv6_record(&mut rng);
+            let _ = responder.kbuckets.insert_or_update(
+                &kad_key(record.id),
+                NodeEntry::new_proven(record),
+                NodeStatus {
+                    direction: ConnectionDirection::Incoming,
+                    state: ConnectionState::Connected,
+                },
+            );
+        }
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = PeerId::random();
+        responder.respond_closest(target, socket.local_addr().unwrap());
+
+        let mut packets = Vec::new();
+        let mut buf = [0; 2 * MAX_PACKET_SIZE];
+        while packets.iter().map(|msg: &Neighbours| msg.nodes.len()).sum::<usize>() <
+            MAX_NODES_PER_BUCKET
+        {
+            let (len, _) = socket.recv_from(&mut buf).await.unwrap();
+            assert!(len <= MAX_PACKET_SIZE, "{len}");
+            let packet = Message::decode(&buf[..len]).unwrap();
+            assert_eq!(packet.node_id, *responder.local_peer_id());
+            let Message::Neighbours(msg) = packet.msg else { panic!("{:?}", packet.msg) };
+            packets.push(msg);
+        }
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].nodes.len(), SAFE_MAX_DATAGRAM_NEIGHBOUR_RECORDS);
+        assert_eq!(
+            packets[1].nodes.len(),
+            MAX_NODES_PER_BUCKET - SAFE_MAX_DATAGRAM_NEIGHBOUR_RECORDS
+        );
+
+        // the requester accumulates both packets for the same `FindNode` request
+        let responder_id = *responder.local_peer_id();
+        let responder_addr = responder.local_addr();
+        let ctx = LookupContext::new(kad_key(target), std::iter::empty(), None);
+        requester.pending_find_nodes.insert(responder_id, FindNodeRequest::new(ctx.clone()));
+
+        let mut packets = packets.into_iter();
+        requester.on_neighbours(packets.next().unwrap(), responder_addr, responder_id);
+        assert_eq!(
+            requester.pending_find_nodes[&responder_id].response_count,
+            SAFE_MAX_DATAGRAM_NEIGHBOUR_RECORDS
+        );
+        requester.on_neighbours(packets.next().unwrap(), responder_addr, responder_id);
+        // the request is complete after a full bucket of records
+        assert!(!requester.pending_find_nodes.contains_key(&responder_id));
+
+        let received = ctx
+            .inner
+            .closest_nodes
+            .borrow()
+            .values()
+            .map(|node| node.record.id)
+            .collect::<HashSet<_>>();
+        let expected = responder
+            .kbuckets
+            .closest_values(&kad_key(target))
+            .map(|node| node.key.preimage().0)
+            .collect::<HashSet<_>>();
+        assert_eq!(received, expected);
+    }
+
     #[tokio::test]
     async fn test_no_local_in_closest() {
         reth_tracing::init_test_tracing();
@@ -2825,6 +3039,140 @@ mod tests {
         }
     }
 
+    /// Returns two records that belong into the same bucket of the service's table.
+    fn same_bucket_records(service: &Discv4Service) -> (NodeRecord, NodeRecord) {
+        let local_addr = service.local_addr();
+        let first = NodeRecord::new(local_addr, PeerId::random());
+        let bucket = service.bucket_index(&kad_key(first.id));
+        loop {
+            let second = NodeRecord::new(local_addr, PeerId::random());
+            if service.bucket_index(&kad_key(second.id)) == bucket {
+                return (first, second)
+            }
+        }
+    }
+
+    /// Returns true if the node is present in the service's table.
+    fn in_table(service: &Discv4Service, id: PeerId) -> bool {
+        let key = kad_key(id);
+        service.kbuckets.get_bucket(&key).and_then(|bucket| bucket.get(&key)).is_some()
+    }
+
+    /// Completes the initial endpoint proof for the given record.
+    fn pong_from(service: &mut Discv4Service, record: NodeRecord) {
+        let echo = service.pending_pings[&record.id].echo_hash;
+        let pong =
+            Pong { to: record.into(), echo, expire: service.ping_expiration(), enr_sq: None };
+        service.on_pong(pong, record.udp_addr(), record.id);
+    }
+
+    #[tokio::test]
+    async fn test_full_bucket_keeps_responsive_node() {
+        let config = Discv4Config::builder().bucket_size(1).build();
+        let (_discv4, mut service) = create_discv4_with_config(config).await;
+        let (lru, candidate) = same_bucket_records(&service);
+
+        assert!(service.add_node(lru));
+        pong_from(&mut service, lru);
+        assert!(!service.pending_pings.contains_key(&lru.id));
+
+        // bucket is full: the candidate is cached and the least-recently seen node is pinged
+        assert!(!service.add_node(candidate));
+        assert!(matches!(
+            service.pending_pings[&lru.id].reason,
+            PingReason::EvictionCheck(record) if record == candidate
+        ));
+        let bucket = service.bucket_index(&kad_key(candidate.id)).unwrap();
+        assert_eq!(service.replacements.len(bucket), 1);
+
+        // the node responds, so the candidate is discarded
+        pong_from(&mut service, lru);
+        assert!(in_table(&service, lru.id));
+        assert!(!in_table(&service, candidate.id));
+        assert_eq!(service.replacements.len(bucket), 0);
+
+        // the candidate is not inserted even after the ping timeout passes
+        service.evict_expired_requests(Instant::now() + service.config.ping_expiration * 2);
+        assert!(in_table(&service, lru.id));
+        assert!(!in_table(&service, candidate.id));
+    }
+
+    #[tokio::test]
+    async fn test_full_bucket_replaces_unresponsive_node() {
+        let config = Discv4Config::builder().bucket_size(1).build();
+        let (_discv4, mut service) = create_discv4_with_config(config).await;
+        let (lru, candidate) = same_bucket_records(&service);
+
+        assert!(service.add_node(lru));
+        pong_from(&mut service, lru);
+
+        assert!(!service.add_node(candidate));
+        assert!(service.pending_pings.contains_key(&lru.id));
+
+        // the node fails to respond in time, so it's evicted in favor of the candidate
+        service.evict_expired_requests(Instant::now() + service.config.ping_expiration * 2);
+        assert!(!in_table(&service, lru.id));
+        assert!(in_table(&service, candidate.id));
+        assert!(matches!(service.pending_pings[&candidate.id].reason, PingReason::InitialInsert));
+        let bucket = service.bucket_index(&kad_key(candidate.id)).unwrap();
+        assert_eq!(service.replacements.len(bucket), 0);
+    }
+
+    #[tokio::test]
+    async fn test_re_ping_evicts_unresponsive_nodes() {
+        let config = Discv4Config::builder().max_ping_failures(3).build();
+        let (_discv4, mut service) = create_discv4_with_config(config).await;
+        let local_addr = service.local_addr();
+
+        let responsive: Vec<_> =
+            (0..3).map(|_| NodeRecord::new(local_addr, PeerId::random())).collect();
+        let unresponsive: Vec<_> =
+            (0..3).map(|_| NodeRecord::new(local_addr, PeerId::random())).collect();
+        for node in responsive.iter().chain(&unresponsive) {
+            assert!(service.add_node(*node));
+            pong_from(&mut service, *node);
+        }
+
+        let re_ping = |service: &mut Discv4Service| {
+            for node in responsive.iter().chain(&unresponsive) {
+                service.send_ping(*node, PingReason::RePing);
+            }
+            for node in &responsive {
+                pong_from(service, *node);
+            }
+            service.evict_expired_requests(Instant::now() + service.config.ping_expiration * 2);
+        };
+
+        // nodes are kept until they failed to answer the configured number of re-pings
+        for _ in 0..2 {
+            re_ping(&mut service);
+            assert!(responsive.iter().chain(&unresponsive).all(|n| in_table(&service, n.id)));
+        }
+
+        re_ping(&mut service);
+        assert!(responsive.iter().all(|n| in_table(&service, n.id)));
+        assert!(unresponsive.iter().all(|n| !in_table(&service, n.id)));
+    }
+
+    #[tokio::test]
+    async fn test_re_ping_failures_reset_on_pong() {
+        let config = Discv4Config::builder().max_ping_failures(2).build();
+        let (_discv4, mut service) = create_discv4_with_config(config).await;
+        let node = NodeRecord::new(service.local_addr(), PeerId::random());
+        assert!(service.add_node(node));
+        pong_from(&mut service, node);
+
+        let timeout = service.config.ping_expiration * 2;
+        for _ in 0..3 {
+            // a missed re-ping followed by an answered one
+            service.send_ping(node, PingReason::RePing);
+            service.evict_expired_requests(Instant::now() + timeout);
+            service.send_ping(node, PingReason::RePing);
+            pong_from(&mut service, node);
+        }
+        assert!(in_table(&service, node.id));
+    }
+
     #[test]
     fn test_insert() {
         let local_node_record = rng_record(&mut rand::thread_rng());
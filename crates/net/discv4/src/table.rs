@@ -1,7 +1,11 @@
 //! Additional support for tracking nodes.
 
-use reth_network_peers::PeerId;
-use std::{collections::HashMap, net::IpAddr, time::Instant};
+use reth_network_peers::{NodeRecord, PeerId};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    time::Instant,
+};
 
 /// Keeps track of nodes from which we have received a `Pong` message.
 #[derive(Debug, Clone, Default)]
@@ -28,8 +32,94 @@ impl PongTable {
     }
 }
 
+/// Caches candidates for k-buckets that were full when the candidate was discovered.
+///
+/// Buckets are identified by their log2 distance to the local node. Each bucket holds at most
+/// `max_per_bucket` candidates, if the limit is exceeded the oldest candidate is dropped.
+#[derive(Debug, Clone)]
+pub(crate) struct ReplacementCache {
+    /// Maximum number of candidates kept per bucket.
+    max_per_bucket: usize,
+    /// Candidates per bucket, ordered from least to most recently discovered.
+    buckets: HashMap<u64, VecDeque<NodeRecord>>,
+}
+
+impl ReplacementCache {
+    /// Creates a new cache that keeps at most `max_per_bucket` candidates per bucket.
+    pub(crate) fn new(max_per_bucket: usize) -> Self {
+        Self { max_per_bucket, buckets: Default::default() }
+    }
+
+    /// Adds a candidate for the given bucket.
+    ///
+    /// If the candidate is already cached it is moved to the most recent position.
+    pub(crate) fn insert(&mut self, bucket: u64, record: NodeRecord) {
+        if self.max_per_bucket == 0 {
+            return
+        }
+        let candidates = self.buckets.entry(bucket).or_default();
+        candidates.retain(|candidate| candidate.id != record.id);
+        if candidates.len() == self.max_per_bucket {
+            candidates.pop_front();
+        }
+        candidates.push_back(record);
+    }
+
+    /// Removes the given candidate from the bucket, returns it if it was cached.
+    pub(crate) fn remove(&mut self, bucket: u64, id: &PeerId) -> Option<NodeRecord> {
+        let candidates = self.buckets.get_mut(&bucket)?;
+        let pos = candidates.iter().position(|candidate| candidate.id == *id)?;
+        let record = candidates.remove(pos);
+        if candidates.is_empty() {
+            self.buckets.remove(&bucket);
+        }
+        record
+    }
+
+    /// Takes the most recently discovered candidate of the bucket.
+    pub(crate) fn pop(&mut self, bucket: u64) -> Option<NodeRecord> {
+        let candidates = self.buckets.get_mut(&bucket)?;
+        let record = candidates.pop_back();
+        if candidates.is_empty() {
+            self.buckets.remove(&bucket);
+        }
+        record
+    }
+
+    /// Returns the number of candidates cached for the bucket.
+    pub(crate) fn len(&self, bucket: u64) -> usize {
+        self.buckets.get(&bucket).map_or(0, VecDeque::len)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub(crate) struct NodeKey {
     pub(crate) remote_id: PeerId,
     pub(crate) remote_ip: IpAddr,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::rng_record;
+
+    #[test]
+    fn replacement_cache_drops_oldest() {
+        let mut rng = rand::thread_rng();
+        let mut cache = ReplacementCache::new(2);
+        let (a, b, c) = (rng_record(&mut rng), rng_record(&mut rng), rng_record(&mut rng));
+
+        cache.insert(255, a);
+        cache.insert(255, b);
+        cache.insert(255, c);
+        assert_eq!(cache.len(255), 2);
+        assert!(cache.remove(255, &a.id).is_none());
+
+        // re-inserting moves the candidate to the most recent position
+        cache.insert(255, b);
+        assert_eq!(cache.pop(255), Some(b));
+        assert_eq!(cache.pop(255), Some(c));
+        assert_eq!(cache.pop(255), None);
+        assert_eq!(cache.len(255), 0);
+    }
+}
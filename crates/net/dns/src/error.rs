@@ -58,4 +58,8 @@ pub(crate) enum LookupError {
     #[error("entry not found")]
     /// Indicates the requested entry was not found.
     EntryNotFound,
+    /// Entry hash mismatch error.
+    #[error("entry doesn't match its hash {0}")]
+    /// Indicates the content of an entry doesn't hash to the name it was resolved from.
+    InvalidEntryHash(String),
 }
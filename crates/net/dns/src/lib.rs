@@ -227,12 +227,12 @@ impl<R: Resolver> DnsDiscoveryService<R> {
 
     fn on_resolved_root(&mut self, resp: ResolveRootResult<SecretKey>) {
         match resp {
-            Ok((root, link)) => match self.trees.entry(link.clone()) {
+            Ok((root, link, ttl)) => match self.trees.entry(link.clone()) {
                 Entry::Occupied(mut entry) => {
-                    entry.get_mut().update_root(root);
+                    entry.get_mut().update_root(root, ttl);
                 }
                 Entry::Vacant(entry) => {
-                    entry.insert(SyncTree::new(root, link));
+                    entry.insert(SyncTree::new(root, link, ttl));
                 }
             },
             Err((err, link)) => {
@@ -410,7 +410,7 @@ fn convert_enr_node_record(enr: &Enr<SecretKey>) -> Option<DnsNodeRecordUpdate>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tree::TreeRootEntry;
+    use crate::tree::{entry_hash, BranchEntry, TreeRootEntry};
     use alloy_chains::Chain;
     use alloy_rlp::{Decodable, Encodable};
     use enr::EnrKey;
@@ -504,13 +504,6 @@ mod tests {
 
         let secret_key = SecretKey::new(&mut thread_rng());
         let resolver = MapResolver::default();
-        let s = "enrtree-root:v1 e=QFT4PBCRX4XQCV3VUYJ6BTCEPU l=JGUFMSAGI7KZYB3P7IZW4S5Y3A seq=3 sig=3FmXuVwpa8Y7OstZTx9PIb1mt8FrW7VpDOFv4AaGCsZ2EIHmhraWhe4NxYhQDlw5MjeFXYMbJjsPeKlHzmJREQE";
-        let mut root: TreeRootEntry = s.parse().unwrap();
-        root.sign(&secret_key).unwrap();
-
-        let link =
-            LinkEntry { domain: "nodes.example.org".to_string(), pubkey: secret_key.public() };
-        resolver.insert(link.domain.clone(), root.to_string());
 
         let mut builder = Enr::builder();
         let fork_id = MAINNET.hardfork_fork_id(EthereumHardfork::Frontier).unwrap();
@@ -521,6 +514,15 @@ mod tests {
             .add_value(b"eth", &EnrForkIdEntry::from(fork_id));
         let enr = builder.build(&secret_key).unwrap();
 
+        let s = "enrtree-root:v1 e=QFT4PBCRX4XQCV3VUYJ6BTCEPU l=JGUFMSAGI7KZYB3P7IZW4S5Y3A seq=3 sig=3FmXuVwpa8Y7OstZTx9PIb1mt8FrW7VpDOFv4AaGCsZ2EIHmhraWhe4NxYhQDlw5MjeFXYMbJjsPeKlHzmJREQE";
+        let mut root: TreeRootEntry = s.parse().unwrap();
+        root.enr_root = entry_hash(&enr.to_base64());
+        root.sign(&secret_key).unwrap();
+
+        let link =
+            LinkEntry { domain: "nodes.example.org".to_string(), pubkey: secret_key.public() };
+        resolver.insert(link.domain.clone(), root.to_string());
+
         resolver.insert(format!("{}.{}", root.enr_root.clone(), link.domain), enr.to_base64());
 
         let mut service = DnsDiscoveryService::new(Arc::new(resolver), Default::default());
@@ -562,8 +564,10 @@ mod tests {
 
         let secret_key = SecretKey::new(&mut thread_rng());
         let resolver = Arc::new(MapResolver::default());
+        let enr = Enr::empty(&secret_key).unwrap();
         let s = "enrtree-root:v1 e=QFT4PBCRX4XQCV3VUYJ6BTCEPU l=JGUFMSAGI7KZYB3P7IZW4S5Y3A seq=3 sig=3FmXuVwpa8Y7OstZTx9PIb1mt8FrW7VpDOFv4AaGCsZ2EIHmhraWhe4NxYhQDlw5MjeFXYMbJjsPeKlHzmJREQE";
         let mut root: TreeRootEntry = s.parse().unwrap();
+        root.enr_root = entry_hash(&enr.to_base64());
         root.sign(&secret_key).unwrap();
 
         let link =
@@ -583,7 +587,6 @@ mod tests {
         // await recheck timeout
         tokio::time::sleep(config.recheck_interval).await;
 
-        let enr = Enr::empty(&secret_key).unwrap();
         resolver.insert(format!("{}.{}", root.enr_root.clone(), link.domain), enr.to_base64());
 
         let event = poll_fn(|cx| service.poll(cx)).await;
@@ -601,6 +604,99 @@ mod tests {
         .await;
     }
 
+    /// Serves a signed tree with two valid, a malformed and a spoofed ENR, and returns the link to
+    /// the tree and the node records of the valid ENRs.
+    fn serve_tree(
+        resolver: &MapResolver,
+        signer: &SecretKey,
+        tree_key: &SecretKey,
+    ) -> (LinkEntry, Vec<NodeRecord>) {
+        let link = LinkEntry { domain: "nodes.example.org".to_string(), pubkey: tree_key.public() };
+        let new_enr = |port| {
+            Enr::builder()
+                .ip4(Ipv4Addr::LOCALHOST)
+                .udp4(port)
+                .tcp4(port)
+                .build(&SecretKey::new(&mut thread_rng()))
+                .unwrap()
+                .to_base64()
+        };
+
+        let mut children = Vec::new();
+        let mut records = Vec::new();
+        for port in [30303, 30304] {
+            let enr = new_enr(port);
+            children.push(entry_hash(&enr));
+            records.push(convert_enr_node_record(&enr.parse().unwrap()).unwrap().node_record);
+            resolver.insert(format!("{}.{}", entry_hash(&enr), link.domain), enr);
+        }
+
+        // hashes correctly, but isn't an ENR
+        let malformed = "enr:-notanenr".to_string();
+        children.push(entry_hash(&malformed));
+        resolver.insert(format!("{}.{}", entry_hash(&malformed), link.domain), malformed);
+
+        // valid ENR served under the hash of another ENR
+        let spoofed = entry_hash(&new_enr(30305));
+        children.push(spoofed.clone());
+        resolver.insert(format!("{spoofed}.{}", link.domain), new_enr(30306));
+
+        let branch = BranchEntry { children }.to_string();
+        resolver.insert(format!("{}.{}", entry_hash(&branch), link.domain), branch.clone());
+
+        let s = "enrtree-root:v1 e=QFT4PBCRX4XQCV3VUYJ6BTCEPU l=JGUFMSAGI7KZYB3P7IZW4S5Y3A seq=3 sig=3FmXuVwpa8Y7OstZTx9PIb1mt8FrW7VpDOFv4AaGCsZ2EIHmhraWhe4NxYhQDlw5MjeFXYMbJjsPeKlHzmJREQE";
+        let mut root: TreeRootEntry = s.parse().unwrap();
+        root.enr_root = entry_hash(&branch);
+        root.sign(signer).unwrap();
+        resolver.insert(link.domain.clone(), root.to_string());
+
+        (link, records)
+    }
+
+    /// Syncs the tree at the link and returns all node records discovered within a second.
+    async fn discovered_node_records(resolver: MapResolver, link: LinkEntry) -> Vec<NodeRecord> {
+        let mut service = DnsDiscoveryService::new(Arc::new(resolver), Default::default());
+        let mut node_records = service.node_record_stream();
+        service.sync_tree_with_link(link);
+
+        let _ = tokio::time::timeout(Duration::from_secs(1), async {
+            while service.next().await.is_some() {}
+        })
+        .await;
+        drop(service);
+
+        let mut records = Vec::new();
+        while let Some(update) = node_records.next().await {
+            records.push(update.node_record);
+        }
+        records
+    }
+
+    #[tokio::test]
+    async fn test_resolve_tree_node_records() {
+        reth_tracing::init_test_tracing();
+
+        let secret_key = SecretKey::new(&mut thread_rng());
+        let resolver = MapResolver::default();
+        let (link, expected) = serve_tree(&resolver, &secret_key, &secret_key);
+
+        let mut records = discovered_node_records(resolver, link).await;
+        records.sort_by_key(|record| record.tcp_port);
+        assert_eq!(records, expected);
+    }
+
+    #[tokio::test]
+    async fn test_reject_unsigned_tree() {
+        reth_tracing::init_test_tracing();
+
+        let secret_key = SecretKey::new(&mut thread_rng());
+        let other_key = SecretKey::new(&mut thread_rng());
+        let resolver = MapResolver::default();
+        let (link, _) = serve_tree(&resolver, &other_key, &secret_key);
+
+        assert!(discovered_node_records(resolver, link).await.is_empty());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_dns_resolver() {
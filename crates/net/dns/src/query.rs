@@ -4,7 +4,7 @@ use crate::{
     error::{LookupError, LookupResult},
     resolver::Resolver,
     sync::ResolveKind,
-    tree::{DnsEntry, LinkEntry, TreeRootEntry},
+    tree::{entry_hash, DnsEntry, LinkEntry, TreeRootEntry},
 };
 use enr::EnrKeyUnambiguous;
 use reth_tokio_util::ratelimit::{Rate, RateLimit};
@@ -117,8 +117,10 @@ pub(crate) struct ResolveEntryResult<K: EnrKeyUnambiguous> {
     pub(crate) kind: ResolveKind,
 }
 
+/// The verified root, the link it was resolved from and how long the root record may be cached
+/// for, if known.
 pub(crate) type ResolveRootResult<K> =
-    Result<(TreeRootEntry, LinkEntry<K>), (LookupError, LinkEntry<K>)>;
+    Result<(TreeRootEntry, LinkEntry<K>, Option<Duration>), (LookupError, LinkEntry<K>)>;
 
 type ResolveRootFuture<K> = Pin<Box<dyn Future<Output = ResolveRootResult<K>> + Send>>;
 
@@ -154,6 +156,8 @@ pub(crate) enum QueryOutcome<K: EnrKeyUnambiguous> {
 }
 
 /// Retrieves the [`DnsEntry`]
+///
+/// Returns an error if the entry doesn't match the hash it was resolved from.
 async fn resolve_entry<K: EnrKeyUnambiguous, R: Resolver>(
     resolver: Arc<R>,
     link: LinkEntry<K>,
@@ -164,8 +168,13 @@ async fn resolve_entry<K: EnrKeyUnambiguous, R: Resolver>(
     let fqn = format!("{hash}.{}", link.domain);
     let mut resp = ResolveEntryResult { entry: None, link, hash, kind };
     match lookup_with_timeout::<R>(&resolver, &fqn, timeout).await {
-        Ok(Some(entry)) => {
-            resp.entry = Some(entry.parse::<DnsEntry<K>>().map_err(|err| err.into()))
+        // entries are immutable, so the TTL is irrelevant
+        Ok(Some((entry, _))) => {
+            resp.entry = Some(if entry_hash(&entry).eq_ignore_ascii_case(&resp.hash) {
+                entry.parse::<DnsEntry<K>>().map_err(|err| err.into())
+            } else {
+                Err(LookupError::InvalidEntryHash(resp.hash.clone()))
+            })
         }
         Err(err) => resp.entry = Some(Err(err)),
         Ok(None) => {}
@@ -182,7 +191,7 @@ async fn resolve_root<K: EnrKeyUnambiguous, R: Resolver>(
     link: LinkEntry<K>,
     timeout: Duration,
 ) -> ResolveRootResult<K> {
-    let root = match lookup_with_timeout::<R>(&resolver, &link.domain, timeout).await {
+    let (root, ttl) = match lookup_with_timeout::<R>(&resolver, &link.domain, timeout).await {
         Ok(Some(root)) => root,
         Ok(_) => return Err((LookupError::EntryNotFound, link)),
         Err(err) => return Err((err, link)),
@@ -191,7 +200,7 @@ async fn resolve_root<K: EnrKeyUnambiguous, R: Resolver>(
     match root.parse::<TreeRootEntry>() {
         Ok(root) => {
             if root.verify::<K>(&link.pubkey) {
-                Ok((root, link))
+                Ok((root, link, ttl))
             } else {
                 Err((LookupError::InvalidRoot(root), link))
             }
@@ -204,8 +213,8 @@ async fn lookup_with_timeout<R: Resolver>(
     r: &R,
     query: &str,
     timeout: Duration,
-) -> LookupResult<Option<String>> {
-    tokio::time::timeout(timeout, r.lookup_txt(query))
+) -> LookupResult<Option<(String, Option<Duration>)>> {
+    tokio::time::timeout(timeout, r.lookup_txt_with_ttl(query))
         .await
         .map_err(|_| LookupError::RequestTimedOut)
 }
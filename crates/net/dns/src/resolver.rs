@@ -1,7 +1,11 @@
 //! Perform DNS lookups
 
 use parking_lot::RwLock;
-use std::{collections::HashMap, future::Future};
+use std::{
+    collections::HashMap,
+    future::Future,
+    time::{Duration, Instant},
+};
 use tracing::trace;
 pub use trust_dns_resolver::{error::ResolveError, TokioAsyncResolver};
 use trust_dns_resolver::{name_server::ConnectionProvider, AsyncResolver};
@@ -10,10 +14,23 @@ use trust_dns_resolver::{name_server::ConnectionProvider, AsyncResolver};
 pub trait Resolver: Send + Sync + Unpin + 'static {
     /// Performs a textual lookup and returns the first text
     fn lookup_txt(&self, query: &str) -> impl Future<Output = Option<String>> + Send;
+
+    /// Performs a textual lookup and returns the first text, and how long the record may still be
+    /// cached for if the resolver knows its TTL.
+    fn lookup_txt_with_ttl(
+        &self,
+        query: &str,
+    ) -> impl Future<Output = Option<(String, Option<Duration>)>> + Send {
+        async move { self.lookup_txt(query).await.map(|txt| (txt, None)) }
+    }
 }
 
 impl<P: ConnectionProvider> Resolver for AsyncResolver<P> {
     async fn lookup_txt(&self, query: &str) -> Option<String> {
+        self.lookup_txt_with_ttl(query).await.map(|(txt, _)| txt)
+    }
+
+    async fn lookup_txt_with_ttl(&self, query: &str) -> Option<(String, Option<Duration>)> {
         // See: [AsyncResolver::txt_lookup]
         // > *hint* queries that end with a '.' are fully qualified names and are cheaper lookups
         let fqn = if query.ends_with('.') { query.to_string() } else { format!("{query}.") };
@@ -23,9 +40,10 @@ impl<P: ConnectionProvider> Resolver for AsyncResolver<P> {
                 None
             }
             Ok(lookup) => {
+                let ttl = lookup.valid_until().saturating_duration_since(Instant::now());
                 let txt = lookup.into_iter().next()?;
                 let entry = txt.iter().next()?;
-                String::from_utf8(entry.to_vec()).ok()
+                String::from_utf8(entry.to_vec()).ok().map(|txt| (txt, Some(ttl)))
             }
         }
     }
@@ -68,29 +86,38 @@ impl Resolver for DnsResolver {
     async fn lookup_txt(&self, query: &str) -> Option<String> {
         Resolver::lookup_txt(&self.0, query).await
     }
+
+    async fn lookup_txt_with_ttl(&self, query: &str) -> Option<(String, Option<Duration>)> {
+        Resolver::lookup_txt_with_ttl(&self.0, query).await
+    }
 }
 
 /// A [Resolver] that uses an in memory map to lookup entries
 #[derive(Debug, Default)]
-pub struct MapResolver(RwLock<HashMap<String, String>>);
+pub struct MapResolver(RwLock<HashMap<String, (String, Option<Duration>)>>);
 
 // === impl MapResolver ===
 
 impl MapResolver {
     /// Inserts a key-value pair into the map.
     pub fn insert(&self, k: String, v: String) -> Option<String> {
-        self.0.write().insert(k, v)
+        self.0.write().insert(k, (v, None)).map(|(v, _)| v)
+    }
+
+    /// Inserts a key-value pair into the map, that's resolved with the given TTL.
+    pub fn insert_with_ttl(&self, k: String, v: String, ttl: Duration) -> Option<String> {
+        self.0.write().insert(k, (v, Some(ttl))).map(|(v, _)| v)
     }
 
     /// Returns the value corresponding to the key
     pub fn get(&self, k: &str) -> Option<String> {
-        self.0.read().get(k).cloned()
+        self.0.read().get(k).map(|(v, _)| v.clone())
     }
 
     /// Removes a key from the map, returning the value at the key if the key was previously in the
     /// map.
     pub fn remove(&self, k: &str) -> Option<String> {
-        self.0.write().remove(k)
+        self.0.write().remove(k).map(|(v, _)| v)
     }
 }
 
@@ -98,6 +125,10 @@ impl Resolver for MapResolver {
     async fn lookup_txt(&self, query: &str) -> Option<String> {
         self.get(query)
     }
+
+    async fn lookup_txt_with_ttl(&self, query: &str) -> Option<(String, Option<Duration>)> {
+        self.0.read().get(query).cloned()
+    }
 }
 
 /// A Resolver that always times out.
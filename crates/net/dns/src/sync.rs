@@ -7,6 +7,9 @@ use std::{
     time::{Duration, Instant},
 };
 
+/// The minimum duration the root of a tree is cached for, regardless of its TTL.
+const MIN_ROOT_TTL: Duration = Duration::from_secs(60);
+
 /// A sync-able tree
 pub(crate) struct SyncTree<K: EnrKeyUnambiguous = SecretKey> {
     /// Root of the tree
@@ -15,6 +18,8 @@ pub(crate) struct SyncTree<K: EnrKeyUnambiguous = SecretKey> {
     link: LinkEntry<K>,
     /// Timestamp when the root was updated
     root_updated: Instant,
+    /// TTL of the root record, if known
+    root_ttl: Option<Duration>,
     /// The state of the tree sync progress.
     sync_state: SyncState,
     /// Links contained in this tree
@@ -28,11 +33,12 @@ pub(crate) struct SyncTree<K: EnrKeyUnambiguous = SecretKey> {
 // === impl SyncTree ===
 
 impl<K: EnrKeyUnambiguous> SyncTree<K> {
-    pub(crate) fn new(root: TreeRootEntry, link: LinkEntry<K>) -> Self {
+    pub(crate) fn new(root: TreeRootEntry, link: LinkEntry<K>, root_ttl: Option<Duration>) -> Self {
         Self {
             root,
             link,
             root_updated: Instant::now(),
+            root_ttl,
             sync_state: SyncState::Pending,
             resolved_links: Default::default(),
             unresolved_links: Default::default(),
@@ -68,6 +74,13 @@ impl<K: EnrKeyUnambiguous> SyncTree<K> {
         }
     }
 
+    /// Returns how long after its last update the root should be checked for updates.
+    ///
+    /// This is the TTL of the root record, bounded by [`MIN_ROOT_TTL`] and `update_timeout`.
+    fn root_refresh_interval(&self, update_timeout: Duration) -> Duration {
+        self.root_ttl.map_or(update_timeout, |ttl| ttl.max(MIN_ROOT_TTL).min(update_timeout))
+    }
+
     /// Advances the state of the tree by returning actions to perform
     pub(crate) fn poll(&mut self, now: Instant, update_timeout: Duration) -> Option<SyncAction> {
         match self.sync_state {
@@ -84,7 +97,7 @@ impl<K: EnrKeyUnambiguous> SyncTree<K> {
                 return Some(SyncAction::Link(self.root.link_root.clone()))
             }
             SyncState::Active => {
-                if now > self.root_updated + update_timeout {
+                if now > self.root_updated + self.root_refresh_interval(update_timeout) {
                     self.sync_state = SyncState::RootUpdate;
                     return Some(SyncAction::UpdateRoot)
                 }
@@ -101,12 +114,13 @@ impl<K: EnrKeyUnambiguous> SyncTree<K> {
     }
 
     /// Updates the root and returns what changed
-    pub(crate) fn update_root(&mut self, root: TreeRootEntry) {
+    pub(crate) fn update_root(&mut self, root: TreeRootEntry, root_ttl: Option<Duration>) {
         let enr = root.enr_root == self.root.enr_root;
         let link = root.link_root == self.root.link_root;
 
         self.root = root;
         self.root_updated = Instant::now();
+        self.root_ttl = root_ttl;
 
         let state = match (enr, link) {
             (true, true) => {
@@ -160,3 +174,52 @@ impl ResolveKind {
         matches!(self, Self::Link)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(root_ttl: Option<Duration>) -> SyncTree {
+        let secret_key = SecretKey::new(&mut secp256k1::rand::thread_rng());
+        let s = "enrtree-root:v1 e=QFT4PBCRX4XQCV3VUYJ6BTCEPU l=JGUFMSAGI7KZYB3P7IZW4S5Y3A seq=3 sig=3FmXuVwpa8Y7OstZTx9PIb1mt8FrW7VpDOFv4AaGCsZ2EIHmhraWhe4NxYhQDlw5MjeFXYMbJjsPeKlHzmJREQE";
+        let link = LinkEntry {
+            domain: "nodes.example.org".to_string(),
+            pubkey: enr::EnrKey::public(&secret_key),
+        };
+        let mut tree = SyncTree::new(s.parse().unwrap(), link, root_ttl);
+        // resolve the link and enr roots
+        let now = tree.root_updated;
+        assert!(matches!(tree.poll(now, Duration::MAX), Some(SyncAction::Link(_))));
+        assert!(matches!(tree.poll(now, Duration::MAX), Some(SyncAction::Enr(_))));
+        tree
+    }
+
+    fn is_update_root(tree: &mut SyncTree, elapsed: Duration, update_timeout: Duration) -> bool {
+        let now = tree.root_updated + elapsed;
+        matches!(tree.poll(now, update_timeout), Some(SyncAction::UpdateRoot))
+    }
+
+    #[test]
+    fn test_root_refresh_honors_ttl() {
+        let recheck = Duration::from_secs(30 * 60);
+
+        // without a TTL the root is rechecked after the recheck interval
+        let mut t = tree(None);
+        assert!(!is_update_root(&mut t, Duration::from_secs(10 * 60), recheck));
+        assert!(is_update_root(&mut t, recheck + Duration::from_secs(1), recheck));
+
+        // a short TTL triggers an earlier update
+        let mut t = tree(Some(Duration::from_secs(5 * 60)));
+        assert!(!is_update_root(&mut t, Duration::from_secs(4 * 60), recheck));
+        assert!(is_update_root(&mut t, Duration::from_secs(5 * 60 + 1), recheck));
+
+        // the TTL is bounded by the minimum
+        let mut t = tree(Some(Duration::ZERO));
+        assert!(!is_update_root(&mut t, Duration::from_secs(1), recheck));
+        assert!(is_update_root(&mut t, MIN_ROOT_TTL + Duration::from_secs(1), recheck));
+
+        // and by the recheck interval
+        let mut t = tree(Some(Duration::from_secs(24 * 60 * 60)));
+        assert!(is_update_root(&mut t, recheck + Duration::from_secs(1), recheck));
+    }
+}
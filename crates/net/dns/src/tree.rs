@@ -21,7 +21,7 @@ use crate::error::{
     ParseDnsEntryError::{FieldNotFound, UnknownEntry},
     ParseEntryResult,
 };
-use alloy_primitives::{hex, Bytes};
+use alloy_primitives::{hex, keccak256, Bytes};
 use data_encoding::{BASE32_NOPAD, BASE64URL_NOPAD};
 use enr::{Enr, EnrKey, EnrKeyUnambiguous, EnrPublicKey, Error as EnrError};
 use secp256k1::SecretKey;
@@ -42,6 +42,14 @@ const BRANCH_PREFIX: &str = "enrtree-branch:";
 /// Prefix used for ENR entries in the ENR tree.
 const ENR_PREFIX: &str = "enr:";
 
+/// Returns the hash of the text of an entry, which is the name of the entry's subdomain.
+///
+/// This is the base32 encoded first 16 bytes of the keccak256 hash of the text. Since the root
+/// is signed, and references its children by hash, this authenticates the entire tree.
+pub fn entry_hash(entry: &str) -> String {
+    BASE32_NOPAD.encode(&keccak256(entry.as_bytes())[..16])
+}
+
 /// Represents all variants of DNS entries for Ethereum node lists.
 #[derive(Debug, Clone)]
 pub enum DnsEntry<K: EnrKeyUnambiguous> {
@@ -379,6 +387,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_entry_hash() {
+        // from the example tree of EIP-1459
+        let branch = "enrtree-branch:2XS2367YHAXJFGLZHVAWLQD4ZY,H4FHT4B454P6UXFD7JCYQ5PWDY,MHTDO6TMUBRIA2XWG5LUDACK24";
+        assert_eq!(entry_hash(branch), "JWXYDBPXYWG6FX3GMDIBFA6CJ4");
+        let link =
+            "enrtree://AM5FCQLWIZX2QFPNJAP7VUERCCRNGRHWZG3YYHIUV7BVDQ5FDPRT2@morenodes.example.org";
+        assert_eq!(entry_hash(link), "C7HRFPF3BLGF3YR4DY5KX3SMBE");
+    }
+
     #[test]
     fn parse_branch_entry() {
         let s = "enrtree-branch:CCCCCCCCCCCCCCCCCCCC,BBBBBBBBBBBBBBBBBBBB";
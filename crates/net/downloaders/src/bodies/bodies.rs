@@ -208,14 +208,10 @@ where
 
     /// Adds a new response to the internal buffer
     fn buffer_bodies_response(&mut self, response: Vec<BlockResponse>) {
-        // take into account capacity
-        let size = response.iter().map(BlockResponse::size).sum::<usize>() +
-            response.capacity() * mem::size_of::<BlockResponse>();
-
-        let response = OrderedBodiesResponse { resp: response, size };
+        let response = OrderedBodiesResponse::new(response);
         let response_len = response.len();
 
-        self.buffered_blocks_size_bytes += size;
+        self.buffered_blocks_size_bytes += response.size();
         self.buffered_responses.push(response);
 
         self.metrics.buffered_blocks.increment(response_len as f64);
@@ -430,18 +426,26 @@ where
 }
 
 #[derive(Debug)]
-struct OrderedBodiesResponse {
-    resp: Vec<BlockResponse>,
+pub(super) struct OrderedBodiesResponse {
+    pub(super) resp: Vec<BlockResponse>,
     /// The total size of the response in bytes
     size: usize,
 }
 
 impl OrderedBodiesResponse {
+    /// Wraps the response and computes its size, see [`BlockResponse::size`].
+    pub(super) fn new(resp: Vec<BlockResponse>) -> Self {
+        // take into account capacity
+        let size = resp.iter().map(BlockResponse::size).sum::<usize>() +
+            resp.capacity() * mem::size_of::<BlockResponse>();
+        Self { resp, size }
+    }
+
     /// Returns the block number of the first element
     ///
     /// # Panics
     /// If the response vec is empty.
-    fn first_block_number(&self) -> u64 {
+    pub(super) fn first_block_number(&self) -> u64 {
         self.resp.first().expect("is not empty").block_number()
     }
 
@@ -449,12 +453,12 @@ impl OrderedBodiesResponse {
     ///
     /// # Panics
     /// If the response vec is empty.
-    fn block_range(&self) -> RangeInclusive<u64> {
+    pub(super) fn block_range(&self) -> RangeInclusive<u64> {
         self.first_block_number()..=self.resp.last().expect("is not empty").block_number()
     }
 
     #[inline]
-    fn len(&self) -> usize {
+    pub(super) fn len(&self) -> usize {
         self.resp.len()
     }
 
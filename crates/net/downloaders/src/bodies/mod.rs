@@ -2,6 +2,9 @@
 #[allow(clippy::module_inception)]
 pub mod bodies;
 
+/// A body downloader that requests bodies while headers are still arriving.
+pub mod pipelined;
+
 /// A body downloader that does nothing. Useful to build unwind-only pipelines.
 pub mod noop;
 
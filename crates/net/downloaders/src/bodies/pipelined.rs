@@ -0,0 +1,411 @@
+use super::{bodies::OrderedBodiesResponse, queue::BodiesRequestQueue};
+use crate::metrics::BodyDownloaderMetrics;
+use alloy_primitives::BlockNumber;
+use futures::Stream;
+use futures_util::StreamExt;
+use reth_consensus::Consensus;
+use reth_network_p2p::{
+    bodies::{client::BodiesClient, downloader::BodyDownloaderResult, response::BlockResponse},
+    error::DownloadError,
+};
+use reth_primitives::{GotExpected, SealedHeader};
+use std::{
+    collections::{BinaryHeap, VecDeque},
+    ops::RangeInclusive,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// Downloads bodies while the headers are still being downloaded.
+///
+/// Headers are consumed from a stream of ascending, contiguous batches. As soon as a batch
+/// arrives, `GetBlockBodies` requests for contiguous ranges of it are dispatched to the
+/// [`BodiesClient`] which spreads them across its peers.
+///
+/// Responses can arrive out of order, they are buffered and the bodies are yielded strictly in
+/// block number order without gaps. The number of blocks that were requested but not yet yielded
+/// is bounded by the configured window, which bounds the memory used by the downloader.
+///
+/// The stream is terminated once the headers stream is exhausted and all bodies were yielded.
+#[must_use = "Stream does nothing unless polled"]
+#[derive(Debug)]
+pub struct PipelinedBodiesDownloader<B: BodiesClient, H> {
+    /// The bodies client
+    client: Arc<B>,
+    /// The consensus client
+    consensus: Arc<dyn Consensus>,
+    /// The stream of headers to download the bodies for
+    headers: H,
+    /// Whether the headers stream is exhausted
+    headers_done: bool,
+    /// The maximum number of non-empty blocks per one request
+    request_limit: u64,
+    /// The allowed range for number of concurrent requests.
+    concurrent_requests_range: RangeInclusive<usize>,
+    /// The maximum number of blocks that were requested but not yet yielded.
+    window: u64,
+    /// Received headers for which no bodies were requested yet.
+    pending_headers: VecDeque<SealedHeader>,
+    /// The number of the next header expected from the headers stream.
+    next_header_number: Option<BlockNumber>,
+    /// The number of the next block to yield.
+    next_block_number: Option<BlockNumber>,
+    /// Requests in progress
+    in_progress_queue: BodiesRequestQueue<B>,
+    /// Buffered responses that can't be yielded yet
+    buffered_responses: BinaryHeap<OrderedBodiesResponse>,
+    /// The bodies downloader metrics.
+    metrics: BodyDownloaderMetrics,
+}
+
+impl<B, H> PipelinedBodiesDownloader<B, H>
+where
+    B: BodiesClient + 'static,
+    H: Stream<Item = Vec<SealedHeader>> + Unpin,
+{
+    /// Max requests to handle at the same time
+    ///
+    /// This depends on the number of active peers but will always be
+    /// [`min_concurrent_requests`..`max_concurrent_requests`]
+    #[inline]
+    fn concurrent_request_limit(&self) -> usize {
+        let num_peers = self.client.num_connected_peers();
+        num_peers
+            .clamp(*self.concurrent_requests_range.start(), *self.concurrent_requests_range.end())
+    }
+
+    /// Returns the number of blocks that were requested but not yet yielded.
+    const fn blocks_in_window(&self) -> u64 {
+        match (self.in_progress_queue.last_requested_block_number, self.next_block_number) {
+            (Some(last), Some(next)) => (last + 1).saturating_sub(next),
+            _ => 0,
+        }
+    }
+
+    /// Returns true if a new request can be submitted.
+    fn can_submit_new_request(&self) -> bool {
+        !self.pending_headers.is_empty() &&
+            self.blocks_in_window() < self.window &&
+            self.in_progress_queue.len() < self.concurrent_request_limit()
+    }
+
+    /// Appends a batch of headers, which must continue the previously received headers.
+    fn on_headers(&mut self, headers: Vec<SealedHeader>) -> Result<(), DownloadError> {
+        for header in headers {
+            if let Some(expected) = self.next_header_number {
+                if header.number != expected {
+                    return Err(DownloadError::HeadersResponseStartBlockMismatch(GotExpected {
+                        got: header.number,
+                        expected,
+                    }))
+                }
+            }
+            self.next_header_number = Some(header.number + 1);
+            self.next_block_number.get_or_insert(header.number);
+            self.pending_headers.push_back(header);
+        }
+        Ok(())
+    }
+
+    /// Takes the next contiguous range of headers to request, bounded by the request limit and
+    /// the remaining window.
+    fn next_request(&mut self) -> Vec<SealedHeader> {
+        let max_len = self.window - self.blocks_in_window();
+        let mut request = Vec::new();
+        let mut non_empty_headers = 0;
+        while non_empty_headers < self.request_limit && (request.len() as u64) < max_len {
+            let Some(header) = self.pending_headers.pop_front() else { break };
+            if !header.is_empty() {
+                non_empty_headers += 1;
+            }
+            request.push(header);
+        }
+        request
+    }
+
+    /// Pops all buffered responses that continue the already yielded bodies.
+    fn next_contiguous_bodies(&mut self) -> Vec<BlockResponse> {
+        let mut bodies = Vec::new();
+        while let Some(next) = self.buffered_responses.peek() {
+            let Some(expected) = self.next_block_number else { break };
+            if next.first_block_number() != expected {
+                break
+            }
+            let resp = self.buffered_responses.pop().expect("exists");
+            self.metrics.buffered_responses.decrement(1.);
+            self.metrics.buffered_blocks.decrement(resp.len() as f64);
+            self.next_block_number = Some(*resp.block_range().end() + 1);
+            bodies.extend(resp.resp);
+        }
+        bodies
+    }
+
+    /// Clears all download related data after a fatal error.
+    fn clear(&mut self) {
+        self.headers_done = true;
+        self.pending_headers.clear();
+        self.in_progress_queue.clear();
+        self.buffered_responses.clear();
+        self.metrics.in_flight_requests.set(0.);
+        self.metrics.buffered_responses.set(0.);
+        self.metrics.buffered_blocks.set(0.);
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.headers_done &&
+            self.pending_headers.is_empty() &&
+            self.in_progress_queue.is_empty() &&
+            self.buffered_responses.is_empty()
+    }
+}
+
+impl<B, H> Stream for PipelinedBodiesDownloader<B, H>
+where
+    B: BodiesClient + 'static,
+    H: Stream<Item = Vec<SealedHeader>> + Unpin,
+{
+    type Item = BodyDownloaderResult;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            // Receive new headers
+            while !this.headers_done {
+                match this.headers.poll_next_unpin(cx) {
+                    Poll::Ready(Some(headers)) => {
+                        if let Err(error) = this.on_headers(headers) {
+                            tracing::debug!(target: "downloaders::bodies", %error, "Received non-contiguous headers");
+                            this.clear();
+                            return Poll::Ready(Some(Err(error)))
+                        }
+                    }
+                    Poll::Ready(None) => this.headers_done = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            // Submit new requests
+            let mut new_request_submitted = false;
+            while this.can_submit_new_request() {
+                let request = this.next_request();
+                this.metrics.in_flight_requests.increment(1.);
+                this.in_progress_queue.push_new_request(
+                    Arc::clone(&this.client),
+                    Arc::clone(&this.consensus),
+                    request,
+                );
+                new_request_submitted = true;
+            }
+
+            // Poll requests
+            while let Poll::Ready(Some(response)) = this.in_progress_queue.poll_next_unpin(cx) {
+                this.metrics.in_flight_requests.decrement(1.);
+                match response {
+                    Ok(response) => {
+                        let response = OrderedBodiesResponse::new(response);
+                        this.metrics.buffered_blocks.increment(response.len() as f64);
+                        this.buffered_responses.push(response);
+                        this.metrics.buffered_responses.increment(1.);
+                    }
+                    Err(error) => {
+                        tracing::debug!(target: "downloaders::bodies", %error, "Request failed");
+                        this.clear();
+                        return Poll::Ready(Some(Err(error)))
+                    }
+                }
+            }
+
+            let bodies = this.next_contiguous_bodies();
+            if !bodies.is_empty() {
+                this.metrics.total_flushed.increment(bodies.len() as u64);
+                return Poll::Ready(Some(Ok(bodies)))
+            }
+
+            if this.is_terminated() {
+                return Poll::Ready(None)
+            }
+
+            if !new_request_submitted {
+                return Poll::Pending
+            }
+        }
+    }
+}
+
+/// Builder for [`PipelinedBodiesDownloader`].
+#[derive(Debug, Clone)]
+pub struct PipelinedBodiesDownloaderBuilder {
+    /// The batch size of non-empty blocks per one request
+    pub request_limit: u64,
+    /// The maximum number of requests to send concurrently.
+    pub concurrent_requests_range: RangeInclusive<usize>,
+    /// The maximum number of blocks that were requested but not yet yielded.
+    pub window: u64,
+}
+
+impl Default for PipelinedBodiesDownloaderBuilder {
+    fn default() -> Self {
+        Self { request_limit: 200, concurrent_requests_range: 5..=100, window: 10_000 }
+    }
+}
+
+impl PipelinedBodiesDownloaderBuilder {
+    /// Set request batch size on the downloader.
+    pub const fn with_request_limit(mut self, request_limit: u64) -> Self {
+        self.request_limit = request_limit;
+        self
+    }
+
+    /// Set concurrent requests range on the downloader.
+    pub const fn with_concurrent_requests_range(
+        mut self,
+        concurrent_requests_range: RangeInclusive<usize>,
+    ) -> Self {
+        self.concurrent_requests_range = concurrent_requests_range;
+        self
+    }
+
+    /// Set the maximum number of blocks that are requested but not yet yielded.
+    pub const fn with_window(mut self, window: u64) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Consume self and return the pipelined downloader for the given headers stream.
+    pub fn build<B, H>(
+        self,
+        client: B,
+        consensus: Arc<dyn Consensus>,
+        headers: H,
+    ) -> PipelinedBodiesDownloader<B, H>
+    where
+        B: BodiesClient + 'static,
+        H: Stream<Item = Vec<SealedHeader>> + Unpin,
+    {
+        let Self { request_limit, concurrent_requests_range, window } = self;
+        let metrics = BodyDownloaderMetrics::default();
+        let in_progress_queue = BodiesRequestQueue::new(metrics.clone());
+        PipelinedBodiesDownloader {
+            client: Arc::new(client),
+            consensus,
+            headers,
+            headers_done: false,
+            request_limit,
+            concurrent_requests_range,
+            window: window.max(1),
+            pending_headers: Default::default(),
+            next_header_number: None,
+            next_block_number: None,
+            in_progress_queue,
+            buffered_responses: Default::default(),
+            metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bodies::test_utils::zip_blocks,
+        test_utils::{generate_bodies, TestBodiesClient},
+    };
+    use reth_consensus::test_utils::TestConsensus;
+    use std::future::poll_fn;
+
+    // Check that bodies of out of order responses are yielded in order and without gaps
+    #[tokio::test]
+    async fn streams_out_of_order_responses_in_order() {
+        let (headers, mut bodies) = generate_bodies(0..=199);
+
+        let client = Arc::new(
+            TestBodiesClient::default().with_bodies(bodies.clone()).with_should_delay(true),
+        );
+        let header_batches = headers.chunks(25).map(<[_]>::to_vec).collect::<Vec<_>>();
+        let mut downloader = PipelinedBodiesDownloaderBuilder::default()
+            .with_request_limit(5)
+            .with_concurrent_requests_range(10..=10)
+            .with_window(50)
+            .build(
+                client.clone(),
+                Arc::new(TestConsensus::default()),
+                futures::stream::iter(header_batches),
+            );
+
+        let mut yielded = Vec::new();
+        while let Some(batch) = downloader.next().await {
+            let batch = batch.unwrap();
+            assert!(!batch.is_empty());
+            yielded.extend(batch);
+        }
+
+        let numbers = yielded.iter().map(BlockResponse::block_number).collect::<Vec<_>>();
+        assert_eq!(numbers, (0..=199).collect::<Vec<_>>());
+        assert_eq!(yielded, zip_blocks(headers.iter(), &mut bodies));
+        assert!(client.times_requested() > 1);
+    }
+
+    // Check that no more blocks than the window are requested at once
+    #[tokio::test]
+    async fn respects_window() {
+        let (headers, bodies) = generate_bodies(0..=99);
+
+        let client = Arc::new(
+            TestBodiesClient::default().with_bodies(bodies.clone()).with_should_delay(true),
+        );
+        let mut downloader = PipelinedBodiesDownloaderBuilder::default()
+            .with_request_limit(100)
+            .with_window(30)
+            .build(
+                client.clone(),
+                Arc::new(TestConsensus::default()),
+                futures::stream::iter(vec![headers.clone()]),
+            );
+
+        poll_fn(|cx| {
+            let _ = downloader.poll_next_unpin(cx);
+            Poll::Ready(())
+        })
+        .await;
+        assert_eq!(downloader.blocks_in_window(), 30);
+        assert_eq!(downloader.pending_headers.len(), 70);
+
+        let mut yielded = Vec::new();
+        while let Some(batch) = downloader.next().await {
+            let batch = batch.unwrap();
+            assert!(downloader.blocks_in_window() <= 30);
+            yielded.extend(batch);
+        }
+        assert_eq!(yielded.len(), 100);
+    }
+
+    // Check that a gap in the headers stream terminates the download
+    #[tokio::test]
+    async fn rejects_non_contiguous_headers() {
+        let (headers, bodies) = generate_bodies(0..=19);
+
+        let client = Arc::new(TestBodiesClient::default().with_bodies(bodies));
+        let mut downloader = PipelinedBodiesDownloaderBuilder::default().build(
+            client,
+            Arc::new(TestConsensus::default()),
+            futures::stream::iter(vec![headers[..5].to_vec(), headers[10..].to_vec()]),
+        );
+
+        let mut result = None;
+        while let Some(next) = downloader.next().await {
+            if next.is_err() {
+                result = Some(next);
+                break
+            }
+        }
+        assert_eq!(
+            result,
+            Some(Err(DownloadError::HeadersResponseStartBlockMismatch(GotExpected {
+                got: 10,
+                expected: 5
+            })))
+        );
+    }
+}
@@ -10,7 +10,7 @@ use reth_network_p2p::{
 use reth_network_peers::{PeerId, WithPeerId};
 use reth_primitives::{BlockBody, GotExpected, SealedBlock, SealedHeader};
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     mem,
     pin::Pin,
     sync::Arc,
@@ -33,6 +33,9 @@ use std::{
 /// NB: This assumes that peers respond with bodies in the order that they were requested.
 /// This is a reasonable assumption to make as that's [what Geth
 /// does](https://github.com/ethereum/go-ethereum/blob/f53ff0ff4a68ffc56004ab1d5cc244bcb64d3277/les/server_requests.go#L245).
+/// Peers omit the bodies they don't have, so the bodies of a response that is shorter than the
+/// request are associated with the requested headers by position, skipping the headers they don't
+/// match. The skipped bodies are requested again.
 /// All errors regarding the response cause the peer to get penalized, meaning that adversaries
 /// that try to give us bodies that do not match the requested order are going to be penalized
 /// and eventually disconnected.
@@ -45,6 +48,8 @@ pub(crate) struct BodiesRequestFuture<B: BodiesClient> {
     response_metrics: ResponseMetrics,
     // Headers to download. The collection is shrunk as responses are buffered.
     pending_headers: VecDeque<SealedHeader>,
+    /// Blocks received ahead of a pending header whose body is still missing, keyed by hash.
+    received: HashMap<B256, SealedBlock>,
     /// Internal buffer for all blocks
     buffer: Vec<BlockResponse>,
     fut: Option<B::Output>,
@@ -68,6 +73,7 @@ where
             metrics,
             response_metrics: Default::default(),
             pending_headers: Default::default(),
+            received: Default::default(),
             buffer: Default::default(),
             last_request_len: None,
             fut: None,
@@ -89,7 +95,7 @@ where
         self.metrics.increment_errors(&error);
         tracing::debug!(target: "downloaders::bodies", ?peer_id, %error, "Error requesting bodies");
         if let Some(peer_id) = peer_id {
-            self.client.report_bad_message(peer_id);
+            self.client.report_bad_response(peer_id);
         }
         self.submit_request(
             self.next_request().expect("existing hashes to resubmit"),
@@ -99,11 +105,17 @@ where
 
     /// Retrieve header hashes for the next request.
     fn next_request(&self) -> Option<Vec<B256>> {
-        let mut hashes =
-            self.pending_headers.iter().filter(|h| !h.is_empty()).map(|h| h.hash()).peekable();
+        let mut hashes = self.requested_headers().map(|h| h.hash()).peekable();
         hashes.peek().is_some().then(|| hashes.collect())
     }
 
+    /// Returns the pending headers whose bodies are yet to be downloaded, in order.
+    fn requested_headers(&self) -> impl Iterator<Item = &SealedHeader> {
+        self.pending_headers
+            .iter()
+            .filter(|h| !h.is_empty() && !self.received.contains_key(&h.hash()))
+    }
+
     /// Submit the request with the given priority.
     fn submit_request(&mut self, req: Vec<B256>, priority: Priority) {
         tracing::trace!(target: "downloaders::bodies", request_len = req.len(), "Requesting bodies");
@@ -155,53 +167,114 @@ where
     /// Attempt to buffer body responses. Returns an error if body response fails validation.
     /// Every body preceding the failed one will be buffered.
     ///
-    /// This method removes headers from the internal collection.
-    /// If the response fails validation, then the header will be put back.
+    /// This method removes headers from the internal collection once their blocks are buffered.
+    /// If the response fails validation, then the header stays pending.
     fn try_buffer_blocks(&mut self, bodies: Vec<BlockBody>) -> DownloadResult<()> {
         let bodies_capacity = bodies.capacity();
         let bodies_len = bodies.len();
-        let mut bodies = bodies.into_iter().peekable();
+        // Peers omit the bodies they don't have, so the bodies of a shorter response might not
+        // belong to the headers at the same position.
+        let has_gaps = bodies_len < self.last_request_len.unwrap_or_default();
+        let requested = self.requested_headers().cloned().collect::<Vec<_>>();
 
         let mut total_size = bodies_capacity * mem::size_of::<BlockBody>();
-        while bodies.peek().is_some() {
-            let next_header = match self.pending_headers.pop_front() {
-                Some(header) => header,
-                None => return Ok(()), // no more headers
-            };
+        let mut result = Ok(());
+        let mut next = 0;
+        for body in bodies {
+            if next == requested.len() {
+                // no more headers
+                break
+            }
 
-            if next_header.is_empty() {
-                // increment empty block body metric
-                total_size += mem::size_of::<BlockBody>();
-                self.buffer.push(BlockResponse::Empty(next_header));
+            // Skip the headers the body doesn't belong to. If there is none it belongs to, it's
+            // validated against the next header, which reports the mismatch.
+            let position = if has_gaps {
+                let roots = BodyRoots::new(&body);
+                requested[next..]
+                    .iter()
+                    .position(|header| roots.matches(header))
+                    .map_or(next, |offset| next + offset)
             } else {
-                let next_body = bodies.next().unwrap();
-
-                // increment full block body metric
-                total_size += next_body.size();
-
-                let block = SealedBlock::new(next_header, next_body);
-
-                if let Err(error) = self.consensus.validate_block_pre_execution(&block) {
-                    // Body is invalid, put the header back and return an error
-                    let hash = block.hash();
-                    let number = block.number;
-                    self.pending_headers.push_front(block.header);
-                    return Err(DownloadError::BodyValidation {
-                        hash,
-                        number,
-                        error: Box::new(error),
-                    })
-                }
+                next
+            };
+            next = position + 1;
 
-                self.buffer.push(BlockResponse::Full(block));
+            // increment full block body metric
+            total_size += body.size();
+
+            let block = SealedBlock::new(requested[position].clone(), body);
+
+            if let Err(error) = self.consensus.validate_block_pre_execution(&block) {
+                // Body is invalid, keep the header pending and return an error
+                result = Err(DownloadError::BodyValidation {
+                    hash: block.hash(),
+                    number: block.number,
+                    error: Box::new(error),
+                });
+                break
             }
+
+            self.received.insert(block.hash(), block);
         }
 
+        total_size += self.buffer_ready_blocks() * mem::size_of::<BlockBody>();
+
         // Increment per-response metric
         self.response_metrics.response_size_bytes.set(total_size as f64);
         self.response_metrics.response_length.set(bodies_len as f64);
 
-        Ok(())
+        result
+    }
+
+    /// Moves the blocks of the leading pending headers that are empty or whose bodies were
+    /// received to the buffer.
+    ///
+    /// Returns the number of buffered empty blocks.
+    fn buffer_ready_blocks(&mut self) -> usize {
+        let mut empty = 0;
+        while let Some(header) = self.pending_headers.front() {
+            if header.is_empty() {
+                let header = self.pending_headers.pop_front().unwrap();
+                self.buffer.push(BlockResponse::Empty(header));
+                empty += 1;
+            } else if let Some(block) = self.received.remove(&header.hash()) {
+                self.pending_headers.pop_front();
+                self.buffer.push(BlockResponse::Full(block));
+            } else {
+                break
+            }
+        }
+        empty
+    }
+}
+
+/// The roots of a block body that its header commits to.
+///
+/// These are computed once per body, so that it can be matched against several headers.
+#[derive(Debug)]
+struct BodyRoots {
+    ommers_hash: B256,
+    transactions_root: B256,
+    withdrawals_root: Option<B256>,
+    requests_root: Option<B256>,
+}
+
+impl BodyRoots {
+    fn new(body: &BlockBody) -> Self {
+        Self {
+            ommers_hash: body.calculate_ommers_root(),
+            transactions_root: body.calculate_tx_root(),
+            withdrawals_root: body.calculate_withdrawals_root(),
+            requests_root: body.calculate_requests_root(),
+        }
+    }
+
+    /// Returns whether the body belongs to the header.
+    fn matches(&self, header: &SealedHeader) -> bool {
+        header.ommers_hash == self.ommers_hash &&
+            header.transactions_root == self.transactions_root &&
+            header.withdrawals_root == self.withdrawals_root &&
+            header.requests_root == self.requests_root
     }
 }
 
@@ -240,10 +313,7 @@ where
             }
 
             // Buffer any empty headers
-            while this.pending_headers.front().is_some_and(|h| h.is_empty()) {
-                let header = this.pending_headers.pop_front().unwrap();
-                this.buffer.push(BlockResponse::Empty(header));
-            }
+            this.buffer_ready_blocks();
         }
     }
 }
@@ -257,6 +327,7 @@ mod tests {
     };
     use reth_consensus::test_utils::TestConsensus;
     use reth_testing_utils::{generators, generators::random_header_range};
+    use std::collections::HashSet;
 
     /// Check if future returns empty bodies without dispatching any requests.
     #[tokio::test]
@@ -303,4 +374,31 @@ mod tests {
             (headers.into_iter().filter(|h| !h.is_empty()).count() as u64 + 1) / 2
         );
     }
+
+    /// Check that the bodies of a response that omits some are associated with their headers.
+    #[tokio::test]
+    async fn request_maps_response_with_gaps() {
+        let (headers, mut bodies) = generate_bodies(0..=19);
+        let requested =
+            headers.iter().filter(|h| !h.is_empty()).map(|h| h.hash()).collect::<Vec<_>>();
+        assert!(requested.len() > 4);
+        // the peer lacks the first requested body and some in between
+        let missing = HashSet::from([requested[0], requested[2], requested[3]]);
+
+        let client = Arc::new(
+            TestBodiesClient::default()
+                .with_bodies(bodies.clone())
+                .with_missing_bodies(missing.clone()),
+        );
+        let fut = BodiesRequestFuture::new(
+            client.clone(),
+            Arc::new(TestConsensus::default()),
+            BodyDownloaderMetrics::default(),
+        )
+        .with_headers(headers.clone());
+
+        // the consensus doesn't validate bodies, every body must be mapped to its own header
+        assert_eq!(fut.await.unwrap(), zip_blocks(headers.iter(), &mut bodies));
+        assert_eq!(client.times_requested(), 2);
+    }
 }
@@ -527,7 +527,7 @@ where
         // Penalize the peer for bad response
         if let Some(peer_id) = peer_id {
             trace!(target: "downloaders::headers", ?peer_id, %error, "Penalizing peer");
-            self.client.report_bad_message(peer_id);
+            self.client.report_bad_response(peer_id);
         }
     }
 
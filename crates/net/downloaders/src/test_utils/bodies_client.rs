@@ -7,7 +7,7 @@ use reth_network_p2p::{
 use reth_network_peers::PeerId;
 use reth_primitives::BlockBody;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Debug,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -21,6 +21,7 @@ use tokio::sync::Mutex;
 #[derive(Debug, Default)]
 pub struct TestBodiesClient {
     bodies: Arc<Mutex<HashMap<B256, BlockBody>>>,
+    missing: Arc<Mutex<HashSet<B256>>>,
     should_delay: bool,
     max_batch_size: Option<usize>,
     times_requested: AtomicU64,
@@ -33,6 +34,13 @@ impl TestBodiesClient {
         self
     }
 
+    /// Instructs the client to omit the bodies of the given hashes from the first response they
+    /// are requested in, as a peer that doesn't have them would.
+    pub(crate) fn with_missing_bodies(mut self, missing: HashSet<B256>) -> Self {
+        self.missing = Arc::new(Mutex::new(missing));
+        self
+    }
+
     pub(crate) const fn with_should_delay(mut self, should_delay: bool) -> Self {
         self.should_delay = should_delay;
         self
@@ -87,6 +95,7 @@ impl BodiesClient for TestBodiesClient {
     ) -> Self::Output {
         let should_delay = self.should_delay;
         let bodies = self.bodies.clone();
+        let missing = self.missing.clone();
         let max_batch_size = self.max_batch_size;
 
         self.times_requested.fetch_add(1, Ordering::Relaxed);
@@ -102,11 +111,13 @@ impl BodiesClient for TestBodiesClient {
             }
 
             let bodies = &mut *bodies.lock().await;
+            let missing = &mut *missing.lock().await;
             Ok((
                 PeerId::default(),
                 hashes
                     .into_iter()
                     .take(max_batch_size.unwrap_or(usize::MAX))
+                    .filter(|hash| !missing.remove(hash))
                     .map(|hash| {
                         bodies
                             .remove(&hash)
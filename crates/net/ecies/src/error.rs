@@ -18,6 +18,15 @@ impl ECIESError {
     pub const fn inner(&self) -> &ECIESErrorImpl {
         &self.inner
     }
+
+    /// Returns `true` if the MAC of a received frame didn't match, i.e. the frame was corrupted
+    /// or tampered with in transit.
+    pub const fn is_frame_mac_failure(&self) -> bool {
+        matches!(
+            self.inner(),
+            ECIESErrorImpl::TagCheckHeaderFailed | ECIESErrorImpl::TagCheckBodyFailed
+        )
+    }
 }
 
 impl fmt::Display for ECIESError {
@@ -97,6 +106,18 @@ impl From<ECIESErrorImpl> for ECIESError {
     }
 }
 
+impl From<ECIESError> for std::io::Error {
+    /// Unwraps IO errors, any other error is wrapped as
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData) and can be recovered with
+    /// [`std::io::Error::into_inner`].
+    fn from(err: ECIESError) -> Self {
+        match err.into_inner() {
+            ECIESErrorImpl::IO(err) => err,
+            err => Self::new(std::io::ErrorKind::InvalidData, ECIESError::from(err)),
+        }
+    }
+}
+
 impl From<std::io::Error> for ECIESError {
     fn from(source: std::io::Error) -> Self {
         ECIESErrorImpl::IO(source).into()
@@ -11,21 +11,35 @@ use futures::{ready, Sink, SinkExt};
 use secp256k1::SecretKey;
 use std::{
     fmt::Debug,
+    future::Future,
     io,
     pin::Pin,
     task::{Context, Poll},
     time::Duration,
 };
 use tokio::{
-    io::{AsyncRead, AsyncWrite},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     time::timeout,
 };
 use tokio_stream::{Stream, StreamExt};
-use tokio_util::codec::{Decoder, Framed};
+use tokio_util::codec::{Decoder, Encoder, Framed, FramedParts};
 use tracing::{instrument, trace};
 
 const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Runs the CPU heavy steps of the `ECIES` handshake, the key agreement and the decryption of the
+/// auth and ack messages, e.g. on a blocking thread pool.
+///
+/// Only the crypto is handed to the executor, reading from and writing to the transport stays on
+/// the task that drives the handshake.
+pub trait HandshakeExecutor {
+    /// Runs the given closure and returns its result.
+    fn execute<F, R>(&self, f: F) -> impl Future<Output = io::Result<R>> + Send
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static;
+}
+
 /// `ECIES` stream over TCP exchanging raw bytes
 #[derive(Debug)]
 #[pin_project::pin_project]
@@ -122,12 +136,135 @@ where
         Ok(Self { stream: transport, remote_id })
     }
 
+    /// Connect to an `ECIES` server, running the crypto of the handshake on the given executor.
+    ///
+    /// See also [`Self::connect_with_timeout`].
+    pub async fn connect_with_executor<E: HandshakeExecutor>(
+        transport: Io,
+        secret_key: SecretKey,
+        remote_id: PeerId,
+        executor: &E,
+    ) -> Result<Self, ECIESError> {
+        timeout(HANDSHAKE_TIMEOUT, async move {
+            let mut transport = transport;
+            let (codec, auth) = executor
+                .execute(move || {
+                    let mut codec = ECIESCodec::new_client(secret_key, remote_id)?;
+                    let mut auth = BytesMut::new();
+                    codec.encode(EgressECIESValue::Auth, &mut auth)?;
+                    Ok::<_, ECIESError>((codec, auth))
+                })
+                .await??;
+
+            trace!("sending ecies auth ...");
+            transport.write_all(&auth).await?;
+
+            trace!("waiting for ecies ack ...");
+            let (codec, msg, read_buf) =
+                read_handshake_message(&mut transport, codec, executor).await?;
+            let msg = msg.ok_or(ECIESErrorImpl::UnreadableStream)?;
+
+            trace!("parsing ecies ack ...");
+            if matches!(msg, IngressECIESValue::Ack) {
+                Ok(Self { stream: framed(transport, codec, read_buf), remote_id })
+            } else {
+                Err(ECIESErrorImpl::InvalidHandshake {
+                    expected: IngressECIESValue::Ack,
+                    msg: Some(msg),
+                }
+                .into())
+            }
+        })
+        .await
+        .map_err(|_| ECIESError::from(ECIESErrorImpl::StreamTimeout))?
+    }
+
+    /// Listen on a just connected ECIES client, running the crypto of the handshake on the given
+    /// executor.
+    pub async fn incoming_with_executor<E: HandshakeExecutor>(
+        mut transport: Io,
+        secret_key: SecretKey,
+        executor: &E,
+    ) -> Result<Self, ECIESError> {
+        let codec = ECIESCodec::new_server(secret_key)?;
+
+        trace!("incoming ecies stream");
+        let (codec, msg, read_buf) =
+            read_handshake_message(&mut transport, codec, executor).await?;
+
+        trace!("receiving ecies auth");
+        let remote_id = match &msg {
+            Some(IngressECIESValue::AuthReceive(remote_id)) => *remote_id,
+            _ => {
+                return Err(ECIESErrorImpl::InvalidHandshake {
+                    expected: IngressECIESValue::AuthReceive(Default::default()),
+                    msg,
+                }
+                .into())
+            }
+        };
+
+        trace!("sending ecies ack");
+        let (codec, ack) = executor
+            .execute(move || {
+                let mut codec = codec;
+                let mut ack = BytesMut::new();
+                codec.encode(EgressECIESValue::Ack, &mut ack).map(|_| (codec, ack))
+            })
+            .await??;
+        transport.write_all(&ack).await?;
+
+        Ok(Self { stream: framed(transport, codec, read_buf), remote_id })
+    }
+
     /// Get the remote id
     pub const fn remote_id(&self) -> PeerId {
         self.remote_id
     }
 }
 
+/// Reads from the transport until the codec decodes the next handshake message, decoding on the
+/// given executor.
+///
+/// Returns the codec, the message, or `None` if the transport was closed before a message was
+/// complete, and the bytes read past the message.
+async fn read_handshake_message<Io, E>(
+    transport: &mut Io,
+    mut codec: ECIESCodec,
+    executor: &E,
+) -> Result<(ECIESCodec, Option<IngressECIESValue>, BytesMut), ECIESError>
+where
+    Io: AsyncRead + Unpin,
+    E: HandshakeExecutor,
+{
+    let mut buf = BytesMut::new();
+    loop {
+        if transport.read_buf(&mut buf).await? == 0 {
+            return Ok((codec, None, buf))
+        }
+        let msg;
+        (codec, buf, msg) = executor
+            .execute(move || {
+                let msg = codec.decode(&mut buf);
+                (codec, buf, msg)
+            })
+            .await?;
+        if let Some(msg) = msg? {
+            return Ok((codec, Some(msg), buf))
+        }
+    }
+}
+
+/// Frames the transport of a completed handshake, keeping the bytes read past the handshake.
+fn framed<Io>(transport: Io, codec: ECIESCodec, read_buf: BytesMut) -> Framed<Io, ECIESCodec>
+where
+    Io: AsyncRead + AsyncWrite,
+{
+    let mut parts = FramedParts::new::<EgressECIESValue>(transport, codec);
+    parts.read_buf = read_buf;
+    Framed::from_parts(parts)
+}
+
 impl<Io> Stream for ECIESStream<Io>
 where
     Io: AsyncRead + Unpin,
@@ -137,6 +274,8 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         match ready!(self.project().stream.poll_next(cx)) {
             Some(Ok(IngressECIESValue::Message(body))) => Poll::Ready(Some(Ok(body))),
+            // keep the error, so a corrupted frame can be told apart from a failing transport
+            Some(Err(err)) => Poll::Ready(Some(Err(err.into()))),
             Some(other) => Poll::Ready(Some(Err(io::Error::new(
                 io::ErrorKind::Other,
                 format!("ECIES stream protocol error: expected message, received {other:?}"),
@@ -206,6 +345,55 @@ mod tests {
         handle.await.unwrap();
     }
 
+    /// Runs the handshake crypto on tokio's blocking pool.
+    struct BlockingExecutor;
+
+    impl HandshakeExecutor for BlockingExecutor {
+        async fn execute<F, R>(&self, f: F) -> io::Result<R>
+        where
+            F: FnOnce() -> R + Send + 'static,
+            R: Send + 'static,
+        {
+            tokio::task::spawn_blocking(f).await.map_err(io::Error::other)
+        }
+    }
+
+    #[tokio::test]
+    async fn can_write_and_read_with_executor() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_key = SecretKey::new(&mut rand::thread_rng());
+
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let mut stream =
+                ECIESStream::incoming_with_executor(incoming, server_key, &BlockingExecutor)
+                    .await
+                    .unwrap();
+
+            // sent right after the ack, so it may arrive in the same read
+            stream.send(Bytes::from("hello client")).await.unwrap();
+            let message = stream.next().await.unwrap().unwrap();
+            assert_eq!(message, Bytes::from("hello server"));
+        });
+
+        let server_id = pk2id(&server_key.public_key(SECP256K1));
+
+        let client_key = SecretKey::new(&mut rand::thread_rng());
+        let outgoing = TcpStream::connect(addr).await.unwrap();
+        let mut client_stream =
+            ECIESStream::connect_with_executor(outgoing, client_key, server_id, &BlockingExecutor)
+                .await
+                .unwrap();
+        assert_eq!(client_stream.remote_id(), server_id);
+
+        client_stream.send(Bytes::from("hello server")).await.unwrap();
+        let message = client_stream.next().await.unwrap().unwrap();
+        assert_eq!(message, Bytes::from("hello client"));
+
+        handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn connection_should_timeout() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
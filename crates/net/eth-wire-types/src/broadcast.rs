@@ -1,6 +1,6 @@
 //! Types for broadcasting new data.
 
-use crate::{EthMessage, EthVersion};
+use crate::{message::MessageError, EthMessage, EthVersion};
 use alloy_rlp::{
     Decodable, Encodable, RlpDecodable, RlpDecodableWrapper, RlpEncodable, RlpEncodableWrapper,
 };
@@ -154,7 +154,7 @@ impl NewPooledTransactionHashes {
                 matches!(version, EthVersion::Eth67 | EthVersion::Eth66)
             }
             Self::Eth68(_) => {
-                matches!(version, EthVersion::Eth68)
+                matches!(version, EthVersion::Eth68 | EthVersion::Eth69)
             }
         }
     }
@@ -290,6 +290,11 @@ impl From<NewPooledTransactionHashes68> for NewPooledTransactionHashes {
     }
 }
 
+/// Default maximum number of hashes in a received [`NewPooledTransactionHashes66`].
+///
+/// The spec recommends announcing at most 4096 hashes per message.
+pub const DEFAULT_MAX_ANNOUNCED_HASHES: usize = 4096;
+
 /// This informs peers of transaction hashes for transactions that have appeared on the network,
 /// but have not been included in a block.
 #[derive(Clone, Debug, PartialEq, Eq, RlpEncodableWrapper, RlpDecodableWrapper, Default)]
@@ -303,6 +308,25 @@ pub struct NewPooledTransactionHashes66(
     pub Vec<B256>,
 );
 
+impl NewPooledTransactionHashes66 {
+    /// Decodes an announcement of at most `max_hashes` hashes.
+    ///
+    /// The number of hashes is checked before they're decoded, so an enormous announcement is
+    /// rejected with [`MessageError::TooManyAnnouncedHashes`] without allocating it.
+    pub fn decode_with_max_hashes(
+        buf: &mut &[u8],
+        max_hashes: usize,
+    ) -> Result<Self, MessageError> {
+        let header = alloy_rlp::Header::decode(&mut &buf[..])?;
+        // each hash is encoded as a 32 byte string with a single byte header
+        let announced = header.payload_length / (B256::len_bytes() + 1);
+        if header.list && announced > max_hashes {
+            return Err(MessageError::TooManyAnnouncedHashes { announced, max: max_hashes })
+        }
+        Ok(Self::decode(buf)?)
+    }
+}
+
 impl From<Vec<B256>> for NewPooledTransactionHashes66 {
     fn from(v: Vec<B256>) -> Self {
         Self(v)
@@ -742,6 +766,21 @@ mod tests {
     use alloy_primitives::{b256, hex};
     use std::str::FromStr;
 
+    #[test]
+    fn test_decode_announcement_with_max_hashes() {
+        let announcement =
+            NewPooledTransactionHashes66((0..4u8).map(B256::repeat_byte).collect::<Vec<_>>());
+        let encoded = alloy_rlp::encode(&announcement);
+
+        let decoded =
+            NewPooledTransactionHashes66::decode_with_max_hashes(&mut &encoded[..], 4).unwrap();
+        assert_eq!(decoded, announcement);
+
+        let err =
+            NewPooledTransactionHashes66::decode_with_max_hashes(&mut &encoded[..], 3).unwrap_err();
+        assert!(matches!(err, MessageError::TooManyAnnouncedHashes { announced: 4, max: 3 }));
+    }
+
     /// Takes as input a struct / encoded hex message pair, ensuring that we encode to the exact hex
     /// message, and decode to the exact struct.
     fn test_encoding_vector<T: Encodable + Decodable + PartialEq + std::fmt::Debug>(
@@ -48,6 +48,11 @@ impl Capability {
         Self::eth(EthVersion::Eth68)
     }
 
+    /// Returns the `snap/1` capability.
+    pub const fn snap_1() -> Self {
+        Self::new_static("snap", 1)
+    }
+
     /// Whether this is eth v66 protocol.
     #[inline]
     pub fn is_eth_v66(&self) -> bool {
@@ -71,6 +76,12 @@ impl Capability {
     pub fn is_eth(&self) -> bool {
         self.is_eth_v66() || self.is_eth_v67() || self.is_eth_v68()
     }
+
+    /// Whether this is the snap protocol.
+    #[inline]
+    pub fn is_snap(&self) -> bool {
+        self.name == "snap"
+    }
 }
 
 impl fmt::Display for Capability {
@@ -141,6 +152,12 @@ impl Capabilities {
     pub const fn supports_eth_v68(&self) -> bool {
         self.eth_68
     }
+
+    /// Whether this peer supports the `snap` sub-protocol.
+    #[inline]
+    pub fn supports_snap(&self) -> bool {
+        self.inner.iter().any(Capability::is_snap)
+    }
 }
 
 impl From<Vec<Capability>> for Capabilities {
@@ -2,12 +2,17 @@
 
 use alloy_primitives::bytes::{Buf, BufMut};
 use alloy_rlp::{Decodable, Encodable, Header};
+use core::time::Duration;
 use derive_more::Display;
 use reth_codecs_derive::add_arbitrary_tests;
 use thiserror::Error;
 
+/// How long the socket stays open after a disconnect with a reason that warrants it was flushed,
+/// see [`DisconnectReason::close_grace_period`].
+pub const DISCONNECT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
 /// RLPx disconnect reason.
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Display)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
 #[add_arbitrary_tests(rlp)]
@@ -54,6 +59,127 @@ pub enum DisconnectReason {
     SubprotocolSpecific = 0x10,
 }
 
+impl DisconnectReason {
+    /// Returns `true` if the disconnect is attributable to the local node rather than the peer,
+    /// given which side initiated it.
+    ///
+    /// Capacity and shutdown disconnects, e.g. [`Self::TooManyPeers`] or
+    /// [`Self::ClientQuitting`], are harmless regardless of who sent them. The same goes for a
+    /// peer that finds us to be a [`Self::UselessPeer`]. Protocol violations, e.g. a
+    /// [`Self::ProtocolBreach`], are the peer's fault, whether we disconnected it or it
+    /// disconnected us. Peers should only be penalized if this returns `false`.
+    pub const fn is_our_fault(&self, initiator: Initiator) -> bool {
+        match (self, initiator) {
+            // the peer has no use for us, e.g. because we're on a different chain
+            (Self::UselessPeer, Initiator::Remote) => true,
+            // reasons that point at a misbehaving or incompatible peer
+            (
+                Self::ProtocolBreach |
+                Self::UselessPeer |
+                Self::IncompatibleP2PProtocolVersion |
+                Self::NullNodeIdentity |
+                Self::UnexpectedHandshakeIdentity |
+                Self::PingTimeout |
+                Self::SubprotocolSpecific,
+                _,
+            ) => false,
+            _ => true,
+        }
+    }
+
+    /// Returns how long to wait before closing the socket once the disconnect message was flushed.
+    ///
+    /// If we disconnect a healthy peer, e.g. with [`Self::TooManyPeers`] or
+    /// [`Self::ClientQuitting`], the socket lingers for [`DISCONNECT_GRACE_PERIOD`] so that the
+    /// peer reliably reads the reason. Sockets of misbehaving peers, e.g. on a
+    /// [`Self::ProtocolBreach`], are closed immediately.
+    pub const fn close_grace_period(&self) -> Duration {
+        match self {
+            Self::DisconnectRequested |
+            Self::TooManyPeers |
+            Self::AlreadyConnected |
+            Self::ClientQuitting => DISCONNECT_GRACE_PERIOD,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns the label of the reason, used in logs and metrics.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::DisconnectRequested => "disconnect_requested",
+            Self::TcpSubsystemError => "tcp_subsystem_error",
+            Self::ProtocolBreach => "protocol_breach",
+            Self::UselessPeer => "useless_peer",
+            Self::TooManyPeers => "too_many_peers",
+            Self::AlreadyConnected => "already_connected",
+            Self::IncompatibleP2PProtocolVersion => "incompatible_p2p_protocol_version",
+            Self::NullNodeIdentity => "null_node_identity",
+            Self::ClientQuitting => "client_quitting",
+            Self::UnexpectedHandshakeIdentity => "unexpected_handshake_identity",
+            Self::ConnectedToSelf => "connected_to_self",
+            Self::PingTimeout => "ping_timeout",
+            Self::SubprotocolSpecific => "subprotocol_specific",
+        }
+    }
+
+    /// Returns the reason with the given label, the inverse of [`Self::as_str`].
+    ///
+    /// This reconstructs reasons from metric labels or logs.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let reason = match label {
+            "disconnect_requested" => Self::DisconnectRequested,
+            "tcp_subsystem_error" => Self::TcpSubsystemError,
+            "protocol_breach" => Self::ProtocolBreach,
+            "useless_peer" => Self::UselessPeer,
+            "too_many_peers" => Self::TooManyPeers,
+            "already_connected" => Self::AlreadyConnected,
+            "incompatible_p2p_protocol_version" => Self::IncompatibleP2PProtocolVersion,
+            "null_node_identity" => Self::NullNodeIdentity,
+            "client_quitting" => Self::ClientQuitting,
+            "unexpected_handshake_identity" => Self::UnexpectedHandshakeIdentity,
+            "connected_to_self" => Self::ConnectedToSelf,
+            "ping_timeout" => Self::PingTimeout,
+            "subprotocol_specific" => Self::SubprotocolSpecific,
+            _ => return None,
+        };
+        Some(reason)
+    }
+
+    /// Returns the coarse category of the reason, used to group disconnects in metrics.
+    ///
+    /// The set of categories is stable, new reasons are assigned to one of the existing
+    /// categories.
+    pub const fn category(&self) -> &'static str {
+        match self {
+            Self::DisconnectRequested | Self::ClientQuitting => "requested",
+            Self::TooManyPeers | Self::AlreadyConnected => "capacity",
+            Self::TcpSubsystemError | Self::PingTimeout => "transport",
+            Self::NullNodeIdentity | Self::UnexpectedHandshakeIdentity | Self::ConnectedToSelf => {
+                "identity"
+            }
+            Self::ProtocolBreach |
+            Self::UselessPeer |
+            Self::IncompatibleP2PProtocolVersion |
+            Self::SubprotocolSpecific => "protocol",
+        }
+    }
+
+    /// Returns the metric labels describing this disconnect.
+    ///
+    /// All label values are taken from a fixed set, so these labels can never blow up the
+    /// cardinality of a metric. Unbounded values like the peer id must not be used as labels.
+    pub const fn to_metric_labels(
+        &self,
+        initiator: Initiator,
+    ) -> [(&'static str, &'static str); 3] {
+        [
+            ("category", self.category()),
+            ("reason", self.as_str()),
+            ("initiator", initiator.as_str()),
+        ]
+    }
+}
+
 impl TryFrom<u8> for DisconnectReason {
     // This error type should not be used to crash the node, but rather to log the error and
     // disconnect the peer.
@@ -90,14 +216,17 @@ impl Encodable for DisconnectReason {
     }
 }
 
+/// The error returned when decoding a disconnect list that doesn't consist of exactly one
+/// element.
+pub const DISCONNECT_LIST_LENGTH_ERROR: alloy_rlp::Error =
+    alloy_rlp::Error::Custom("disconnect list must have exactly one element");
+
 impl Decodable for DisconnectReason {
     /// The [`Decodable`] implementation for [`DisconnectReason`] supports either a disconnect
     /// reason encoded a single byte or a RLP list containing the disconnect reason.
     fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
         if buf.is_empty() {
             return Err(alloy_rlp::Error::InputTooShort)
-        } else if buf.len() > 2 {
-            return Err(alloy_rlp::Error::Overflow)
         }
 
         if buf.len() > 1 {
@@ -109,11 +238,16 @@ impl Decodable for DisconnectReason {
                 return Err(alloy_rlp::Error::UnexpectedString)
             }
 
-            if header.payload_length != 1 {
-                return Err(alloy_rlp::Error::ListLengthMismatch {
-                    expected: 1,
-                    got: header.payload_length,
-                })
+            match header.payload_length {
+                0 => return Err(alloy_rlp::Error::ListLengthMismatch { expected: 1, got: 0 }),
+                // every known reason is encoded as a single byte, so a longer payload holds more
+                // than one element
+                1 => {}
+                _ => return Err(DISCONNECT_LIST_LENGTH_ERROR),
+            }
+
+            if buf.len() > 1 {
+                return Err(alloy_rlp::Error::Overflow)
             }
         }
 
@@ -129,7 +263,174 @@ impl Decodable for DisconnectReason {
     }
 }
 
+/// The side of a connection that initiated a disconnect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Initiator {
+    /// The local node sent the disconnect.
+    Local,
+    /// The remote peer sent the disconnect.
+    Remote,
+}
+
+impl Initiator {
+    /// Returns the label of the initiator, used in logs and metrics.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Remote => "remote",
+        }
+    }
+}
+
+/// Disconnect reason specific to the `eth` protocol.
+///
+/// These have no wire code of their own and are sent as
+/// [`DisconnectReason::SubprotocolSpecific`]. The granular reason is only kept locally, for logs
+/// and metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EthDisconnectReason {
+    /// Peer sent an invalid `Status` message.
+    #[display("invalid status")]
+    InvalidStatus,
+    /// Peer responded with a request id that doesn't match any of our requests.
+    #[display("unexpected request id")]
+    UnexpectedRequestId,
+    /// Peer sent a response exceeding the protocol's size limits.
+    #[display("oversized response")]
+    OversizedResponse,
+}
+
+impl EthDisconnectReason {
+    /// Returns the label of the reason, used in logs and metrics.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvalidStatus => "invalid_status",
+            Self::UnexpectedRequestId => "unexpected_request_id",
+            Self::OversizedResponse => "oversized_response",
+        }
+    }
+}
+
+impl From<EthDisconnectReason> for DisconnectReason {
+    fn from(_: EthDisconnectReason) -> Self {
+        Self::SubprotocolSpecific
+    }
+}
+
 /// This represents an unknown disconnect reason with the given code.
 #[derive(Debug, Clone, Error)]
 #[error("unknown disconnect reason: {0}")]
 pub struct UnknownDisconnectReason(u8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_grace_period() {
+        assert_eq!(DisconnectReason::ProtocolBreach.close_grace_period(), Duration::ZERO);
+        assert_eq!(DisconnectReason::PingTimeout.close_grace_period(), Duration::ZERO);
+        assert_eq!(DisconnectReason::TooManyPeers.close_grace_period(), DISCONNECT_GRACE_PERIOD);
+        assert_eq!(DisconnectReason::ClientQuitting.close_grace_period(), DISCONNECT_GRACE_PERIOD);
+    }
+
+    #[test]
+    fn capacity_disconnects_are_our_fault() {
+        for reason in [
+            DisconnectReason::TooManyPeers,
+            DisconnectReason::ClientQuitting,
+            DisconnectReason::DisconnectRequested,
+            DisconnectReason::AlreadyConnected,
+        ] {
+            // we disconnected the peer for a local reason, no penalty
+            assert!(reason.is_our_fault(Initiator::Local), "{reason}");
+            // the peer left because it's full or shutting down, no penalty either
+            assert!(reason.is_our_fault(Initiator::Remote), "{reason}");
+        }
+    }
+
+    #[test]
+    fn protocol_breaches_are_not_our_fault() {
+        for reason in [
+            DisconnectReason::ProtocolBreach,
+            DisconnectReason::PingTimeout,
+            DisconnectReason::SubprotocolSpecific,
+        ] {
+            assert!(!reason.is_our_fault(Initiator::Local), "{reason}");
+            assert!(!reason.is_our_fault(Initiator::Remote), "{reason}");
+        }
+    }
+
+    #[test]
+    fn useless_peer_depends_on_initiator() {
+        // the peer is useless to us
+        assert!(!DisconnectReason::UselessPeer.is_our_fault(Initiator::Local));
+        // we are useless to the peer
+        assert!(DisconnectReason::UselessPeer.is_our_fault(Initiator::Remote));
+    }
+
+    #[test]
+    fn eth_disconnect_reasons_are_subprotocol_specific() {
+        for (reason, label) in [
+            (EthDisconnectReason::InvalidStatus, "invalid_status"),
+            (EthDisconnectReason::UnexpectedRequestId, "unexpected_request_id"),
+            (EthDisconnectReason::OversizedResponse, "oversized_response"),
+        ] {
+            assert_eq!(DisconnectReason::from(reason), DisconnectReason::SubprotocolSpecific);
+            assert_eq!(reason.as_str(), label);
+        }
+    }
+
+    #[test]
+    fn metric_labels_have_bounded_cardinality() {
+        const CATEGORIES: [&str; 5] =
+            ["requested", "capacity", "transport", "identity", "protocol"];
+        const INITIATORS: [&str; 2] = ["local", "remote"];
+
+        let reasons = (0..=u8::MAX).filter_map(|code| DisconnectReason::try_from(code).ok());
+        let mut reason_labels = std::collections::HashSet::new();
+        for reason in reasons {
+            for initiator in [Initiator::Local, Initiator::Remote] {
+                let labels = reason.to_metric_labels(initiator);
+                assert_eq!(labels.map(|(key, _)| key), ["category", "reason", "initiator"]);
+
+                let [(_, category), (_, label), (_, initiator)] = labels;
+                assert!(CATEGORIES.contains(&category), "{reason}: {category}");
+                assert!(INITIATORS.contains(&initiator), "{reason}: {initiator}");
+                assert_eq!(label, reason.as_str());
+                reason_labels.insert(label);
+            }
+        }
+
+        // every known reason has a distinct label
+        assert_eq!(reason_labels.len(), 13);
+    }
+
+    #[test]
+    fn labels_round_trip() {
+        let reasons = (0..=u8::MAX).filter_map(|code| DisconnectReason::try_from(code).ok());
+        for reason in reasons {
+            assert_eq!(DisconnectReason::from_label(reason.as_str()), Some(reason));
+        }
+
+        assert_eq!(DisconnectReason::from_label("Protocol_Breach"), None);
+        assert_eq!(DisconnectReason::from_label("breach of protocol"), None);
+        assert_eq!(DisconnectReason::from_label(""), None);
+    }
+
+    #[test]
+    fn reject_multi_element_disconnect_list() {
+        // [ProtocolBreach, UselessPeer]
+        let mut buf = &[0xc2, 0x02, 0x03][..];
+        assert_eq!(DisconnectReason::decode(&mut buf), Err(DISCONNECT_LIST_LENGTH_ERROR));
+
+        // a single-element list with trailing bytes
+        let mut buf = &[0xc1, 0x02, 0x03][..];
+        assert_eq!(DisconnectReason::decode(&mut buf), Err(alloy_rlp::Error::Overflow));
+
+        let mut buf = &[0xc1, 0x02][..];
+        assert_eq!(DisconnectReason::decode(&mut buf), Ok(DisconnectReason::ProtocolBreach));
+    }
+}
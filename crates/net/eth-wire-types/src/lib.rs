@@ -9,7 +9,10 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 mod status;
-pub use status::{Status, StatusBuilder};
+pub use status::{
+    NegotiatedEth, Status, StatusBuilder, StatusEth69, StatusMismatch,
+    STATUS_TOTAL_DIFFICULTY_ERROR,
+};
 
 pub mod version;
 pub use version::{EthVersion, ProtocolVersion};
@@ -35,6 +38,8 @@ pub use state::*;
 pub mod receipts;
 pub use receipts::*;
 
+pub mod snap;
+
 pub mod disconnect_reason;
 pub use disconnect_reason::*;
 
@@ -1,4 +1,4 @@
-//! Implements Ethereum wire protocol for versions 66, 67, and 68.
+//! Implements Ethereum wire protocol for versions 66, 67, 68 and 69.
 //! Defines structs/enums for messages, request-response pairs, and broadcasts.
 //! Handles compatibility with [`EthVersion`].
 //!
@@ -9,7 +9,8 @@
 use super::{
     broadcast::NewBlockHashes, BlockBodies, BlockHeaders, GetBlockBodies, GetBlockHeaders,
     GetNodeData, GetPooledTransactions, GetReceipts, NewBlock, NewPooledTransactionHashes66,
-    NewPooledTransactionHashes68, NodeData, PooledTransactions, Receipts, Status, Transactions,
+    NewPooledTransactionHashes68, NodeData, PooledTransactions, Receipts, Receipts69, Status,
+    StatusEth69, Transactions, DEFAULT_MAX_ANNOUNCED_HASHES,
 };
 use crate::{EthVersion, SharedTransactions};
 
@@ -30,6 +31,14 @@ pub enum MessageError {
     /// Thrown when rlp decoding a message message failed.
     #[error("RLP error: {0}")]
     RlpError(#[from] alloy_rlp::Error),
+    /// Thrown when a transaction hash announcement exceeds the maximum number of hashes.
+    #[error("announced {announced} transaction hashes, max is {max}")]
+    TooManyAnnouncedHashes {
+        /// The number of announced hashes.
+        announced: usize,
+        /// The maximum number of hashes in an announcement.
+        max: usize,
+    },
 }
 
 /// An `eth` protocol message, containing a message ID and payload.
@@ -44,11 +53,29 @@ pub struct ProtocolMessage {
 
 impl ProtocolMessage {
     /// Create a new `ProtocolMessage` from a message type and message rlp bytes.
+    ///
+    /// Transaction hash announcements are limited to [`DEFAULT_MAX_ANNOUNCED_HASHES`] hashes.
     pub fn decode_message(version: EthVersion, buf: &mut &[u8]) -> Result<Self, MessageError> {
+        Self::decode_message_with_max_announced_hashes(version, buf, DEFAULT_MAX_ANNOUNCED_HASHES)
+    }
+
+    /// Create a new `ProtocolMessage` from a message type and message rlp bytes, rejecting eth/66
+    /// transaction hash announcements of more than `max_announced_hashes` hashes.
+    pub fn decode_message_with_max_announced_hashes(
+        version: EthVersion,
+        buf: &mut &[u8],
+        max_announced_hashes: usize,
+    ) -> Result<Self, MessageError> {
         let message_type = EthMessageID::decode(buf)?;
 
         let message = match message_type {
-            EthMessageID::Status => EthMessage::Status(Status::decode(buf)?),
+            EthMessageID::Status => {
+                if version >= EthVersion::Eth69 {
+                    EthMessage::StatusEth69(StatusEth69::decode(buf)?)
+                } else {
+                    EthMessage::Status(Status::decode(buf)?)
+                }
+            }
             EthMessageID::NewBlockHashes => {
                 EthMessage::NewBlockHashes(NewBlockHashes::decode(buf)?)
             }
@@ -60,9 +87,12 @@ impl ProtocolMessage {
                         buf,
                     )?)
                 } else {
-                    EthMessage::NewPooledTransactionHashes66(NewPooledTransactionHashes66::decode(
-                        buf,
-                    )?)
+                    EthMessage::NewPooledTransactionHashes66(
+                        NewPooledTransactionHashes66::decode_with_max_hashes(
+                            buf,
+                            max_announced_hashes,
+                        )?,
+                    )
                 }
             }
             EthMessageID::GetBlockHeaders => {
@@ -108,8 +138,11 @@ impl ProtocolMessage {
                 EthMessage::GetReceipts(request_pair)
             }
             EthMessageID::Receipts => {
-                let request_pair = RequestPair::<Receipts>::decode(buf)?;
-                EthMessage::Receipts(request_pair)
+                if version >= EthVersion::Eth69 {
+                    EthMessage::Receipts69(RequestPair::<Receipts69>::decode(buf)?)
+                } else {
+                    EthMessage::Receipts(RequestPair::<Receipts>::decode(buf)?)
+                }
             }
         };
         Ok(Self { message_type, message })
@@ -179,11 +212,16 @@ impl From<EthBroadcastMessage> for ProtocolBroadcastMessage {
 /// The `eth/68` changes only `NewPooledTransactionHashes` to include `types` and `sized`. For
 /// it, `NewPooledTransactionHashes` is renamed as [`NewPooledTransactionHashes66`] and
 /// [`NewPooledTransactionHashes68`] is defined.
+///
+/// The `eth/69` replaces the [`Status`] with [`StatusEth69`], which drops the total difficulty,
+/// and the [`Receipts`] with [`Receipts69`], which drops the logs bloom of every receipt.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EthMessage {
     /// Represents a Status message required for the protocol handshake.
     Status(Status),
+    /// Represents an eth/69 Status message without the total difficulty.
+    StatusEth69(StatusEth69),
     /// Represents a `NewBlockHashes` message broadcast to the network.
     NewBlockHashes(NewBlockHashes),
     /// Represents a `NewBlock` message broadcast to the network.
@@ -215,13 +253,15 @@ pub enum EthMessage {
     GetReceipts(RequestPair<GetReceipts>),
     /// Represents a Receipts request-response pair.
     Receipts(RequestPair<Receipts>),
+    /// Represents an eth/69 Receipts request-response pair, without the logs blooms.
+    Receipts69(RequestPair<Receipts69>),
 }
 
 impl EthMessage {
     /// Returns the message's ID.
     pub const fn message_id(&self) -> EthMessageID {
         match self {
-            Self::Status(_) => EthMessageID::Status,
+            Self::Status(_) | Self::StatusEth69(_) => EthMessageID::Status,
             Self::NewBlockHashes(_) => EthMessageID::NewBlockHashes,
             Self::NewBlock(_) => EthMessageID::NewBlock,
             Self::Transactions(_) => EthMessageID::Transactions,
@@ -237,7 +277,31 @@ impl EthMessage {
             Self::GetNodeData(_) => EthMessageID::GetNodeData,
             Self::NodeData(_) => EthMessageID::NodeData,
             Self::GetReceipts(_) => EthMessageID::GetReceipts,
-            Self::Receipts(_) => EthMessageID::Receipts,
+            Self::Receipts(_) | Self::Receipts69(_) => EthMessageID::Receipts,
+        }
+    }
+
+    /// Returns the request id if this is a request or response message.
+    pub const fn request_id(&self) -> Option<u64> {
+        match self {
+            Self::GetBlockHeaders(RequestPair { request_id, .. }) |
+            Self::BlockHeaders(RequestPair { request_id, .. }) |
+            Self::GetBlockBodies(RequestPair { request_id, .. }) |
+            Self::BlockBodies(RequestPair { request_id, .. }) |
+            Self::GetPooledTransactions(RequestPair { request_id, .. }) |
+            Self::PooledTransactions(RequestPair { request_id, .. }) |
+            Self::GetNodeData(RequestPair { request_id, .. }) |
+            Self::NodeData(RequestPair { request_id, .. }) |
+            Self::GetReceipts(RequestPair { request_id, .. }) |
+            Self::Receipts(RequestPair { request_id, .. }) |
+            Self::Receipts69(RequestPair { request_id, .. }) => Some(*request_id),
+            Self::Status(_) |
+            Self::StatusEth69(_) |
+            Self::NewBlockHashes(_) |
+            Self::NewBlock(_) |
+            Self::Transactions(_) |
+            Self::NewPooledTransactionHashes66(_) |
+            Self::NewPooledTransactionHashes68(_) => None,
         }
     }
 }
@@ -246,6 +310,7 @@ impl Encodable for EthMessage {
     fn encode(&self, out: &mut dyn BufMut) {
         match self {
             Self::Status(status) => status.encode(out),
+            Self::StatusEth69(status) => status.encode(out),
             Self::NewBlockHashes(new_block_hashes) => new_block_hashes.encode(out),
             Self::NewBlock(new_block) => new_block.encode(out),
             Self::Transactions(transactions) => transactions.encode(out),
@@ -261,11 +326,13 @@ impl Encodable for EthMessage {
             Self::NodeData(data) => data.encode(out),
             Self::GetReceipts(request) => request.encode(out),
             Self::Receipts(receipts) => receipts.encode(out),
+            Self::Receipts69(receipts) => receipts.encode(out),
         }
     }
     fn length(&self) -> usize {
         match self {
             Self::Status(status) => status.length(),
+            Self::StatusEth69(status) => status.length(),
             Self::NewBlockHashes(new_block_hashes) => new_block_hashes.length(),
             Self::NewBlock(new_block) => new_block.length(),
             Self::Transactions(transactions) => transactions.length(),
@@ -281,6 +348,7 @@ impl Encodable for EthMessage {
             Self::NodeData(data) => data.length(),
             Self::GetReceipts(request) => request.length(),
             Self::Receipts(receipts) => receipts.length(),
+            Self::Receipts69(receipts) => receipts.length(),
         }
     }
 }
@@ -444,6 +512,13 @@ pub struct RequestPair<T> {
     pub message: T,
 }
 
+impl<T> RequestPair<T> {
+    /// Converts the message payload, keeping the request id.
+    pub fn map<R>(self, f: impl FnOnce(T) -> R) -> RequestPair<R> {
+        RequestPair { request_id: self.request_id, message: f(self.message) }
+    }
+}
+
 /// Allows messages with request ids to be serialized into RLP bytes.
 impl<T> Encodable for RequestPair<T>
 where
@@ -494,10 +569,17 @@ where
 mod tests {
     use super::MessageError;
     use crate::{
-        message::RequestPair, EthMessage, EthMessageID, GetNodeData, NodeData, ProtocolMessage,
+        message::RequestPair, BlockBodies, BlockHeaders, EthMessage, EthMessageID, EthVersion,
+        GetBlockBodies, GetBlockHeaders, GetNodeData, GetPooledTransactions, GetReceipts, NewBlock,
+        NewBlockHashes, NewPooledTransactionHashes66, NewPooledTransactionHashes68, NodeData,
+        PooledTransactions, ProtocolMessage, Receipts, Receipts69, Status, StatusEth69,
+        Transactions, STATUS_TOTAL_DIFFICULTY_ERROR,
     };
-    use alloy_primitives::hex;
+    use alloy_primitives::{hex, B256};
     use alloy_rlp::{Decodable, Encodable, Error};
+    use proptest::{collection::vec, prelude::*};
+    use proptest_arbitrary_interop::arb;
+    use reth_primitives::{PooledTransactionsElement, TransactionSigned, TxType};
 
     fn encode<T: Encodable>(value: T) -> Vec<u8> {
         let mut buf = vec![];
@@ -505,6 +587,80 @@ mod tests {
         buf
     }
 
+    /// Strategy for a request or response with a random request id.
+    fn arb_request_pair<T>(
+        message: impl Strategy<Value = T>,
+    ) -> impl Strategy<Value = RequestPair<T>>
+    where
+        T: std::fmt::Debug,
+    {
+        (any::<u64>(), message)
+            .prop_map(|(request_id, message)| RequestPair { request_id, message })
+    }
+
+    /// Strategy for `eth/68` announcements, with the same number of types, sizes and hashes.
+    fn arb_hashes_68() -> impl Strategy<Value = NewPooledTransactionHashes68> {
+        (1..100usize)
+            .prop_flat_map(|len| {
+                (
+                    vec(arb::<TxType>().prop_map(|ty| ty as u8), len),
+                    vec(0..131072usize, len),
+                    vec(any::<B256>(), len),
+                )
+            })
+            .prop_map(|(types, sizes, hashes)| NewPooledTransactionHashes68 {
+                types,
+                sizes,
+                hashes,
+            })
+    }
+
+    /// Strategy for pooled transactions without blob transactions, whose sidecars are too large
+    /// for arbitrary data.
+    fn arb_pooled_transaction() -> impl Strategy<Value = PooledTransactionsElement> {
+        arb::<TransactionSigned>().prop_filter_map("blob transaction without sidecar", |tx| {
+            PooledTransactionsElement::try_from_broadcast(tx).ok()
+        })
+    }
+
+    /// Strategy for all `eth` messages.
+    fn arb_eth_message() -> impl Strategy<Value = EthMessage> {
+        prop_oneof![
+            arb::<Status>().prop_map(EthMessage::Status),
+            arb::<StatusEth69>().prop_map(EthMessage::StatusEth69),
+            arb::<NewBlockHashes>().prop_map(EthMessage::NewBlockHashes),
+            arb::<NewBlock>().prop_map(|block| EthMessage::NewBlock(Box::new(block))),
+            arb::<Transactions>().prop_map(EthMessage::Transactions),
+            arb::<NewPooledTransactionHashes66>()
+                .prop_map(EthMessage::NewPooledTransactionHashes66),
+            arb_hashes_68().prop_map(EthMessage::NewPooledTransactionHashes68),
+            arb_request_pair(arb::<GetBlockHeaders>()).prop_map(EthMessage::GetBlockHeaders),
+            arb_request_pair(arb::<BlockHeaders>()).prop_map(EthMessage::BlockHeaders),
+            arb_request_pair(arb::<GetBlockBodies>()).prop_map(EthMessage::GetBlockBodies),
+            arb_request_pair(arb::<BlockBodies>()).prop_map(EthMessage::BlockBodies),
+            arb_request_pair(arb::<GetPooledTransactions>())
+                .prop_map(EthMessage::GetPooledTransactions),
+            arb_request_pair(vec(arb_pooled_transaction(), 0..4).prop_map(PooledTransactions))
+                .prop_map(EthMessage::PooledTransactions),
+            arb_request_pair(arb::<GetNodeData>()).prop_map(EthMessage::GetNodeData),
+            arb_request_pair(arb::<NodeData>()).prop_map(EthMessage::NodeData),
+            arb_request_pair(arb::<GetReceipts>()).prop_map(EthMessage::GetReceipts),
+            arb_request_pair(arb::<Receipts>()).prop_map(EthMessage::Receipts),
+            arb_request_pair(arb::<Receipts69>()).prop_map(EthMessage::Receipts69),
+        ]
+    }
+
+    proptest! {
+        /// A `length` that doesn't match the encoding breaks the framing of the message.
+        #[test]
+        fn test_eth_message_length(message in arb_eth_message()) {
+            prop_assert_eq!(message.length(), encode(&message).len());
+
+            let message = ProtocolMessage::from(message);
+            prop_assert_eq!(message.length(), encode(&message).len());
+        }
+    }
+
     #[test]
     fn test_removed_message_at_eth67() {
         let get_node_data =
@@ -524,6 +680,50 @@ mod tests {
         assert!(matches!(msg, Err(MessageError::Invalid(..))));
     }
 
+    #[test]
+    fn test_status_decoded_by_version() {
+        let status = EthMessage::Status(Status::default());
+        let buf = encode(ProtocolMessage { message_type: EthMessageID::Status, message: status });
+        let msg = ProtocolMessage::decode_message(EthVersion::Eth68, &mut &buf[..]).unwrap();
+        assert!(matches!(msg.message, EthMessage::Status(_)));
+
+        let msg = ProtocolMessage::decode_message(EthVersion::Eth69, &mut &buf[..]);
+        assert!(
+            matches!(msg, Err(MessageError::RlpError(err)) if err == STATUS_TOTAL_DIFFICULTY_ERROR)
+        );
+
+        let status = StatusEth69 {
+            version: EthVersion::Eth69 as u8,
+            chain: Default::default(),
+            genesis: Default::default(),
+            forkid: Status::default().forkid,
+            earliest: 0,
+            latest: 100,
+            latest_hash: Default::default(),
+        };
+        let buf = encode(ProtocolMessage {
+            message_type: EthMessageID::Status,
+            message: EthMessage::StatusEth69(status),
+        });
+        let msg = ProtocolMessage::decode_message(EthVersion::Eth69, &mut &buf[..]).unwrap();
+        assert_eq!(msg.message, EthMessage::StatusEth69(status));
+    }
+
+    #[test]
+    fn test_receipts_decoded_by_version() {
+        let receipts = Receipts(vec![vec![Default::default()]]);
+        let message = EthMessage::Receipts(RequestPair { request_id: 1, message: receipts });
+        let buf = encode(ProtocolMessage { message_type: EthMessageID::Receipts, message });
+        let msg = ProtocolMessage::decode_message(EthVersion::Eth68, &mut &buf[..]).unwrap();
+        assert!(matches!(msg.message, EthMessage::Receipts(_)));
+
+        let receipts = Receipts69(vec![vec![Default::default()]]);
+        let message = EthMessage::Receipts69(RequestPair { request_id: 1, message: receipts });
+        let buf = encode(ProtocolMessage { message_type: EthMessageID::Receipts, message });
+        let msg = ProtocolMessage::decode_message(EthVersion::Eth69, &mut &buf[..]).unwrap();
+        assert!(matches!(msg.message, EthMessage::Receipts69(_)));
+    }
+
     #[test]
     fn request_pair_encode() {
         let request_pair = RequestPair { request_id: 1337, message: vec![5u8] };
@@ -3,7 +3,7 @@
 use alloy_primitives::B256;
 use alloy_rlp::{RlpDecodableWrapper, RlpEncodableWrapper};
 use reth_codecs_derive::add_arbitrary_tests;
-use reth_primitives::ReceiptWithBloom;
+use reth_primitives::{Receipt, ReceiptWithBloom};
 
 /// A request for transaction receipts from the given block hashes.
 #[derive(Clone, Debug, PartialEq, Eq, RlpEncodableWrapper, RlpDecodableWrapper, Default)]
@@ -26,9 +26,47 @@ pub struct Receipts(
     pub Vec<Vec<ReceiptWithBloom>>,
 );
 
+/// The `eth/69` response to [`GetReceipts`].
+///
+/// Unlike [`Receipts`], the receipts don't include the logs bloom, which the receiving side is
+/// expected to recompute from the logs, see also [EIP-7642](https://eips.ethereum.org/EIPS/eip-7642).
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodableWrapper, RlpDecodableWrapper, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct Receipts69(
+    /// Each receipt list should correspond to a block hash in the request.
+    pub Vec<Vec<Receipt>>,
+);
+
+impl From<Receipts> for Receipts69 {
+    fn from(receipts: Receipts) -> Self {
+        Self(
+            receipts
+                .0
+                .into_iter()
+                .map(|block| block.into_iter().map(|receipt| receipt.receipt).collect())
+                .collect(),
+        )
+    }
+}
+
+impl From<Receipts69> for Receipts {
+    /// Converts the bloom-less receipts, recomputing the logs bloom of every receipt.
+    fn from(receipts: Receipts69) -> Self {
+        Self(
+            receipts
+                .0
+                .into_iter()
+                .map(|block| block.into_iter().map(Receipt::with_bloom).collect())
+                .collect(),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{message::RequestPair, GetReceipts, Receipts};
+    use crate::{message::RequestPair, GetReceipts, Receipts, Receipts69};
     use alloy_primitives::{hex, Log};
     use alloy_rlp::{Decodable, Encodable};
     use reth_primitives::{Receipt, ReceiptWithBloom, TxType};
@@ -49,6 +87,43 @@ mod tests {
         assert_eq!(receipts, decoded);
     }
 
+    #[test]
+    #[allow(clippy::needless_update)]
+    fn receipts69_omit_bloom() {
+        let log = Log::new_unchecked(
+            hex!("0000000000000000000000000000000000000011").into(),
+            vec![hex!("000000000000000000000000000000000000000000000000000000000000dead").into()],
+            hex!("0100ff")[..].into(),
+        );
+        let receipt = Receipt {
+            tx_type: TxType::Eip1559,
+            success: true,
+            cumulative_gas_used: 21_000,
+            logs: vec![log],
+            ..Default::default()
+        };
+        let receipts = Receipts(vec![vec![receipt.clone().with_bloom()]]);
+
+        let receipts69 = Receipts69::from(receipts.clone());
+        assert_eq!(receipts69, Receipts69(vec![vec![receipt.clone()]]));
+
+        // the bloom is the only difference between the two encodings
+        let mut out = vec![];
+        receipts69.encode(&mut out);
+        assert!(receipts.length() - out.len() >= 256);
+
+        // receipts are encoded as `[tx-type, status, cumulative-gas, logs]`
+        let mut expected = vec![];
+        vec![vec![receipt]].encode(&mut expected);
+        assert_eq!(out, expected);
+
+        let decoded = Receipts69::decode(&mut out.as_slice()).unwrap();
+        assert_eq!(decoded, receipts69);
+
+        // the bloom is recomputed from the logs
+        assert_eq!(Receipts::from(decoded), receipts);
+    }
+
     #[test]
     // Test vector from: https://eips.ethereum.org/EIPS/eip-2481
     fn encode_get_receipts() {
@@ -0,0 +1,1179 @@
+//! Implements the snap protocol message types.
+//!
+//! See also <https://github.com/ethereum/devp2p/blob/master/caps/snap.md>
+
+use alloy_primitives::{keccak256, Bytes, B256, U256};
+use alloy_rlp::{
+    Decodable, Encodable, Header, RlpDecodable, RlpDecodableWrapper, RlpEncodable,
+    RlpEncodableWrapper, EMPTY_STRING_CODE,
+};
+use alloy_trie::{
+    nodes::{word_rlp, TrieNode, CHILD_INDEX_RANGE},
+    proof::ProofVerificationError,
+    HashBuilder, HashMap, Nibbles, EMPTY_ROOT_HASH,
+};
+use reth_codecs_derive::add_arbitrary_tests;
+use reth_primitives::KECCAK_EMPTY;
+
+use crate::DisconnectReason;
+
+/// Hard cap on the size of a snap response, in bytes, regardless of the soft limit requested.
+pub const MAX_SNAP_RESPONSE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// A request for the accounts of the state trie with the given root, starting at the `origin`
+/// account hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct GetAccountRange {
+    /// The root of the state trie to serve the accounts from.
+    pub root_hash: B256,
+    /// The account hash of the first account to retrieve.
+    pub starting_hash: B256,
+    /// The account hash after which to stop serving accounts.
+    pub limit_hash: B256,
+    /// Soft limit for the size of the response, in bytes.
+    pub response_bytes: u64,
+}
+
+/// The response to [`GetAccountRange`], containing consecutive accounts and the Merkle proofs of
+/// the range boundaries.
+///
+/// Use [`AccountRange::verify`] to check the response against the request it answers.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct AccountRange {
+    /// The accounts, in ascending account hash order.
+    pub accounts: Vec<AccountData>,
+    /// The trie nodes proving the first and the last account of the range.
+    pub proof: Vec<Bytes>,
+}
+
+/// An account of an [`AccountRange`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+pub struct AccountData {
+    /// The hash of the account address, i.e. the key of the account in the state trie.
+    pub hash: B256,
+    /// The account.
+    pub account: SnapAccount,
+}
+
+/// An account in the "slim" encoding of the snap protocol.
+///
+/// The slim encoding replaces the storage root of an account without storage and the code hash of
+/// an account without code with the empty string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+pub struct SnapAccount {
+    /// Account nonce.
+    pub nonce: u64,
+    /// Account balance.
+    pub balance: U256,
+    /// Root of the account's storage trie.
+    pub storage_root: B256,
+    /// Hash of the account's bytecode.
+    pub code_hash: B256,
+}
+
+impl Default for SnapAccount {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            balance: U256::ZERO,
+            storage_root: EMPTY_ROOT_HASH,
+            code_hash: KECCAK_EMPTY,
+        }
+    }
+}
+
+impl SnapAccount {
+    /// Returns the account in the encoding used as leaf value in the state trie.
+    pub fn trie_encoded(&self) -> Vec<u8> {
+        alloy_rlp::encode(TrieAccount {
+            nonce: self.nonce,
+            balance: self.balance,
+            storage_root: self.storage_root,
+            code_hash: self.code_hash,
+        })
+    }
+
+    fn slim_payload_length(&self) -> usize {
+        let slim_len = |hash: &B256, empty: B256| if *hash == empty { 1 } else { hash.length() };
+        self.nonce.length() +
+            self.balance.length() +
+            slim_len(&self.storage_root, EMPTY_ROOT_HASH) +
+            slim_len(&self.code_hash, KECCAK_EMPTY)
+    }
+}
+
+impl Encodable for SnapAccount {
+    fn encode(&self, out: &mut dyn bytes::BufMut) {
+        Header { list: true, payload_length: self.slim_payload_length() }.encode(out);
+        self.nonce.encode(out);
+        self.balance.encode(out);
+        for (hash, empty) in [(self.storage_root, EMPTY_ROOT_HASH), (self.code_hash, KECCAK_EMPTY)]
+        {
+            if hash == empty {
+                out.put_u8(EMPTY_STRING_CODE);
+            } else {
+                hash.encode(out);
+            }
+        }
+    }
+
+    fn length(&self) -> usize {
+        let payload_length = self.slim_payload_length();
+        payload_length + alloy_rlp::length_of_length(payload_length)
+    }
+}
+
+impl Decodable for SnapAccount {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let header = Header::decode(buf)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString)
+        }
+        let started_len = buf.len();
+
+        let nonce = u64::decode(buf)?;
+        let balance = U256::decode(buf)?;
+        let mut decode_slim_hash = |empty: B256| {
+            let hash = Bytes::decode(buf)?;
+            match hash.len() {
+                0 => Ok(empty),
+                32 => Ok(B256::from_slice(&hash)),
+                _ => Err(alloy_rlp::Error::UnexpectedLength),
+            }
+        };
+        let storage_root = decode_slim_hash(EMPTY_ROOT_HASH)?;
+        let code_hash = decode_slim_hash(KECCAK_EMPTY)?;
+
+        let consumed = started_len - buf.len();
+        if consumed != header.payload_length {
+            return Err(alloy_rlp::Error::ListLengthMismatch {
+                expected: header.payload_length,
+                got: consumed,
+            })
+        }
+
+        Ok(Self { nonce, balance, storage_root, code_hash })
+    }
+}
+
+/// The account as encoded in the state trie.
+#[derive(RlpEncodable)]
+struct TrieAccount {
+    nonce: u64,
+    balance: U256,
+    storage_root: B256,
+    code_hash: B256,
+}
+
+/// A request for the storage slots of the given accounts of the state trie with the given root.
+///
+/// The `starting_hash` and `limit_hash` only apply to the first account, all subsequent accounts
+/// are requested in full.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct GetStorageRanges {
+    /// The root of the state trie the accounts belong to.
+    pub root_hash: B256,
+    /// The hashes of the accounts to retrieve the storage of.
+    pub account_hashes: Vec<B256>,
+    /// The storage slot hash of the first slot to retrieve.
+    pub starting_hash: B256,
+    /// The storage slot hash after which to stop serving slots.
+    pub limit_hash: B256,
+    /// Soft limit for the size of the response, in bytes.
+    pub response_bytes: u64,
+}
+
+/// The response to [`GetStorageRanges`], containing the storage slots of consecutive requested
+/// accounts.
+///
+/// All accounts but the last are returned in full. If the storage of the last account is
+/// incomplete, the response carries the Merkle proof of its range boundaries.
+///
+/// Use [`StorageRanges::verify`] to check the response against the request it answers.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct StorageRanges {
+    /// The storage slots of each account, in ascending slot hash order.
+    pub slots: Vec<Vec<StorageData>>,
+    /// The trie nodes proving the first and the last slot of the last account, if its storage is
+    /// incomplete.
+    pub proof: Vec<Bytes>,
+}
+
+/// A storage slot of a [`StorageRanges`] response.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+pub struct StorageData {
+    /// The hash of the storage slot key, i.e. the key of the slot in the storage trie.
+    pub hash: B256,
+    /// The RLP encoded slot value, as stored in the storage trie.
+    pub data: Bytes,
+}
+
+/// A request for the bytecodes with the given code hashes.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct GetByteCodes {
+    /// The code hashes of the requested bytecodes.
+    pub hashes: Vec<B256>,
+    /// Soft limit for the size of the response, in bytes.
+    pub response_bytes: u64,
+}
+
+/// The response to [`GetByteCodes`], containing the requested bytecodes in request order.
+///
+/// Unknown bytecodes are omitted, and the response may stop early because of its size limit.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodableWrapper, RlpDecodableWrapper, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct ByteCodes(pub Vec<Bytes>);
+
+/// A request for the trie nodes at the given paths of the state trie with the given root.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodable, RlpDecodable, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct GetTrieNodes {
+    /// The root of the state trie to serve the nodes from.
+    pub root_hash: B256,
+    /// The requested paths, grouped by account.
+    ///
+    /// The first path of each group is the compact encoded path of a node in the account trie. If
+    /// the group contains more paths, they are paths in the storage trie of the account at the
+    /// first path, and only the storage trie nodes are requested.
+    pub paths: Vec<Vec<Bytes>>,
+    /// Soft limit for the size of the response, in bytes.
+    pub response_bytes: u64,
+}
+
+/// The response to [`GetTrieNodes`], containing the requested trie nodes in request order.
+///
+/// The response may stop early because of its size limit, or at the first unknown node.
+#[derive(Clone, Debug, PartialEq, Eq, RlpEncodableWrapper, RlpDecodableWrapper, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct TrieNodes(pub Vec<Bytes>);
+
+/// The path of a single requested trie node, see [`GetTrieNodes::paths`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrieNodePath<'a> {
+    /// The path of the node in the account trie, or of the account owning the storage trie.
+    pub account: &'a Bytes,
+    /// The path of the node in the storage trie of the account, if it's a storage trie node.
+    pub storage: Option<&'a Bytes>,
+}
+
+impl GetByteCodes {
+    /// Assembles the response to this request from the bytecodes returned by `lookup`.
+    ///
+    /// Unknown bytecodes are omitted. The response stops once it reaches the requested size, which
+    /// is capped at [`MAX_SNAP_RESPONSE_BYTES`].
+    pub fn respond(&self, mut lookup: impl FnMut(&B256) -> Option<Bytes>) -> ByteCodes {
+        let limit = self.response_bytes.min(MAX_SNAP_RESPONSE_BYTES) as usize;
+        let mut codes = Vec::new();
+        let mut size = 0;
+        for hash in &self.hashes {
+            if size >= limit {
+                break
+            }
+            if let Some(code) = lookup(hash) {
+                size += code.len();
+                codes.push(code);
+            }
+        }
+        ByteCodes(codes)
+    }
+}
+
+impl ByteCodes {
+    /// Matches the returned bytecodes to the code hashes of the request they answer.
+    ///
+    /// Returns an error if a bytecode wasn't requested or isn't in request order.
+    pub fn match_request(
+        &self,
+        request: &GetByteCodes,
+    ) -> Result<Vec<(B256, Bytes)>, UnrequestedItemsError> {
+        let mut requested = request.hashes.iter();
+        self.0
+            .iter()
+            .map(|code| {
+                let hash = keccak256(code);
+                requested
+                    .find(|requested| **requested == hash)
+                    .map(|_| (hash, code.clone()))
+                    .ok_or(UnrequestedItemsError)
+            })
+            .collect()
+    }
+}
+
+impl GetTrieNodes {
+    /// Returns the paths of all requested trie nodes, in request order.
+    pub fn node_paths(&self) -> impl Iterator<Item = TrieNodePath<'_>> + '_ {
+        self.paths.iter().filter_map(|group| group.split_first()).flat_map(|(account, storage)| {
+            let account_node =
+                storage.is_empty().then_some(TrieNodePath { account, storage: None });
+            account_node.into_iter().chain(
+                storage.iter().map(move |slot| TrieNodePath { account, storage: Some(slot) }),
+            )
+        })
+    }
+
+    /// Assembles the response to this request from the trie nodes returned by `lookup`.
+    ///
+    /// The response stops at the first unknown node, or once it reaches the requested size, which
+    /// is capped at [`MAX_SNAP_RESPONSE_BYTES`].
+    pub fn respond(&self, mut lookup: impl FnMut(TrieNodePath<'_>) -> Option<Bytes>) -> TrieNodes {
+        let limit = self.response_bytes.min(MAX_SNAP_RESPONSE_BYTES) as usize;
+        let mut nodes = Vec::new();
+        let mut size = 0;
+        for path in self.node_paths() {
+            if size >= limit {
+                break
+            }
+            let Some(node) = lookup(path) else { break };
+            size += node.len();
+            nodes.push(node);
+        }
+        TrieNodes(nodes)
+    }
+}
+
+impl TrieNodes {
+    /// Matches the returned trie nodes to the paths of the request they answer.
+    ///
+    /// Returns an error if there are more nodes than requested.
+    pub fn match_request<'a>(
+        &self,
+        request: &'a GetTrieNodes,
+    ) -> Result<Vec<(TrieNodePath<'a>, Bytes)>, UnrequestedItemsError> {
+        let mut paths = request.node_paths();
+        self.0
+            .iter()
+            .map(|node| paths.next().map(|path| (path, node.clone())).ok_or(UnrequestedItemsError))
+            .collect()
+    }
+}
+
+impl AccountRange {
+    /// Verifies that this is a valid response to the given request.
+    ///
+    /// The accounts must be in strictly ascending hash order, starting at or after the requested
+    /// origin. Only the last account may lie past the requested limit, which is how the range is
+    /// closed if there are no accounts up to the limit. If the response carries no proof, the
+    /// accounts must make up the entire state trie. Otherwise, the proof must prove that the
+    /// accounts are exactly the accounts of the state trie from the origin to the last account,
+    /// or that there are no accounts at or after the origin if the response is empty.
+    ///
+    /// Peers sending responses that fail verification should be disconnected, see
+    /// [`RangeProofError::disconnect_reason`].
+    pub fn verify(&self, request: &GetAccountRange) -> Result<(), RangeProofError> {
+        let leaves = self
+            .accounts
+            .iter()
+            .map(|account| (account.hash, account.account.trie_encoded()))
+            .collect::<Vec<_>>();
+        verify_range(
+            request.root_hash,
+            request.starting_hash,
+            request.limit_hash,
+            &leaves,
+            &self.proof,
+        )
+    }
+}
+
+impl StorageRanges {
+    /// Verifies that this is a valid response to the given request.
+    ///
+    /// `storage_roots` are the storage roots of the requested accounts, in the order of
+    /// [`GetStorageRanges::account_hashes`].
+    ///
+    /// The slots of each account must be in strictly ascending hash order, the slots of the first
+    /// account starting at or after the requested origin. The storage of all accounts but the last
+    /// must be complete. The storage of the last account must be complete as well if the response
+    /// carries no proof, otherwise the proof must prove that its slots are exactly the slots of
+    /// its storage trie from the origin to the last slot. Only the last slot of the last account
+    /// may lie past the requested limit.
+    ///
+    /// Peers sending responses that fail verification should be disconnected, see
+    /// [`RangeProofError::disconnect_reason`].
+    pub fn verify(
+        &self,
+        request: &GetStorageRanges,
+        storage_roots: &[B256],
+    ) -> Result<(), RangeProofError> {
+        if self.slots.len() > request.account_hashes.len() || self.slots.len() > storage_roots.len()
+        {
+            return Err(RangeProofError::UnrequestedStorage)
+        }
+
+        for (idx, (slots, storage_root)) in self.slots.iter().zip(storage_roots).enumerate() {
+            let origin = if idx == 0 { request.starting_hash } else { B256::ZERO };
+            let last = idx == self.slots.len() - 1;
+            // the limit only applies to the account the response is cut off at
+            let (limit, proof) = if last {
+                (request.limit_hash, &self.proof[..])
+            } else {
+                (B256::repeat_byte(0xff), &[][..])
+            };
+            let leaves =
+                slots.iter().map(|slot| (slot.hash, slot.data.to_vec())).collect::<Vec<_>>();
+            verify_range(*storage_root, origin, limit, &leaves, proof)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifies that the leaves, given as `(key, value)` pairs, are a range of the trie with the given
+/// root, starting at or after `origin`.
+///
+/// Without a proof, the leaves must make up the entire trie. Otherwise, the proof must contain the
+/// nodes on the paths to `origin` and to the last leaf. The trie is then rebuilt from the subtries
+/// the proof references to the left and to the right of these paths, plus all the leaves, which
+/// must hash to the root. This binds every leaf of the range to the root, not just its edges, see
+/// also geth's `VerifyRangeProof`. For an empty range, the proof must show that there are no
+/// leaves at or after `origin`.
+fn verify_range(
+    root: B256,
+    origin: B256,
+    limit: B256,
+    leaves: &[(B256, Vec<u8>)],
+    proof: &[Bytes],
+) -> Result<(), RangeProofError> {
+    if let Some((first, _)) = leaves.first() {
+        if *first < origin {
+            return Err(RangeProofError::BeforeOrigin(*first))
+        }
+    }
+    if leaves.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(RangeProofError::NotAscending)
+    }
+    // only the last leaf may lie past the limit, it closes the range
+    if let Some((key, _)) = leaves.iter().rev().nth(1).filter(|(key, _)| *key > limit) {
+        return Err(RangeProofError::BeyondLimit(*key))
+    }
+
+    let mut entries = leaves
+        .iter()
+        .map(|(key, value)| (Nibbles::unpack(key), RangeEntry::Leaf(value.clone())))
+        .collect::<Vec<_>>();
+    if !proof.is_empty() {
+        let nodes: HashMap<B256, &Bytes> =
+            proof.iter().map(|node| (keccak256(node), node)).collect();
+        collect_subtries(root, &Nibbles::unpack(origin), Side::Left, &nodes, &mut entries)?;
+        if let Some((last, _)) = leaves.last() {
+            collect_subtries(root, &Nibbles::unpack(last), Side::Right, &nodes, &mut entries)?;
+        }
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        if entries.windows(2).any(|pair| pair[1].0.starts_with(&pair[0].0)) {
+            return Err(RangeProofError::MalformedProof)
+        }
+    }
+
+    let mut hash_builder = HashBuilder::default();
+    for (path, entry) in entries {
+        match entry {
+            RangeEntry::Leaf(value) => hash_builder.add_leaf(path, &value),
+            RangeEntry::Subtrie(hash) => hash_builder.add_branch(path, hash, false),
+        }
+    }
+    let got = hash_builder.root();
+    if got != root {
+        return Err(ProofVerificationError::RootMismatch { got, expected: root }.into())
+    }
+
+    Ok(())
+}
+
+/// An entry of the trie rebuilt by [`verify_range`], keyed by its path.
+#[derive(Debug)]
+enum RangeEntry {
+    /// A leaf with the given value.
+    Leaf(Vec<u8>),
+    /// A subtrie with the given root hash.
+    Subtrie(B256),
+}
+
+/// The side of a proven path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    /// Keys before the path.
+    Left,
+    /// Keys after the path.
+    Right,
+}
+
+impl Side {
+    /// Returns whether `key` lies on this side of `path`.
+    fn contains(self, key: &[u8], path: &[u8]) -> bool {
+        match self {
+            Self::Left => key < path,
+            Self::Right => key > path,
+        }
+    }
+}
+
+/// Walks the proof along the path to the given key and collects all subtries on the given side of
+/// the path into `entries`.
+///
+/// The walk ends at the first node that isn't part of the proof, or where the key diverges from
+/// the trie. Everything between the paths of a range is left out, and has to be made up by the
+/// leaves of the range.
+fn collect_subtries(
+    root: B256,
+    key: &Nibbles,
+    side: Side,
+    nodes: &HashMap<B256, &Bytes>,
+    entries: &mut Vec<(Nibbles, RangeEntry)>,
+) -> Result<(), RangeProofError> {
+    let mut path = Nibbles::default();
+    let mut next = word_rlp(&root);
+    loop {
+        let node = match child_hash(&next) {
+            Some(hash) => match nodes.get(&hash) {
+                Some(node) => &node[..],
+                None => return Ok(()),
+            },
+            None => &next[..],
+        };
+        let node = TrieNode::decode(&mut &node[..]).map_err(ProofVerificationError::Rlp)?;
+        match node {
+            TrieNode::Branch(branch) => {
+                let Some(nibble) = key.get(path.len()).copied() else { return Ok(()) };
+                let children = CHILD_INDEX_RANGE
+                    .filter(|i| branch.state_mask.is_bit_set(*i))
+                    .zip(branch.stack.iter());
+                let mut on_path = None;
+                for (i, child) in children {
+                    if i == nibble {
+                        on_path = Some(child.clone());
+                    } else if side.contains(&[i], &[nibble]) {
+                        let mut child_path = path.clone();
+                        child_path.push(i);
+                        collect_subtrie(child_path, child, entries)?;
+                    }
+                }
+                let Some(child) = on_path else { return Ok(()) };
+                path.push(nibble);
+                next = child;
+            }
+            TrieNode::Extension(extension) => {
+                let mut extended = path.clone();
+                extended.extend_from_slice(&extension.key);
+                if key.starts_with(&extended) {
+                    path = extended;
+                    next = extension.child;
+                } else {
+                    if side.contains(&extended, key) {
+                        collect_subtrie(extended, &extension.child, entries)?;
+                    }
+                    return Ok(())
+                }
+            }
+            TrieNode::Leaf(leaf) => {
+                let mut leaf_key = path;
+                leaf_key.extend_from_slice(&leaf.key);
+                if side.contains(&leaf_key, key) {
+                    push_leaf(leaf_key, leaf.value, entries)?;
+                }
+                return Ok(())
+            }
+            TrieNode::EmptyRoot => return Ok(()),
+        }
+    }
+}
+
+/// Collects the subtrie at the given path, referenced by its RLP encoded hash or by the node
+/// itself if it's embedded in its parent.
+fn collect_subtrie(
+    path: Nibbles,
+    node: &[u8],
+    entries: &mut Vec<(Nibbles, RangeEntry)>,
+) -> Result<(), RangeProofError> {
+    if let Some(hash) = child_hash(node) {
+        // a subtrie can't start at the root or at the depth of a leaf
+        if path.is_empty() || path.len() >= 2 * B256::len_bytes() {
+            return Err(RangeProofError::MalformedProof)
+        }
+        entries.push((path, RangeEntry::Subtrie(hash)));
+        return Ok(())
+    }
+
+    match TrieNode::decode(&mut &node[..]).map_err(ProofVerificationError::Rlp)? {
+        TrieNode::Branch(branch) => {
+            let children = CHILD_INDEX_RANGE
+                .filter(|i| branch.state_mask.is_bit_set(*i))
+                .zip(branch.stack.iter());
+            for (i, child) in children {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                collect_subtrie(child_path, child, entries)?;
+            }
+        }
+        TrieNode::Extension(extension) => {
+            let mut child_path = path;
+            child_path.extend_from_slice(&extension.key);
+            collect_subtrie(child_path, &extension.child, entries)?;
+        }
+        TrieNode::Leaf(leaf) => {
+            let mut leaf_key = path;
+            leaf_key.extend_from_slice(&leaf.key);
+            push_leaf(leaf_key, leaf.value, entries)?;
+        }
+        TrieNode::EmptyRoot => {}
+    }
+    Ok(())
+}
+
+/// Collects a leaf of a subtrie, which must be at the depth of a full key.
+fn push_leaf(
+    key: Nibbles,
+    value: Vec<u8>,
+    entries: &mut Vec<(Nibbles, RangeEntry)>,
+) -> Result<(), RangeProofError> {
+    if key.len() != 2 * B256::len_bytes() {
+        return Err(RangeProofError::MalformedProof)
+    }
+    entries.push((key, RangeEntry::Leaf(value)));
+    Ok(())
+}
+
+/// Returns the hash a child reference refers to, or `None` if the child is embedded in place.
+fn child_hash(child: &[u8]) -> Option<B256> {
+    (child.len() == B256::len_bytes() + 1).then(|| B256::from_slice(&child[1..]))
+}
+
+/// Errors when verifying an [`AccountRange`] or [`StorageRanges`] response.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum RangeProofError {
+    /// The accounts or slots are not in strictly ascending hash order.
+    #[error("range is not in ascending hash order")]
+    NotAscending,
+    /// The first account or slot precedes the requested origin.
+    #[error("{0} precedes the requested origin")]
+    BeforeOrigin(B256),
+    /// An account or slot other than the last one lies past the requested limit.
+    #[error("{0} lies past the requested limit")]
+    BeyondLimit(B256),
+    /// The response contains storage of more accounts than requested.
+    #[error("storage of unrequested accounts")]
+    UnrequestedStorage,
+    /// The proof references nodes that can't be part of a trie with 32 byte keys.
+    #[error("malformed range proof")]
+    MalformedProof,
+    /// The proof doesn't connect the range to the requested root.
+    #[error("invalid range proof: {0}")]
+    InvalidProof(Box<ProofVerificationError>),
+}
+
+impl RangeProofError {
+    /// Returns the reason to disconnect the peer that sent the invalid response with.
+    ///
+    /// An invalid response is always a breach of protocol.
+    pub const fn disconnect_reason(&self) -> DisconnectReason {
+        DisconnectReason::ProtocolBreach
+    }
+}
+
+impl From<ProofVerificationError> for RangeProofError {
+    fn from(err: ProofVerificationError) -> Self {
+        Self::InvalidProof(Box::new(err))
+    }
+}
+
+/// Error when a snap response contains items that weren't requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("response contains items that weren't requested")]
+pub struct UnrequestedItemsError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RequestPair;
+    use alloy_trie::proof::ProofRetainer;
+
+    /// Returns the proof of the given keys in the trie with the given sorted leaves, and its root.
+    fn trie_with_proof(leaves: &[(B256, Vec<u8>)], proven: &[B256]) -> (B256, Vec<Bytes>) {
+        let targets = proven.iter().map(Nibbles::unpack).collect();
+        let mut hash_builder =
+            HashBuilder::default().with_proof_retainer(ProofRetainer::new(targets));
+        for (key, value) in leaves {
+            hash_builder.add_leaf(Nibbles::unpack(key), value);
+        }
+        let root = hash_builder.root();
+        let proof = hash_builder
+            .take_proof_nodes()
+            .into_nodes_sorted()
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect();
+        (root, proof)
+    }
+
+    /// Returns sorted storage slots with non-zero values.
+    fn storage(num_slots: u64) -> Vec<StorageData> {
+        let mut slots = (1..=num_slots)
+            .map(|value| StorageData {
+                hash: B256::random(),
+                data: alloy_rlp::encode(U256::from(value)).into(),
+            })
+            .collect::<Vec<_>>();
+        slots.sort_by_key(|slot| slot.hash);
+        slots
+    }
+
+    fn leaves(slots: &[StorageData]) -> Vec<(B256, Vec<u8>)> {
+        slots.iter().map(|slot| (slot.hash, slot.data.to_vec())).collect()
+    }
+
+    fn account(nonce: u64) -> SnapAccount {
+        SnapAccount { nonce, balance: U256::from(nonce * 1000), ..Default::default() }
+    }
+
+    /// Returns sorted accounts, the root of the trie containing them, and the proof of the
+    /// accounts with the given indices.
+    fn accounts_with_proof(
+        num_accounts: u64,
+        proven: &[usize],
+    ) -> (Vec<AccountData>, B256, Vec<Bytes>) {
+        let mut accounts = (0..num_accounts)
+            .map(|nonce| AccountData { hash: B256::random(), account: account(nonce) })
+            .collect::<Vec<_>>();
+        accounts.sort_by_key(|account| account.hash);
+
+        let proven = proven.iter().map(|i| accounts[*i].hash).collect::<Vec<_>>();
+        accounts_with_proof_of(&accounts, &proven)
+    }
+
+    /// Returns the accounts, the root of the trie containing them, and the proof of the given
+    /// keys, which don't need to exist.
+    fn accounts_with_proof_of(
+        accounts: &[AccountData],
+        proven: &[B256],
+    ) -> (Vec<AccountData>, B256, Vec<Bytes>) {
+        let leaves = accounts
+            .iter()
+            .map(|account| (account.hash, account.account.trie_encoded()))
+            .collect::<Vec<_>>();
+        let (root, proof) = trie_with_proof(&leaves, proven);
+        (accounts.to_vec(), root, proof)
+    }
+
+    #[test]
+    fn get_account_range_roundtrip() {
+        let request = RequestPair {
+            request_id: 1337,
+            message: GetAccountRange {
+                root_hash: B256::random(),
+                starting_hash: B256::ZERO,
+                limit_hash: B256::repeat_byte(0xff),
+                response_bytes: 512 * 1024,
+            },
+        };
+        let encoded = alloy_rlp::encode(&request);
+        assert_eq!(RequestPair::<GetAccountRange>::decode(&mut &encoded[..]).unwrap(), request);
+    }
+
+    #[test]
+    fn account_range_roundtrip() {
+        let (accounts, _, proof) = accounts_with_proof(10, &[0, 9]);
+        let mut accounts = accounts;
+        accounts[0].account.storage_root = B256::random();
+        accounts[1].account.code_hash = B256::random();
+
+        let response = RequestPair { request_id: 1337, message: AccountRange { accounts, proof } };
+        let encoded = alloy_rlp::encode(&response);
+        assert_eq!(RequestPair::<AccountRange>::decode(&mut &encoded[..]).unwrap(), response);
+    }
+
+    #[test]
+    fn snap_account_slim_encoding() {
+        // empty storage root and code hash are encoded as empty strings
+        let encoded = alloy_rlp::encode(account(1));
+        assert_eq!(encoded, alloy_primitives::hex!("c6018203e88080"));
+        assert_eq!(SnapAccount::decode(&mut &encoded[..]).unwrap(), account(1));
+        assert_eq!(account(1).length(), encoded.len());
+    }
+
+    #[test]
+    fn verify_account_range_proof() {
+        let (accounts, root, proof) = accounts_with_proof(100, &[20, 59]);
+        let request = GetAccountRange {
+            root_hash: root,
+            starting_hash: accounts[20].hash,
+            limit_hash: B256::repeat_byte(0xff),
+            response_bytes: 512 * 1024,
+        };
+
+        let response = AccountRange { accounts: accounts[20..60].to_vec(), proof: proof.clone() };
+        assert_eq!(response.verify(&request), Ok(()));
+
+        // the entire trie doesn't need a proof
+        let full = AccountRange { accounts: accounts.clone(), proof: vec![] };
+        assert_eq!(full.verify(&GetAccountRange { starting_hash: B256::ZERO, ..request }), Ok(()));
+
+        // tampered root node, nodes that aren't referenced by the proof are ignored
+        let mut tampered_proof = proof.clone();
+        let node = tampered_proof.first_mut().unwrap();
+        let mut tampered_node = node.to_vec();
+        *tampered_node.last_mut().unwrap() ^= 0x01;
+        *node = tampered_node.into();
+        let tampered = AccountRange { accounts: accounts[20..60].to_vec(), proof: tampered_proof };
+        let err = tampered.verify(&request).unwrap_err();
+        assert!(matches!(err, RangeProofError::InvalidProof(_)), "{err:?}");
+        assert_eq!(err.disconnect_reason(), DisconnectReason::ProtocolBreach);
+
+        // tampered edge account
+        let mut tampered_accounts = accounts[20..60].to_vec();
+        tampered_accounts[0].account.balance += U256::from(1);
+        let tampered = AccountRange { accounts: tampered_accounts, proof: proof.clone() };
+        assert!(matches!(tampered.verify(&request), Err(RangeProofError::InvalidProof(_))));
+
+        // different state root
+        let other_root = GetAccountRange { root_hash: B256::random(), ..request };
+        assert!(matches!(response.verify(&other_root), Err(RangeProofError::InvalidProof(_))));
+
+        // not in ascending order
+        let mut unordered = accounts[20..60].to_vec();
+        unordered.swap(1, 2);
+        let unordered = AccountRange { accounts: unordered, proof: proof.clone() };
+        assert_eq!(unordered.verify(&request), Err(RangeProofError::NotAscending));
+
+        // starts before the origin
+        let before_origin = AccountRange { accounts: accounts[19..60].to_vec(), proof };
+        assert_eq!(
+            before_origin.verify(&request),
+            Err(RangeProofError::BeforeOrigin(accounts[19].hash))
+        );
+
+        // accounts other than the last past the limit
+        let limited = GetAccountRange { limit_hash: accounts[58].hash, ..request };
+        assert_eq!(response.verify(&limited), Ok(()));
+        let limited = GetAccountRange { limit_hash: accounts[57].hash, ..request };
+        assert_eq!(response.verify(&limited), Err(RangeProofError::BeyondLimit(accounts[58].hash)));
+    }
+
+    #[test]
+    fn verify_account_range_interior() {
+        let (accounts, root, proof) = accounts_with_proof(100, &[20, 59]);
+        let request = GetAccountRange {
+            root_hash: root,
+            starting_hash: accounts[20].hash,
+            limit_hash: B256::repeat_byte(0xff),
+            response_bytes: 512 * 1024,
+        };
+
+        // tampered interior account
+        let mut tampered = accounts[20..60].to_vec();
+        tampered[17].account.nonce += 1;
+        let tampered = AccountRange { accounts: tampered, proof: proof.clone() };
+        assert!(matches!(tampered.verify(&request), Err(RangeProofError::InvalidProof(_))));
+
+        // dropped interior account
+        let mut dropped = accounts[20..60].to_vec();
+        dropped.remove(17);
+        let dropped = AccountRange { accounts: dropped, proof: proof.clone() };
+        assert!(matches!(dropped.verify(&request), Err(RangeProofError::InvalidProof(_))));
+
+        // inserted interior account
+        let mut inserted = accounts[20..60].to_vec();
+        let mut hash = inserted[17].hash;
+        hash.0[31] ^= 0x01;
+        inserted.push(AccountData { hash, account: account(1000) });
+        inserted.sort_by_key(|account| account.hash);
+        let inserted = AccountRange { accounts: inserted, proof };
+        assert!(matches!(inserted.verify(&request), Err(RangeProofError::InvalidProof(_))));
+
+        // the origin doesn't need to exist
+        let origin = B256::from(U256::from_be_bytes(accounts[20].hash.0) - U256::from(1));
+        let (_, _, proof) = accounts_with_proof_of(&accounts, &[origin, accounts[59].hash]);
+        let request = GetAccountRange { starting_hash: origin, ..request };
+        let response = AccountRange { accounts: accounts[20..60].to_vec(), proof: proof.clone() };
+        assert_eq!(response.verify(&request), Ok(()));
+
+        // but the accounts between the origin and the first account must not be skipped
+        let skipped = AccountRange { accounts: accounts[21..60].to_vec(), proof };
+        assert!(matches!(skipped.verify(&request), Err(RangeProofError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn verify_empty_account_range() {
+        let (accounts, root, _) = accounts_with_proof(100, &[]);
+        let origin = B256::from(U256::from_be_bytes(accounts[99].hash.0) + U256::from(1));
+        let request = GetAccountRange {
+            root_hash: root,
+            starting_hash: origin,
+            limit_hash: B256::repeat_byte(0xff),
+            response_bytes: 512 * 1024,
+        };
+
+        // proof that there are no accounts after the origin
+        let (_, _, proof) = accounts_with_proof_of(&accounts, &[origin]);
+        let empty = AccountRange { accounts: vec![], proof };
+        assert_eq!(empty.verify(&request), Ok(()));
+
+        // an empty response with the proof of another account proves nothing
+        let (_, _, proof) = accounts_with_proof_of(&accounts, &[accounts[50].hash]);
+        let empty = AccountRange { accounts: vec![], proof: proof.clone() };
+        let request = GetAccountRange { starting_hash: accounts[50].hash, ..request };
+        assert!(matches!(empty.verify(&request), Err(RangeProofError::InvalidProof(_))));
+        let origin = GetAccountRange { starting_hash: accounts[40].hash, ..request };
+        assert!(matches!(empty.verify(&origin), Err(RangeProofError::InvalidProof(_))));
+
+        // nor does one without the state root
+        let empty = AccountRange { accounts: vec![], proof: proof[1..].to_vec() };
+        assert!(matches!(empty.verify(&request), Err(RangeProofError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn storage_ranges_single_full_account() {
+        let slots = storage(20);
+        let (storage_root, _) = trie_with_proof(&leaves(&slots), &[]);
+        let request = RequestPair {
+            request_id: 1337,
+            message: GetStorageRanges {
+                root_hash: B256::random(),
+                account_hashes: vec![B256::random()],
+                starting_hash: B256::ZERO,
+                limit_hash: B256::repeat_byte(0xff),
+                response_bytes: 512 * 1024,
+            },
+        };
+        let encoded = alloy_rlp::encode(&request);
+        assert_eq!(RequestPair::<GetStorageRanges>::decode(&mut &encoded[..]).unwrap(), request);
+
+        let response = RequestPair {
+            request_id: 1337,
+            message: StorageRanges { slots: vec![slots], proof: vec![] },
+        };
+        let encoded = alloy_rlp::encode(&response);
+        let decoded = RequestPair::<StorageRanges>::decode(&mut &encoded[..]).unwrap();
+        assert_eq!(decoded, response);
+        assert_eq!(decoded.message.verify(&request.message, &[storage_root]), Ok(()));
+
+        // incomplete storage without a proof
+        let mut incomplete = decoded.message;
+        incomplete.slots[0].pop();
+        let err = incomplete.verify(&request.message, &[storage_root]).unwrap_err();
+        assert!(
+            matches!(&err, RangeProofError::InvalidProof(err) if matches!(**err, ProofVerificationError::RootMismatch { .. })),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn storage_ranges_partial_last_account() {
+        let full_slots = storage(10);
+        let (full_root, _) = trie_with_proof(&leaves(&full_slots), &[]);
+
+        let last_slots = storage(50);
+        let partial = last_slots[..30].to_vec();
+        let (last_root, proof) =
+            trie_with_proof(&leaves(&last_slots), &[partial[0].hash, partial[29].hash]);
+
+        let request = GetStorageRanges {
+            root_hash: B256::random(),
+            account_hashes: vec![B256::random(), B256::random(), B256::random()],
+            starting_hash: B256::ZERO,
+            limit_hash: B256::repeat_byte(0xff),
+            response_bytes: 512 * 1024,
+        };
+        let storage_roots = [full_root, last_root, B256::random()];
+
+        let response = RequestPair {
+            request_id: 1337,
+            message: StorageRanges { slots: vec![full_slots, partial], proof },
+        };
+        let encoded = alloy_rlp::encode(&response);
+        let decoded = RequestPair::<StorageRanges>::decode(&mut &encoded[..]).unwrap();
+        assert_eq!(decoded, response);
+        assert_eq!(decoded.message.verify(&request, &storage_roots), Ok(()));
+
+        // tampered slot of the partial account
+        let mut tampered = decoded.message.clone();
+        tampered.slots[1][29].data = alloy_rlp::encode(U256::MAX).into();
+        assert!(matches!(
+            tampered.verify(&request, &storage_roots),
+            Err(RangeProofError::InvalidProof(_))
+        ));
+
+        // tampered interior slot of the partial account
+        let mut tampered = decoded.message.clone();
+        tampered.slots[1][15].data = alloy_rlp::encode(U256::MAX).into();
+        assert!(matches!(
+            tampered.verify(&request, &storage_roots),
+            Err(RangeProofError::InvalidProof(_))
+        ));
+
+        // dropped interior slot of the partial account
+        let mut tampered = decoded.message.clone();
+        tampered.slots[1].remove(15);
+        assert!(matches!(
+            tampered.verify(&request, &storage_roots),
+            Err(RangeProofError::InvalidProof(_))
+        ));
+
+        // slots of the partial account other than the last past the limit
+        let limited = GetStorageRanges { limit_hash: last_slots[27].hash, ..request.clone() };
+        assert_eq!(
+            decoded.message.verify(&limited, &storage_roots),
+            Err(RangeProofError::BeyondLimit(last_slots[28].hash))
+        );
+
+        // only the last account may be incomplete
+        let mut tampered = decoded.message.clone();
+        tampered.slots[0].pop();
+        assert!(matches!(
+            tampered.verify(&request, &storage_roots),
+            Err(RangeProofError::InvalidProof(_))
+        ));
+
+        // slots out of order
+        let mut tampered = decoded.message.clone();
+        tampered.slots[1].swap(3, 4);
+        assert_eq!(tampered.verify(&request, &storage_roots), Err(RangeProofError::NotAscending));
+
+        // storage of more accounts than requested
+        let mut tampered = decoded.message;
+        tampered.slots.extend([vec![], vec![]]);
+        assert_eq!(
+            tampered.verify(&request, &storage_roots),
+            Err(RangeProofError::UnrequestedStorage)
+        );
+    }
+
+    #[test]
+    fn byte_codes_roundtrip_and_match() {
+        let codes = (0..5u8).map(|i| Bytes::from(vec![i; 100])).collect::<Vec<_>>();
+        let unknown = B256::random();
+        let request = RequestPair {
+            request_id: 1337,
+            message: GetByteCodes {
+                hashes: vec![
+                    keccak256(&codes[0]),
+                    unknown,
+                    keccak256(&codes[1]),
+                    keccak256(&codes[2]),
+                ],
+                response_bytes: 512 * 1024,
+            },
+        };
+        let encoded = alloy_rlp::encode(&request);
+        assert_eq!(RequestPair::<GetByteCodes>::decode(&mut &encoded[..]).unwrap(), request);
+
+        let lookup = |hash: &B256| codes.iter().find(|code| keccak256(code) == *hash).cloned();
+
+        // unknown code is omitted
+        let response = RequestPair { request_id: 1337, message: request.message.respond(lookup) };
+        assert_eq!(response.message, ByteCodes(codes[..3].to_vec()));
+        let encoded = alloy_rlp::encode(&response);
+        assert_eq!(RequestPair::<ByteCodes>::decode(&mut &encoded[..]).unwrap(), response);
+        assert_eq!(
+            response.message.match_request(&request.message).unwrap(),
+            codes[..3].iter().map(|code| (keccak256(code), code.clone())).collect::<Vec<_>>()
+        );
+
+        // size capped response returns fewer codes
+        let capped = GetByteCodes { response_bytes: 150, ..request.message.clone() };
+        let response = capped.respond(lookup);
+        assert_eq!(response, ByteCodes(codes[..2].to_vec()));
+        assert_eq!(response.match_request(&capped).unwrap().len(), 2);
+
+        // codes that weren't requested, or not in request order
+        assert_eq!(
+            ByteCodes(vec![codes[3].clone()]).match_request(&request.message),
+            Err(UnrequestedItemsError)
+        );
+        assert_eq!(
+            ByteCodes(vec![codes[1].clone(), codes[0].clone()]).match_request(&request.message),
+            Err(UnrequestedItemsError)
+        );
+    }
+
+    #[test]
+    fn trie_nodes_roundtrip_and_match() {
+        let account_path = Bytes::from_static(&[0x01]);
+        let storage_account = Bytes::from(B256::random().to_vec());
+        let slot_paths = [Bytes::from_static(&[0x00]), Bytes::from_static(&[0x12])];
+        let request = RequestPair {
+            request_id: 1337,
+            message: GetTrieNodes {
+                root_hash: B256::random(),
+                paths: vec![
+                    vec![account_path.clone()],
+                    vec![storage_account.clone(), slot_paths[0].clone(), slot_paths[1].clone()],
+                ],
+                response_bytes: 512 * 1024,
+            },
+        };
+        let encoded = alloy_rlp::encode(&request);
+        assert_eq!(RequestPair::<GetTrieNodes>::decode(&mut &encoded[..]).unwrap(), request);
+
+        let expected_paths = vec![
+            TrieNodePath { account: &account_path, storage: None },
+            TrieNodePath { account: &storage_account, storage: Some(&slot_paths[0]) },
+            TrieNodePath { account: &storage_account, storage: Some(&slot_paths[1]) },
+        ];
+        assert_eq!(request.message.node_paths().collect::<Vec<_>>(), expected_paths);
+
+        let node = |path: TrieNodePath<'_>| {
+            let mut node = path.account.to_vec();
+            node.extend(path.storage.into_iter().flatten());
+            node.resize(100, 0xaa);
+            Bytes::from(node)
+        };
+
+        let response = RequestPair {
+            request_id: 1337,
+            message: request.message.respond(|path| Some(node(path))),
+        };
+        assert_eq!(response.message.0.len(), 3);
+        let encoded = alloy_rlp::encode(&response);
+        assert_eq!(RequestPair::<TrieNodes>::decode(&mut &encoded[..]).unwrap(), response);
+        let matched = response.message.match_request(&request.message).unwrap();
+        assert_eq!(matched.iter().map(|(path, _)| *path).collect::<Vec<_>>(), expected_paths);
+        assert!(matched.iter().all(|(path, data)| *data == node(*path)));
+
+        // size capped response returns fewer nodes
+        let capped = GetTrieNodes { response_bytes: 150, ..request.message.clone() };
+        let response = capped.respond(|path| Some(node(path)));
+        assert_eq!(response.0.len(), 2);
+        assert_eq!(response.match_request(&capped).unwrap().len(), 2);
+
+        // partial response stops at the first unknown node
+        let response = request.message.respond(|path| path.storage.is_none().then(|| node(path)));
+        assert_eq!(response.0.len(), 1);
+        assert_eq!(response.match_request(&request.message).unwrap()[0].0, expected_paths[0]);
+
+        // more nodes than requested
+        let mut too_many = response;
+        too_many.0.extend([
+            node(expected_paths[1]),
+            node(expected_paths[2]),
+            node(expected_paths[2]),
+        ]);
+        assert_eq!(too_many.match_request(&request.message), Err(UnrequestedItemsError));
+    }
+}
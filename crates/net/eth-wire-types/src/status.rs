@@ -2,10 +2,10 @@ use crate::EthVersion;
 use alloy_chains::{Chain, NamedChain};
 use alloy_genesis::Genesis;
 use alloy_primitives::{hex, B256, U256};
-use alloy_rlp::{RlpDecodable, RlpEncodable};
+use alloy_rlp::{Buf, Decodable, Header, RlpDecodable, RlpEncodable};
 use reth_chainspec::{ChainSpec, EthChainSpec, Hardforks, MAINNET};
 use reth_codecs_derive::add_arbitrary_tests;
-use reth_primitives::{EthereumHardfork, ForkId, Head};
+use reth_primitives::{EthereumHardfork, ForkId, GotExpected, Head};
 use std::fmt::{Debug, Display};
 
 /// The status message is used in the eth protocol handshake to ensure that peers are on the same
@@ -71,6 +71,54 @@ impl Status {
         self.version = version as u8;
     }
 
+    /// Returns the chain the peer is on.
+    pub const fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    /// Returns the hash of the best block the peer has seen.
+    pub const fn best_hash(&self) -> B256 {
+        self.blockhash
+    }
+
+    /// Returns the genesis hash of the peer's chain.
+    pub const fn genesis(&self) -> B256 {
+        self.genesis
+    }
+
+    /// Returns the [`ForkId`] of the peer.
+    pub const fn fork_id(&self) -> ForkId {
+        self.forkid
+    }
+
+    /// Returns the total difficulty of the peer's best chain.
+    pub const fn total_difficulty(&self) -> U256 {
+        self.total_difficulty
+    }
+
+    /// Validates this status, received from a peer, against the `local` status.
+    ///
+    /// Checks the genesis hash and the network id, in that order, and returns the first mismatch.
+    ///
+    /// Note: the fork id isn't checked, the fork ids of compatible peers may differ if one side is
+    /// still syncing or already passed the next fork. Use
+    /// [`ForkFilter::validate`](reth_primitives::ForkFilter::validate) for that.
+    pub fn validate_against(&self, local: &Self) -> Result<(), StatusMismatch> {
+        if self.genesis != local.genesis {
+            return Err(StatusMismatch::Genesis(GotExpected {
+                got: self.genesis,
+                expected: local.genesis,
+            }))
+        }
+        if self.chain != local.chain {
+            return Err(StatusMismatch::NetworkId(GotExpected {
+                got: self.chain,
+                expected: local.chain,
+            }))
+        }
+        Ok(())
+    }
+
     /// Create a [`StatusBuilder`] from the given [`ChainSpec`] and head block.
     ///
     /// Sets the `chain` and `genesis`, `blockhash`, and `forkid` fields based on the [`ChainSpec`]
@@ -88,6 +136,127 @@ impl Status {
     }
 }
 
+/// A mismatch between a peer's [`Status`] and the local one, see [`Status::validate_against`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StatusMismatch {
+    /// The peer's chain has a different genesis block.
+    #[error("mismatched genesis: {0}")]
+    Genesis(GotExpected<B256>),
+    /// The peer is on a different network.
+    #[error("mismatched network id: {0}")]
+    NetworkId(GotExpected<Chain>),
+}
+
+/// The parameters agreed on with a peer in the `eth` [`Status`] handshake.
+///
+/// A successful handshake implies that both sides use the same version, network and genesis, and
+/// that the peer's fork id is compatible with ours.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NegotiatedEth {
+    /// The negotiated `eth` version.
+    pub version: EthVersion,
+    /// The chain id of the network.
+    pub network_id: Chain,
+    /// The genesis hash of the chain.
+    pub genesis: B256,
+    /// The fork id the peer announced.
+    pub fork_id: ForkId,
+}
+
+impl NegotiatedEth {
+    /// Creates the negotiated parameters from the negotiated version and the [`Status`] the peer
+    /// sent in a successful handshake.
+    pub const fn new(version: EthVersion, status: &Status) -> Self {
+        Self { version, network_id: status.chain, genesis: status.genesis, fork_id: status.forkid }
+    }
+}
+
+/// The error returned when decoding an eth/69 [`StatusEth69`] that still carries the total
+/// difficulty, i.e. a legacy [`Status`].
+pub const STATUS_TOTAL_DIFFICULTY_ERROR: alloy_rlp::Error =
+    alloy_rlp::Error::Custom("eth/69 status must not contain the total difficulty");
+
+/// The status message of `eth/69`, as defined in [EIP-7642](https://eips.ethereum.org/EIPS/eip-7642).
+///
+/// This drops the total difficulty of the legacy [`Status`] and replaces the best block hash with
+/// the range of blocks the peer is able to serve.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, RlpEncodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(arbitrary::Arbitrary))]
+#[add_arbitrary_tests(rlp)]
+pub struct StatusEth69 {
+    /// The current protocol version, 69.
+    pub version: u8,
+
+    /// The chain id, as introduced in
+    /// [EIP155](https://eips.ethereum.org/EIPS/eip-155#list-of-chain-ids).
+    pub chain: Chain,
+
+    /// The genesis hash of the peer's chain.
+    pub genesis: B256,
+
+    /// The fork identifier, as defined by
+    /// [EIP-2124](https://github.com/ethereum/EIPs/blob/master/EIPS/eip-2124.md).
+    pub forkid: ForkId,
+
+    /// The number of the earliest block the peer is able to serve.
+    pub earliest: u64,
+
+    /// The number of the latest block the peer is able to serve.
+    pub latest: u64,
+
+    /// The hash of the latest block the peer is able to serve.
+    pub latest_hash: B256,
+}
+
+impl StatusEth69 {
+    /// The number of fields in the RLP list of the message.
+    const FIELDS: usize = 7;
+}
+
+impl Decodable for StatusEth69 {
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let header = Header::decode(buf)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString)
+        }
+        if buf.len() < header.payload_length {
+            return Err(alloy_rlp::Error::InputTooShort)
+        }
+        let (mut payload, rest) = buf.split_at(header.payload_length);
+
+        // count the items up front so a legacy status can be told apart from a malformed one
+        let mut items = 0;
+        let mut remaining = payload;
+        while !remaining.is_empty() {
+            let item = Header::decode(&mut remaining)?;
+            if remaining.len() < item.payload_length {
+                return Err(alloy_rlp::Error::InputTooShort)
+            }
+            remaining.advance(item.payload_length);
+            items += 1;
+        }
+        match items {
+            Self::FIELDS => {}
+            // version, chain, total difficulty, blockhash, genesis, forkid
+            6 => return Err(STATUS_TOTAL_DIFFICULTY_ERROR),
+            got => return Err(alloy_rlp::Error::ListLengthMismatch { expected: Self::FIELDS, got }),
+        }
+
+        let status = Self {
+            version: Decodable::decode(&mut payload)?,
+            chain: Decodable::decode(&mut payload)?,
+            genesis: Decodable::decode(&mut payload)?,
+            forkid: Decodable::decode(&mut payload)?,
+            earliest: Decodable::decode(&mut payload)?,
+            latest: Decodable::decode(&mut payload)?,
+            latest_hash: Decodable::decode(&mut payload)?,
+        };
+        *buf = rest;
+        Ok(status)
+    }
+}
+
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let hexed_blockhash = hex::encode(self.blockhash);
@@ -233,13 +402,13 @@ impl StatusBuilder {
 
 #[cfg(test)]
 mod tests {
-    use crate::{EthVersion, Status};
+    use crate::{EthVersion, Status, StatusEth69, StatusMismatch, STATUS_TOTAL_DIFFICULTY_ERROR};
     use alloy_genesis::Genesis;
     use alloy_primitives::{hex, B256, U256};
     use alloy_rlp::{Decodable, Encodable};
     use rand::Rng;
     use reth_chainspec::{Chain, ChainSpec, ForkCondition, NamedChain};
-    use reth_primitives::{EthereumHardfork, ForkHash, ForkId, Head};
+    use reth_primitives::{EthereumHardfork, ForkHash, ForkId, Head, MAINNET_GENESIS_HASH};
     use std::str::FromStr;
 
     #[test]
@@ -410,4 +579,62 @@ mod tests {
         assert_eq!(status.blockhash, head_hash);
         assert_eq!(status.genesis, genesis_hash);
     }
+
+    #[test]
+    fn validate_status_against_local() {
+        let local = Status::spec_builder(&*reth_chainspec::MAINNET, &Head::default()).build();
+        let peer = Status { blockhash: B256::random(), total_difficulty: U256::from(1), ..local };
+        assert_eq!(peer.validate_against(&local), Ok(()));
+        assert_eq!(peer.best_hash(), peer.blockhash);
+        assert_eq!(peer.genesis(), local.genesis);
+        assert_eq!(peer.fork_id(), local.forkid);
+
+        let peer = Status { genesis: B256::random(), ..local };
+        assert!(matches!(peer.validate_against(&local), Err(StatusMismatch::Genesis(_))));
+
+        let peer = Status { chain: Chain::from_named(NamedChain::Sepolia), ..local };
+        assert!(matches!(peer.validate_against(&local), Err(StatusMismatch::NetworkId(_))));
+
+        // fork compatibility is left to the fork filter
+        let peer = Status {
+            forkid: ForkId { hash: ForkHash([0xde, 0xad, 0xbe, 0xef]), next: 0 },
+            ..local
+        };
+        assert_eq!(peer.validate_against(&local), Ok(()));
+
+        // the first mismatch is returned
+        let peer = Status { chain: Chain::from_id(1337), genesis: B256::random(), ..local };
+        assert!(matches!(peer.validate_against(&local), Err(StatusMismatch::Genesis(_))));
+    }
+
+    #[test]
+    fn decode_eth69_status_message() {
+        let status = StatusEth69 {
+            version: EthVersion::Eth69 as u8,
+            chain: Chain::from_named(NamedChain::Mainnet),
+            genesis: MAINNET_GENESIS_HASH,
+            forkid: ForkId { hash: ForkHash([0xb7, 0x15, 0x07, 0x7d]), next: 0 },
+            earliest: 15_537_394,
+            latest: 20_000_000,
+            latest_hash: B256::repeat_byte(0xaa),
+        };
+
+        let mut rlp_status = vec![];
+        status.encode(&mut rlp_status);
+        assert_eq!(StatusEth69::decode(&mut &rlp_status[..]).unwrap(), status);
+
+        // a legacy status still carries the total difficulty
+        let legacy = Status::builder()
+            .version(EthVersion::Eth68 as u8)
+            .chain(Chain::from_named(NamedChain::Mainnet))
+            .genesis(MAINNET_GENESIS_HASH)
+            .build();
+        let mut rlp_legacy = vec![];
+        legacy.encode(&mut rlp_legacy);
+        assert_eq!(
+            StatusEth69::decode(&mut &rlp_legacy[..]).unwrap_err(),
+            STATUS_TOTAL_DIFFICULTY_ERROR
+        );
+        assert!(Status::decode(&mut &rlp_status[..]).is_err());
+    }
 }
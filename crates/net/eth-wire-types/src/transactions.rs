@@ -58,6 +58,25 @@ impl PooledTransactions {
     pub fn hashes(&self) -> impl Iterator<Item = &B256> + '_ {
         self.0.iter().map(|tx| tx.hash())
     }
+
+    /// Returns `true` if, for every blob transaction in this response, the commitments of the
+    /// sidecar hash to the versioned hashes listed in the transaction.
+    ///
+    /// See [EIP-4844](https://eips.ethereum.org/EIPS/eip-4844#networking): a response carrying a
+    /// blob transaction whose commitments don't match its versioned hashes is invalid.
+    pub fn has_valid_blob_versioned_hashes(&self) -> bool {
+        self.0.iter().all(|tx| match tx {
+            PooledTransactionsElement::BlobTransaction(blob_tx) => {
+                let (tx, sidecar) = (&blob_tx.transaction.tx, &blob_tx.transaction.sidecar);
+                tx.blob_versioned_hashes.len() == sidecar.commitments.len() &&
+                    sidecar
+                        .versioned_hashes()
+                        .zip(&tx.blob_versioned_hashes)
+                        .all(|(computed, listed)| computed == *listed)
+            }
+            _ => true,
+        })
+    }
 }
 
 impl TryFrom<Vec<TransactionSigned>> for PooledTransactions {
@@ -77,13 +96,52 @@ impl FromIterator<PooledTransactionsElement> for PooledTransactions {
 #[cfg(test)]
 mod tests {
     use crate::{message::RequestPair, GetPooledTransactions, PooledTransactions};
-    use alloy_consensus::{TxEip1559, TxLegacy};
-    use alloy_primitives::{hex, Parity, TxKind, U256};
+    use alloy_consensus::{TxEip1559, TxEip4844, TxEip4844WithSidecar, TxLegacy};
+    use alloy_eips::eip4844::{kzg_to_versioned_hash, Bytes48};
+    use alloy_primitives::{hex, Parity, TxKind, B256, U256};
     use alloy_rlp::{Decodable, Encodable};
     use reth_chainspec::MIN_TRANSACTION_GAS;
-    use reth_primitives::{PooledTransactionsElement, Signature, Transaction, TransactionSigned};
+    use reth_primitives::{
+        BlobTransaction, BlobTransactionSidecar, PooledTransactionsElement, Signature, Transaction,
+        TransactionSigned,
+    };
     use std::str::FromStr;
 
+    /// Returns a blob transaction listing the given versioned hashes, with a sidecar carrying the
+    /// given commitments.
+    fn blob_tx(
+        blob_versioned_hashes: Vec<B256>,
+        commitments: Vec<Bytes48>,
+    ) -> PooledTransactionsElement {
+        PooledTransactionsElement::BlobTransaction(BlobTransaction {
+            hash: B256::random(),
+            signature: Signature::new(U256::from(1), U256::from(1), Parity::Parity(false)),
+            transaction: TxEip4844WithSidecar {
+                tx: TxEip4844 { blob_versioned_hashes, ..Default::default() },
+                sidecar: BlobTransactionSidecar { blobs: vec![], commitments, proofs: vec![] },
+            },
+        })
+    }
+
+    #[test]
+    fn blob_versioned_hashes_validation() {
+        let commitment = Bytes48::repeat_byte(0xc0);
+        let versioned_hash = kzg_to_versioned_hash(commitment.as_slice());
+
+        let valid = PooledTransactions(vec![blob_tx(vec![versioned_hash], vec![commitment])]);
+        assert!(valid.has_valid_blob_versioned_hashes());
+
+        // commitment doesn't hash to the listed versioned hash
+        let other_commitment = Bytes48::repeat_byte(0xc1);
+        let invalid =
+            PooledTransactions(vec![blob_tx(vec![versioned_hash], vec![other_commitment])]);
+        assert!(!invalid.has_valid_blob_versioned_hashes());
+
+        // sidecar is missing a commitment
+        let invalid = PooledTransactions(vec![blob_tx(vec![versioned_hash], vec![])]);
+        assert!(!invalid.has_valid_blob_versioned_hashes());
+    }
+
     #[test]
     // Test vector from: https://eips.ethereum.org/EIPS/eip-2481
     fn encode_get_pooled_transactions() {
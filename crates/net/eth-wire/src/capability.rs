@@ -3,7 +3,7 @@
 use crate::{
     errors::{P2PHandshakeError, P2PStreamError},
     p2pstream::MAX_RESERVED_MESSAGE_ID,
-    protocol::{ProtoVersion, Protocol},
+    protocol::{ProtoVersion, Protocol, SNAP_1_MESSAGES},
     version::ParseVersionError,
     Capability, EthMessage, EthMessageID, EthVersion,
 };
@@ -13,8 +13,15 @@ use derive_more::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
 };
+use tracing::{trace, warn};
+
+/// The maximum number of duplicate capabilities tolerated in a peer's `Hello` message.
+///
+/// Duplicates are ignored when negotiating, but a peer that repeats capabilities more often than
+/// this is considered to be in breach of the protocol.
+pub const MAX_DUPLICATE_CAPABILITIES: usize = 8;
 
 /// A Capability message consisting of the message-id and the payload
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -55,6 +62,16 @@ pub enum SharedCapability {
         /// the message id space.
         offset: u8,
     },
+    /// The `snap` capability, which is served alongside `eth`.
+    Snap {
+        /// Negotiated version of the snap capability.
+        version: u8,
+        /// The message ID offset for this capability.
+        ///
+        /// This represents the message ID offset for the first message of the snap capability in
+        /// the message id space.
+        offset: u8,
+    },
     /// Any other unknown capability.
     UnknownCapability {
         /// Shared capability.
@@ -87,6 +104,7 @@ impl SharedCapability {
 
         match name {
             "eth" => Ok(Self::eth(EthVersion::try_from(version)?, offset)),
+            "snap" => Ok(Self::Snap { version, offset }),
             _ => Ok(Self::UnknownCapability {
                 cap: Capability::new(name.to_string(), version as usize),
                 offset,
@@ -104,6 +122,9 @@ impl SharedCapability {
     pub const fn capability(&self) -> Cow<'_, Capability> {
         match self {
             Self::Eth { version, .. } => Cow::Owned(Capability::eth(*version)),
+            Self::Snap { version, .. } => {
+                Cow::Owned(Capability::new_static("snap", *version as usize))
+            }
             Self::UnknownCapability { cap, .. } => Cow::Borrowed(cap),
         }
     }
@@ -113,6 +134,7 @@ impl SharedCapability {
     pub fn name(&self) -> &str {
         match self {
             Self::Eth { .. } => "eth",
+            Self::Snap { .. } => "snap",
             Self::UnknownCapability { cap, .. } => cap.name.as_ref(),
         }
     }
@@ -123,10 +145,17 @@ impl SharedCapability {
         matches!(self, Self::Eth { .. })
     }
 
+    /// Returns true if the capability is snap.
+    #[inline]
+    pub const fn is_snap(&self) -> bool {
+        matches!(self, Self::Snap { .. })
+    }
+
     /// Returns the version of the capability.
     pub const fn version(&self) -> u8 {
         match self {
             Self::Eth { version, .. } => *version as u8,
+            Self::Snap { version, .. } => *version,
             Self::UnknownCapability { cap, .. } => cap.version as u8,
         }
     }
@@ -145,7 +174,9 @@ impl SharedCapability {
     /// message id space.
     pub const fn message_id_offset(&self) -> u8 {
         match self {
-            Self::Eth { offset, .. } | Self::UnknownCapability { offset, .. } => *offset,
+            Self::Eth { offset, .. } |
+            Self::Snap { offset, .. } |
+            Self::UnknownCapability { offset, .. } => *offset,
         }
     }
 
@@ -159,9 +190,90 @@ impl SharedCapability {
     pub const fn num_messages(&self) -> u8 {
         match self {
             Self::Eth { version: _version, .. } => EthMessageID::max() + 1,
+            Self::Snap { .. } => SNAP_1_MESSAGES,
             Self::UnknownCapability { messages, .. } => *messages,
         }
     }
+
+    /// Returns the message ID on the wire of a message of this capability, given its ID relative
+    /// to the capability, e.g. [`EthMessageID::GetBlockHeaders`] for `eth`.
+    ///
+    /// The relative ID must be less than [`Self::num_messages`].
+    pub const fn message_id(&self, relative_id: u8) -> u8 {
+        debug_assert!(relative_id < self.num_messages(), "relative message id out of range");
+        self.message_id_offset() + relative_id
+    }
+
+    /// Returns the ID relative to this capability of a message with the given ID on the wire, or
+    /// `None` if the message doesn't belong to this capability.
+    ///
+    /// This is the inverse of [`Self::message_id`].
+    pub const fn relative_message_id(&self, message_id: u8) -> Option<u8> {
+        match message_id.checked_sub(self.message_id_offset()) {
+            Some(relative_id) if relative_id < self.num_messages() => Some(relative_id),
+            _ => None,
+        }
+    }
+}
+
+/// Limits the subprotocols that run simultaneously on a single session.
+///
+/// Only capabilities named in the priority list are used, at most `max` of them, picked in the
+/// order of the list. The limit is applied after the negotiation, see
+/// [`SharedCapabilities::try_new_with_limit`], so the message id offsets still match the offsets
+/// of the peer, which negotiates all capabilities both peers support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedCapabilityLimit {
+    /// The maximum number of capabilities used.
+    max: usize,
+    /// The names of the capabilities that may be used, most preferred first.
+    priority: Vec<String>,
+}
+
+impl SharedCapabilityLimit {
+    /// Creates a new limit that uses all capabilities named in the given priority list.
+    pub fn new(priority: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let priority = priority.into_iter().map(Into::into).collect::<Vec<_>>();
+        Self { max: priority.len(), priority }
+    }
+
+    /// Sets the maximum number of capabilities used.
+    pub const fn with_max(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Returns the maximum number of capabilities used.
+    pub const fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Returns the names of the capabilities that may be used, most preferred first.
+    pub fn priority(&self) -> &[String] {
+        &self.priority
+    }
+
+    /// Drops all negotiated capabilities that aren't used under this limit.
+    ///
+    /// The message id offsets of the remaining capabilities are kept.
+    pub fn apply(&self, shared: Vec<SharedCapability>) -> Vec<SharedCapability> {
+        let used = self
+            .priority
+            .iter()
+            .filter(|name| shared.iter().any(|cap| cap.name() == name.as_str()))
+            .take(self.max)
+            .collect::<Vec<_>>();
+        shared
+            .into_iter()
+            .filter(|cap| {
+                let use_cap = used.iter().any(|name| cap.name() == name.as_str());
+                if !use_cap {
+                    trace!(target: "net::p2p", capability=%cap.capability(), "not using shared capability, limit reached");
+                }
+                use_cap
+            })
+            .collect()
+    }
 }
 
 /// Non-empty,ordered list of recognized shared capabilities.
@@ -180,6 +292,23 @@ impl SharedCapabilities {
         shared_capability_offsets(local_protocols, peer_capabilities).map(Self)
     }
 
+    /// Merges the local and peer capabilities like [`Self::try_new`], then drops the shared
+    /// capabilities that aren't used under the given limit.
+    ///
+    /// The message id offsets are those of the full negotiation, the ids of dropped capabilities
+    /// aren't routed to any capability.
+    pub fn try_new_with_limit(
+        local_protocols: Vec<Protocol>,
+        peer_capabilities: Vec<Capability>,
+        limit: &SharedCapabilityLimit,
+    ) -> Result<Self, P2PStreamError> {
+        let shared = limit.apply(shared_capability_offsets(local_protocols, peer_capabilities)?);
+        if shared.is_empty() {
+            return Err(P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities))
+        }
+        Ok(Self(shared))
+    }
+
     /// Iterates over the shared capabilities.
     #[inline]
     pub fn iter_caps(&self) -> impl Iterator<Item = &SharedCapability> {
@@ -200,6 +329,12 @@ impl SharedCapabilities {
             .ok_or(P2PStreamError::CapabilityNotShared)
     }
 
+    /// Returns the snap capability if it is shared.
+    #[inline]
+    pub fn snap(&self) -> Option<&SharedCapability> {
+        self.iter_caps().find(|c| c.is_snap())
+    }
+
     /// Returns true if the shared capabilities contain the given capability.
     #[inline]
     pub fn contains(&self, cap: &Capability) -> bool {
@@ -212,6 +347,19 @@ impl SharedCapabilities {
         self.0.iter().find(|c| c.version() == cap.version as u8 && c.name() == cap.name)
     }
 
+    /// Returns the message ID offset of the capability with the given name and version, if it was
+    /// negotiated.
+    ///
+    /// This is the base the message ID on the wire of a message of the capability is built from.
+    /// Returns `None` if the peer didn't negotiate the capability, in which case it can't route
+    /// its messages and they must not be sent.
+    #[inline]
+    pub fn offset_of(&self, name: &str, version: u8) -> Option<u8> {
+        self.iter_caps()
+            .find(|cap| cap.name() == name && cap.version() == version)
+            .map(SharedCapability::message_id_offset)
+    }
+
     /// Returns the matching shared capability for the given capability offset.
     ///
     /// `offset` is the multiplexed message id offset of the capability relative to the reserved
@@ -243,12 +391,15 @@ impl SharedCapabilities {
 
         for next in iter {
             if offset < next.message_id_offset() {
-                return Some(cap)
+                // the ids between two capabilities belong to a capability dropped by a
+                // `SharedCapabilityLimit`
+                return cap.relative_message_id(offset).map(|_| cap)
             }
             cap = next
         }
 
-        Some(cap)
+        // the ids after the last capability belong to a dropped capability that sorts after it
+        cap.relative_message_id(offset).map(|_| cap)
     }
 
     /// Returns the shared capability for the given capability or an error if it's not compatible.
@@ -281,11 +432,15 @@ impl SharedCapabilities {
 ///
 /// **Note**: For `local_protocols` this takes [Protocol] because we need to know the number of
 /// messages per versioned capability. From the remote we only get the plain [Capability].
+///
+/// Capabilities without any messages are skipped, they would occupy an empty message id range.
 #[inline]
 pub fn shared_capability_offsets(
     local_protocols: Vec<Protocol>,
     peer_capabilities: Vec<Capability>,
 ) -> Result<Vec<SharedCapability>, P2PStreamError> {
+    let peer_capabilities = dedup_capabilities(peer_capabilities)?;
+
     // find intersection of capabilities
     let our_capabilities =
         local_protocols.into_iter().map(Protocol::split).collect::<HashMap<_, _>>();
@@ -309,7 +464,13 @@ pub fn shared_capability_offsets(
     // find highest shared version of each shared capability
     for peer_capability in peer_capabilities {
         // if we contain this specific capability both peers share it
+        // the number of messages is taken from our own protocol definition, the peer only sends
+        // the name and version
         if let Some(messages) = our_capabilities.get(&peer_capability).copied() {
+            if messages == 0 {
+                trace!(target: "net::p2p", capability=%peer_capability, "skipping shared capability without messages");
+                continue
+            }
             // If multiple versions are shared of the same (equal name) capability, the numerically
             // highest wins, others are ignored
             if shared_capabilities
@@ -325,6 +486,13 @@ pub fn shared_capability_offsets(
         }
     }
 
+    // snap is served over an eth connection, so it's unusable if eth isn't shared as well
+    if shared_capabilities.contains_key("snap") && !shared_capabilities.contains_key("eth") {
+        trace!(target: "net::p2p", "dropping shared snap capability, eth is not shared");
+        shared_capabilities.remove("snap");
+        shared_capability_names.remove("snap");
+    }
+
     // disconnect if we don't share any capabilities
     if shared_capabilities.is_empty() {
         return Err(P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities))
@@ -346,7 +514,9 @@ pub fn shared_capability_offsets(
             offset,
             proto_version.messages,
         )?;
-        offset += shared_capability.num_messages();
+        // the message ids of the last capability may end at the end of the message id space,
+        // the next capability would then overflow the offset
+        offset = offset.saturating_add(shared_capability.num_messages());
         shared_with_offsets.push(shared_capability);
     }
 
@@ -354,9 +524,53 @@ pub fn shared_capability_offsets(
         return Err(P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities))
     }
 
+    validate_message_id_ranges(&shared_with_offsets)?;
+
     Ok(shared_with_offsets)
 }
 
+/// Ensures the message id ranges of the given shared capabilities are contiguous, starting right
+/// after the reserved `p2p` message ids, and don't overlap.
+///
+/// Messages would be routed to the wrong capability otherwise.
+fn validate_message_id_ranges(shared: &[SharedCapability]) -> Result<(), SharedCapabilityError> {
+    // the end of the previous range, exclusive
+    let mut next_offset = MAX_RESERVED_MESSAGE_ID as usize + 1;
+    for cap in shared {
+        let offset = cap.message_id_offset() as usize;
+        let end = offset + cap.num_messages() as usize;
+        if offset != next_offset || end > u8::MAX as usize + 1 {
+            return Err(SharedCapabilityError::OverlappingMessageIds(cap.capability().into_owned()))
+        }
+        next_offset = end;
+    }
+    Ok(())
+}
+
+/// Removes duplicate entries from the capabilities advertised by a peer, preserving the order of
+/// first occurrence.
+///
+/// Returns [`P2PHandshakeError::TooManyDuplicateCapabilities`] if more than
+/// [`MAX_DUPLICATE_CAPABILITIES`] duplicates were advertised.
+fn dedup_capabilities(capabilities: Vec<Capability>) -> Result<Vec<Capability>, P2PStreamError> {
+    let total = capabilities.len();
+    let mut seen = HashSet::with_capacity(total);
+    let deduped =
+        capabilities.into_iter().filter(|cap| seen.insert(cap.clone())).collect::<Vec<_>>();
+
+    let duplicates = total - deduped.len();
+    if duplicates > 0 {
+        warn!(target: "net::p2p", duplicates, "peer advertised duplicate capabilities in hello");
+        if duplicates > MAX_DUPLICATE_CAPABILITIES {
+            return Err(P2PStreamError::HandshakeError(
+                P2PHandshakeError::TooManyDuplicateCapabilities(duplicates),
+            ))
+        }
+    }
+
+    Ok(deduped)
+}
+
 /// An error that may occur while creating a [`SharedCapability`].
 #[derive(Debug, thiserror::Error)]
 pub enum SharedCapabilityError {
@@ -367,6 +581,10 @@ pub enum SharedCapabilityError {
     /// message id space [`MAX_RESERVED_MESSAGE_ID`].
     #[error("message id offset `{0}` is reserved")]
     ReservedMessageIdOffset(u8),
+    /// Thrown when the message id range of a [`SharedCapability`] isn't directly following the
+    /// range of the previous capability, or exceeds the message id space.
+    #[error("message ids of capability `{0}` overlap with another capability")]
+    OverlappingMessageIds(Capability),
 }
 
 /// An error thrown when capabilities mismatch.
@@ -381,6 +599,47 @@ mod tests {
     use super::*;
     use crate::{Capabilities, Capability};
 
+    #[test]
+    fn test_shared_capability_limit() {
+        let les = Protocol::new(Capability::new_static("les", 4), 24);
+        let protocols = vec![Protocol::eth_68(), Protocol::snap_1(), les.clone()];
+        let peer = vec![Capability::eth_68(), Capability::snap_1(), les.cap];
+        let all = SharedCapabilities::try_new(protocols.clone(), peer.clone()).unwrap();
+        let with_limit = |limit: SharedCapabilityLimit| {
+            SharedCapabilities::try_new_with_limit(protocols.clone(), peer.clone(), &limit)
+        };
+
+        // the offsets of the full negotiation are kept
+        let shared = with_limit(SharedCapabilityLimit::new(["eth", "snap"])).unwrap();
+        assert_eq!(shared.len(), 2);
+        assert_eq!(shared.eth().unwrap(), all.eth().unwrap());
+        assert_eq!(shared.snap().unwrap(), all.snap().unwrap());
+
+        // the ids of the dropped capability aren't routed to the capability before it
+        let les_offset = all.offset_of("les", 4).unwrap();
+        assert_eq!(all.find_by_offset(les_offset).unwrap().name(), "les");
+        assert_eq!(shared.find_by_offset(les_offset), None);
+        let snap_offset = shared.snap().unwrap().message_id_offset();
+        assert_eq!(shared.find_by_offset(snap_offset), shared.snap());
+
+        // nor to the last kept capability if the dropped capability sorts after it
+        let shared = with_limit(SharedCapabilityLimit::new(["eth", "les"])).unwrap();
+        assert_eq!(shared.snap(), None);
+        assert_eq!(shared.find_by_offset(snap_offset), None);
+        assert_eq!(shared.find_by_offset(snap_offset - 1).unwrap().name(), "les");
+        assert_eq!(shared.find_by_offset(les_offset).unwrap().name(), "les");
+
+        // capabilities that aren't shared don't count towards the limit
+        let shared =
+            with_limit(SharedCapabilityLimit::new(["bzz", "les", "eth"]).with_max(1)).unwrap();
+        assert_eq!(shared.iter_caps().map(SharedCapability::name).collect::<Vec<_>>(), ["les"]);
+
+        assert!(matches!(
+            with_limit(SharedCapabilityLimit::new(["eth"]).with_max(0)),
+            Err(P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities))
+        ));
+    }
+
     #[test]
     fn from_eth_68() {
         let capability = SharedCapability::new("eth", 68, MAX_RESERVED_MESSAGE_ID + 1, 13).unwrap();
@@ -445,12 +704,58 @@ mod tests {
     fn test_peer_capability_version_zero() {
         let cap = Capability::new_static("TestName", 0);
         let local_capabilities: Vec<Protocol> =
-            vec![Protocol::new(cap.clone(), 0), EthVersion::Eth67.into(), EthVersion::Eth68.into()];
+            vec![Protocol::new(cap.clone(), 1), EthVersion::Eth67.into(), EthVersion::Eth68.into()];
         let peer_capabilities = vec![cap.clone()];
 
         let shared = shared_capability_offsets(local_capabilities, peer_capabilities).unwrap();
         assert_eq!(shared.len(), 1);
-        assert_eq!(shared[0], SharedCapability::UnknownCapability { cap, offset: 16, messages: 0 })
+        assert_eq!(shared[0], SharedCapability::UnknownCapability { cap, offset: 16, messages: 1 })
+    }
+
+    #[test]
+    fn test_capability_without_messages_skipped() {
+        let cap = Capability::new_static("TestName", 0);
+        let local_capabilities: Vec<Protocol> =
+            vec![Protocol::new(cap.clone(), 0), EthVersion::Eth68.into()];
+
+        let shared =
+            shared_capability_offsets(local_capabilities.clone(), vec![cap.clone()]).unwrap_err();
+        assert!(matches!(
+            shared,
+            P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities)
+        ));
+
+        let shared =
+            shared_capability_offsets(local_capabilities, vec![cap, EthVersion::Eth68.into()])
+                .unwrap();
+        assert_eq!(
+            shared,
+            vec![SharedCapability::Eth {
+                version: EthVersion::Eth68,
+                offset: MAX_RESERVED_MESSAGE_ID + 1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_message_counts_from_local_protocols() {
+        let les = Protocol::new(Capability::new_static("les", 4), 24);
+        let local_capabilities: Vec<Protocol> = vec![EthVersion::Eth68.into(), les];
+        let peer_capabilities = vec![
+            Capability::new_static("bzz", 1),
+            EthVersion::Eth68.into(),
+            Capability::new_static("les", 4),
+        ];
+
+        // the unknown capability is skipped instead of being assigned an empty range
+        let shared = shared_capability_offsets(local_capabilities, peer_capabilities).unwrap();
+        assert_eq!(shared.len(), 2);
+        let eth = &shared[0];
+        assert_eq!(eth.message_id_offset(), MAX_RESERVED_MESSAGE_ID + 1);
+        let les = &shared[1];
+        assert_eq!(les.name(), "les");
+        assert_eq!(les.num_messages(), 24);
+        assert_eq!(les.message_id_offset(), MAX_RESERVED_MESSAGE_ID + 1 + eth.num_messages());
     }
 
     #[test]
@@ -539,4 +844,221 @@ mod tests {
         let shared_eth = shared.find_by_relative_offset(1 + proto.messages()).unwrap();
         assert_eq!(shared_eth.name(), "eth");
     }
+
+    #[test]
+    fn test_eth_and_snap_shared() {
+        let local_capabilities = vec![EthVersion::Eth68.into(), Protocol::snap_1()];
+        let peer_capabilities = vec![Capability::snap_1(), EthVersion::Eth68.into()];
+
+        let shared = SharedCapabilities::try_new(local_capabilities, peer_capabilities).unwrap();
+        assert_eq!(shared.len(), 2);
+
+        // alphabetical order: eth's id block comes first, snap's directly follows it
+        let eth = shared.eth().unwrap();
+        assert_eq!(eth, &SharedCapability::eth(EthVersion::Eth68, MAX_RESERVED_MESSAGE_ID + 1));
+        let snap = shared.snap().unwrap();
+        assert_eq!(
+            snap,
+            &SharedCapability::Snap {
+                version: 1,
+                offset: MAX_RESERVED_MESSAGE_ID + 1 + eth.num_messages()
+            }
+        );
+        assert_eq!(snap.capability().into_owned(), Capability::snap_1());
+
+        // snap message ids are routed to the snap capability
+        let first_snap_id = snap.message_id_offset();
+        assert!(shared.find_by_offset(first_snap_id - 1).unwrap().is_eth());
+        assert!(shared.find_by_offset(first_snap_id).unwrap().is_snap());
+        assert!(shared.find_by_offset(first_snap_id + SNAP_1_MESSAGES - 1).unwrap().is_snap());
+    }
+
+    #[test]
+    fn test_offset_of() {
+        let shared = SharedCapabilities::try_new(
+            vec![EthVersion::Eth68.into(), Protocol::snap_1()],
+            vec![EthVersion::Eth68.into(), Capability::snap_1()],
+        )
+        .unwrap();
+
+        let eth_offset = MAX_RESERVED_MESSAGE_ID + 1;
+        assert_eq!(shared.offset_of("eth", 68), Some(eth_offset));
+        assert_eq!(
+            shared.offset_of("snap", 1),
+            Some(eth_offset + shared.eth().unwrap().num_messages())
+        );
+
+        // versions that weren't negotiated
+        assert_eq!(shared.offset_of("eth", 67), None);
+        assert_eq!(shared.offset_of("snap", 2), None);
+    }
+
+    #[test]
+    fn test_offset_of_absent() {
+        // the peer only negotiated eth
+        let shared = SharedCapabilities::try_new(
+            vec![EthVersion::Eth68.into(), Protocol::snap_1()],
+            vec![EthVersion::Eth68.into()],
+        )
+        .unwrap();
+
+        assert_eq!(shared.offset_of("eth", 68), Some(MAX_RESERVED_MESSAGE_ID + 1));
+        assert_eq!(shared.offset_of("snap", 1), None);
+        assert_eq!(shared.offset_of("les", 4), None);
+    }
+
+    #[test]
+    fn test_message_ids_eth_only() {
+        let shared = SharedCapabilities::try_new(
+            vec![EthVersion::Eth68.into()],
+            vec![EthVersion::Eth68.into()],
+        )
+        .unwrap();
+        let eth = shared.eth().unwrap();
+
+        // eth directly follows the reserved p2p message ids
+        assert_eq!(eth.message_id(EthMessageID::Status as u8), 0x10);
+        assert_eq!(eth.message_id(EthMessageID::GetBlockHeaders as u8), 0x13);
+        assert_eq!(eth.message_id(EthMessageID::Receipts as u8), 0x20);
+
+        assert_eq!(eth.relative_message_id(0x13), Some(EthMessageID::GetBlockHeaders as u8));
+        assert_eq!(eth.relative_message_id(MAX_RESERVED_MESSAGE_ID), None);
+        assert_eq!(eth.relative_message_id(0x10 + eth.num_messages()), None);
+    }
+
+    #[test]
+    fn test_message_ids_eth_and_snap() {
+        let shared = SharedCapabilities::try_new(
+            vec![EthVersion::Eth68.into(), Protocol::snap_1()],
+            vec![EthVersion::Eth68.into(), Capability::snap_1()],
+        )
+        .unwrap();
+        let eth = shared.eth().unwrap();
+        let snap = shared.snap().unwrap();
+
+        // the eth ids aren't affected by snap, which is ordered after it
+        assert_eq!(eth.message_id(EthMessageID::GetBlockHeaders as u8), 0x13);
+        assert_eq!(eth.message_id(EthMessageID::Receipts as u8), 0x20);
+
+        // snap's ids directly follow the last eth id
+        let get_account_range = snap.message_id(0);
+        assert_eq!(get_account_range, 0x21);
+        assert_eq!(snap.message_id(SNAP_1_MESSAGES - 1), 0x21 + SNAP_1_MESSAGES - 1);
+
+        for id in 0..eth.num_messages() {
+            let message_id = eth.message_id(id);
+            assert_eq!(eth.relative_message_id(message_id), Some(id));
+            assert_eq!(snap.relative_message_id(message_id), None);
+        }
+        assert_eq!(snap.relative_message_id(get_account_range), Some(0));
+        assert_eq!(eth.relative_message_id(get_account_range), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "relative message id out of range")]
+    #[cfg(debug_assertions)]
+    fn test_message_id_out_of_range() {
+        let eth = SharedCapability::eth(EthVersion::Eth68, MAX_RESERVED_MESSAGE_ID + 1);
+        eth.message_id(eth.num_messages());
+    }
+
+    #[test]
+    fn test_snap_without_eth_dropped() {
+        let cap = Capability::new_static("aaa", 1);
+        let local_capabilities =
+            vec![Protocol::new(cap.clone(), 5), EthVersion::Eth68.into(), Protocol::snap_1()];
+        let peer_capabilities = vec![cap, Capability::snap_1()];
+
+        let shared = SharedCapabilities::try_new(local_capabilities, peer_capabilities).unwrap();
+        assert_eq!(shared.len(), 1);
+        assert!(shared.snap().is_none());
+        assert!(shared.eth().is_err());
+
+        // snap alone is not a usable shared capability
+        let local_capabilities = vec![EthVersion::Eth68.into(), Protocol::snap_1()];
+        let peer_capabilities = vec![Capability::snap_1()];
+        assert!(matches!(
+            shared_capability_offsets(local_capabilities, peer_capabilities),
+            Err(P2PStreamError::HandshakeError(P2PHandshakeError::NoSharedCapabilities))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_peer_capabilities_deduped() {
+        let local_capabilities =
+            vec![EthVersion::Eth67.into(), EthVersion::Eth68.into(), Protocol::snap_1()];
+        let peer_capabilities = vec![
+            EthVersion::Eth68.into(),
+            Capability::snap_1(),
+            EthVersion::Eth68.into(),
+            EthVersion::Eth67.into(),
+            Capability::snap_1(),
+            EthVersion::Eth68.into(),
+        ];
+
+        let shared = SharedCapabilities::try_new(local_capabilities, peer_capabilities).unwrap();
+        assert_eq!(shared.len(), 2);
+
+        // offsets are the same as for a hello without duplicates
+        let eth = shared.eth().unwrap();
+        assert_eq!(eth, &SharedCapability::eth(EthVersion::Eth68, MAX_RESERVED_MESSAGE_ID + 1));
+        assert_eq!(
+            shared.snap().unwrap(),
+            &SharedCapability::Snap {
+                version: 1,
+                offset: MAX_RESERVED_MESSAGE_ID + 1 + eth.num_messages()
+            }
+        );
+    }
+
+    #[test]
+    fn test_too_many_duplicate_peer_capabilities() {
+        let local_capabilities = vec![EthVersion::Eth68.into()];
+
+        // the threshold itself is tolerated
+        let peer_capabilities =
+            vec![Capability::from(EthVersion::Eth68); MAX_DUPLICATE_CAPABILITIES + 1];
+        let shared = shared_capability_offsets(local_capabilities.clone(), peer_capabilities);
+        assert_eq!(shared.unwrap().len(), 1);
+
+        let peer_capabilities =
+            vec![Capability::from(EthVersion::Eth68); MAX_DUPLICATE_CAPABILITIES + 2];
+        assert!(matches!(
+            shared_capability_offsets(local_capabilities, peer_capabilities),
+            Err(P2PStreamError::HandshakeError(P2PHandshakeError::TooManyDuplicateCapabilities(
+                duplicates
+            ))) if duplicates == MAX_DUPLICATE_CAPABILITIES + 1
+        ));
+    }
+
+    #[test]
+    fn test_overlapping_message_ids() {
+        // the message ids of the second capability would exceed the message id space and wrap
+        // around, overlapping the first capability
+        let aaa = Capability::new_static("aaa", 1);
+        let bbb = Capability::new_static("bbb", 1);
+        let local_capabilities = vec![
+            Protocol::new(aaa.clone(), 200),
+            Protocol::new(bbb.clone(), 100),
+            EthVersion::Eth68.into(),
+        ];
+        let peer_capabilities = vec![aaa, bbb.clone(), EthVersion::Eth68.into()];
+        assert!(matches!(
+            shared_capability_offsets(local_capabilities, peer_capabilities),
+            Err(P2PStreamError::ParseSharedCapability(
+                SharedCapabilityError::OverlappingMessageIds(cap)
+            )) if cap == bbb
+        ));
+
+        // ranges must be contiguous
+        let offset = MAX_RESERVED_MESSAGE_ID + 1;
+        let eth = SharedCapability::eth(EthVersion::Eth68, offset);
+        let snap = SharedCapability::Snap { version: 1, offset: offset + 1 };
+        assert!(matches!(
+            validate_message_id_ranges(&[eth.clone(), snap]),
+            Err(SharedCapabilityError::OverlappingMessageIds(cap)) if cap == Capability::snap_1()
+        ));
+        let snap = SharedCapability::Snap { version: 1, offset: offset + eth.num_messages() };
+        validate_message_id_ranges(&[eth, snap]).unwrap();
+    }
 }
@@ -0,0 +1,95 @@
+//! Time source of the keepalive and timeout logic.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A source of the current time.
+///
+/// Timers still wake up the task in real time, but whether a deadline passed is decided by the
+/// clock. This allows tests to advance time deterministically with a [`MockClock`] and poll again.
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// A shared [`Clock`].
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The [`Clock`] of the system, this is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    /// Returns the system clock as a [`SharedClock`].
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A [`Clock`] for tests that only moves when it's advanced.
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    /// The time the clock was created at.
+    start: Instant,
+    /// Nanoseconds the clock was advanced by since `start`.
+    elapsed: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Creates a new clock, starting at the current time.
+    pub fn new() -> Self {
+        Self { start: Instant::now(), elapsed: Arc::default() }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Returns the clock as a [`SharedClock`].
+    pub fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_advance() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        let shared = clock.shared();
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(shared.now(), start + Duration::from_secs(15));
+    }
+}
@@ -0,0 +1,99 @@
+//! Snappy compression of `RLPx` message payloads.
+//!
+//! Compression is only available with the `snappy` feature. Without it, [`Snappy`] rejects every
+//! operation and [`P2PStream`](crate::P2PStream)s fall back to raw mode, see
+//! [`P2PStream::new_raw`](crate::P2PStream::new_raw).
+
+use crate::errors::P2PStreamError;
+
+/// The snappy encoder and decoder of a [`P2PStream`](crate::P2PStream).
+#[derive(Debug)]
+#[cfg_attr(not(feature = "snappy"), derive(Default))]
+pub(crate) struct Snappy {
+    /// The snappy encoder used for compressing outgoing messages
+    #[cfg(feature = "snappy")]
+    encoder: snap::raw::Encoder,
+
+    /// The snappy decoder used for decompressing incoming messages
+    #[cfg(feature = "snappy")]
+    decoder: snap::raw::Decoder,
+}
+
+#[cfg(feature = "snappy")]
+impl Default for Snappy {
+    fn default() -> Self {
+        Self { encoder: snap::raw::Encoder::new(), decoder: snap::raw::Decoder::new() }
+    }
+}
+
+#[cfg_attr(
+    not(feature = "snappy"),
+    allow(
+        unused_variables,
+        clippy::unused_self,
+        clippy::missing_const_for_fn,
+        clippy::needless_pass_by_ref_mut
+    )
+)]
+impl Snappy {
+    /// Whether snappy compression is available in this build.
+    pub(crate) const ENABLED: bool = cfg!(feature = "snappy");
+
+    /// Returns the maximum length of `input_len` bytes after compression.
+    pub(crate) fn max_compress_len(input_len: usize) -> usize {
+        #[cfg(feature = "snappy")]
+        return snap::raw::max_compress_len(input_len);
+        #[cfg(not(feature = "snappy"))]
+        return input_len
+    }
+
+    /// Returns the decompressed length of the compressed `input`.
+    pub(crate) fn decompress_len(input: &[u8]) -> Result<usize, P2PStreamError> {
+        #[cfg(feature = "snappy")]
+        return Ok(snap::raw::decompress_len(input)?);
+        #[cfg(not(feature = "snappy"))]
+        return Err(unavailable())
+    }
+
+    /// Compresses `input` into `output`, returning the compressed length.
+    pub(crate) fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, P2PStreamError> {
+        #[cfg(feature = "snappy")]
+        return Ok(self.encoder.compress(input, output)?);
+        #[cfg(not(feature = "snappy"))]
+        return Err(unavailable())
+    }
+
+    /// Decompresses `input` into `output`, returning the decompressed length.
+    pub(crate) fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<usize, P2PStreamError> {
+        #[cfg(feature = "snappy")]
+        return Ok(self.decoder.decompress(input, output)?);
+        #[cfg(not(feature = "snappy"))]
+        return Err(unavailable())
+    }
+
+    /// Decompresses `input` into a new buffer.
+    pub(crate) fn decompress_vec(&mut self, input: &[u8]) -> Result<Vec<u8>, P2PStreamError> {
+        #[cfg(feature = "snappy")]
+        return Ok(self.decoder.decompress_vec(input)?);
+        #[cfg(not(feature = "snappy"))]
+        return Err(unavailable())
+    }
+}
+
+/// The error returned by all operations when the `snappy` feature is disabled.
+#[cfg(not(feature = "snappy"))]
+fn unavailable() -> P2PStreamError {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "snappy compression is unavailable, the `snappy` feature is disabled",
+    )
+    .into()
+}
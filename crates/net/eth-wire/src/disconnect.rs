@@ -1,10 +1,15 @@
 //! Disconnect
 
-use std::future::Future;
+use std::{collections::HashMap, fmt, future::Future, sync::Arc};
 
+use crate::{
+    compression::Snappy,
+    p2pstream::{decode_snappy_single_byte_disconnect, P2PMessage, P2PMessageID},
+};
+use alloy_rlp::{Decodable, Header};
 use futures::{Sink, SinkExt};
 use reth_ecies::stream::ECIESStream;
-use reth_eth_wire_types::DisconnectReason;
+use reth_eth_wire_types::{DisconnectReason, DISCONNECT_LIST_LENGTH_ERROR};
 use tokio::io::AsyncWrite;
 use tokio_util::codec::{Encoder, Framed};
 
@@ -44,28 +49,278 @@ where
     }
 }
 
+/// Interprets the code of a subprotocol specific disconnect as a human-readable reason.
+pub type SubprotocolReasonFn = Arc<dyn Fn(u8) -> String + Send + Sync>;
+
+/// Maps subprotocol names to the interpretation of their disconnect codes.
+///
+/// Every subprotocol disconnects with [`DisconnectReason::SubprotocolSpecific`] on the wire, so the
+/// reason alone doesn't tell why a custom subprotocol disconnected. The subprotocol's own code is
+/// interpreted by the registered mapping, which is only used for logging.
+#[derive(Clone, Default)]
+pub struct SubprotocolDisconnectReasons {
+    reasons: HashMap<String, SubprotocolReasonFn>,
+}
+
+impl SubprotocolDisconnectReasons {
+    /// Registers the mapping of the disconnect codes of the subprotocol with the given name, e.g.
+    /// `snap`.
+    ///
+    /// This replaces a previously registered mapping of the subprotocol.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        reason: impl Fn(u8) -> String + Send + Sync + 'static,
+    ) {
+        self.reasons.insert(name.into(), Arc::new(reason));
+    }
+
+    /// Registers the mapping of the disconnect codes of the subprotocol with the given name, see
+    /// [`Self::register`].
+    pub fn with_reason(
+        mut self,
+        name: impl Into<String>,
+        reason: impl Fn(u8) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.register(name, reason);
+        self
+    }
+
+    /// Returns the human-readable reason of the disconnect code of the subprotocol with the given
+    /// name, if a mapping is registered for it.
+    pub fn get(&self, name: &str, code: u8) -> Option<String> {
+        self.reasons.get(name).map(|reason| reason(code))
+    }
+
+    /// Returns the reason to log for the disconnect code of the subprotocol with the given name.
+    ///
+    /// Falls back to the code itself if no mapping is registered for the subprotocol.
+    pub fn describe(&self, name: &str, code: u8) -> String {
+        self.get(name, code).unwrap_or_else(|| format!("{name} code {code:#04x}"))
+    }
+}
+
+impl fmt::Debug for SubprotocolDisconnectReasons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.reasons.keys()).finish()
+    }
+}
+
+/// The on-wire form a [`DisconnectReason`] was encoded in.
+///
+/// Clients disagree on how the payload of a `p2p` disconnect message is encoded, and on whether it
+/// is snappy compressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DisconnectWireForm {
+    /// The reason as a bare byte, e.g. `0x03`.
+    SingleByte,
+    /// The reason in a single-element RLP list, e.g. `0xc103`.
+    RlpList,
+    /// The bare reason byte, snappy compressed, e.g. `0x010003`.
+    SnappySingle,
+    /// The single-element RLP list, snappy compressed, e.g. `0x0204c103`.
+    SnappyList,
+    /// [`DisconnectReason::DisconnectRequested`] as the bare RLP empty string `0x80`, instead of
+    /// the bare byte `0x00` that geth sends.
+    ///
+    /// This is the `0180` message of the samples the disconnect decoding is tested against. The
+    /// same reason in a list, `0xc180`, is how we encode it, so it's an [`Self::RlpList`].
+    Legacy0101,
+}
+
+/// How strictly the payload of a `p2p` disconnect message is decoded, see [`decode_with_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisconnectDecodeMode {
+    /// Accepts all [`DisconnectWireForm`]s.
+    #[default]
+    Lenient,
+    /// Only accepts [`DisconnectWireForm::SnappyList`], the canonical form disconnects are sent
+    /// in after the handshake.
+    ///
+    /// This detects peers that deviate from the canonical form, e.g. for conformance testing
+    /// against a specific client.
+    Strict,
+}
+
+impl DisconnectDecodeMode {
+    /// Returns `true` if a disconnect encoded in the given form is accepted in this mode.
+    pub const fn accepts(&self, form: DisconnectWireForm) -> bool {
+        match self {
+            Self::Lenient => true,
+            Self::Strict => matches!(form, DisconnectWireForm::SnappyList),
+        }
+    }
+}
+
+/// The error returned when decoding a disconnect that isn't in the canonical form in
+/// [`DisconnectDecodeMode::Strict`].
+pub const NON_CANONICAL_DISCONNECT_ERROR: alloy_rlp::Error =
+    alloy_rlp::Error::Custom("non-canonical disconnect encoding");
+
+/// The maximum size of the payload of a `p2p` disconnect message, i.e. without the message id.
+///
+/// The compact forms are at most 4 bytes, but some clients frame the snappy compressed reason less
+/// compactly, e.g. as multiple literals or with a wider literal length. Larger payloads are
+/// rejected before they're decompressed.
+pub const MAX_DISCONNECT_PAYLOAD_SIZE: usize = 8;
+
+/// The error returned when decoding a disconnect payload that exceeds
+/// [`MAX_DISCONNECT_PAYLOAD_SIZE`].
+pub const OVERSIZED_DISCONNECT_ERROR: alloy_rlp::Error =
+    alloy_rlp::Error::Custom("disconnect payload exceeds the maximum size");
+
+/// Decodes the payload of a `p2p` disconnect message, i.e. without the message id, returning the
+/// [`DisconnectReason`] and the [`DisconnectWireForm`] it was encoded in.
+///
+/// Fails with [`OVERSIZED_DISCONNECT_ERROR`] if the payload exceeds
+/// [`MAX_DISCONNECT_PAYLOAD_SIZE`].
+pub fn decode_with_form(buf: &[u8]) -> alloy_rlp::Result<(DisconnectReason, DisconnectWireForm)> {
+    if buf.len() > MAX_DISCONNECT_PAYLOAD_SIZE {
+        return Err(OVERSIZED_DISCONNECT_ERROR)
+    }
+
+    match DisconnectReason::decode(&mut &buf[..]) {
+        Ok(reason) => {
+            let form = match buf {
+                [0x80] => DisconnectWireForm::Legacy0101,
+                [_] => DisconnectWireForm::SingleByte,
+                _ => DisconnectWireForm::RlpList,
+            };
+            return Ok((reason, form))
+        }
+        // a snappy compressed payload never starts with a list header, so this can't be retried
+        // as one
+        Err(err @ DISCONNECT_LIST_LENGTH_ERROR) => return Err(err),
+        Err(_) => {}
+    }
+
+    if let Some(reason) = decode_snappy_single_byte_disconnect(buf) {
+        return Ok((reason, DisconnectWireForm::SnappySingle))
+    }
+
+    // the uncompressed payload is at most 2 bytes, see `DisconnectReason::decode`, regardless of
+    // how it's framed
+    let decompressed_len = Snappy::decompress_len(buf)
+        .map_err(|_| alloy_rlp::Error::Custom("invalid snappy compressed disconnect reason"))?;
+    if decompressed_len != 2 {
+        return Err(alloy_rlp::Error::Custom("invalid snappy compressed disconnect reason"))
+    }
+    let decompressed = Snappy::default()
+        .decompress_vec(buf)
+        .map_err(|_| alloy_rlp::Error::Custom("invalid snappy compressed disconnect reason"))?;
+    let reason = DisconnectReason::decode(&mut &decompressed[..])?;
+    Ok((reason, DisconnectWireForm::SnappyList))
+}
+
+/// Decodes the payload of a `p2p` disconnect message like [`decode_with_form`], but fails with
+/// [`NON_CANONICAL_DISCONNECT_ERROR`] if the given mode doesn't accept the form it was encoded in.
+pub fn decode_with_mode(
+    buf: &[u8],
+    mode: DisconnectDecodeMode,
+) -> alloy_rlp::Result<DisconnectReason> {
+    let (reason, form) = decode_with_form(buf)?;
+    if !mode.accepts(form) {
+        return Err(NON_CANONICAL_DISCONNECT_ERROR)
+    }
+    Ok(reason)
+}
+
+/// Decodes the subprotocol's own code of a [`DisconnectReason::SubprotocolSpecific`] disconnect,
+/// which some subprotocols send as a trailing list element, i.e. `[0x10, code]`, bare or snappy
+/// compressed.
+///
+/// Returns `None` if the payload isn't a subprotocol specific disconnect with a code. The code is
+/// interpreted by the [`SubprotocolDisconnectReasons`] of the stream.
+pub fn decode_subprotocol_code(buf: &[u8]) -> Option<u8> {
+    fn decode_list(mut buf: &[u8]) -> Option<u8> {
+        let header = Header::decode(&mut buf).ok()?;
+        if !header.list || header.payload_length != buf.len() {
+            return None
+        }
+        if u8::decode(&mut buf).ok()? != DisconnectReason::SubprotocolSpecific as u8 {
+            return None
+        }
+        let code = u8::decode(&mut buf).ok()?;
+        buf.is_empty().then_some(code)
+    }
+
+    if buf.len() > MAX_DISCONNECT_PAYLOAD_SIZE {
+        return None
+    }
+    decode_list(buf).or_else(|| {
+        // the uncompressed list is 3 bytes, `0xc2 0x10 code`
+        if Snappy::decompress_len(buf).ok()? != 3 {
+            return None
+        }
+        decode_list(&Snappy::default().decompress_vec(buf).ok()?)
+    })
+}
+
+/// Encodes a `p2p` disconnect message with the given [`DisconnectReason`], including the message
+/// id.
+///
+/// The payload is not snappy compressed, this is the form disconnects are sent in during the
+/// handshake.
+pub fn encode_disconnect(reason: DisconnectReason) -> Vec<u8> {
+    alloy_rlp::encode(P2PMessage::Disconnect(reason))
+}
+
+/// Decodes a `p2p` disconnect message, including the message id, in any of the
+/// [`DisconnectWireForm`]s, see [`decode_with_form`].
+pub fn decode_disconnect(buf: &[u8]) -> alloy_rlp::Result<DisconnectReason> {
+    let (&id, payload) = buf.split_first().ok_or(alloy_rlp::Error::InputTooShort)?;
+    if id != P2PMessageID::Disconnect as u8 {
+        return Err(alloy_rlp::Error::Custom("expected a disconnect message"))
+    }
+    decode_with_form(payload).map(|(reason, _)| reason)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{p2pstream::P2PMessage, DisconnectReason};
+    use super::{
+        decode_disconnect, decode_subprotocol_code, decode_with_form, decode_with_mode,
+        encode_disconnect, DisconnectDecodeMode, DisconnectWireForm, SubprotocolDisconnectReasons,
+        MAX_DISCONNECT_PAYLOAD_SIZE, NON_CANONICAL_DISCONNECT_ERROR, OVERSIZED_DISCONNECT_ERROR,
+    };
+    use crate::{
+        compression::Snappy,
+        p2pstream::{P2PMessage, P2PMessageID},
+        DisconnectReason, DISCONNECT_LIST_LENGTH_ERROR,
+    };
     use alloy_primitives::hex;
     use alloy_rlp::{Decodable, Encodable};
+    use std::collections::HashSet;
 
+    /// Returns all known reasons.
+    ///
+    /// These are derived from the wire codes, so a new reason is covered by every test using this
+    /// as soon as it can be decoded.
     fn all_reasons() -> Vec<DisconnectReason> {
-        vec![
-            DisconnectReason::DisconnectRequested,
-            DisconnectReason::TcpSubsystemError,
-            DisconnectReason::ProtocolBreach,
-            DisconnectReason::UselessPeer,
-            DisconnectReason::TooManyPeers,
-            DisconnectReason::AlreadyConnected,
-            DisconnectReason::IncompatibleP2PProtocolVersion,
-            DisconnectReason::NullNodeIdentity,
-            DisconnectReason::ClientQuitting,
-            DisconnectReason::UnexpectedHandshakeIdentity,
-            DisconnectReason::ConnectedToSelf,
-            DisconnectReason::PingTimeout,
-            DisconnectReason::SubprotocolSpecific,
-        ]
+        (0..=u8::MAX).filter_map(|code| DisconnectReason::try_from(code).ok()).collect()
+    }
+
+    /// Returns all disconnect payloads, i.e. without the message id, that are accepted for the
+    /// given reason, with the wire form they're in.
+    fn wire_forms(reason: DisconnectReason) -> Vec<(Vec<u8>, DisconnectWireForm)> {
+        let code = reason as u8;
+        let mut forms = vec![
+            (vec![code], DisconnectWireForm::SingleByte),
+            (vec![0xc1, code], DisconnectWireForm::RlpList),
+            // the snappy framing of a one byte payload, which is stored uncompressed
+            (vec![0x01, 0x00, code], DisconnectWireForm::SnappySingle),
+        ];
+        if Snappy::ENABLED {
+            let mut compressed = vec![0; Snappy::max_compress_len(2)];
+            let len = Snappy::default().compress(&[0xc1, code], &mut compressed).unwrap();
+            compressed.truncate(len);
+            forms.push((compressed, DisconnectWireForm::SnappyList));
+        }
+        if reason == DisconnectReason::DisconnectRequested {
+            // zero is RLP encoded as the empty string
+            forms.push((vec![0x80], DisconnectWireForm::Legacy0101));
+            forms.push((vec![0xc1, 0x80], DisconnectWireForm::RlpList));
+        }
+        forms
     }
 
     #[test]
@@ -118,27 +373,204 @@ mod tests {
 
     #[test]
     fn test_decode_known_reasons() {
-        let all_reasons = vec![
-            // encoding the disconnect reason as a single byte
-            "0100", // 0x00 case
-            "0180", // second 0x00 case
-            "0101", "0102", "0103", "0104", "0105", "0106", "0107", "0108", "0109", "010a", "010b",
-            "0110",   // encoding the disconnect reason in a list
-            "01c100", // 0x00 case
-            "01c180", // second 0x00 case
-            "01c101", "01c102", "01c103", "01c104", "01c105", "01c106", "01c107", "01c108",
-            "01c109", "01c10a", "01c10b", "01c110",
+        let mut all_forms = HashSet::new();
+        for reason in all_reasons() {
+            // our own encoding is a single-element list, which holds the empty string for
+            // `DisconnectRequested`, since RLP encodes zero as `0x80`
+            let encoded = alloy_rlp::encode(reason);
+            assert_eq!(DisconnectReason::decode(&mut &encoded[..]), Ok(reason));
+            let forms = wire_forms(reason);
+            assert!(forms.iter().any(|(payload, _)| *payload == encoded), "{reason:?}");
+
+            for (payload, form) in forms {
+                let hex = hex::encode(&payload);
+                assert_eq!(decode_with_form(&payload), Ok((reason, form)), "{reason:?}: {hex}");
+
+                let mut msg = vec![P2PMessageID::Disconnect as u8];
+                msg.extend_from_slice(&payload);
+                assert_eq!(decode_disconnect(&msg), Ok(reason), "{reason:?}: {hex}");
+                // uncompressed forms are also accepted by the p2p message decoder, see
+                // `P2PStream::poll_next_message`
+                if !matches!(
+                    form,
+                    DisconnectWireForm::SnappySingle | DisconnectWireForm::SnappyList
+                ) {
+                    assert_eq!(
+                        P2PMessage::decode(&mut &msg[..]),
+                        Ok(P2PMessage::Disconnect(reason)),
+                        "{reason:?}: {hex}"
+                    );
+                }
+
+                // only the canonical form is accepted in strict mode
+                assert_eq!(
+                    decode_with_mode(&payload, DisconnectDecodeMode::Strict).is_ok(),
+                    form == DisconnectWireForm::SnappyList,
+                    "{reason:?}: {hex}"
+                );
+                all_forms.insert(form);
+            }
+        }
+
+        // every wire form is covered, snappy compressed lists only if compression is available
+        assert_eq!(all_forms.len(), if Snappy::ENABLED { 5 } else { 4 });
+    }
+
+    #[test]
+    fn test_decode_known_reasons_with_form() {
+        let mut all_reasons = vec![
+            ("0100".to_string(), DisconnectWireForm::SingleByte),
+            ("0180".to_string(), DisconnectWireForm::Legacy0101),
+            ("01c100".to_string(), DisconnectWireForm::RlpList),
+            ("01c180".to_string(), DisconnectWireForm::RlpList),
         ];
+        for code in ["01", "02", "03", "04", "05", "06", "07", "08", "09", "0a", "0b", "10"] {
+            all_reasons.push((format!("01{code}"), DisconnectWireForm::SingleByte));
+            all_reasons.push((format!("01c1{code}"), DisconnectWireForm::RlpList));
+        }
 
-        for reason in all_reasons {
-            let reason = hex::decode(reason).unwrap();
-            let message = P2PMessage::decode(&mut &reason[..]).unwrap();
-            let P2PMessage::Disconnect(_) = message else {
+        for (msg, expected_form) in all_reasons {
+            let msg = hex::decode(&msg).unwrap();
+            let P2PMessage::Disconnect(expected_reason) =
+                P2PMessage::decode(&mut &msg[..]).unwrap()
+            else {
                 panic!("expected a disconnect message");
             };
+
+            let (reason, form) = decode_with_form(&msg[1..]).unwrap();
+            assert_eq!(reason, expected_reason, "{}", hex::encode(&msg));
+            assert_eq!(form, expected_form, "{}", hex::encode(&msg));
         }
     }
 
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_decode_snappy_reasons_with_form() {
+        for (payload, expected_reason, expected_form) in [
+            ("010003", DisconnectReason::UselessPeer, DisconnectWireForm::SnappySingle),
+            ("010000", DisconnectReason::DisconnectRequested, DisconnectWireForm::SnappySingle),
+            ("0204c103", DisconnectReason::UselessPeer, DisconnectWireForm::SnappyList),
+            ("0204c110", DisconnectReason::SubprotocolSpecific, DisconnectWireForm::SnappyList),
+        ] {
+            let payload = hex::decode(payload).unwrap();
+            assert_eq!(decode_with_form(&payload).unwrap(), (expected_reason, expected_form));
+        }
+
+        for invalid in ["", "c000", "0204c1ff", "0304c10300", "ffffffff"] {
+            assert!(decode_with_form(&hex::decode(invalid).unwrap()).is_err(), "{invalid}");
+        }
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_decode_loosely_framed_snappy_reasons() {
+        // the reason as two one byte literals, and as a literal with a two byte length
+        for payload in ["0200c10003", "02f40100c103"] {
+            let payload = hex::decode(payload).unwrap();
+            assert!(payload.len() > 4 && payload.len() <= MAX_DISCONNECT_PAYLOAD_SIZE);
+            assert_eq!(
+                decode_with_form(&payload).unwrap(),
+                (DisconnectReason::UselessPeer, DisconnectWireForm::SnappyList)
+            );
+
+            let mut message = vec![P2PMessageID::Disconnect as u8];
+            message.extend_from_slice(&payload);
+            assert_eq!(decode_disconnect(&message).unwrap(), DisconnectReason::UselessPeer);
+        }
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_decode_oversized_snappy_reason() {
+        // valid snappy, with an overlong varint of the decompressed length
+        let payload = hex!("828000f8010000c103");
+        assert_eq!(Snappy::default().decompress_vec(&payload).unwrap(), hex!("c103"));
+        assert!(payload.len() > MAX_DISCONNECT_PAYLOAD_SIZE);
+
+        assert_eq!(decode_with_form(&payload), Err(OVERSIZED_DISCONNECT_ERROR));
+        let mut message = vec![P2PMessageID::Disconnect as u8];
+        message.extend_from_slice(&payload);
+        assert_eq!(decode_disconnect(&message), Err(OVERSIZED_DISCONNECT_ERROR));
+    }
+
+    #[test]
+    fn test_decode_with_mode() {
+        // bare RLP, in a list and as a single byte
+        for payload in ["c103", "03"] {
+            let payload = hex::decode(payload).unwrap();
+            assert_eq!(
+                decode_with_mode(&payload, DisconnectDecodeMode::Lenient).unwrap(),
+                DisconnectReason::UselessPeer
+            );
+            assert_eq!(
+                decode_with_mode(&payload, DisconnectDecodeMode::Strict),
+                Err(NON_CANONICAL_DISCONNECT_ERROR)
+            );
+        }
+
+        #[cfg(feature = "snappy")]
+        {
+            // the snappy compressed single byte isn't canonical either
+            let payload = hex!("010003");
+            assert_eq!(
+                decode_with_mode(&payload, DisconnectDecodeMode::Lenient).unwrap(),
+                DisconnectReason::UselessPeer
+            );
+            assert_eq!(
+                decode_with_mode(&payload, DisconnectDecodeMode::Strict),
+                Err(NON_CANONICAL_DISCONNECT_ERROR)
+            );
+
+            let payload = hex!("0204c103");
+            for mode in [DisconnectDecodeMode::Lenient, DisconnectDecodeMode::Strict] {
+                assert_eq!(
+                    decode_with_mode(&payload, mode).unwrap(),
+                    DisconnectReason::UselessPeer
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_subprotocol_disconnect_reasons() {
+        let reasons = SubprotocolDisconnectReasons::default()
+            .with_reason("fake", |code| format!("fake reason {code}"));
+        assert_eq!(reasons.get("fake", 3), Some("fake reason 3".to_string()));
+        assert_eq!(reasons.describe("fake", 3), "fake reason 3");
+
+        // unknown subprotocols fall back to the code
+        assert_eq!(reasons.get("snap", 3), None);
+        assert_eq!(reasons.describe("snap", 3), "snap code 0x03");
+    }
+
+    #[test]
+    fn test_decode_subprotocol_code() {
+        assert_eq!(decode_subprotocol_code(&hex!("c21002")), Some(0x02));
+        assert_eq!(decode_subprotocol_code(&hex!("c21080")), Some(0x00));
+        #[cfg(feature = "snappy")]
+        assert_eq!(decode_subprotocol_code(&hex!("0308c21002")), Some(0x02));
+
+        // no trailing code
+        assert_eq!(decode_subprotocol_code(&hex!("c110")), None);
+        assert_eq!(decode_subprotocol_code(&hex!("0204c110")), None);
+        // not a subprotocol specific disconnect
+        assert_eq!(decode_subprotocol_code(&hex!("c20302")), None);
+        // more than one trailing element
+        assert_eq!(decode_subprotocol_code(&hex!("c3100203")), None);
+    }
+
+    #[test]
+    fn test_decode_oversized_payload() {
+        let payload = vec![0x03; MAX_DISCONNECT_PAYLOAD_SIZE + 1];
+        assert_eq!(decode_with_form(&payload), Err(OVERSIZED_DISCONNECT_ERROR));
+    }
+
+    #[test]
+    fn test_decode_multi_element_list_with_form() {
+        let payload = hex::decode("c20203").unwrap();
+        assert_eq!(decode_with_form(&payload), Err(DISCONNECT_LIST_LENGTH_ERROR));
+    }
+
     #[test]
     fn test_decode_disconnect_requested() {
         let reason = "0100";
@@ -150,4 +582,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_encode_decode_disconnect() {
+        for reason in all_reasons() {
+            let encoded = encode_disconnect(reason);
+            assert_eq!(encoded, alloy_rlp::encode(P2PMessage::Disconnect(reason)));
+            assert_eq!(
+                P2PMessage::decode(&mut &encoded[..]).unwrap(),
+                P2PMessage::Disconnect(reason)
+            );
+            assert_eq!(decode_disconnect(&encoded).unwrap(), reason);
+        }
+
+        // snappy compressed, as sent after the handshake
+        #[cfg(feature = "snappy")]
+        assert_eq!(decode_disconnect(&hex!("010204c103")).unwrap(), DisconnectReason::UselessPeer);
+
+        assert!(decode_disconnect(&[]).is_err());
+        assert!(decode_disconnect(&alloy_rlp::encode(P2PMessage::Ping)).is_err());
+    }
 }
@@ -5,6 +5,7 @@ use crate::{
 };
 use alloy_primitives::B256;
 use reth_chainspec::Chain;
+use reth_ecies::ECIESError;
 use reth_primitives::{GotExpected, GotExpectedBoxed, ValidationError};
 use std::io;
 
@@ -60,6 +61,26 @@ impl EthStreamError {
         }
         None
     }
+
+    /// Returns the [`ECIESError`] if the ECIES framing of the connection failed.
+    pub fn as_ecies(&self) -> Option<&ECIESError> {
+        if let Self::P2PStreamError(err) = self {
+            return err.as_ecies()
+        }
+        None
+    }
+
+    /// Returns the [`DisconnectReason`] that should be sent to the peer because of this error.
+    ///
+    /// If the peer disconnected, this is the reason it sent us.
+    pub fn recommended_disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::P2PStreamError(err) => err.recommended_disconnect_reason(),
+            Self::EthHandshakeError(err) => err.recommended_disconnect_reason(),
+            Self::StreamTimeout => DisconnectReason::PingTimeout,
+            _ => DisconnectReason::ProtocolBreach,
+        }
+    }
 }
 
 impl From<io::Error> for EthStreamError {
@@ -101,3 +122,61 @@ pub enum EthHandshakeError {
         maximum: usize,
     },
 }
+
+// === impl EthHandshakeError ===
+
+impl EthHandshakeError {
+    /// Returns the [`DisconnectReason`] that should be sent to the peer because of this error.
+    pub const fn recommended_disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::NoResponse => DisconnectReason::DisconnectRequested,
+            _ => DisconnectReason::ProtocolBreach,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{message::EthMessageID, EthVersion};
+    use reth_ecies::ECIESErrorImpl;
+
+    #[test]
+    fn test_recommended_disconnect_reason() {
+        let cases = vec![
+            (
+                EthStreamError::from(io::Error::from(ECIESError::from(
+                    ECIESErrorImpl::TagCheckBodyFailed,
+                ))),
+                DisconnectReason::TcpSubsystemError,
+            ),
+            (
+                P2PStreamError::Disconnected(DisconnectReason::TooManyPeers).into(),
+                DisconnectReason::TooManyPeers,
+            ),
+            (
+                EthHandshakeError::MismatchedGenesis(GotExpectedBoxed(Box::new(GotExpected {
+                    got: Default::default(),
+                    expected: Default::default(),
+                })))
+                .into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (EthHandshakeError::NoResponse.into(), DisconnectReason::DisconnectRequested),
+            (EthStreamError::StreamTimeout, DisconnectReason::PingTimeout),
+            (EthStreamError::MessageTooBig(usize::MAX), DisconnectReason::ProtocolBreach),
+            (
+                MessageError::RlpError(alloy_rlp::Error::Overflow).into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (
+                MessageError::Invalid(EthVersion::Eth67, EthMessageID::GetNodeData).into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+        ];
+
+        for (err, expected) in cases {
+            assert_eq!(err.recommended_disconnect_reason(), expected, "{err:?}");
+        }
+    }
+}
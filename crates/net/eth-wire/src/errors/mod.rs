@@ -2,6 +2,8 @@
 
 mod eth;
 mod p2p;
+mod session;
 
 pub use eth::*;
 pub use p2p::*;
+pub use session::*;
@@ -2,10 +2,11 @@
 
 use std::io;
 
+use reth_ecies::{ECIESError, ECIESErrorImpl};
 use reth_eth_wire_types::{DisconnectReason, UnknownDisconnectReason};
 use reth_primitives::GotExpected;
 
-use crate::{capability::SharedCapabilityError, ProtocolVersion};
+use crate::{capability::SharedCapabilityError, Capability, DisconnectWireForm, ProtocolVersion};
 
 /// Errors when sending/receiving p2p messages. These should result in kicking the peer.
 #[derive(thiserror::Error, Debug)]
@@ -19,6 +20,7 @@ pub enum P2PStreamError {
     Rlp(#[from] alloy_rlp::Error),
 
     /// Error in compression/decompression using Snappy.
+    #[cfg(feature = "snappy")]
     #[error(transparent)]
     Snap(#[from] snap::Error),
 
@@ -35,6 +37,22 @@ pub enum P2PStreamError {
         max_size: usize,
     },
 
+    /// Message size exceeds the maximum length configured for its subprotocol.
+    #[error("{capability} message size ({message_size}) exceeds max length ({max_size})")]
+    SubprotocolMessageTooBig {
+        /// The capability of the message.
+        capability: Capability,
+        /// The actual size of the message received.
+        message_size: usize,
+        /// The maximum allowed size for messages of the capability.
+        max_size: usize,
+    },
+
+    /// A message of a dependent subprotocol was received before the handshake of the primary
+    /// protocol completed, e.g. a `snap` message before the `eth` status exchange.
+    #[error("{0} message received before the primary protocol handshake completed")]
+    SubprotocolMessageBeforeHandshake(Capability),
+
     /// Unknown reserved P2P message ID error.
     #[error("unknown reserved p2p message id: {0}")]
     UnknownReservedMessageId(u8),
@@ -71,10 +89,29 @@ pub enum P2PStreamError {
     #[error("too many messages buffered before sending")]
     SendBufferFull,
 
+    /// Too many bytes received and buffered before being processed.
+    #[error("received messages buffered ({buffered} bytes) exceed max ({max_buffered} bytes)")]
+    ReceiveBufferFull {
+        /// The number of buffered bytes, including the message that exceeded the limit.
+        buffered: usize,
+        /// The maximum number of bytes that may be buffered.
+        max_buffered: usize,
+    },
+
     /// Disconnected error.
     #[error("disconnected")]
     Disconnected(DisconnectReason),
 
+    /// The peer disconnected with a disconnect message that isn't in the canonical form, see
+    /// [`DisconnectDecodeMode::Strict`](crate::DisconnectDecodeMode::Strict).
+    #[error("disconnected with a non-canonical {form:?} disconnect message: {reason}")]
+    NonCanonicalDisconnect {
+        /// The reason of the disconnect.
+        reason: DisconnectReason,
+        /// The form the disconnect message was encoded in.
+        form: DisconnectWireForm,
+    },
+
     /// Unknown disconnect reason error.
     #[error("unknown disconnect reason: {0}")]
     UnknownDisconnectReason(#[from] UnknownDisconnectReason),
@@ -87,12 +124,58 @@ impl P2PStreamError {
     pub const fn as_disconnected(&self) -> Option<DisconnectReason> {
         let reason = match self {
             Self::HandshakeError(P2PHandshakeError::Disconnected(reason)) |
-            Self::Disconnected(reason) => reason,
+            Self::Disconnected(reason) |
+            Self::NonCanonicalDisconnect { reason, .. } => reason,
             _ => return None,
         };
 
         Some(*reason)
     }
+
+    /// Returns the [`ECIESError`] if the ECIES framing of the connection failed.
+    pub fn as_ecies(&self) -> Option<&ECIESError> {
+        if let Self::Io(err) = self {
+            return ecies_error(err)
+        }
+        None
+    }
+
+    /// Returns the [`DisconnectReason`] that should be sent to the peer because of this error.
+    ///
+    /// If the peer disconnected, this is the reason it sent us.
+    pub fn recommended_disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::HandshakeError(err) => err.recommended_disconnect_reason(),
+            Self::Io(err) => match ecies_error(err) {
+                Some(err) => ecies_disconnect_reason(err),
+                None => DisconnectReason::TcpSubsystemError,
+            },
+            Self::Disconnected(reason) | Self::NonCanonicalDisconnect { reason, .. } => *reason,
+            Self::PingTimeout => DisconnectReason::PingTimeout,
+            Self::MismatchedProtocolVersion(_) => DisconnectReason::IncompatibleP2PProtocolVersion,
+            Self::ParseSharedCapability(SharedCapabilityError::OverlappingMessageIds(_)) => {
+                DisconnectReason::ProtocolBreach
+            }
+            // we don't share any capabilities
+            Self::ParseSharedCapability(_) | Self::CapabilityNotShared => {
+                DisconnectReason::UselessPeer
+            }
+            Self::SubprotocolMessageTooBig { .. } => DisconnectReason::SubprotocolSpecific,
+            // the send buffer is ours, the peer isn't at fault
+            Self::SendBufferFull => DisconnectReason::DisconnectRequested,
+            #[cfg(feature = "snappy")]
+            Self::Snap(_) => DisconnectReason::ProtocolBreach,
+            Self::Rlp(_) |
+            Self::MessageTooBig { .. } |
+            Self::ReceiveBufferFull { .. } |
+            Self::SubprotocolMessageBeforeHandshake(_) |
+            Self::UnknownReservedMessageId(_) |
+            Self::EmptyProtocolMessage |
+            Self::PingerError(_) |
+            Self::PingBeforeHandshake |
+            Self::UnknownDisconnectReason(_) => DisconnectReason::ProtocolBreach,
+        }
+    }
 }
 
 /// Errors when conducting a p2p handshake.
@@ -110,6 +193,18 @@ pub enum P2PHandshakeError {
     #[error("no capabilities shared with peer")]
     NoSharedCapabilities,
 
+    /// The peer advertised too many duplicate capabilities in its hello message.
+    #[error("too many duplicate capabilities advertised by peer: {0}")]
+    TooManyDuplicateCapabilities(usize),
+
+    /// The peer advertised more capabilities in its hello message than we accept.
+    #[error("too many capabilities advertised by peer: {0}")]
+    TooManyCapabilities(usize),
+
+    /// The peer's hello contains a null node id, i.e. all zeros or not a valid public key.
+    #[error("null node identity in hello message")]
+    NullNodeIdentity,
+
     /// No response received when sending out handshake.
     #[error("no response received when sending out handshake")]
     NoResponse,
@@ -127,10 +222,226 @@ pub enum P2PHandshakeError {
     DecodeError(#[from] alloy_rlp::Error),
 }
 
+// === impl P2PHandshakeError ===
+
+impl P2PHandshakeError {
+    /// Returns the [`DisconnectReason`] that should be sent to the peer because of this error.
+    ///
+    /// If the peer disconnected, this is the reason it sent us.
+    pub const fn recommended_disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::NoSharedCapabilities => DisconnectReason::UselessPeer,
+            Self::NoResponse => DisconnectReason::DisconnectRequested,
+            Self::Disconnected(reason) => *reason,
+            Self::NullNodeIdentity => DisconnectReason::NullNodeIdentity,
+            Self::HelloNotInHandshake |
+            Self::NonHelloMessageInHandshake |
+            Self::TooManyDuplicateCapabilities(_) |
+            Self::TooManyCapabilities(_) |
+            Self::Timeout |
+            Self::DecodeError(_) => DisconnectReason::ProtocolBreach,
+        }
+    }
+}
+
 /// An error that can occur when interacting with a pinger.
 #[derive(Debug, thiserror::Error)]
 pub enum PingerError {
-    /// An unexpected pong was received while the pinger was in the `Ready` state.
-    #[error("pong received while ready")]
+    /// Too many pongs were received while the pinger was in the `Ready` state, i.e. without a
+    /// ping to respond to.
+    #[error("too many pongs received while ready")]
     UnexpectedPong,
 }
+
+/// Returns the [`ECIESError`] if the [`io::Error`] was caused by the ECIES framing.
+fn ecies_error(err: &io::Error) -> Option<&ECIESError> {
+    err.get_ref().and_then(|inner| inner.downcast_ref())
+}
+
+/// Returns the [`DisconnectReason`] for a failure of the ECIES framing.
+pub(crate) const fn ecies_disconnect_reason(err: &ECIESError) -> DisconnectReason {
+    match err.inner() {
+        // an authentic peer can't produce a frame with a bad MAC, so the frame was corrupted in
+        // transit
+        ECIESErrorImpl::TagCheckHeaderFailed |
+        ECIESErrorImpl::TagCheckBodyFailed |
+        ECIESErrorImpl::IO(_) |
+        ECIESErrorImpl::UnreadableStream |
+        ECIESErrorImpl::StreamTimeout => DisconnectReason::TcpSubsystemError,
+        _ => DisconnectReason::ProtocolBreach,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{message::EthMessageID, test_utils::eth_hello, EthVersion, UnauthedP2PStream};
+    use alloy_primitives::bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use reth_ecies::stream::ECIESStream;
+    use reth_network_peers::pk2id;
+    use secp256k1::{SecretKey, SECP256K1};
+    use std::{
+        pin::Pin,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::{ready, Context, Poll},
+    };
+    use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+    /// A transport that flips the last byte of the next read once `corrupt` is set.
+    struct CorruptingIo {
+        inner: DuplexStream,
+        corrupt: Arc<AtomicBool>,
+    }
+
+    impl AsyncRead for CorruptingIo {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let filled = buf.filled().len();
+            ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+            if buf.filled().len() > filled && self.corrupt.swap(false, Ordering::SeqCst) {
+                if let Some(last) = buf.filled_mut().last_mut() {
+                    *last ^= 0xff;
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for CorruptingIo {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// Returns the server and client ends of an ECIES connection, and the flag that corrupts the
+    /// next read of the server.
+    async fn ecies_pair() -> (ECIESStream<CorruptingIo>, ECIESStream<DuplexStream>, Arc<AtomicBool>)
+    {
+        let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+        let corrupt = Arc::new(AtomicBool::new(false));
+        let server_io = CorruptingIo { inner: server_io, corrupt: corrupt.clone() };
+
+        let server_key = SecretKey::new(&mut rand::thread_rng());
+        let server_id = pk2id(&server_key.public_key(SECP256K1));
+        let client_key = SecretKey::new(&mut rand::thread_rng());
+        let (server, client) = tokio::try_join!(
+            ECIESStream::incoming(server_io, server_key),
+            ECIESStream::connect(client_io, client_key, server_id),
+        )
+        .unwrap();
+        (server, client, corrupt)
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_frame_is_transport_error() {
+        let (server, client, corrupt) = ecies_pair().await;
+        let ((mut server, _), (mut client, _)) = tokio::try_join!(
+            UnauthedP2PStream::new(server).handshake(eth_hello().0),
+            UnauthedP2PStream::new(client).handshake(eth_hello().0),
+        )
+        .unwrap();
+
+        corrupt.store(true, Ordering::SeqCst);
+        client.send(Bytes::from(vec![EthMessageID::Transactions as u8, 0xc0])).await.unwrap();
+
+        let err = server.next().await.unwrap().unwrap_err();
+        assert!(err.as_ecies().is_some_and(ECIESError::is_frame_mac_failure), "{err:?}");
+        assert_eq!(err.recommended_disconnect_reason(), DisconnectReason::TcpSubsystemError);
+    }
+
+    #[cfg(feature = "snappy")]
+    #[tokio::test]
+    async fn test_authentic_garbage_is_protocol_breach() {
+        let (server, mut client, _) = ecies_pair().await;
+
+        // the client completes the handshake, but then skips the snappy compression
+        let hello =
+            Bytes::from(alloy_rlp::encode(crate::P2PMessage::Hello(eth_hello().0.message())));
+        let (server, sent) = tokio::join!(
+            UnauthedP2PStream::new(server).handshake(eth_hello().0),
+            client.send(hello),
+        );
+        let (mut server, _) = server.unwrap();
+        sent.unwrap();
+        client.send(Bytes::from_static(&[0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff])).await.unwrap();
+
+        let err = server.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Snap(_)), "{err:?}");
+        assert_eq!(err.recommended_disconnect_reason(), DisconnectReason::ProtocolBreach);
+    }
+
+    #[test]
+    fn test_recommended_disconnect_reason() {
+        let ecies = |err: ECIESErrorImpl| P2PStreamError::Io(ECIESError::from(err).into());
+        let cases = vec![
+            (ecies(ECIESErrorImpl::TagCheckHeaderFailed), DisconnectReason::TcpSubsystemError),
+            (ecies(ECIESErrorImpl::TagCheckBodyFailed), DisconnectReason::TcpSubsystemError),
+            (ecies(ECIESErrorImpl::TagCheckDecryptFailed), DisconnectReason::ProtocolBreach),
+            (ecies(ECIESErrorImpl::InvalidAuthData), DisconnectReason::ProtocolBreach),
+            (ecies(ECIESErrorImpl::UnreadableStream), DisconnectReason::TcpSubsystemError),
+            (
+                P2PStreamError::Io(io::Error::from(io::ErrorKind::BrokenPipe)),
+                DisconnectReason::TcpSubsystemError,
+            ),
+            (P2PHandshakeError::NoSharedCapabilities.into(), DisconnectReason::UselessPeer),
+            (P2PHandshakeError::NoResponse.into(), DisconnectReason::DisconnectRequested),
+            (P2PHandshakeError::Timeout.into(), DisconnectReason::ProtocolBreach),
+            (P2PHandshakeError::NullNodeIdentity.into(), DisconnectReason::NullNodeIdentity),
+            (
+                P2PHandshakeError::Disconnected(DisconnectReason::TooManyPeers).into(),
+                DisconnectReason::TooManyPeers,
+            ),
+            (alloy_rlp::Error::Overflow.into(), DisconnectReason::ProtocolBreach),
+            (P2PStreamError::PingTimeout, DisconnectReason::PingTimeout),
+            (
+                P2PStreamError::ParseSharedCapability(
+                    SharedCapabilityError::OverlappingMessageIds(Capability::eth(
+                        EthVersion::Eth68,
+                    )),
+                ),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (P2PStreamError::CapabilityNotShared, DisconnectReason::UselessPeer),
+            (
+                P2PStreamError::SubprotocolMessageTooBig {
+                    capability: Capability::snap_1(),
+                    message_size: 2,
+                    max_size: 1,
+                },
+                DisconnectReason::SubprotocolSpecific,
+            ),
+            (
+                P2PStreamError::ReceiveBufferFull { buffered: 2, max_buffered: 1 },
+                DisconnectReason::ProtocolBreach,
+            ),
+            (PingerError::UnexpectedPong.into(), DisconnectReason::ProtocolBreach),
+            (
+                DisconnectReason::try_from(0xffu8).unwrap_err().into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+        ];
+
+        for (err, expected) in cases {
+            assert_eq!(err.recommended_disconnect_reason(), expected, "{err:?}");
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Unified error type for all phases of a session.
+
+use std::io;
+
+use reth_ecies::ECIESError;
+use reth_eth_wire_types::{message::MessageError, DisconnectReason};
+
+use crate::errors::{
+    ecies_disconnect_reason, EthHandshakeError, EthStreamError, P2PHandshakeError, P2PStreamError,
+};
+
+/// Errors that can occur during any phase of a session: the ECIES handshake, the `p2p` and `eth`
+/// handshakes, and the exchange of messages afterwards.
+///
+/// Decoding and IO failures are lifted out of the stream errors, so the same failure maps to the
+/// same variant regardless of the layer it surfaced in.
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    /// The ECIES handshake or the encrypted framing failed.
+    #[error(transparent)]
+    Ecies(#[from] ECIESError),
+    /// The `p2p` handshake failed.
+    #[error(transparent)]
+    P2PHandshake(#[from] P2PHandshakeError),
+    /// The `eth` handshake failed.
+    #[error(transparent)]
+    EthHandshake(#[from] EthHandshakeError),
+    /// A message of the peer could not be decoded.
+    #[error(transparent)]
+    Decode(#[from] alloy_rlp::Error),
+    /// The underlying connection failed.
+    #[error(transparent)]
+    Io(io::Error),
+    /// Any other error of the `p2p` stream.
+    #[error(transparent)]
+    P2P(P2PStreamError),
+    /// Any other error of the `eth` stream.
+    #[error(transparent)]
+    Eth(EthStreamError),
+}
+
+// === impl SessionError ===
+
+impl SessionError {
+    /// Returns the [`DisconnectReason`] if the peer disconnected.
+    pub const fn as_disconnected(&self) -> Option<DisconnectReason> {
+        match self {
+            Self::P2PHandshake(P2PHandshakeError::Disconnected(reason)) => Some(*reason),
+            Self::P2P(err) => err.as_disconnected(),
+            Self::Eth(err) => err.as_disconnected(),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`DisconnectReason`] that should be sent to the peer because of this error.
+    ///
+    /// If the peer disconnected, this is the reason it sent us.
+    pub fn recommended_disconnect_reason(&self) -> DisconnectReason {
+        match self {
+            Self::Ecies(err) => ecies_disconnect_reason(err),
+            Self::P2PHandshake(err) => err.recommended_disconnect_reason(),
+            Self::EthHandshake(err) => err.recommended_disconnect_reason(),
+            Self::Decode(_) => DisconnectReason::ProtocolBreach,
+            Self::Io(_) => DisconnectReason::TcpSubsystemError,
+            Self::P2P(err) => err.recommended_disconnect_reason(),
+            Self::Eth(err) => err.recommended_disconnect_reason(),
+        }
+    }
+}
+
+impl From<io::Error> for SessionError {
+    /// Errors of the ECIES framing that surfaced as [`io::Error`]s are lifted back out.
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<ECIESError>()) {
+            let inner = err.into_inner().expect("has an inner error");
+            return Self::Ecies(*inner.downcast().expect("is an ECIESError"))
+        }
+        Self::Io(err)
+    }
+}
+
+impl From<P2PStreamError> for SessionError {
+    fn from(err: P2PStreamError) -> Self {
+        match err {
+            P2PStreamError::Io(err) => err.into(),
+            P2PStreamError::Rlp(err) => Self::Decode(err),
+            P2PStreamError::HandshakeError(err) => Self::P2PHandshake(err),
+            err => Self::P2P(err),
+        }
+    }
+}
+
+impl From<EthStreamError> for SessionError {
+    fn from(err: EthStreamError) -> Self {
+        match err {
+            EthStreamError::P2PStreamError(err) => err.into(),
+            EthStreamError::EthHandshakeError(err) => Self::EthHandshake(err),
+            EthStreamError::InvalidMessage(MessageError::RlpError(err)) => Self::Decode(err),
+            err => Self::Eth(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capability::SharedCapabilityError, errors::PingerError, message::EthMessageID, Capability,
+        EthVersion,
+    };
+    use reth_ecies::ECIESErrorImpl;
+    use reth_primitives::{GotExpected, GotExpectedBoxed};
+
+    #[test]
+    fn test_recommended_disconnect_reason() {
+        let cases: Vec<(SessionError, DisconnectReason)> = vec![
+            (
+                ECIESError::from(ECIESErrorImpl::TagCheckHeaderFailed).into(),
+                DisconnectReason::TcpSubsystemError,
+            ),
+            (
+                ECIESError::from(ECIESErrorImpl::TagCheckDecryptFailed).into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (
+                ECIESError::from(ECIESErrorImpl::InvalidAuthData).into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (
+                ECIESError::from(ECIESErrorImpl::UnreadableStream).into(),
+                DisconnectReason::TcpSubsystemError,
+            ),
+            (P2PHandshakeError::NoSharedCapabilities.into(), DisconnectReason::UselessPeer),
+            (P2PHandshakeError::Timeout.into(), DisconnectReason::ProtocolBreach),
+            (P2PHandshakeError::NullNodeIdentity.into(), DisconnectReason::NullNodeIdentity),
+            (
+                P2PHandshakeError::Disconnected(DisconnectReason::TooManyPeers).into(),
+                DisconnectReason::TooManyPeers,
+            ),
+            (
+                EthHandshakeError::MismatchedGenesis(GotExpectedBoxed(Box::new(GotExpected {
+                    got: Default::default(),
+                    expected: Default::default(),
+                })))
+                .into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (EthHandshakeError::NoResponse.into(), DisconnectReason::DisconnectRequested),
+            (alloy_rlp::Error::Overflow.into(), DisconnectReason::ProtocolBreach),
+            (
+                io::Error::from(io::ErrorKind::BrokenPipe).into(),
+                DisconnectReason::TcpSubsystemError,
+            ),
+            (P2PStreamError::PingTimeout.into(), DisconnectReason::PingTimeout),
+            (
+                P2PStreamError::ParseSharedCapability(
+                    SharedCapabilityError::OverlappingMessageIds(Capability::eth(
+                        EthVersion::Eth68,
+                    )),
+                )
+                .into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (P2PStreamError::CapabilityNotShared.into(), DisconnectReason::UselessPeer),
+            (
+                P2PStreamError::SubprotocolMessageTooBig {
+                    capability: Capability::snap_1(),
+                    message_size: 2,
+                    max_size: 1,
+                }
+                .into(),
+                DisconnectReason::SubprotocolSpecific,
+            ),
+            (
+                P2PStreamError::ReceiveBufferFull { buffered: 2, max_buffered: 1 }.into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (
+                P2PStreamError::PingerError(PingerError::UnexpectedPong).into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (
+                P2PStreamError::UnknownDisconnectReason(
+                    DisconnectReason::try_from(0xffu8).unwrap_err(),
+                )
+                .into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+            (EthStreamError::StreamTimeout.into(), DisconnectReason::PingTimeout),
+            (EthStreamError::MessageTooBig(usize::MAX).into(), DisconnectReason::ProtocolBreach),
+            (
+                EthStreamError::InvalidMessage(MessageError::Invalid(
+                    EthVersion::Eth67,
+                    EthMessageID::GetNodeData,
+                ))
+                .into(),
+                DisconnectReason::ProtocolBreach,
+            ),
+        ];
+
+        for (err, expected) in cases {
+            assert_eq!(err.recommended_disconnect_reason(), expected, "{err:?}");
+        }
+    }
+
+    #[test]
+    fn test_stream_errors_are_flattened() {
+        let err = SessionError::from(EthStreamError::from(io::Error::from(
+            io::ErrorKind::ConnectionReset,
+        )));
+        assert!(matches!(err, SessionError::Io(_)));
+
+        let ecies = io::Error::from(ECIESError::from(ECIESErrorImpl::TagCheckBodyFailed));
+        let err = SessionError::from(EthStreamError::from(ecies));
+        assert!(matches!(err, SessionError::Ecies(err) if err.is_frame_mac_failure()));
+
+        let ecies = io::Error::from(ECIESError::from(io::Error::from(io::ErrorKind::BrokenPipe)));
+        assert_eq!(ecies.kind(), io::ErrorKind::BrokenPipe);
+        assert!(matches!(SessionError::from(ecies), SessionError::Io(_)));
+
+        let err = SessionError::from(EthStreamError::P2PStreamError(P2PStreamError::Rlp(
+            alloy_rlp::Error::InputTooShort,
+        )));
+        assert!(matches!(err, SessionError::Decode(alloy_rlp::Error::InputTooShort)));
+
+        let err = SessionError::from(EthStreamError::InvalidMessage(MessageError::RlpError(
+            alloy_rlp::Error::Overflow,
+        )));
+        assert!(matches!(err, SessionError::Decode(alloy_rlp::Error::Overflow)));
+
+        let err = SessionError::from(EthStreamError::P2PStreamError(
+            P2PStreamError::HandshakeError(P2PHandshakeError::NoResponse),
+        ));
+        assert!(matches!(err, SessionError::P2PHandshake(P2PHandshakeError::NoResponse)));
+
+        let err = SessionError::from(EthStreamError::EthHandshakeError(
+            EthHandshakeError::NonStatusMessageInHandshake,
+        ));
+        assert!(matches!(
+            err,
+            SessionError::EthHandshake(EthHandshakeError::NonStatusMessageInHandshake)
+        ));
+    }
+
+    #[test]
+    fn test_as_disconnected() {
+        let err = SessionError::from(EthStreamError::P2PStreamError(P2PStreamError::Disconnected(
+            DisconnectReason::TooManyPeers,
+        )));
+        assert_eq!(err.as_disconnected(), Some(DisconnectReason::TooManyPeers));
+
+        let err = SessionError::from(P2PStreamError::HandshakeError(
+            P2PHandshakeError::Disconnected(DisconnectReason::UselessPeer),
+        ));
+        assert_eq!(err.as_disconnected(), Some(DisconnectReason::UselessPeer));
+
+        let err = SessionError::from(EthStreamError::StreamTimeout);
+        assert_eq!(err.as_disconnected(), None);
+    }
+}
@@ -2,12 +2,13 @@ use crate::{
     errors::{EthHandshakeError, EthStreamError},
     message::{EthBroadcastMessage, ProtocolBroadcastMessage},
     p2pstream::HANDSHAKE_TIMEOUT,
-    CanDisconnect, DisconnectReason, EthMessage, EthVersion, ProtocolMessage, Status,
+    CanDisconnect, DisconnectReason, EthDisconnectReason, EthMessage, EthVersion, ProtocolMessage,
+    Status, StatusMismatch, DEFAULT_MAX_ANNOUNCED_HASHES,
 };
 use alloy_primitives::bytes::{Bytes, BytesMut};
-use futures::{ready, Sink, SinkExt, StreamExt};
+use futures::{Sink, SinkExt, StreamExt};
 use pin_project::pin_project;
-use reth_primitives::{ForkFilter, GotExpected};
+use reth_primitives::{ForkFilter, ForkRelation, GotExpected};
 use std::{
     pin::Pin,
     task::{Context, Poll},
@@ -21,6 +22,10 @@ use tracing::{debug, trace};
 // https://github.com/ethereum/go-ethereum/blob/30602163d5d8321fbc68afdcbbaf2362b2641bde/eth/protocols/eth/protocol.go#L50
 pub const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
 
+/// [`DEFAULT_POLL_BUDGET`] is the default number of messages an [`EthStream`] yields in a row
+/// before it yields back to the executor.
+pub const DEFAULT_POLL_BUDGET: usize = 128;
+
 /// An un-authenticated [`EthStream`]. This is consumed and returns a [`EthStream`] after the
 /// `Status` handshake is completed.
 #[pin_project]
@@ -120,12 +125,17 @@ where
                     status=%resp,
                     "validating incoming eth status from peer"
                 );
-                if status.genesis != resp.genesis {
+                if let Err(mismatch) = resp.validate_against(&status) {
                     self.inner.disconnect(DisconnectReason::ProtocolBreach).await?;
-                    return Err(EthHandshakeError::MismatchedGenesis(
-                        GotExpected { expected: status.genesis, got: resp.genesis }.into(),
-                    )
-                    .into())
+                    let err = match mismatch {
+                        StatusMismatch::Genesis(mismatch) => {
+                            EthHandshakeError::MismatchedGenesis(mismatch.into())
+                        }
+                        StatusMismatch::NetworkId(mismatch) => {
+                            EthHandshakeError::MismatchedChain(mismatch)
+                        }
+                    };
+                    return Err(err.into())
                 }
 
                 if status.version != resp.version {
@@ -137,15 +147,6 @@ where
                     .into())
                 }
 
-                if status.chain != resp.chain {
-                    self.inner.disconnect(DisconnectReason::ProtocolBreach).await?;
-                    return Err(EthHandshakeError::MismatchedChain(GotExpected {
-                        got: resp.chain,
-                        expected: status.chain,
-                    })
-                    .into())
-                }
-
                 // TD at mainnet block #7753254 is 76 bits. If it becomes 100 million times
                 // larger, it will still fit within 100 bits
                 if status.total_difficulty.bit_len() > 100 {
@@ -164,6 +165,22 @@ where
                     return Err(err.into())
                 }
 
+                // the fork ids are compatible but may still differ if one side already passed a
+                // fork the other side only knows of
+                match fork_filter.relation(resp.forkid) {
+                    ForkRelation::RemoteAhead => debug!(
+                        local=?fork_filter.current(),
+                        remote=?resp.forkid,
+                        "peer is ahead of the local fork"
+                    ),
+                    ForkRelation::RemoteBehind => debug!(
+                        local=?fork_filter.current(),
+                        remote=?resp.forkid,
+                        "peer is behind the local fork"
+                    ),
+                    ForkRelation::Same | ForkRelation::Unknown => {}
+                }
+
                 // now we can create the `EthStream` because the peer has successfully completed
                 // the handshake
                 let stream = EthStream::new(version, self.inner);
@@ -189,6 +206,12 @@ pub struct EthStream<S> {
     version: EthVersion,
     #[pin]
     inner: S,
+    /// Max number of messages yielded in a row before yielding back to the executor.
+    poll_budget: usize,
+    /// Number of messages yielded since the stream last yielded back to the executor.
+    polled_in_budget: usize,
+    /// Max number of hashes in a received eth/66 transaction hash announcement.
+    max_announced_hashes: usize,
 }
 
 impl<S> EthStream<S> {
@@ -196,7 +219,33 @@ impl<S> EthStream<S> {
     /// to manually handshake a peer.
     #[inline]
     pub const fn new(version: EthVersion, inner: S) -> Self {
-        Self { version, inner }
+        Self {
+            version,
+            inner,
+            poll_budget: DEFAULT_POLL_BUDGET,
+            polled_in_budget: 0,
+            max_announced_hashes: DEFAULT_MAX_ANNOUNCED_HASHES,
+        }
+    }
+
+    /// Sets the max number of messages the stream yields in a row before it yields back to the
+    /// executor, so that a peer flooding messages can't starve other tasks.
+    ///
+    /// Default is [`DEFAULT_POLL_BUDGET`].
+    #[inline]
+    pub const fn with_poll_budget(mut self, poll_budget: usize) -> Self {
+        self.poll_budget = poll_budget;
+        self
+    }
+
+    /// Sets the max number of hashes in a received eth/66 `NewPooledTransactionHashes` message.
+    /// Larger announcements fail to decode, which is a protocol breach.
+    ///
+    /// Default is [`DEFAULT_MAX_ANNOUNCED_HASHES`].
+    #[inline]
+    pub const fn with_max_announced_hashes(mut self, max_announced_hashes: usize) -> Self {
+        self.max_announced_hashes = max_announced_hashes;
+        self
     }
 
     /// Returns the eth version.
@@ -251,7 +300,23 @@ where
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
-        let res = ready!(this.inner.poll_next(cx));
+
+        if *this.polled_in_budget >= *this.poll_budget {
+            // budget is consumed, yield back to the executor and continue on the next poll
+            *this.polled_in_budget = 0;
+            cx.waker().wake_by_ref();
+            return Poll::Pending
+        }
+
+        let res = match this.inner.poll_next(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => {
+                *this.polled_in_budget = 0;
+                return Poll::Pending
+            }
+        };
+        *this.polled_in_budget += 1;
+
         let bytes = match res {
             Some(Ok(bytes)) => bytes,
             Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
@@ -262,7 +327,11 @@ where
             return Poll::Ready(Some(Err(EthStreamError::MessageTooBig(bytes.len()))))
         }
 
-        let msg = match ProtocolMessage::decode_message(*this.version, &mut bytes.as_ref()) {
+        let msg = match ProtocolMessage::decode_message_with_max_announced_hashes(
+            *this.version,
+            &mut bytes.as_ref(),
+            *this.max_announced_hashes,
+        ) {
             Ok(m) => m,
             Err(err) => {
                 let msg = if bytes.len() > 50 {
@@ -340,6 +409,24 @@ where
     }
 }
 
+impl<S> EthStream<S>
+where
+    S: CanDisconnect<Bytes> + Send,
+    EthStreamError: From<<S as Sink<Bytes>>::Error>,
+{
+    /// Disconnects the peer because of an `eth` specific problem.
+    ///
+    /// The peer receives [`DisconnectReason::SubprotocolSpecific`], the granular reason is only
+    /// logged.
+    pub async fn disconnect_eth(
+        &mut self,
+        reason: EthDisconnectReason,
+    ) -> Result<(), EthStreamError> {
+        debug!(target: "net::eth", reason=reason.as_str(), "disconnecting peer with eth specific reason");
+        self.disconnect(reason.into()).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::UnauthedEthStream;
@@ -347,18 +434,31 @@ mod tests {
         broadcast::BlockHashNumber,
         errors::{EthHandshakeError, EthStreamError},
         hello::DEFAULT_TCP_PORT,
+        message::{MessageError, RequestPair},
         p2pstream::UnauthedP2PStream,
-        EthMessage, EthStream, EthVersion, HelloMessageWithProtocols, PassthroughCodec,
-        ProtocolVersion, Status,
+        test_utils::{duplex, eth_hello},
+        BlockHeaders, DisconnectReason, EthDisconnectReason, EthMessage, EthStream, EthVersion,
+        GetBlockHeaders, HeadersDirection, HelloMessageWithProtocols, PassthroughCodec,
+        ProtocolMessage, ProtocolVersion, Status,
     };
-    use alloy_primitives::{B256, U256};
-    use futures::{SinkExt, StreamExt};
-    use reth_chainspec::NamedChain;
+    use alloy_primitives::{bytes::BytesMut, B256, U256};
+    use futures::{FutureExt, SinkExt, StreamExt};
+    use reth_chainspec::{NamedChain, MAINNET};
     use reth_ecies::stream::ECIESStream;
     use reth_network_peers::pk2id;
-    use reth_primitives::{ForkFilter, Head};
+    use reth_primitives::{
+        BlockHashOrNumber, ForkFilter, ForkId, ForkRelation, Head, Header, ValidationError,
+    };
     use secp256k1::{SecretKey, SECP256K1};
-    use std::time::Duration;
+    use std::{
+        io,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+        time::Duration,
+    };
     use tokio::net::{TcpListener, TcpStream};
     use tokio_util::codec::Decoder;
 
@@ -510,6 +610,111 @@ mod tests {
         handle.await.unwrap();
     }
 
+    /// Runs the `eth` handshake between a local and a remote node on mainnet with the given heads
+    /// and returns the results of both sides.
+    async fn mainnet_handshake(
+        local_filter: ForkFilter,
+        remote_filter: ForkFilter,
+    ) -> (Result<Status, EthStreamError>, Result<Status, EthStreamError>) {
+        let status = |fork_filter: &ForkFilter| Status {
+            version: EthVersion::Eth68 as u8,
+            chain: NamedChain::Mainnet.into(),
+            total_difficulty: U256::ZERO,
+            blockhash: B256::random(),
+            genesis: MAINNET.genesis_hash(),
+            forkid: fork_filter.current(),
+        };
+        let local_status = status(&local_filter);
+        let remote_status = status(&remote_filter);
+
+        let (local, remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+        let (local, remote) = tokio::join!(
+            UnauthedEthStream::new(local).handshake(local_status, local_filter),
+            UnauthedEthStream::new(remote).handshake(remote_status, remote_filter),
+        );
+        (local.map(|(_, status)| status), remote.map(|(_, status)| status))
+    }
+
+    /// A mainnet head after Shanghai and before Cancun.
+    const SHANGHAI_HEAD: Head = Head {
+        hash: B256::ZERO,
+        number: 19_000_000,
+        timestamp: 1_705_000_000,
+        difficulty: U256::ZERO,
+        total_difficulty: U256::ZERO,
+    };
+
+    /// A mainnet head after Cancun.
+    const CANCUN_HEAD: Head = Head {
+        hash: B256::ZERO,
+        number: 19_500_000,
+        timestamp: 1_711_000_000,
+        difficulty: U256::ZERO,
+        total_difficulty: U256::ZERO,
+    };
+
+    #[tokio::test]
+    async fn handshake_with_compatible_remote_ahead() {
+        let local_filter = MAINNET.fork_filter(SHANGHAI_HEAD);
+        let remote_filter = MAINNET.fork_filter(CANCUN_HEAD);
+        assert_eq!(local_filter.relation(remote_filter.current()), ForkRelation::RemoteAhead);
+
+        let (local, remote) = mainnet_handshake(local_filter, remote_filter).await;
+        assert_eq!(local.unwrap().forkid, MAINNET.fork_id(&CANCUN_HEAD));
+        assert_eq!(remote.unwrap().forkid, MAINNET.fork_id(&SHANGHAI_HEAD));
+    }
+
+    #[tokio::test]
+    async fn handshake_with_compatible_remote_behind() {
+        let local_filter = MAINNET.fork_filter(CANCUN_HEAD);
+        let remote_filter = MAINNET.fork_filter(SHANGHAI_HEAD);
+        assert_eq!(local_filter.relation(remote_filter.current()), ForkRelation::RemoteBehind);
+
+        let (local, remote) = mainnet_handshake(local_filter, remote_filter).await;
+        assert_eq!(local.unwrap().forkid, MAINNET.fork_id(&SHANGHAI_HEAD));
+        assert_eq!(remote.unwrap().forkid, MAINNET.fork_id(&CANCUN_HEAD));
+    }
+
+    #[tokio::test]
+    async fn handshake_with_incompatible_remote() {
+        let local_filter = MAINNET.fork_filter(CANCUN_HEAD);
+        // the remote is on Shanghai and not aware of Cancun, e.g. a node that was not updated
+        let mut remote_filter = MAINNET.fork_filter(SHANGHAI_HEAD);
+        remote_filter.set_current_fork_id(ForkId { next: 0, ..remote_filter.current() });
+
+        let (local, _) = mainnet_handshake(local_filter, remote_filter).await;
+        assert!(matches!(
+            local,
+            Err(EthStreamError::EthHandshakeError(EthHandshakeError::InvalidFork(
+                ValidationError::RemoteStale { .. }
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn handshake_with_mismatched_genesis() {
+        let fork_filter = MAINNET.fork_filter(CANCUN_HEAD);
+        let local_status = Status {
+            version: EthVersion::Eth68 as u8,
+            chain: NamedChain::Mainnet.into(),
+            total_difficulty: U256::ZERO,
+            blockhash: B256::random(),
+            genesis: MAINNET.genesis_hash(),
+            forkid: fork_filter.current(),
+        };
+        let remote_status = Status { genesis: B256::random(), ..local_status };
+
+        let (local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+        let msg = ProtocolMessage::from(EthMessage::Status(remote_status));
+        remote.send(alloy_rlp::encode(msg).into()).await.unwrap();
+
+        let local = UnauthedEthStream::new(local).handshake(local_status, fork_filter).await;
+        assert!(matches!(
+            local,
+            Err(EthStreamError::EthHandshakeError(EthHandshakeError::MismatchedGenesis(_)))
+        ));
+    }
+
     #[tokio::test]
     async fn can_write_and_read_cleartext() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -668,6 +873,73 @@ mod tests {
         handle.await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disconnect_eth_sends_subprotocol_specific() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let server_key = SecretKey::new(&mut rand::thread_rng());
+
+        let genesis = B256::random();
+        let fork_filter = ForkFilter::new(Head::default(), genesis, 0, Vec::new());
+
+        let status = Status {
+            version: EthVersion::Eth67 as u8,
+            chain: NamedChain::Mainnet.into(),
+            total_difficulty: U256::ZERO,
+            blockhash: B256::random(),
+            genesis,
+            forkid: fork_filter.current(),
+        };
+
+        let status_copy = status;
+        let fork_filter_clone = fork_filter.clone();
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = ECIESStream::incoming(incoming, server_key).await.unwrap();
+
+            let server_hello = HelloMessageWithProtocols {
+                protocol_version: ProtocolVersion::V5,
+                client_version: "bitcoind/1.0.0".to_string(),
+                protocols: vec![EthVersion::Eth67.into()],
+                port: DEFAULT_TCP_PORT,
+                id: pk2id(&server_key.public_key(SECP256K1)),
+            };
+
+            let (p2p_stream, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            let (mut eth_stream, _) = UnauthedEthStream::new(p2p_stream)
+                .handshake(status_copy, fork_filter_clone)
+                .await
+                .unwrap();
+
+            eth_stream.disconnect_eth(EthDisconnectReason::UnexpectedRequestId).await.unwrap();
+        });
+
+        let server_id = pk2id(&server_key.public_key(SECP256K1));
+        let client_key = SecretKey::new(&mut rand::thread_rng());
+
+        let outgoing = TcpStream::connect(local_addr).await.unwrap();
+        let sink = ECIESStream::connect(outgoing, client_key, server_id).await.unwrap();
+
+        let client_hello = HelloMessageWithProtocols {
+            protocol_version: ProtocolVersion::V5,
+            client_version: "bitcoind/1.0.0".to_string(),
+            protocols: vec![EthVersion::Eth67.into()],
+            port: DEFAULT_TCP_PORT,
+            id: pk2id(&client_key.public_key(SECP256K1)),
+        };
+
+        let (p2p_stream, _) = UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+        let (mut client_stream, _) =
+            UnauthedEthStream::new(p2p_stream).handshake(status, fork_filter).await.unwrap();
+
+        let err = client_stream.next().await.unwrap().unwrap_err();
+        assert_eq!(err.as_disconnected(), Some(DisconnectReason::SubprotocolSpecific));
+
+        handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn handshake_should_timeout() {
         let genesis = B256::random();
@@ -716,4 +988,114 @@ mod tests {
             matches!(handshake_result, Err(e) if e.to_string() == EthStreamError::StreamTimeout.to_string())
         );
     }
+
+    #[test]
+    fn eth_stream_yields_after_poll_budget() {
+        struct FlagWaker(AtomicBool);
+
+        impl futures::task::ArcWake for FlagWaker {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let msg = EthMessage::NewBlockHashes(
+            vec![BlockHashNumber { hash: B256::random(), number: 5 }].into(),
+        );
+        let bytes = BytesMut::from(&alloy_rlp::encode(ProtocolMessage::from(msg.clone()))[..]);
+
+        // a peer flooding messages, all of which are immediately ready
+        let num_msgs = 10;
+        let budget = 4;
+        let inner = futures::stream::iter((0..num_msgs).map(|_| Ok::<_, io::Error>(bytes.clone())));
+        let mut stream = EthStream::new(EthVersion::Eth67, inner).with_poll_budget(budget);
+
+        let flag = Arc::new(FlagWaker(AtomicBool::new(false)));
+        let waker = futures::task::waker(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut received = 0;
+        for _ in 0..3 {
+            let mut polled = 0;
+            while let Poll::Ready(Some(res)) = stream.poll_next_unpin(&mut cx) {
+                assert_eq!(res.unwrap(), msg);
+                polled += 1;
+            }
+            received += polled;
+
+            if received < num_msgs {
+                // yielded after consuming the budget, and scheduled itself to be polled again
+                assert_eq!(polled, budget);
+                assert!(flag.0.swap(false, Ordering::SeqCst));
+            }
+        }
+        assert_eq!(received, num_msgs);
+    }
+
+    #[test]
+    fn test_max_announced_hashes() {
+        let announcement = |num_hashes: u8| {
+            let msg = EthMessage::NewPooledTransactionHashes66(
+                (0..num_hashes).map(B256::repeat_byte).collect::<Vec<_>>().into(),
+            );
+            BytesMut::from(&alloy_rlp::encode(ProtocolMessage::from(msg))[..])
+        };
+        let inner = futures::stream::iter(
+            [announcement(2), announcement(3)].into_iter().map(Ok::<_, io::Error>),
+        );
+        let mut stream = EthStream::new(EthVersion::Eth67, inner).with_max_announced_hashes(2);
+
+        let msg = stream.next().now_or_never().unwrap().unwrap().unwrap();
+        assert!(
+            matches!(msg, EthMessage::NewPooledTransactionHashes66(hashes) if hashes.0.len() == 2)
+        );
+
+        let err = stream.next().now_or_never().unwrap().unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            EthStreamError::InvalidMessage(MessageError::TooManyAnnouncedHashes {
+                announced: 3,
+                max: 2
+            })
+        ));
+        assert_eq!(err.recommended_disconnect_reason(), DisconnectReason::ProtocolBreach);
+    }
+
+    #[tokio::test]
+    async fn test_duplex_headers_round_trip() {
+        let (mut client, mut server) = duplex::eth_pair().await;
+
+        let request = GetBlockHeaders {
+            start_block: BlockHashOrNumber::Number(1),
+            limit: 2,
+            skip: 0,
+            direction: HeadersDirection::Rising,
+        };
+        client
+            .send(EthMessage::GetBlockHeaders(RequestPair { request_id: 7, message: request }))
+            .await
+            .unwrap();
+
+        let EthMessage::GetBlockHeaders(RequestPair { request_id, message }) =
+            server.next().await.unwrap().unwrap()
+        else {
+            panic!("expected GetBlockHeaders")
+        };
+        assert_eq!(message, request);
+
+        let headers = BlockHeaders(vec![
+            Header { number: 1, ..Default::default() },
+            Header { number: 2, ..Default::default() },
+        ]);
+        server
+            .send(EthMessage::BlockHeaders(RequestPair { request_id, message: headers.clone() }))
+            .await
+            .unwrap();
+
+        let response = client.next().await.unwrap().unwrap();
+        assert_eq!(
+            response,
+            EthMessage::BlockHeaders(RequestPair { request_id: 7, message: headers })
+        );
+    }
 }
@@ -1,5 +1,5 @@
-use crate::{Capability, EthVersion, ProtocolVersion};
-use alloy_rlp::{RlpDecodable, RlpEncodable};
+use crate::{errors::P2PHandshakeError, Capability, EthVersion, ProtocolVersion};
+use alloy_rlp::{Buf, Decodable, Header, RlpDecodable, RlpEncodable};
 use reth_codecs::add_arbitrary_tests;
 use reth_network_peers::PeerId;
 use reth_primitives::constants::RETH_CLIENT_VERSION;
@@ -9,6 +9,9 @@ use reth_primitives::constants::RETH_CLIENT_VERSION;
 /// Note: this is the same as discovery port: `DEFAULT_DISCOVERY_PORT`
 pub(crate) const DEFAULT_TCP_PORT: u16 = 30303;
 
+/// The default maximum number of capabilities accepted in the [`HelloMessage`] of a peer.
+pub const DEFAULT_MAX_CAPABILITIES: usize = 64;
+
 use crate::protocol::Protocol;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -137,6 +140,53 @@ impl HelloMessage {
     pub const fn builder(id: PeerId) -> HelloMessageBuilder {
         HelloMessageBuilder::new(id)
     }
+
+    /// Decodes a [`HelloMessage`] that advertises at most `max_capabilities` capabilities.
+    ///
+    /// The capabilities are counted before any of them are decoded, so a peer advertising a huge
+    /// number of capabilities is rejected with [`P2PHandshakeError::TooManyCapabilities`] without
+    /// allocating them.
+    pub fn decode_with_max_capabilities(
+        buf: &mut &[u8],
+        max_capabilities: usize,
+    ) -> Result<Self, P2PHandshakeError> {
+        let mut fields = *buf;
+        let header = Header::decode(&mut fields)?;
+        if !header.list {
+            return Err(alloy_rlp::Error::UnexpectedString.into())
+        }
+        ProtocolVersion::decode(&mut fields)?;
+        Header::decode(&mut fields).and_then(|client_version| {
+            if fields.len() < client_version.payload_length {
+                return Err(alloy_rlp::Error::InputTooShort)
+            }
+            fields.advance(client_version.payload_length);
+            Ok(())
+        })?;
+
+        let capabilities = Header::decode(&mut fields)?;
+        if !capabilities.list {
+            return Err(alloy_rlp::Error::UnexpectedString.into())
+        }
+        if fields.len() < capabilities.payload_length {
+            return Err(alloy_rlp::Error::InputTooShort.into())
+        }
+        let mut remaining = &fields[..capabilities.payload_length];
+        let mut count = 0;
+        while !remaining.is_empty() {
+            let capability = Header::decode(&mut remaining)?;
+            if remaining.len() < capability.payload_length {
+                return Err(alloy_rlp::Error::InputTooShort.into())
+            }
+            remaining.advance(capability.payload_length);
+            count += 1;
+        }
+        if count > max_capabilities {
+            return Err(P2PHandshakeError::TooManyCapabilities(count))
+        }
+
+        Ok(Self::decode(buf)?)
+    }
 }
 
 /// Builder for [`HelloMessageWithProtocols`]
@@ -215,7 +265,10 @@ impl HelloMessageBuilder {
 
 #[cfg(test)]
 mod tests {
-    use crate::{p2pstream::P2PMessage, Capability, EthVersion, HelloMessage, ProtocolVersion};
+    use crate::{
+        errors::P2PHandshakeError, hello::DEFAULT_MAX_CAPABILITIES, p2pstream::P2PMessage,
+        Capability, EthVersion, HelloMessage, ProtocolVersion,
+    };
     use alloy_rlp::{Decodable, Encodable, EMPTY_STRING_CODE};
     use reth_network_peers::pk2id;
     use secp256k1::{SecretKey, SECP256K1};
@@ -277,4 +330,32 @@ mod tests {
         // zero is encoded as 0x80, the empty string code in RLP
         assert_eq!(hello_encoded[0], EMPTY_STRING_CODE);
     }
+
+    #[test]
+    fn test_decode_with_max_capabilities() {
+        let secret_key = SecretKey::new(&mut rand::thread_rng());
+        let id = pk2id(&secret_key.public_key(SECP256K1));
+        let hello_with = |capabilities: usize| HelloMessage {
+            protocol_version: ProtocolVersion::V5,
+            client_version: "reth/0.1.0".to_string(),
+            capabilities: (0..capabilities)
+                .map(|version| Capability::new_static("cap", version))
+                .collect(),
+            port: 30303,
+            id,
+        };
+
+        let hello = hello_with(DEFAULT_MAX_CAPABILITIES);
+        let encoded = alloy_rlp::encode(&hello);
+        let decoded =
+            HelloMessage::decode_with_max_capabilities(&mut &encoded[..], DEFAULT_MAX_CAPABILITIES)
+                .unwrap();
+        assert_eq!(decoded, hello);
+
+        let encoded = alloy_rlp::encode(hello_with(DEFAULT_MAX_CAPABILITIES + 1));
+        let err =
+            HelloMessage::decode_with_max_capabilities(&mut &encoded[..], DEFAULT_MAX_CAPABILITIES)
+                .unwrap_err();
+        assert_eq!(err, P2PHandshakeError::TooManyCapabilities(DEFAULT_MAX_CAPABILITIES + 1));
+    }
 }
@@ -14,6 +14,8 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
 pub mod capability;
+pub mod clock;
+mod compression;
 mod disconnect;
 pub mod errors;
 mod ethstream;
@@ -32,13 +34,22 @@ pub use tokio_util::codec::{
 };
 
 pub use crate::{
-    disconnect::CanDisconnect,
-    ethstream::{EthStream, UnauthedEthStream, MAX_MESSAGE_SIZE},
-    hello::{HelloMessage, HelloMessageBuilder, HelloMessageWithProtocols},
+    disconnect::{
+        decode_disconnect, decode_subprotocol_code, decode_with_form, decode_with_mode,
+        encode_disconnect, CanDisconnect, DisconnectDecodeMode, DisconnectWireForm,
+        SubprotocolDisconnectReasons, SubprotocolReasonFn, MAX_DISCONNECT_PAYLOAD_SIZE,
+        NON_CANONICAL_DISCONNECT_ERROR, OVERSIZED_DISCONNECT_ERROR,
+    },
+    ethstream::{EthStream, UnauthedEthStream, DEFAULT_POLL_BUDGET, MAX_MESSAGE_SIZE},
+    hello::{
+        HelloMessage, HelloMessageBuilder, HelloMessageWithProtocols, DEFAULT_MAX_CAPABILITIES,
+    },
     p2pstream::{
-        DisconnectP2P, P2PMessage, P2PMessageID, P2PStream, UnauthedP2PStream,
-        MAX_RESERVED_MESSAGE_ID,
+        DisconnectP2P, GracefulDisconnect, P2PMessage, P2PMessageID, P2PStream, UnauthedP2PStream,
+        DEFAULT_PING_JITTER, DEFAULT_RECEIVE_BUFFER_HARD_LIMIT, DEFAULT_RECEIVE_BUFFER_SOFT_LIMIT,
+        DISCONNECT_FLUSH_TIMEOUT, MAX_RESERVED_MESSAGE_ID,
     },
+    pinger::DEFAULT_MAX_UNSOLICITED_PONGS,
     Capability, ProtocolVersion,
 };
 
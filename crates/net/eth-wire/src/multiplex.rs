@@ -133,6 +133,11 @@ impl<St> RlpxProtocolMultiplexer<St> {
     /// This accepts a closure that does a handshake with the remote peer and returns a tuple of the
     /// primary stream and extra data.
     ///
+    /// Messages of other subprotocols that arrive before the handshake completed are delegated to
+    /// the installed protocols, unless the subprotocol depends on the handshake of the primary
+    /// protocol, like `snap` on the `eth` [`Status`]. Such a message is a protocol breach: the
+    /// peer is disconnected and [`P2PStreamError::SubprotocolMessageBeforeHandshake`] is returned.
+    ///
     /// See also [`UnauthedEthStream::handshake`]
     pub async fn into_satellite_stream_with_tuple_handshake<F, Fut, Err, Primary, Extra>(
         mut self,
@@ -175,6 +180,15 @@ impl<St> RlpxProtocolMultiplexer<St> {
                             if cap == shared_cap {
                                 // delegate to primary
                                 let _ = to_primary.send(msg);
+                            } else if requires_primary_handshake(&shared_cap, &cap) {
+                                let reason = DisconnectReason::ProtocolBreach;
+                                if self.inner.conn.start_disconnect(reason).is_ok() {
+                                    let _ = self.inner.conn.close().await;
+                                }
+                                return Err(P2PStreamError::SubprotocolMessageBeforeHandshake(
+                                    cap.capability().into_owned(),
+                                )
+                                .into())
                             } else {
                                 // delegate to satellite
                                 self.inner.delegate_message(&cap, msg);
@@ -223,6 +237,12 @@ impl<St> RlpxProtocolMultiplexer<St> {
     }
 }
 
+/// Returns whether the subprotocol can only be used once the handshake of the primary protocol
+/// completed, which is the case for `snap` since it depends on the `eth` [`Status`].
+const fn requires_primary_handshake(primary: &SharedCapability, cap: &SharedCapability) -> bool {
+    primary.is_eth() && cap.is_snap()
+}
+
 #[derive(Debug)]
 struct MultiplexInner<St> {
     /// The raw p2p stream
@@ -668,12 +688,14 @@ impl fmt::Debug for ProtocolStream {
 mod tests {
     use super::*;
     use crate::{
+        protocol::Protocol,
         test_utils::{
             connect_passthrough, eth_handshake, eth_hello,
             proto::{test_hello, TestProtoMessage},
         },
-        UnauthedP2PStream,
+        EthMessage, HelloMessageWithProtocols, UnauthedP2PStream,
     };
+    use alloy_rlp::EMPTY_LIST_CODE;
     use tokio::{net::TcpListener, sync::oneshot};
     use tokio_util::codec::Decoder;
 
@@ -804,4 +826,184 @@ mod tests {
             }
         }
     }
+
+    /// Frames in the message id range of an installed protocol are dispatched to that protocol
+    /// with relative ids, while eth frames are handled by the primary stream.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_protocol_dispatch_by_message_id() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let (status, fork_filter) = eth_handshake();
+        let other_status = status;
+        let other_fork_filter = fork_filter.clone();
+        let _handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (server_hello, _) = test_hello();
+            let (conn, _) = UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            let offset = conn
+                .shared_capabilities()
+                .ensure_matching_capability(&TestProtoMessage::capability())
+                .unwrap()
+                .relative_message_id_offset();
+
+            let (mut st, _) = UnauthedEthStream::new(conn)
+                .handshake(other_status, other_fork_filter)
+                .await
+                .unwrap();
+
+            st.send(EthMessage::NewBlockHashes(Vec::new().into())).await.unwrap();
+            for msg in [TestProtoMessage::ping(), TestProtoMessage::message("hello")] {
+                let mut frame = msg.encoded();
+                frame[0] += offset;
+                st.inner_mut().send(frame.freeze()).await.unwrap();
+            }
+
+            futures::future::pending::<()>().await;
+        });
+
+        let conn = connect_passthrough(local_addr, test_hello().0).await;
+        let (mut st, _their_status) = RlpxProtocolMultiplexer::new(conn)
+            .into_eth_satellite_stream(status, fork_filter)
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        st.install_protocol(&TestProtoMessage::capability(), |mut conn| {
+            async_stream::stream! {
+                while let Some(msg) = conn.next().await {
+                    tx.send(TestProtoMessage::decode_message(&mut &msg[..]).unwrap()).unwrap();
+                }
+                yield BytesMut::new();
+            }
+        })
+        .unwrap();
+
+        let mut received = Vec::new();
+        let mut eth_received = false;
+        while received.len() < 2 || !eth_received {
+            tokio::select! {
+                msg = rx.recv() => received.push(msg.unwrap()),
+                msg = st.next() => {
+                    assert_eq!(msg.unwrap().unwrap(), EthMessage::NewBlockHashes(Vec::new().into()));
+                    eth_received = true;
+                }
+            }
+        }
+        assert_eq!(received, vec![TestProtoMessage::ping(), TestProtoMessage::message("hello")]);
+    }
+
+    /// `snap` depends on `eth`, so a `snap` message before the `eth` status exchange is a protocol
+    /// breach.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subprotocol_message_before_status() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let (status, fork_filter) = eth_handshake();
+        let hello = |(hello, _): (HelloMessageWithProtocols, _)| {
+            HelloMessageWithProtocols::builder(hello.id)
+                .protocols(hello.protocols)
+                .protocol(Protocol::snap_1())
+                .build()
+        };
+        let server_hello = hello(eth_hello());
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (mut conn, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            let offset = conn.shared_capabilities().snap().unwrap().relative_message_id_offset();
+
+            // an empty `GetAccountRange` request before our status
+            conn.send(Bytes::from(vec![offset, EMPTY_LIST_CODE])).await.unwrap();
+
+            // the status is received before the disconnect
+            loop {
+                match conn.next().await {
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Some(err),
+                    None => return None,
+                }
+            }
+        });
+
+        let conn = connect_passthrough(local_addr, hello(eth_hello())).await;
+        let err = RlpxProtocolMultiplexer::new(conn)
+            .into_eth_satellite_stream(status, fork_filter)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                EthStreamError::P2PStreamError(P2PStreamError::SubprotocolMessageBeforeHandshake(
+                    ref cap
+                )) if *cap == Capability::snap_1()
+            ),
+            "{err:?}"
+        );
+
+        let err = handle.await.unwrap().unwrap();
+        assert_eq!(err.as_disconnected(), Some(DisconnectReason::ProtocolBreach));
+    }
+
+    /// Messages of subprotocols that don't depend on `eth` are delegated even if they arrive before
+    /// the `eth` status exchange.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_independent_subprotocol_message_before_status() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let (status, fork_filter) = eth_handshake();
+        let other_status = status;
+        let other_fork_filter = fork_filter.clone();
+        let _handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (server_hello, _) = test_hello();
+            let (mut conn, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            let offset = conn
+                .shared_capabilities()
+                .ensure_matching_capability(&TestProtoMessage::capability())
+                .unwrap()
+                .relative_message_id_offset();
+
+            // a test protocol message before our status
+            let mut frame = TestProtoMessage::ping().encoded();
+            frame[0] += offset;
+            conn.send(frame.freeze()).await.unwrap();
+
+            let (_st, _) = UnauthedEthStream::new(conn)
+                .handshake(other_status, other_fork_filter)
+                .await
+                .unwrap();
+
+            futures::future::pending::<()>().await;
+        });
+
+        let conn = connect_passthrough(local_addr, test_hello().0).await;
+        let mut multiplexer = RlpxProtocolMultiplexer::new(conn);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        multiplexer
+            .install_protocol(&TestProtoMessage::capability(), |mut conn| {
+                async_stream::stream! {
+                    while let Some(msg) = conn.next().await {
+                        tx.send(TestProtoMessage::decode_message(&mut &msg[..]).unwrap()).unwrap();
+                    }
+                    yield BytesMut::new();
+                }
+            })
+            .unwrap();
+
+        let (mut st, _their_status) =
+            multiplexer.into_eth_satellite_stream(status, fork_filter).await.unwrap();
+
+        let msg = tokio::select! {
+            msg = rx.recv() => msg.unwrap(),
+            _ = st.next() => unreachable!("no eth message is sent"),
+        };
+        assert_eq!(msg, TestProtoMessage::ping());
+    }
 }
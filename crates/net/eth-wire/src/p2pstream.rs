@@ -1,7 +1,14 @@
 use crate::{
-    capability::SharedCapabilities,
-    disconnect::CanDisconnect,
+    capability::{SharedCapabilities, SharedCapabilityLimit},
+    clock::SharedClock,
+    compression::Snappy,
+    disconnect::{
+        decode_disconnect, decode_subprotocol_code, decode_with_form, encode_disconnect,
+        CanDisconnect, DisconnectDecodeMode, SubprotocolDisconnectReasons,
+        MAX_DISCONNECT_PAYLOAD_SIZE, OVERSIZED_DISCONNECT_ERROR,
+    },
     errors::{P2PHandshakeError, P2PStreamError},
+    hello::DEFAULT_MAX_CAPABILITIES,
     pinger::{Pinger, PingerEvent},
     DisconnectReason, HelloMessage, HelloMessageWithProtocols,
 };
@@ -9,21 +16,26 @@ use alloy_primitives::{
     bytes::{Buf, BufMut, Bytes, BytesMut},
     hex,
 };
-use alloy_rlp::{Decodable, Encodable, Error as RlpError, EMPTY_LIST_CODE};
+use alloy_rlp::{
+    Decodable, Encodable, Error as RlpError, Header, EMPTY_LIST_CODE, EMPTY_STRING_CODE,
+};
 use futures::{Sink, SinkExt, StreamExt};
 use pin_project::pin_project;
 use reth_codecs::add_arbitrary_tests;
 use reth_metrics::metrics::counter;
+use reth_network_peers::id2pk;
 use reth_primitives::GotExpected;
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    future::Future,
     io,
     pin::Pin,
     task::{ready, Context, Poll},
     time::Duration,
 };
+use tokio::time::Sleep;
 use tokio_stream::Stream;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -43,6 +55,10 @@ const MAX_P2P_MESSAGE_ID: u8 = P2PMessageID::Pong as u8;
 /// handshake has timed out.
 pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// [`DISCONNECT_FLUSH_TIMEOUT`] is the maximum amount of time to wait for the `Disconnect`
+/// message to be flushed in [`P2PStream::graceful_disconnect`].
+pub const DISCONNECT_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// [`PING_TIMEOUT`] determines the amount of time to wait before determining that a `p2p` ping has
 /// timed out.
 const PING_TIMEOUT: Duration = Duration::from_secs(15);
@@ -51,6 +67,10 @@ const PING_TIMEOUT: Duration = Duration::from_secs(15);
 /// when the peer is responsive.
 const PING_INTERVAL: Duration = Duration::from_secs(60);
 
+/// [`DEFAULT_PING_JITTER`] is the default fraction of [`PING_INTERVAL`] by which the ping interval
+/// of a stream is randomized, see [`P2PStream::set_ping_jitter`].
+pub const DEFAULT_PING_JITTER: f64 = 0.2;
+
 /// [`MAX_P2P_CAPACITY`] is the maximum number of messages that can be buffered to be sent in the
 /// `p2p` stream.
 ///
@@ -59,6 +79,34 @@ const PING_INTERVAL: Duration = Duration::from_secs(60);
 /// encoded data.
 const MAX_P2P_CAPACITY: usize = 2;
 
+/// [`DEFAULT_RECEIVE_BUFFER_SOFT_LIMIT`] is the default number of bytes of received subprotocol
+/// messages the [`P2PStream`] buffers before it stops reading from the underlying stream, see
+/// [`P2PStream::set_receive_buffer_limits`].
+///
+/// By default nothing is read ahead: a message is only read from the underlying stream when the
+/// previous one was consumed.
+pub const DEFAULT_RECEIVE_BUFFER_SOFT_LIMIT: usize = 1;
+
+/// [`DEFAULT_RECEIVE_BUFFER_HARD_LIMIT`] is the default maximum number of bytes of received
+/// subprotocol messages the [`P2PStream`] buffers, a single message of the maximum size.
+pub const DEFAULT_RECEIVE_BUFFER_HARD_LIMIT: usize = MAX_PAYLOAD_SIZE + 1;
+
+/// [`COMPRESSION_RATIO_SAMPLE_IMPACT`] is how much a received message affects the moving average
+/// of the compression ratio, see [`P2PStream::compression_ratio`].
+const COMPRESSION_RATIO_SAMPLE_IMPACT: f64 = 0.1;
+
+/// [`SNAPPY_SINGLE_BYTE_PREFIX`] is the snappy framing of a one byte payload: the uncompressed
+/// length `0x01`, followed by the tag of a one byte literal, `0x00`.
+///
+/// Disconnect messages whose reason was RLP encoded as a bare byte instead of the `[reason]` list
+/// and then snappy compressed arrive in this form. For example `0x01010003` is a disconnect
+/// (`0x01`) carrying the compressed reason `0x03` ([`DisconnectReason::UselessPeer`]), and
+/// `0x01010000` carries [`DisconnectReason::DisconnectRequested`].
+///
+/// Some clients send a snappy compressed empty list instead, `0x010100c0`, which is treated like a
+/// zero reason: [`DisconnectReason::DisconnectRequested`].
+const SNAPPY_SINGLE_BYTE_PREFIX: [u8; 2] = [0x01, 0x00];
+
 /// An un-authenticated [`P2PStream`]. This is consumed and returns a [`P2PStream`] after the
 /// `Hello` handshake is completed.
 #[pin_project]
@@ -66,12 +114,34 @@ const MAX_P2P_CAPACITY: usize = 2;
 pub struct UnauthedP2PStream<S> {
     #[pin]
     inner: S,
+    /// The maximum number of capabilities accepted in the `Hello` of the peer.
+    max_capabilities: usize,
+    /// Limits the shared capabilities used on the session, if set.
+    capability_limit: Option<SharedCapabilityLimit>,
 }
 
 impl<S> UnauthedP2PStream<S> {
     /// Create a new `UnauthedP2PStream` from a type `S` which implements `Stream` and `Sink`.
     pub const fn new(inner: S) -> Self {
-        Self { inner }
+        Self { inner, max_capabilities: DEFAULT_MAX_CAPABILITIES, capability_limit: None }
+    }
+
+    /// Sets the maximum number of capabilities accepted in the `Hello` of the peer.
+    ///
+    /// A peer advertising more capabilities is disconnected with
+    /// [`DisconnectReason::ProtocolBreach`].
+    pub const fn with_max_capabilities(mut self, max_capabilities: usize) -> Self {
+        self.max_capabilities = max_capabilities;
+        self
+    }
+
+    /// Sets a limit on the subprotocols that run on the session.
+    ///
+    /// Shared capabilities that aren't used under the limit are dropped after the negotiation, see
+    /// [`SharedCapabilities::try_new_with_limit`].
+    pub fn with_capability_limit(mut self, limit: SharedCapabilityLimit) -> Self {
+        self.capability_limit = Some(limit);
+        self
     }
 
     /// Returns a reference to the inner stream.
@@ -87,17 +157,33 @@ where
     /// Consumes the `UnauthedP2PStream` and returns a `P2PStream` after the `Hello` handshake is
     /// completed successfully. This also returns the `Hello` message sent by the remote peer.
     pub async fn handshake(
+        self,
+        hello: HelloMessageWithProtocols,
+    ) -> Result<(P2PStream<S>, HelloMessage), P2PStreamError> {
+        self.handshake_with_timeout(hello, HANDSHAKE_TIMEOUT).await
+    }
+
+    /// Same as [`Self::handshake`], but waits at most `timeout_limit` for the `Hello` of the
+    /// remote peer.
+    ///
+    /// A peer that doesn't send its `Hello` in time is in breach of the protocol, so it's
+    /// disconnected with [`DisconnectReason::ProtocolBreach`].
+    pub async fn handshake_with_timeout(
         mut self,
         hello: HelloMessageWithProtocols,
+        timeout_limit: Duration,
     ) -> Result<(P2PStream<S>, HelloMessage), P2PStreamError> {
         trace!(?hello, "sending p2p hello to peer");
 
         // send our hello message with the Sink
         self.inner.send(alloy_rlp::encode(P2PMessage::Hello(hello.message())).into()).await?;
 
-        let first_message_bytes = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.inner.next())
-            .await
-            .or(Err(P2PStreamError::HandshakeError(P2PHandshakeError::Timeout)))?
+        let Ok(first_message) = tokio::time::timeout(timeout_limit, self.inner.next()).await else {
+            debug!("Peer did not send a hello in time");
+            self.send_disconnect(DisconnectReason::ProtocolBreach).await?;
+            return Err(P2PStreamError::HandshakeError(P2PHandshakeError::Timeout))
+        };
+        let first_message_bytes = first_message
             .ok_or(P2PStreamError::HandshakeError(P2PHandshakeError::NoResponse))??;
 
         // let's check the compressed length first, we will need to check again once confirming
@@ -115,7 +201,17 @@ where
         // Decodable::decode, because the first message (either Disconnect or Hello) is not snappy
         // compressed, and the Decodable implementation assumes that non-hello messages are snappy
         // compressed.
-        let their_hello = match P2PMessage::decode(&mut &first_message_bytes[..]) {
+        let first_message = match u8::decode(&mut &first_message_bytes[..]) {
+            Ok(id) if id == P2PMessageID::Hello as u8 => {
+                HelloMessage::decode_with_max_capabilities(
+                    &mut &first_message_bytes[1..],
+                    self.max_capabilities,
+                )
+                .map(P2PMessage::Hello)
+            }
+            _ => P2PMessage::decode(&mut &first_message_bytes[..]).map_err(Into::into),
+        };
+        let their_hello = match first_message {
             Ok(P2PMessage::Hello(hello)) => Ok(hello),
             Ok(P2PMessage::Disconnect(reason)) => {
                 if matches!(reason, DisconnectReason::TooManyPeers) {
@@ -127,9 +223,14 @@ where
                 counter!("p2pstream.disconnected_errors").increment(1);
                 Err(P2PStreamError::HandshakeError(P2PHandshakeError::Disconnected(reason)))
             }
+            Err(P2PHandshakeError::TooManyCapabilities(count)) => {
+                debug!(count, max = self.max_capabilities, "Peer advertised too many capabilities");
+                self.send_disconnect(DisconnectReason::ProtocolBreach).await?;
+                Err(P2PStreamError::HandshakeError(P2PHandshakeError::TooManyCapabilities(count)))
+            }
             Err(err) => {
                 debug!(%err, msg=%hex::encode(&first_message_bytes), "Failed to decode first message from peer");
-                Err(P2PStreamError::HandshakeError(err.into()))
+                Err(P2PStreamError::HandshakeError(err))
             }
             Ok(msg) => {
                 debug!(?msg, "expected hello message but received another message");
@@ -142,6 +243,12 @@ where
             "validating incoming p2p hello from peer"
         );
 
+        if their_hello.id.is_zero() || id2pk(their_hello.id).is_err() {
+            debug!(id=%their_hello.id, "Peer sent a null node id");
+            self.send_disconnect(DisconnectReason::NullNodeIdentity).await?;
+            return Err(P2PStreamError::HandshakeError(P2PHandshakeError::NullNodeIdentity))
+        }
+
         if (hello.protocol_version as u8) != their_hello.protocol_version as u8 {
             // send a disconnect message notifying the peer of the protocol version mismatch
             self.send_disconnect(DisconnectReason::IncompatibleP2PProtocolVersion).await?;
@@ -152,18 +259,31 @@ where
         }
 
         // determine shared capabilities (currently returns only one capability)
-        let capability_res =
-            SharedCapabilities::try_new(hello.protocols, their_hello.capabilities.clone());
+        let capability_res = match &self.capability_limit {
+            Some(limit) => SharedCapabilities::try_new_with_limit(
+                hello.protocols,
+                their_hello.capabilities.clone(),
+                limit,
+            ),
+            None => SharedCapabilities::try_new(hello.protocols, their_hello.capabilities.clone()),
+        };
 
         let shared_capability = match capability_res {
             Err(err) => {
-                // we don't share any capabilities, send a disconnect message
-                self.send_disconnect(DisconnectReason::UselessPeer).await?;
+                self.send_disconnect(err.recommended_disconnect_reason()).await?;
                 Err(err)
             }
             Ok(cap) => Ok(cap),
         }?;
 
+        if !Snappy::ENABLED {
+            // the stream falls back to raw mode, which only peers in raw mode understand
+            warn!(
+                id=%their_hello.id,
+                "Built without the `snappy` feature, messages to the peer won't be compressed"
+            );
+        }
+
         let stream = P2PStream::new(self.inner, shared_capability);
 
         Ok((stream, their_hello))
@@ -183,10 +303,7 @@ where
             %reason,
             "Sending disconnect message during the handshake",
         );
-        self.inner
-            .send(Bytes::from(alloy_rlp::encode(P2PMessage::Disconnect(reason))))
-            .await
-            .map_err(P2PStreamError::Io)
+        self.inner.send(Bytes::from(encode_disconnect(reason))).await.map_err(P2PStreamError::Io)
     }
 }
 
@@ -229,11 +346,9 @@ pub struct P2PStream<S> {
     #[pin]
     inner: S,
 
-    /// The snappy encoder used for compressing outgoing messages
-    encoder: snap::raw::Encoder,
-
-    /// The snappy decoder used for decompressing incoming messages
-    decoder: snap::raw::Decoder,
+    /// The snappy encoder and decoder used for compressing outgoing and decompressing incoming
+    /// messages
+    snappy: Snappy,
 
     /// The state machine used for keeping track of the peer's ping status.
     pinger: Pinger,
@@ -251,25 +366,99 @@ pub struct P2PStream<S> {
     /// Whether this stream is currently in the process of disconnecting by sending a disconnect
     /// message.
     disconnecting: bool,
+
+    /// Moving average of the compression ratio of received messages, if any were received.
+    compression_ratio: Option<f64>,
+
+    /// Stricter maximum message sizes of subprotocols, by capability name.
+    max_capability_message_sizes: HashMap<String, usize>,
+
+    /// Subprotocol messages read from the underlying stream that weren't yet returned.
+    received_messages: VecDeque<BytesMut>,
+
+    /// Total size of the messages in `received_messages`.
+    received_messages_size: usize,
+
+    /// Number of buffered bytes at which we stop reading from the underlying stream.
+    receive_buffer_soft_limit: usize,
+
+    /// Maximum number of buffered bytes, exceeding it is a protocol breach.
+    receive_buffer_hard_limit: usize,
+
+    /// An error that was read after the buffered messages, returned once they were consumed.
+    received_error: Option<P2PStreamError>,
+
+    /// The reason of the disconnect we sent, if we started to disconnect.
+    local_disconnect_reason: Option<DisconnectReason>,
+
+    /// The grace period after flushing our disconnect before the connection is closed, see
+    /// [`DisconnectReason::close_grace_period`].
+    close_grace: Option<Pin<Box<Sleep>>>,
+
+    /// The reason of the disconnect the peer sent, if one was received.
+    remote_disconnect_reason: Option<DisconnectReason>,
+
+    /// Whether messages are framed as raw RLP, without snappy compression, see
+    /// [`P2PStream::new_raw`].
+    raw: bool,
+
+    /// Which forms of disconnect messages are accepted from the peer.
+    disconnect_decode_mode: DisconnectDecodeMode,
+
+    /// Interprets the disconnect codes of subprotocols for logging.
+    subprotocol_disconnect_reasons: SubprotocolDisconnectReasons,
 }
 
 impl<S> P2PStream<S> {
     /// Create a new [`P2PStream`] from the provided stream.
     /// New [`P2PStream`]s are assumed to have completed the `p2p` handshake successfully and are
     /// ready to send and receive subprotocol messages.
+    ///
+    /// Without the `snappy` feature, the stream can't compress messages and is created in raw
+    /// mode, which **is not part of the `RLPx` spec**, see [`P2PStream::new_raw`].
     pub fn new(inner: S, shared_capabilities: SharedCapabilities) -> Self {
         Self {
             inner,
-            encoder: snap::raw::Encoder::new(),
-            decoder: snap::raw::Decoder::new(),
-            pinger: Pinger::new(PING_INTERVAL, PING_TIMEOUT),
+            snappy: Snappy::default(),
+            pinger: Pinger::new(PING_INTERVAL, PING_TIMEOUT).with_jitter(DEFAULT_PING_JITTER),
             shared_capabilities,
             outgoing_messages: VecDeque::new(),
             outgoing_message_buffer_capacity: MAX_P2P_CAPACITY,
             disconnecting: false,
+            compression_ratio: None,
+            max_capability_message_sizes: HashMap::new(),
+            received_messages: VecDeque::new(),
+            received_messages_size: 0,
+            receive_buffer_soft_limit: DEFAULT_RECEIVE_BUFFER_SOFT_LIMIT,
+            receive_buffer_hard_limit: DEFAULT_RECEIVE_BUFFER_HARD_LIMIT,
+            received_error: None,
+            local_disconnect_reason: None,
+            close_grace: None,
+            remote_disconnect_reason: None,
+            raw: !Snappy::ENABLED,
+            disconnect_decode_mode: DisconnectDecodeMode::default(),
+            subprotocol_disconnect_reasons: SubprotocolDisconnectReasons::default(),
         }
     }
 
+    /// Create a new [`P2PStream`] that sends and receives raw RLP frames, without snappy
+    /// compression.
+    ///
+    /// **This is not part of the `RLPx` spec** and can't talk to other nodes. It's only meant for
+    /// tests and custom transports between trusted endpoints, e.g. a local experimental
+    /// subprotocol, where both ends use this mode. All messages, including `p2p` messages like
+    /// [`P2PMessage::Disconnect`], are exchanged as the message id followed by the plain RLP
+    /// payload.
+    pub fn new_raw(inner: S, shared_capabilities: SharedCapabilities) -> Self {
+        Self { raw: true, ..Self::new(inner, shared_capabilities) }
+    }
+
+    /// Returns `true` if this stream exchanges raw RLP frames without snappy compression, see
+    /// [`P2PStream::new_raw`].
+    pub const fn is_raw(&self) -> bool {
+        self.raw
+    }
+
     /// Returns a reference to the inner stream.
     pub const fn inner(&self) -> &S {
         &self.inner
@@ -284,6 +473,115 @@ impl<S> P2PStream<S> {
         self.outgoing_message_buffer_capacity = capacity;
     }
 
+    /// Sets the limits of the buffer of received subprotocol messages.
+    ///
+    /// The stream reads ahead from the underlying stream until the buffered messages reach
+    /// `soft_limit` bytes, then it stops reading until they were consumed, which applies
+    /// backpressure to the peer. If a message would grow the buffer beyond `hard_limit` bytes, the
+    /// buffered messages are dropped, the stream is disconnected with
+    /// [`DisconnectReason::ProtocolBreach`] and yields a [`P2PStreamError::ReceiveBufferFull`].
+    ///
+    /// Any other error is only returned after the messages that were received before it.
+    ///
+    /// The defaults are [`DEFAULT_RECEIVE_BUFFER_SOFT_LIMIT`] and
+    /// [`DEFAULT_RECEIVE_BUFFER_HARD_LIMIT`].
+    ///
+    /// # Panics
+    ///
+    /// If `soft_limit` is `0` or greater than `hard_limit`.
+    pub fn set_receive_buffer_limits(&mut self, soft_limit: usize, hard_limit: usize) {
+        assert!(soft_limit > 0, "receive buffer soft limit must be positive");
+        assert!(soft_limit <= hard_limit, "receive buffer soft limit exceeds hard limit");
+        self.receive_buffer_soft_limit = soft_limit;
+        self.receive_buffer_hard_limit = hard_limit;
+    }
+
+    /// Returns the number of bytes of received subprotocol messages that are buffered.
+    pub const fn buffered_received_bytes(&self) -> usize {
+        self.received_messages_size
+    }
+
+    /// Returns `true` if the stream stopped reading from the underlying stream because the buffer
+    /// of received messages reached its soft limit.
+    pub const fn is_receive_paused(&self) -> bool {
+        self.received_messages_size >= self.receive_buffer_soft_limit
+    }
+
+    /// Returns the reason of the disconnect we sent, if we started to disconnect.
+    pub const fn local_disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.local_disconnect_reason
+    }
+
+    /// Returns the reason of the disconnect the peer sent, if one was received.
+    ///
+    /// If both sides disconnect at the same time, this is also recorded when the peer's
+    /// disconnect arrives after ours was sent, see [`DisconnectP2P::start_disconnect`].
+    pub const fn remote_disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.remote_disconnect_reason
+    }
+
+    /// Returns true if the given message id is the id of a [`P2PMessage::Hello`].
+    ///
+    /// The id of the hello message is RLP encoded like in the handshake, as `0x80`, or sent as
+    /// the bare byte `0x00`. `0x80` is only a hello if it isn't the id of a message of a shared
+    /// capability.
+    fn is_hello_message_id(&self, id: u8) -> bool {
+        id == P2PMessageID::Hello as u8 ||
+            (id == EMPTY_STRING_CODE &&
+                self.shared_capabilities
+                    .find_by_offset(id)
+                    .and_then(|cap| cap.relative_message_id(id))
+                    .is_none())
+    }
+
+    /// Sets the fraction of the ping interval by which the interval of this stream is
+    /// randomized, so pings of streams that were created at the same time aren't sent in
+    /// lockstep.
+    ///
+    /// The jitter is clamped to `[0, 1)`, a jitter of `0` disables the randomization.
+    ///
+    /// Note: this restarts the ping interval, so it should be set right after the stream is
+    /// created.
+    pub fn set_ping_jitter(&mut self, jitter: f64) {
+        self.pinger = Pinger::with_clock(PING_INTERVAL, PING_TIMEOUT, self.pinger.clock())
+            .with_jitter(jitter)
+            .with_max_unsolicited_pongs(self.pinger.max_unsolicited_pongs());
+    }
+
+    /// Sets the clock that decides when pings are due and when they time out, see
+    /// [`Clock`](crate::clock::Clock).
+    ///
+    /// Note: this restarts the ping interval, so it should be set right after the stream is
+    /// created.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.pinger.set_clock(clock);
+    }
+
+    /// Sets which forms of disconnect messages are accepted from the peer.
+    ///
+    /// In [`DisconnectDecodeMode::Strict`], a disconnect that isn't in the canonical form yields
+    /// [`P2PStreamError::NonCanonicalDisconnect`]. This has no effect on a raw stream, see
+    /// [`P2PStream::new_raw`].
+    ///
+    /// The default is [`DisconnectDecodeMode::Lenient`].
+    pub fn set_disconnect_decode_mode(&mut self, mode: DisconnectDecodeMode) {
+        self.disconnect_decode_mode = mode;
+    }
+
+    /// Sets the number of pongs the peer may send without a ping before the stream fails with
+    /// [`PingerError::UnexpectedPong`](crate::errors::PingerError::UnexpectedPong).
+    ///
+    /// The count of unsolicited pongs decays by one per ping interval. The default is
+    /// [`DEFAULT_MAX_UNSOLICITED_PONGS`].
+    pub fn set_max_unsolicited_pongs(&mut self, max_unsolicited_pongs: u32) {
+        self.pinger.set_max_unsolicited_pongs(max_unsolicited_pongs);
+    }
+
+    /// Returns the number of pongs the peer sent without a ping, which were ignored.
+    pub const fn unsolicited_pongs(&self) -> u32 {
+        self.pinger.unsolicited_pongs()
+    }
+
     /// Returns the shared capabilities for this stream.
     ///
     /// This includes all the shared capabilities that were negotiated during the handshake and
@@ -292,19 +590,120 @@ impl<S> P2PStream<S> {
         &self.shared_capabilities
     }
 
+    /// Sets the maximum size of a decompressed message of the capability with the given name, e.g.
+    /// `snap`.
+    ///
+    /// This is checked in addition to the global maximum message size of the `p2p` protocol. A
+    /// message that exceeds it is a breach of the subprotocol: the stream is disconnected with
+    /// [`DisconnectReason::SubprotocolSpecific`] and yields a
+    /// [`P2PStreamError::SubprotocolMessageTooBig`].
+    pub fn set_max_capability_message_size(&mut self, name: impl Into<String>, max_size: usize) {
+        self.max_capability_message_sizes.insert(name.into(), max_size);
+    }
+
+    /// Sets the mappings that interpret the disconnect codes of subprotocols, see
+    /// [`P2PStream::start_subprotocol_disconnect`].
+    pub fn set_subprotocol_disconnect_reasons(&mut self, reasons: SubprotocolDisconnectReasons) {
+        self.subprotocol_disconnect_reasons = reasons;
+    }
+
+    /// Starts to gracefully disconnect on behalf of the subprotocol with the given name, e.g.
+    /// `snap`, see [`DisconnectP2P::start_disconnect`].
+    ///
+    /// The peer receives [`DisconnectReason::SubprotocolSpecific`], the given code only determines
+    /// the logged reason, see [`P2PStream::set_subprotocol_disconnect_reasons`].
+    pub fn start_subprotocol_disconnect(
+        &mut self,
+        name: &str,
+        code: u8,
+    ) -> Result<(), P2PStreamError> {
+        let reason = self.subprotocol_disconnect_reasons.describe(name, code);
+        debug!(capability = name, code, %reason, "subprotocol disconnecting");
+        self.start_disconnect(DisconnectReason::SubprotocolSpecific)
+    }
+
+    /// Logs the reason of a subprotocol specific disconnect of the peer with the given code, as
+    /// interpreted by the mappings of the shared subprotocols, see
+    /// [`P2PStream::set_subprotocol_disconnect_reasons`].
+    fn on_remote_subprotocol_disconnect(&self, code: u8) {
+        let mut described = false;
+        for cap in self.shared_capabilities.iter_caps() {
+            if let Some(reason) = self.subprotocol_disconnect_reasons.get(cap.name(), code) {
+                debug!(capability = cap.name(), code, %reason, "peer disconnected by subprotocol");
+                described = true;
+            }
+        }
+        if !described {
+            debug!(code, "peer disconnected by subprotocol");
+        }
+    }
+
+    /// Returns the subprotocol message size limit that's exceeded by a message with the given
+    /// multiplexed id and decompressed size, if any.
+    fn exceeded_capability_message_size(
+        &self,
+        id: u8,
+        message_size: usize,
+    ) -> Option<P2PStreamError> {
+        if self.max_capability_message_sizes.is_empty() {
+            return None
+        }
+        let cap = self.shared_capabilities.find_by_offset(id)?;
+        let max_size = *self.max_capability_message_sizes.get(cap.name())?;
+        (message_size > max_size).then(|| P2PStreamError::SubprotocolMessageTooBig {
+            capability: cap.capability().into_owned(),
+            message_size,
+            max_size,
+        })
+    }
+
+    /// Returns the moving average of the compression ratio, the decompressed size divided by the
+    /// compressed size, of the messages received from the peer.
+    ///
+    /// Snappy can't shrink incompressible payloads, e.g. random bytes, so a ratio at or below `1`
+    /// indicates a peer that's flooding us with messages that are expensive to decompress.
+    ///
+    /// Returns `None` if no message was received yet.
+    pub const fn compression_ratio(&self) -> Option<f64> {
+        self.compression_ratio
+    }
+
+    /// Updates the moving average of the compression ratio with a received message.
+    fn on_decompressed(&mut self, compressed_len: usize, decompressed_len: usize) {
+        let ratio = decompressed_len as f64 / compressed_len.max(1) as f64;
+        self.compression_ratio = Some(self.compression_ratio.map_or(ratio, |current| {
+            current.mul_add(
+                1.0 - COMPRESSION_RATIO_SAMPLE_IMPACT,
+                ratio * COMPRESSION_RATIO_SAMPLE_IMPACT,
+            )
+        }));
+    }
+
     /// Returns `true` if the stream has outgoing capacity.
     fn has_outgoing_capacity(&self) -> bool {
         self.outgoing_messages.len() < self.outgoing_message_buffer_capacity
     }
 
-    /// Queues in a _snappy_ encoded [`P2PMessage::Pong`] message.
-    fn send_pong(&mut self) {
-        self.outgoing_messages.push_back(Bytes::from(alloy_rlp::encode(P2PMessage::Pong)));
+    /// Queues in a [`P2PMessage::Pong`] message, _snappy_ encoded unless in raw mode.
+    pub fn send_pong(&mut self) {
+        let pong = self.encode_ping_pong(P2PMessage::Pong);
+        self.outgoing_messages.push_back(pong);
     }
 
-    /// Queues in a _snappy_ encoded [`P2PMessage::Ping`] message.
+    /// Queues in a [`P2PMessage::Ping`] message, _snappy_ encoded unless in raw mode.
     pub fn send_ping(&mut self) {
-        self.outgoing_messages.push_back(Bytes::from(alloy_rlp::encode(P2PMessage::Ping)));
+        let ping = self.encode_ping_pong(P2PMessage::Ping);
+        self.outgoing_messages.push_back(ping);
+    }
+
+    /// Encodes a [`P2PMessage::Ping`] or [`P2PMessage::Pong`], whose empty payload is a raw RLP
+    /// list in raw mode.
+    fn encode_ping_pong(&self, msg: P2PMessage) -> Bytes {
+        if self.raw {
+            Bytes::from(vec![msg.message_id() as u8, EMPTY_LIST_CODE])
+        } else {
+            Bytes::from(alloy_rlp::encode(msg))
+        }
     }
 }
 
@@ -322,7 +721,9 @@ impl<S> DisconnectP2P for P2PStream<S> {
     /// Starts to gracefully disconnect the connection by sending a Disconnect message and stop
     /// reading new messages.
     ///
-    /// Once disconnect process has started, the [`Stream`] will terminate immediately.
+    /// Once disconnect process has started, the [`Stream`] will terminate immediately. If the
+    /// peer disconnected at the same time, its disconnect is consumed without an error and its
+    /// reason is recorded, see [`P2PStream::remote_disconnect_reason`].
     ///
     /// # Errors
     ///
@@ -334,9 +735,16 @@ impl<S> DisconnectP2P for P2PStream<S> {
         let mut buf = Vec::with_capacity(disconnect.length());
         disconnect.encode(&mut buf);
 
-        let mut compressed = vec![0u8; 1 + snap::raw::max_compress_len(buf.len() - 1)];
+        if self.raw {
+            self.outgoing_messages.push_back(buf.into());
+            self.disconnecting = true;
+            self.local_disconnect_reason = Some(reason);
+            return Ok(())
+        }
+
+        let mut compressed = vec![0u8; 1 + Snappy::max_compress_len(buf.len() - 1)];
         let compressed_size =
-            self.encoder.compress(&buf[1..], &mut compressed[1..]).map_err(|err| {
+            self.snappy.compress(&buf[1..], &mut compressed[1..]).map_err(|err| {
                 debug!(
                     %err,
                     msg=%hex::encode(&buf[1..]),
@@ -355,6 +763,7 @@ impl<S> DisconnectP2P for P2PStream<S> {
 
         self.outgoing_messages.push_back(compressed.into());
         self.disconnecting = true;
+        self.local_disconnect_reason = Some(reason);
         Ok(())
     }
 
@@ -370,11 +779,66 @@ where
     /// Disconnects the connection by sending a disconnect message.
     ///
     /// This future resolves once the disconnect message has been sent and the stream has been
-    /// closed.
+    /// closed, after the grace period of the reason, see [`DisconnectReason::close_grace_period`].
     pub async fn disconnect(&mut self, reason: DisconnectReason) -> Result<(), P2PStreamError> {
         self.start_disconnect(reason)?;
         self.close().await
     }
+
+    /// Disconnects the connection by sending a disconnect message and reports whether the message
+    /// was written before the connection was closed, waiting at most
+    /// [`DISCONNECT_FLUSH_TIMEOUT`] for it.
+    ///
+    /// See also [`P2PStream::graceful_disconnect_with_timeout`].
+    pub async fn graceful_disconnect(
+        self,
+        reason: DisconnectReason,
+    ) -> Result<GracefulDisconnect, P2PStreamError> {
+        self.graceful_disconnect_with_timeout(reason, DISCONNECT_FLUSH_TIMEOUT).await
+    }
+
+    /// Disconnects the connection by sending a disconnect message and reports whether the message
+    /// was written before the connection was closed.
+    ///
+    /// If the message can't be flushed within the given timeout, the connection is dropped
+    /// without waiting for the peer and [`GracefulDisconnect::Incomplete`] is returned. Otherwise
+    /// the connection is closed after the grace period of the reason, see
+    /// [`DisconnectReason::close_grace_period`].
+    pub async fn graceful_disconnect_with_timeout(
+        mut self,
+        reason: DisconnectReason,
+        timeout: Duration,
+    ) -> Result<GracefulDisconnect, P2PStreamError> {
+        self.start_disconnect(reason)?;
+        match tokio::time::timeout(timeout, self.flush()).await {
+            Ok(res) => res?,
+            Err(_) => {
+                debug!(?reason, "timed out flushing disconnect, dropping connection");
+                return Ok(GracefulDisconnect::Incomplete)
+            }
+        }
+        self.close().await?;
+        Ok(GracefulDisconnect::Flushed)
+    }
+}
+
+/// The outcome of a [`P2PStream::graceful_disconnect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GracefulDisconnect {
+    /// The `Disconnect` message was written to the underlying stream before it was closed, so the
+    /// peer received the reason.
+    Flushed,
+    /// The `Disconnect` message could not be written within the timeout, e.g. because the peer
+    /// stopped reading and its buffer is full. The connection was dropped and the peer may not
+    /// have received the reason.
+    Incomplete,
+}
+
+impl GracefulDisconnect {
+    /// Returns `true` if the `Disconnect` message was written before the connection was closed.
+    pub const fn is_flushed(&self) -> bool {
+        matches!(self, Self::Flushed)
+    }
 }
 
 // S must also be `Sink` because we need to be able to respond with ping messages to follow the
@@ -389,13 +853,88 @@ where
         let this = self.get_mut();
 
         if this.disconnecting {
-            // if disconnecting, stop reading messages
+            // if disconnecting, stop reading messages, but consume a disconnect the peer sent at
+            // the same time as ours, so the teardown completes without an error
+            this.poll_remote_disconnect(cx);
             return Poll::Ready(None)
         }
 
+        // read ahead until the buffer reaches the soft limit, anything beyond that stays in the
+        // underlying stream until the buffered messages are consumed
+        while this.received_error.is_none() && !this.is_receive_paused() {
+            match this.poll_next_message(cx) {
+                Poll::Ready(Some(Ok(msg))) => {
+                    this.received_messages_size += msg.len();
+                    this.received_messages.push_back(msg);
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    if let Some(reason) = err.as_disconnected() {
+                        this.remote_disconnect_reason = Some(reason);
+                    }
+                    if this.received_messages.is_empty() {
+                        return Poll::Ready(Some(Err(err)))
+                    }
+                    if this.disconnecting {
+                        // we're dropping the peer, the buffered messages are discarded
+                        this.received_messages.clear();
+                        this.received_messages_size = 0;
+                        return Poll::Ready(Some(Err(err)))
+                    }
+                    this.received_error = Some(err);
+                }
+                Poll::Ready(None) if this.received_messages.is_empty() => return Poll::Ready(None),
+                Poll::Ready(None) | Poll::Pending => break,
+            }
+        }
+
+        if let Some(msg) = this.received_messages.pop_front() {
+            this.received_messages_size -= msg.len();
+            return Poll::Ready(Some(Ok(msg)))
+        }
+        if let Some(err) = this.received_error.take() {
+            return Poll::Ready(Some(Err(err)))
+        }
+        Poll::Pending
+    }
+}
+
+impl<S> P2PStream<S>
+where
+    S: Stream<Item = io::Result<BytesMut>> + Sink<Bytes, Error = io::Error> + Unpin,
+{
+    /// Reads the messages that are already available from the underlying stream while we're
+    /// disconnecting, until the peer's disconnect is found.
+    ///
+    /// All other messages are discarded, they're not answered because our disconnect was already
+    /// sent.
+    fn poll_remote_disconnect(&mut self, cx: &mut Context<'_>) {
+        while self.remote_disconnect_reason.is_none() {
+            let Poll::Ready(Some(Ok(bytes))) = self.inner.poll_next_unpin(cx) else { return };
+            if bytes.first() == Some(&(P2PMessageID::Disconnect as u8)) {
+                if let Some(code) = decode_subprotocol_code(&bytes[1..]) {
+                    self.on_remote_subprotocol_disconnect(code);
+                    self.remote_disconnect_reason = Some(DisconnectReason::SubprotocolSpecific);
+                    continue
+                }
+            }
+            if let Ok(reason) = decode_disconnect(&bytes) {
+                if self.local_disconnect_reason != Some(reason) {
+                    debug!(local=?self.local_disconnect_reason, remote=%reason, "Peer disconnected at the same time with a different reason");
+                }
+                self.remote_disconnect_reason = Some(reason);
+            }
+        }
+    }
+
+    /// Reads the next subprotocol message from the underlying stream, handling all `p2p` messages
+    /// that precede it.
+    fn poll_next_message(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<BytesMut, P2PStreamError>>> {
         // we should loop here to ensure we don't return Poll::Pending if we have a message to
         // return behind any pings we need to respond to
-        while let Poll::Ready(res) = this.inner.poll_next_unpin(cx) {
+        while let Poll::Ready(res) = self.inner.poll_next_unpin(cx) {
             let bytes = match res {
                 Some(Ok(bytes)) => bytes,
                 Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
@@ -412,6 +951,31 @@ where
             //
             // see: [crate::disconnect::tests::test_decode_known_reasons]
             let id = bytes[0];
+            if id == P2PMessageID::Disconnect as u8 {
+                if let Some(code) = decode_subprotocol_code(&bytes[1..]) {
+                    self.on_remote_subprotocol_disconnect(code);
+                    return Poll::Ready(Some(Err(P2PStreamError::Disconnected(
+                        DisconnectReason::SubprotocolSpecific,
+                    ))))
+                }
+            }
+            if id == P2PMessageID::Disconnect as u8 &&
+                self.disconnect_decode_mode == DisconnectDecodeMode::Strict &&
+                !self.raw
+            {
+                let (reason, form) = decode_with_form(&bytes[1..])?;
+                if !self.disconnect_decode_mode.accepts(form) {
+                    return Poll::Ready(Some(Err(P2PStreamError::NonCanonicalDisconnect {
+                        reason,
+                        form,
+                    })))
+                }
+                return Poll::Ready(Some(Err(P2PStreamError::Disconnected(reason))))
+            }
+            if id == P2PMessageID::Disconnect as u8 && bytes.len() > MAX_DISCONNECT_PAYLOAD_SIZE + 1
+            {
+                return Poll::Ready(Some(Err(OVERSIZED_DISCONNECT_ERROR.into())))
+            }
             if id == P2PMessageID::Disconnect as u8 {
                 // We can't handle the error here because disconnect reasons are encoded as both:
                 // * snappy compressed, AND
@@ -427,11 +991,29 @@ where
                 if let Ok(reason) = DisconnectReason::decode(&mut &bytes[1..]) {
                     return Poll::Ready(Some(Err(P2PStreamError::Disconnected(reason))))
                 }
+
+                if !self.raw {
+                    if let Some(reason) = decode_snappy_single_byte_disconnect(&bytes[1..]) {
+                        return Poll::Ready(Some(Err(P2PStreamError::Disconnected(reason))))
+                    }
+                }
+            }
+
+            if self.is_hello_message_id(id) {
+                // we have received a hello message outside of the handshake, the handshake is only
+                // done once, so this is a protocol violation. This is checked before decompressing
+                // because the hello message is never compressed.
+                debug!("received hello message after the handshake");
+                self.start_disconnect(DisconnectReason::ProtocolBreach)?;
+                return Poll::Ready(Some(Err(P2PStreamError::HandshakeError(
+                    P2PHandshakeError::HelloNotInHandshake,
+                ))))
             }
 
             // first check that the compressed message length does not exceed the max
             // payload size
-            let decompressed_len = snap::raw::decompress_len(&bytes[1..])?;
+            let decompressed_len =
+                if self.raw { bytes.len() - 1 } else { Snappy::decompress_len(&bytes[1..])? };
             if decompressed_len > MAX_PAYLOAD_SIZE {
                 return Poll::Ready(Some(Err(P2PStreamError::MessageTooBig {
                     message_size: decompressed_len,
@@ -439,39 +1021,60 @@ where
                 })))
             }
 
-            // create a buffer to hold the decompressed message, adding a byte to the length for
-            // the message ID byte, which is the first byte in this buffer
-            let mut decompress_buf = BytesMut::zeroed(decompressed_len + 1);
+            // subprotocols may have stricter limits
+            if id > MAX_RESERVED_MESSAGE_ID {
+                if let Some(err) = self.exceeded_capability_message_size(id, decompressed_len) {
+                    debug!(%err, "subprotocol message too big");
+                    self.start_disconnect(DisconnectReason::SubprotocolSpecific)?;
+                    return Poll::Ready(Some(Err(err)))
+                }
 
-            // each message following a successful handshake is compressed with snappy, so we need
-            // to decompress the message before we can decode it.
-            this.decoder.decompress(&bytes[1..], &mut decompress_buf[1..]).map_err(|err| {
-                debug!(
-                    %err,
-                    msg=%hex::encode(&bytes[1..]),
-                    "error decompressing p2p message"
-                );
-                err
-            })?;
+                // check the buffer before allocating, including the message id byte
+                let buffered = self.received_messages_size + decompressed_len + 1;
+                if buffered > self.receive_buffer_hard_limit {
+                    let err = P2PStreamError::ReceiveBufferFull {
+                        buffered,
+                        max_buffered: self.receive_buffer_hard_limit,
+                    };
+                    debug!(%err, "receive buffer full");
+                    self.start_disconnect(DisconnectReason::ProtocolBreach)?;
+                    return Poll::Ready(Some(Err(err)))
+                }
+            }
+
+            let mut decompress_buf = if self.raw {
+                bytes
+            } else {
+                // create a buffer to hold the decompressed message, adding a byte to the length
+                // for the message ID byte, which is the first byte in this buffer
+                let mut decompress_buf = BytesMut::zeroed(decompressed_len + 1);
+
+                // each message following a successful handshake is compressed with snappy, so we
+                // need to decompress the message before we can decode it.
+                self.snappy.decompress(&bytes[1..], &mut decompress_buf[1..]).map_err(|err| {
+                    debug!(
+                        %err,
+                        msg=%hex::encode(&bytes[1..]),
+                        "error decompressing p2p message"
+                    );
+                    err
+                })?;
+                self.on_decompressed(bytes.len() - 1, decompressed_len);
+                decompress_buf
+            };
 
             match id {
                 _ if id == P2PMessageID::Ping as u8 => {
                     trace!("Received Ping, Sending Pong");
-                    this.send_pong();
+                    self.send_pong();
                     // This is required because the `Sink` may not be polled externally, and if
                     // that happens, the pong will never be sent.
                     cx.waker().wake_by_ref();
                 }
-                _ if id == P2PMessageID::Hello as u8 => {
-                    // we have received a hello message outside of the handshake, so we will return
-                    // an error
-                    return Poll::Ready(Some(Err(P2PStreamError::HandshakeError(
-                        P2PHandshakeError::HelloNotInHandshake,
-                    ))))
-                }
                 _ if id == P2PMessageID::Pong as u8 => {
-                    // if we were waiting for a pong, this will reset the pinger state
-                    this.pinger.on_pong()?
+                    // if we were waiting for a pong, this will reset the pinger state, a pong
+                    // without a ping is ignored unless the peer sent too many of them
+                    self.pinger.on_pong()?
                 }
                 _ if id == P2PMessageID::Disconnect as u8 => {
                     // At this point, the `decompress_buf` contains the snappy decompressed
@@ -514,7 +1117,7 @@ where
                     //  * `eth/67` is reserved message IDs 0x10 - 0x19.
                     //  * `qrs/65` is reserved message IDs 0x1a - 0x21.
                     //
-                    decompress_buf[0] = bytes[0] - MAX_RESERVED_MESSAGE_ID - 1;
+                    decompress_buf[0] = id - MAX_RESERVED_MESSAGE_ID - 1;
 
                     return Poll::Ready(Some(Ok(decompress_buf)))
                 }
@@ -588,9 +1191,16 @@ where
 
         let this = self.project();
 
-        let mut compressed = BytesMut::zeroed(1 + snap::raw::max_compress_len(item.len() - 1));
+        if *this.raw {
+            let mut msg = BytesMut::from(&item[..]);
+            msg[0] = item[0] + MAX_RESERVED_MESSAGE_ID + 1;
+            this.outgoing_messages.push_back(msg.freeze());
+            return Ok(())
+        }
+
+        let mut compressed = BytesMut::zeroed(1 + Snappy::max_compress_len(item.len() - 1));
         let compressed_size =
-            this.encoder.compress(&item[1..], &mut compressed[1..]).map_err(|err| {
+            this.snappy.compress(&item[1..], &mut compressed[1..]).map_err(|err| {
                 debug!(
                     %err,
                     msg=%hex::encode(&item[1..]),
@@ -634,9 +1244,23 @@ where
         poll_res
     }
 
+    /// Flushes the buffered messages and closes the underlying stream.
+    ///
+    /// If we started to disconnect, the underlying stream is closed after the grace period of the
+    /// reason, see [`DisconnectReason::close_grace_period`].
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         ready!(self.as_mut().poll_flush(cx))?;
-        ready!(self.project().inner.poll_close(cx))?;
+
+        let this = self.project();
+        if let Some(reason) = *this.local_disconnect_reason {
+            let grace = reason.close_grace_period();
+            if !grace.is_zero() {
+                let sleep =
+                    this.close_grace.get_or_insert_with(|| Box::pin(tokio::time::sleep(grace)));
+                ready!(sleep.as_mut().poll(cx));
+            }
+        }
+        ready!(this.inner.poll_close(cx))?;
 
         Poll::Ready(Ok(()))
     }
@@ -672,6 +1296,32 @@ impl P2PMessage {
             Self::Pong => P2PMessageID::Pong,
         }
     }
+
+    /// Decodes a message from the start of the buffer, see the [`Decodable`] implementation.
+    ///
+    /// Returns the message and the number of bytes it consumed, so the caller can advance past it
+    /// if the buffer holds more data.
+    pub fn decode_with_len(buf: &[u8]) -> alloy_rlp::Result<(Self, usize)> {
+        let mut end = buf.len();
+        if let [id, payload @ ..] = buf {
+            // a disconnect reason is decoded from the entire remaining buffer, so it's limited to
+            // the length of the reason, which is either a single byte or a list
+            if *id == P2PMessageID::Disconnect as u8 {
+                let reason_len = match payload.first() {
+                    Some(&header) if header >= EMPTY_LIST_CODE => {
+                        let header = Header::decode(&mut &payload[..])?;
+                        header.length() + header.payload_length
+                    }
+                    _ => 1,
+                };
+                end = end.min(1 + reason_len);
+            }
+        }
+
+        let mut rest = &buf[..end];
+        let msg = Self::decode(&mut rest)?;
+        Ok((msg, end - rest.len()))
+    }
 }
 
 impl Encodable for P2PMessage {
@@ -749,6 +1399,21 @@ impl Decodable for P2PMessage {
     }
 }
 
+/// Decodes the payload of a disconnect message in the snappy compressed single byte form, see
+/// [`SNAPPY_SINGLE_BYTE_PREFIX`].
+///
+/// A compressed empty list is decoded as [`DisconnectReason::DisconnectRequested`], like geth
+/// treats a `0x00` reason.
+///
+/// Returns `None` if the payload is not in this form.
+pub(crate) fn decode_snappy_single_byte_disconnect(payload: &[u8]) -> Option<DisconnectReason> {
+    let [reason] = payload.strip_prefix(&SNAPPY_SINGLE_BYTE_PREFIX)? else { return None };
+    if *reason == EMPTY_LIST_CODE {
+        return Some(DisconnectReason::DisconnectRequested)
+    }
+    DisconnectReason::decode(&mut &[*reason][..]).ok()
+}
+
 /// Message IDs for `p2p` subprotocol messages.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum P2PMessageID {
@@ -793,7 +1458,16 @@ impl TryFrom<u8> for P2PMessageID {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{capability::SharedCapability, test_utils::eth_hello, EthVersion, ProtocolVersion};
+    use crate::{
+        capability::SharedCapability,
+        errors::PingerError,
+        protocol::Protocol,
+        test_utils::{duplex, eth_hello},
+        Capability, EthMessageID, EthVersion, ProtocolVersion,
+    };
+    use reth_ecies::stream::ECIESStream;
+    use reth_network_peers::{pk2id, PeerId};
+    use secp256k1::{SecretKey, SECP256K1};
     use tokio::net::{TcpListener, TcpStream};
     use tokio_util::codec::Decoder;
 
@@ -835,6 +1509,559 @@ mod tests {
         handle.await.unwrap();
     }
 
+    #[cfg(feature = "snappy")]
+    #[tokio::test]
+    async fn test_compression_ratio() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let num_incompressible = 100;
+
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (server_hello, _) = eth_hello();
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            assert_eq!(p2p_stream.compression_ratio(), None);
+
+            // a compressible message
+            p2p_stream.next().await.unwrap().unwrap();
+            assert!(p2p_stream.compression_ratio().unwrap() > 10.0);
+
+            // a flood of incompressible messages
+            for _ in 0..num_incompressible {
+                p2p_stream.next().await.unwrap().unwrap();
+            }
+            assert!(p2p_stream.compression_ratio().unwrap() < 1.0);
+        });
+
+        let outgoing = TcpStream::connect(local_addr).await.unwrap();
+        let sink = crate::PassthroughCodec::default().framed(outgoing);
+        let (client_hello, _) = eth_hello();
+        let (mut p2p_stream, _) =
+            UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+
+        let mut compressible = vec![0u8; 1024];
+        compressible[0] = EthMessageID::Transactions as u8;
+        p2p_stream.send(compressible.into()).await.unwrap();
+
+        for _ in 0..num_incompressible {
+            let mut incompressible = vec![0u8; 1024];
+            rand::Rng::fill(&mut rand::thread_rng(), &mut incompressible[..]);
+            incompressible[0] = EthMessageID::Transactions as u8;
+            p2p_stream.send(incompressible.into()).await.unwrap();
+        }
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_unsolicited_pongs() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (server_hello, _) = eth_hello();
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            p2p_stream.set_max_unsolicited_pongs(3);
+
+            // the pongs are ignored, the next item is the message sent after them
+            let msg = p2p_stream.next().await.unwrap().unwrap();
+            assert_eq!(msg[0], EthMessageID::Transactions as u8);
+            assert_eq!(p2p_stream.unsolicited_pongs(), 3);
+
+            let err = p2p_stream.next().await.unwrap().unwrap_err();
+            assert!(matches!(err, P2PStreamError::PingerError(PingerError::UnexpectedPong)));
+            assert_eq!(p2p_stream.unsolicited_pongs(), 4);
+        });
+
+        let outgoing = TcpStream::connect(local_addr).await.unwrap();
+        let sink = crate::PassthroughCodec::default().framed(outgoing);
+        let (client_hello, _) = eth_hello();
+        let (mut p2p_stream, _) =
+            UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+
+        for _ in 0..3 {
+            p2p_stream.send_pong();
+        }
+        p2p_stream
+            .send(Bytes::from_static(&[EthMessageID::Transactions as u8, 0xc0]))
+            .await
+            .unwrap();
+        p2p_stream.send_pong();
+        p2p_stream.flush().await.unwrap();
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subprotocol_message_too_big() {
+        reth_tracing::init_test_tracing();
+        const MAX_SNAP_MESSAGE_SIZE: usize = 1024;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = crate::PassthroughCodec::default().framed(incoming);
+            let (mut server_hello, _) = eth_hello();
+            server_hello.protocols.push(Protocol::snap_1());
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.unwrap();
+            p2p_stream.set_max_capability_message_size("snap", MAX_SNAP_MESSAGE_SIZE);
+
+            // other subprotocols are only subject to the global limit
+            let msg = p2p_stream.next().await.unwrap().unwrap();
+            assert_eq!(msg.len(), 1 + 2 * MAX_SNAP_MESSAGE_SIZE);
+            let msg = p2p_stream.next().await.unwrap().unwrap();
+            assert_eq!(msg.len(), 1 + MAX_SNAP_MESSAGE_SIZE);
+
+            let err = p2p_stream.next().await.unwrap().unwrap_err();
+            assert!(matches!(
+                &err,
+                P2PStreamError::SubprotocolMessageTooBig { capability, message_size, max_size }
+                    if *capability == Capability::snap_1() &&
+                        *message_size == MAX_SNAP_MESSAGE_SIZE + 1 &&
+                        *max_size == MAX_SNAP_MESSAGE_SIZE
+            ));
+            assert_eq!(err.recommended_disconnect_reason(), DisconnectReason::SubprotocolSpecific);
+            assert!(p2p_stream.is_disconnecting());
+            p2p_stream.close().await.unwrap();
+        });
+
+        let outgoing = TcpStream::connect(local_addr).await.unwrap();
+        let sink = crate::PassthroughCodec::default().framed(outgoing);
+        let (mut client_hello, _) = eth_hello();
+        client_hello.protocols.push(Protocol::snap_1());
+        let (mut p2p_stream, _) =
+            UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+        let snap_offset =
+            p2p_stream.shared_capabilities().snap().unwrap().relative_message_id_offset();
+
+        let mut eth_msg = vec![0u8; 1 + 2 * MAX_SNAP_MESSAGE_SIZE];
+        eth_msg[0] = EthMessageID::Transactions as u8;
+        p2p_stream.send(eth_msg.into()).await.unwrap();
+        for size in [MAX_SNAP_MESSAGE_SIZE, MAX_SNAP_MESSAGE_SIZE + 1] {
+            let mut snap_msg = vec![0u8; 1 + size];
+            snap_msg[0] = snap_offset;
+            p2p_stream.send(snap_msg.into()).await.unwrap();
+        }
+
+        let err = p2p_stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::SubprotocolSpecific)));
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_buffer_limits() {
+        const SOFT_LIMIT: usize = 1000;
+        const HARD_LIMIT: usize = 1500;
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+        local.set_receive_buffer_limits(SOFT_LIMIT, HARD_LIMIT);
+
+        let msg = |len: usize| {
+            let mut msg = vec![0u8; len];
+            msg[0] = EthMessageID::Transactions as u8;
+            Bytes::from(msg)
+        };
+        let sizes = [100, 100, 900, 100, 100, 100, 100, 100];
+        for size in sizes {
+            remote.feed(msg(size)).await.unwrap();
+        }
+        remote.flush().await.unwrap();
+
+        // reading stops once the first three messages are buffered, the first one is returned
+        assert_eq!(local.next().await.unwrap().unwrap().len(), 100);
+        assert_eq!(local.buffered_received_bytes(), 1000);
+        assert!(local.is_receive_paused());
+
+        // no more messages are read while paused
+        assert_eq!(local.next().await.unwrap().unwrap().len(), 100);
+        assert_eq!(local.buffered_received_bytes(), 900);
+        assert!(!local.is_receive_paused());
+
+        for size in &sizes[2..] {
+            assert_eq!(local.next().await.unwrap().unwrap().len(), *size);
+        }
+        assert_eq!(local.buffered_received_bytes(), 0);
+
+        // a message that grows the buffer beyond the hard limit is a protocol breach
+        remote.feed(msg(100)).await.unwrap();
+        remote.feed(msg(HARD_LIMIT)).await.unwrap();
+        remote.flush().await.unwrap();
+
+        let err = local.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            P2PStreamError::ReceiveBufferFull { buffered: 1600, max_buffered: HARD_LIMIT }
+        ));
+        assert_eq!(err.recommended_disconnect_reason(), DisconnectReason::ProtocolBreach);
+        assert!(local.is_disconnecting());
+        assert_eq!(local.buffered_received_bytes(), 0);
+        local.close().await.unwrap();
+
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::ProtocolBreach)));
+    }
+
+    #[tokio::test]
+    async fn test_protocol_breach_closes_immediately() {
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        let start = std::time::Instant::now();
+        local.disconnect(DisconnectReason::ProtocolBreach).await.unwrap();
+        // no grace, the connection is closed once the disconnect is flushed
+        assert!(start.elapsed() < DisconnectReason::TooManyPeers.close_grace_period());
+
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::ProtocolBreach)));
+    }
+
+    #[tokio::test]
+    async fn test_too_many_peers_allows_flush_grace() {
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        let start = std::time::Instant::now();
+        local.disconnect(DisconnectReason::TooManyPeers).await.unwrap();
+        assert!(start.elapsed() >= DisconnectReason::TooManyPeers.close_grace_period());
+
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::TooManyPeers)));
+    }
+
+    #[tokio::test]
+    async fn test_graceful_disconnect_flushed() {
+        let (local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        let res = local.graceful_disconnect(DisconnectReason::ClientQuitting).await.unwrap();
+        assert_eq!(res, GracefulDisconnect::Flushed);
+
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::ClientQuitting)));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_subprotocol_disconnect_logs_reason() {
+        use reth_tracing::tracing_subscriber::{layer::Context, prelude::*, Layer};
+        use std::sync::{Arc, Mutex};
+        use tracing::{
+            field::{Field, Visit},
+            Event, Subscriber,
+        };
+
+        /// Records the `reason` field of every event.
+        #[derive(Clone, Default)]
+        struct Reasons(Arc<Mutex<Vec<String>>>);
+
+        impl Visit for Reasons {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "reason" {
+                    self.0.lock().unwrap().push(format!("{value:?}"));
+                }
+            }
+        }
+
+        impl<S: Subscriber> Layer<S> for Reasons {
+            fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+                event.record(&mut self.clone());
+            }
+        }
+
+        let reasons = Reasons::default();
+        let _guard = tracing::subscriber::set_default(
+            reth_tracing::tracing_subscriber::registry().with(reasons.clone()),
+        );
+
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+        local.set_subprotocol_disconnect_reasons(
+            SubprotocolDisconnectReasons::default().with_reason("fake", |code| match code {
+                0x01 => "fake chain mismatch".to_string(),
+                code => format!("unknown fake reason {code}"),
+            }),
+        );
+
+        local.start_subprotocol_disconnect("fake", 0x01).unwrap();
+        local.close().await.unwrap();
+        assert!(reasons.0.lock().unwrap().contains(&"fake chain mismatch".to_string()));
+
+        // the peer only sees the generic reason
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::SubprotocolSpecific)));
+
+        // a received disconnect with a trailing code is interpreted by the mapping of the shared
+        // subprotocol
+        let mut hello = eth_hello().0;
+        hello.try_add_protocol(Protocol::new(Capability::new_static("fake", 1), 1)).unwrap();
+        let (mut local, mut remote) = duplex::p2p_pair(hello.clone(), hello).await;
+        local.set_subprotocol_disconnect_reasons(
+            SubprotocolDisconnectReasons::default()
+                .with_reason("fake", |_| "fake stale head".to_string()),
+        );
+
+        remote.inner.send(Bytes::from_static(&hex!("01c21002"))).await.unwrap();
+
+        let err = local.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::SubprotocolSpecific)));
+        assert!(reasons.0.lock().unwrap().contains(&"fake stale head".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_graceful_disconnect_stalled_peer() {
+        use tokio_util::codec::LengthDelimitedCodec;
+
+        let (local, remote) = tokio::io::duplex(1024);
+        let local = UnauthedP2PStream::new(LengthDelimitedCodec::default().framed(local));
+        let remote = UnauthedP2PStream::new(LengthDelimitedCodec::default().framed(remote));
+        let ((mut local, _), (_remote, _)) =
+            tokio::try_join!(local.handshake(eth_hello().0), remote.handshake(eth_hello().0))
+                .unwrap();
+
+        // the peer stops reading, so a message larger than the buffer never gets through
+        let msg = Bytes::from(
+            std::iter::once(EthMessageID::Transactions as u8)
+                .chain((0..64 * 1024).map(|i| (i % 251) as u8))
+                .collect::<Vec<_>>(),
+        );
+        local.start_send_unpin(msg).unwrap();
+        assert!(futures::poll!(local.flush()).is_pending());
+
+        let timeout = Duration::from_millis(100);
+        let start = std::time::Instant::now();
+        let res = local
+            .graceful_disconnect_with_timeout(DisconnectReason::ClientQuitting, timeout)
+            .await
+            .unwrap();
+        assert_eq!(res, GracefulDisconnect::Incomplete);
+        assert!(!res.is_flushed());
+        assert!(start.elapsed() >= timeout);
+    }
+
+    #[tokio::test]
+    async fn test_second_hello_is_protocol_breach() {
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        // the hello message is never compressed, so it's sent on the underlying transport
+        let hello = alloy_rlp::encode(P2PMessage::Hello(eth_hello().0.message()));
+        remote.inner.send(hello.into()).await.unwrap();
+
+        let err = local.next().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err,
+            P2PStreamError::HandshakeError(P2PHandshakeError::HelloNotInHandshake)
+        ));
+        assert_eq!(local.local_disconnect_reason(), Some(DisconnectReason::ProtocolBreach));
+
+        // the peer is told why it was disconnected
+        local.close().await.unwrap();
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::ProtocolBreach)));
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_disconnect() {
+        let (mut local, mut remote) = duplex::p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        // a message that's still in flight when both sides disconnect is discarded
+        let msg = Bytes::from(vec![EthMessageID::Transactions as u8, 0xc0]);
+        remote.send(msg).await.unwrap();
+
+        tokio::try_join!(
+            local.disconnect(DisconnectReason::UselessPeer),
+            remote.disconnect(DisconnectReason::TooManyPeers)
+        )
+        .unwrap();
+
+        // both streams end without an error and know why both sides disconnected
+        assert!(local.next().await.is_none());
+        assert!(remote.next().await.is_none());
+        for (stream, local_reason, remote_reason) in [
+            (&local, DisconnectReason::UselessPeer, DisconnectReason::TooManyPeers),
+            (&remote, DisconnectReason::TooManyPeers, DisconnectReason::UselessPeer),
+        ] {
+            assert_eq!(stream.local_disconnect_reason(), Some(local_reason));
+            assert_eq!(stream.remote_disconnect_reason(), Some(remote_reason));
+        }
+    }
+
+    #[cfg(feature = "snappy")]
+    #[tokio::test]
+    async fn test_raw_mode() {
+        use tokio_util::codec::LengthDelimitedCodec;
+
+        let caps = || {
+            SharedCapabilities::try_new(
+                vec![EthVersion::Eth67.into()],
+                vec![EthVersion::Eth67.into()],
+            )
+            .unwrap()
+        };
+        let stream_pair = |raw: bool| {
+            let (local, remote) = tokio::io::duplex(1024);
+            let local = LengthDelimitedCodec::default().framed(local);
+            let local =
+                if raw { P2PStream::new_raw(local, caps()) } else { P2PStream::new(local, caps()) };
+            (local, LengthDelimitedCodec::default().framed(remote))
+        };
+        let reason = DisconnectReason::UselessPeer;
+
+        // the raw disconnect is the plain RLP encoded message, without a snappy header
+        let (mut raw, mut raw_remote) = stream_pair(true);
+        assert!(raw.is_raw());
+        raw.disconnect(reason).await.unwrap();
+        let raw_frame = raw_remote.next().await.unwrap().unwrap();
+        assert_eq!(&raw_frame[..], &alloy_rlp::encode(P2PMessage::Disconnect(reason))[..]);
+
+        let (mut snappy, mut snappy_remote) = stream_pair(false);
+        snappy.disconnect(reason).await.unwrap();
+        let snappy_frame = snappy_remote.next().await.unwrap().unwrap();
+        assert_ne!(raw_frame, snappy_frame);
+
+        // a raw stream reads the raw disconnect
+        let (local, remote) = tokio::io::duplex(1024);
+        let mut local = P2PStream::new_raw(LengthDelimitedCodec::default().framed(local), caps());
+        let mut remote = P2PStream::new_raw(LengthDelimitedCodec::default().framed(remote), caps());
+
+        // subprotocol messages are exchanged uncompressed as well
+        let msg = Bytes::from(vec![EthMessageID::Transactions as u8, 0xc0]);
+        local.send(msg.clone()).await.unwrap();
+        assert_eq!(remote.next().await.unwrap().unwrap(), msg);
+
+        local.disconnect(reason).await.unwrap();
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(r) if r == reason));
+        assert_eq!(remote.remote_disconnect_reason(), Some(reason));
+    }
+
+    #[cfg(feature = "snappy")]
+    #[tokio::test]
+    async fn test_loosely_framed_disconnect() {
+        use tokio_util::codec::LengthDelimitedCodec;
+
+        let caps = SharedCapabilities::try_new(
+            vec![EthVersion::Eth67.into()],
+            vec![EthVersion::Eth67.into()],
+        )
+        .unwrap();
+        let stream_pair = || {
+            let (local, remote) = tokio::io::duplex(1024);
+            let local = P2PStream::new(LengthDelimitedCodec::default().framed(local), caps.clone());
+            (local, LengthDelimitedCodec::default().framed(remote))
+        };
+
+        // the reason compressed as two one byte literals
+        let (mut local, mut remote) = stream_pair();
+        remote.send(Bytes::from_static(&hex!("010200c10003"))).await.unwrap();
+        let err = local.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(DisconnectReason::UselessPeer)));
+
+        // valid snappy, but beyond the maximum size of a disconnect
+        let (mut local, mut remote) = stream_pair();
+        remote.send(Bytes::from_static(&hex!("01828000f8010000c103"))).await.unwrap();
+        let err = local.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Rlp(err) if err == OVERSIZED_DISCONNECT_ERROR));
+    }
+
+    #[cfg(not(feature = "snappy"))]
+    #[tokio::test]
+    async fn test_new_without_snappy_is_raw() {
+        use tokio_util::codec::LengthDelimitedCodec;
+
+        let caps = || {
+            SharedCapabilities::try_new(
+                vec![EthVersion::Eth67.into()],
+                vec![EthVersion::Eth67.into()],
+            )
+            .unwrap()
+        };
+        let (local, remote) = tokio::io::duplex(1024);
+        let mut local = P2PStream::new(LengthDelimitedCodec::default().framed(local), caps());
+        let mut remote = P2PStream::new(LengthDelimitedCodec::default().framed(remote), caps());
+        assert!(local.is_raw());
+        assert!(remote.is_raw());
+
+        let msg = Bytes::from(vec![EthMessageID::Transactions as u8, 0xc0]);
+        local.send(msg.clone()).await.unwrap();
+        assert_eq!(remote.next().await.unwrap().unwrap(), msg);
+
+        let reason = DisconnectReason::UselessPeer;
+        local.disconnect(reason).await.unwrap();
+        let err = remote.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, P2PStreamError::Disconnected(r) if r == reason));
+        assert_eq!(remote.remote_disconnect_reason(), Some(reason));
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_decode_snappy_single_byte_disconnect() {
+        for (msg, expected) in [
+            (hex!("01010003"), DisconnectReason::UselessPeer),
+            (hex!("01010000"), DisconnectReason::DisconnectRequested),
+            (hex!("01010004"), DisconnectReason::TooManyPeers),
+            (hex!("01010010"), DisconnectReason::SubprotocolSpecific),
+        ] {
+            assert_eq!(msg[0], P2PMessageID::Disconnect as u8);
+            // not decodable without decompressing
+            assert!(DisconnectReason::decode(&mut &msg[1..]).is_err());
+
+            let reason = decode_snappy_single_byte_disconnect(&msg[1..]).unwrap();
+            assert_eq!(reason, expected);
+
+            // agrees with actually decompressing the payload
+            let decompressed = Snappy::default().decompress_vec(&msg[1..]).unwrap();
+            assert_eq!(DisconnectReason::decode(&mut &decompressed[..]).unwrap(), expected);
+        }
+
+        // other forms are left to the generic decoding
+        assert_eq!(decode_snappy_single_byte_disconnect(&hex!("0204c103")), None);
+        assert_eq!(decode_snappy_single_byte_disconnect(&hex!("0100")), None);
+        assert_eq!(decode_snappy_single_byte_disconnect(&hex!("0100c103")), None);
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_decode_snappy_empty_list_disconnect() {
+        let msg = hex!("010100c0");
+        // the compressed payload is an empty list, which carries no reason
+        let decompressed = Snappy::default().decompress_vec(&msg[1..]).unwrap();
+        assert_eq!(decompressed, [EMPTY_LIST_CODE]);
+        assert!(DisconnectReason::decode(&mut &decompressed[..]).is_err());
+
+        assert_eq!(
+            decode_snappy_single_byte_disconnect(&msg[1..]),
+            Some(DisconnectReason::DisconnectRequested)
+        );
+    }
+
+    #[test]
+    fn test_decode_with_len() {
+        let pong = alloy_rlp::encode(P2PMessage::Pong);
+        for msg in [P2PMessage::Disconnect(DisconnectReason::TooManyPeers), P2PMessage::Ping] {
+            let encoded = alloy_rlp::encode(&msg);
+            assert_eq!(
+                P2PMessage::decode_with_len(&encoded).unwrap(),
+                (msg.clone(), encoded.len())
+            );
+
+            // the next message in the buffer isn't consumed
+            let buf = [&encoded[..], &pong[..]].concat();
+            assert_eq!(P2PMessage::decode_with_len(&buf).unwrap(), (msg, encoded.len()));
+        }
+
+        // a reason that isn't wrapped in a list
+        let buf = [&hex!("0104")[..], &pong[..]].concat();
+        assert_eq!(
+            P2PMessage::decode_with_len(&buf).unwrap(),
+            (P2PMessage::Disconnect(DisconnectReason::TooManyPeers), 2)
+        );
+    }
+
     #[tokio::test]
     async fn test_can_disconnect_weird_disconnect_encoding() {
         reth_tracing::init_test_tracing();
@@ -880,6 +2107,57 @@ mod tests {
         handle.await.unwrap();
     }
 
+    #[cfg(feature = "snappy")]
+    #[tokio::test]
+    async fn test_strict_disconnect_decode_mode() {
+        let reason = DisconnectReason::SubprotocolSpecific;
+        for canonical in [true, false] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let local_addr = listener.local_addr().unwrap();
+
+            let handle = tokio::spawn(async move {
+                let (incoming, _) = listener.accept().await.unwrap();
+                let stream = crate::PassthroughCodec::default().framed(incoming);
+                let (mut p2p_stream, _) =
+                    UnauthedP2PStream::new(stream).handshake(eth_hello().0).await.unwrap();
+
+                if canonical {
+                    p2p_stream.disconnect(reason).await.unwrap();
+                } else {
+                    // bare RLP, without compression
+                    p2p_stream.outgoing_messages.push_back(Bytes::from(encode_disconnect(reason)));
+                    p2p_stream.disconnecting = true;
+                    p2p_stream.close().await.unwrap();
+                }
+            });
+
+            let outgoing = TcpStream::connect(local_addr).await.unwrap();
+            let sink = crate::PassthroughCodec::default().framed(outgoing);
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(sink).handshake(eth_hello().0).await.unwrap();
+            p2p_stream.set_disconnect_decode_mode(DisconnectDecodeMode::Strict);
+
+            let err = p2p_stream.next().await.unwrap().unwrap_err();
+            if canonical {
+                assert!(matches!(err, P2PStreamError::Disconnected(r) if r == reason), "{err:?}");
+            } else {
+                assert!(
+                    matches!(
+                        err,
+                        P2PStreamError::NonCanonicalDisconnect {
+                            reason: r,
+                            form: crate::DisconnectWireForm::RlpList
+                        } if r == reason
+                    ),
+                    "{err:?}"
+                );
+            }
+            assert_eq!(p2p_stream.remote_disconnect_reason(), Some(reason));
+
+            handle.await.unwrap();
+        }
+    }
+
     #[tokio::test]
     async fn test_handshake_passthrough() {
         // create a p2p stream and server, then confirm that the two are authed
@@ -928,6 +2206,184 @@ mod tests {
         handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_hello_timeout_sends_protocol_breach() {
+        reth_tracing::init_test_tracing();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+        let server_key = SecretKey::new(&mut rand::thread_rng());
+
+        let handle = tokio::spawn(async move {
+            let (incoming, _) = listener.accept().await.unwrap();
+            let stream = ECIESStream::incoming(incoming, server_key).await.unwrap();
+            let (server_hello, _) = eth_hello();
+
+            let err = UnauthedP2PStream::new(stream)
+                .handshake_with_timeout(server_hello, Duration::from_millis(100))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, P2PStreamError::HandshakeError(P2PHandshakeError::Timeout)));
+        });
+
+        // complete the ECIES handshake, but never send a hello
+        let outgoing = TcpStream::connect(local_addr).await.unwrap();
+        let server_id = pk2id(&server_key.public_key(SECP256K1));
+        let client_key = SecretKey::new(&mut rand::thread_rng());
+        let mut stream = ECIESStream::connect(outgoing, client_key, server_id).await.unwrap();
+
+        let hello = stream.next().await.unwrap().unwrap();
+        assert!(matches!(P2PMessage::decode(&mut &hello[..]).unwrap(), P2PMessage::Hello(_)));
+
+        let disconnect = stream.next().await.unwrap().unwrap();
+        assert_eq!(
+            P2PMessage::decode(&mut &disconnect[..]).unwrap(),
+            P2PMessage::Disconnect(DisconnectReason::ProtocolBreach)
+        );
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_null_node_identity() {
+        reth_tracing::init_test_tracing();
+
+        // all zeros, and a key that's not on the curve
+        for id in [PeerId::ZERO, PeerId::repeat_byte(0xff)] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let local_addr = listener.local_addr().unwrap();
+
+            let handle = tokio::spawn(async move {
+                let (incoming, _) = listener.accept().await.unwrap();
+                let stream = crate::PassthroughCodec::default().framed(incoming);
+                let (server_hello, _) = eth_hello();
+                UnauthedP2PStream::new(stream).handshake(server_hello).await.map(|_| ())
+            });
+
+            let outgoing = TcpStream::connect(local_addr).await.unwrap();
+            let sink = crate::PassthroughCodec::default().framed(outgoing);
+            let (mut client_hello, _) = eth_hello();
+            client_hello.id = id;
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+
+            let err = handle.await.unwrap().unwrap_err();
+            assert!(matches!(
+                err,
+                P2PStreamError::HandshakeError(P2PHandshakeError::NullNodeIdentity)
+            ));
+            let err = p2p_stream.next().await.unwrap().unwrap_err();
+            assert!(matches!(
+                err,
+                P2PStreamError::Disconnected(DisconnectReason::NullNodeIdentity)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capability_limit() {
+        reth_tracing::init_test_tracing();
+
+        let les = || Protocol::new(Capability::new_static("les", 4), 24);
+        let hello = move || {
+            let (mut hello, _) = eth_hello();
+            hello.protocols.push(Protocol::snap_1());
+            hello.protocols.push(les());
+            hello
+        };
+
+        // les sorts between eth and snap, snap sorts last
+        for (limit, dropped) in [
+            (SharedCapabilityLimit::new(["eth", "snap", "les"]).with_max(2), les().cap),
+            (SharedCapabilityLimit::new(["eth", "les"]), Capability::snap_1()),
+        ] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let local_addr = listener.local_addr().unwrap();
+
+            let handle = tokio::spawn(async move {
+                let (incoming, _) = listener.accept().await.unwrap();
+                let stream = crate::PassthroughCodec::default().framed(incoming);
+                let (p2p_stream, _) = UnauthedP2PStream::new(stream)
+                    .with_capability_limit(limit)
+                    .handshake(hello())
+                    .await
+                    .unwrap();
+                p2p_stream.shared_capabilities().clone()
+            });
+
+            // both peers offer eth, snap and les
+            let outgoing = TcpStream::connect(local_addr).await.unwrap();
+            let sink = crate::PassthroughCodec::default().framed(outgoing);
+            let (p2p_stream, server_hello) =
+                UnauthedP2PStream::new(sink).handshake(hello()).await.unwrap();
+            assert!(server_hello.capabilities.contains(&les().cap));
+
+            // the limited peer drops a capability, but keeps the offsets the peer negotiated
+            let server_caps = handle.await.unwrap();
+            let client_caps = p2p_stream.shared_capabilities();
+            assert_eq!(client_caps.len(), 3);
+            assert_eq!(server_caps.len(), 2);
+            assert!(!server_caps.contains(&dropped));
+            for cap in server_caps.iter_caps() {
+                assert_eq!(client_caps.find_by_offset(cap.message_id_offset()), Some(cap));
+            }
+
+            // the ids of the dropped capability aren't routed to any kept capability
+            let offset = client_caps.offset_of(dropped.name.as_ref(), dropped.version as u8);
+            assert_eq!(server_caps.find_by_offset(offset.unwrap()), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_hello_capabilities() {
+        reth_tracing::init_test_tracing();
+        const MAX_CAPABILITIES: usize = 4;
+
+        for capabilities in [MAX_CAPABILITIES, MAX_CAPABILITIES + 1] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let local_addr = listener.local_addr().unwrap();
+
+            let handle = tokio::spawn(async move {
+                let (incoming, _) = listener.accept().await.unwrap();
+                let stream = crate::PassthroughCodec::default().framed(incoming);
+                let (server_hello, _) = eth_hello();
+
+                UnauthedP2PStream::new(stream)
+                    .with_max_capabilities(MAX_CAPABILITIES)
+                    .handshake(server_hello)
+                    .await
+                    .map(|(p2p_stream, _)| p2p_stream)
+            });
+
+            let outgoing = TcpStream::connect(local_addr).await.unwrap();
+            let sink = crate::PassthroughCodec::default().framed(outgoing);
+            let (mut client_hello, _) = eth_hello();
+            for version in 1..capabilities {
+                client_hello
+                    .protocols
+                    .push(Protocol::new(Capability::new_static("cap", version), 1));
+            }
+
+            let (mut p2p_stream, _) =
+                UnauthedP2PStream::new(sink).handshake(client_hello).await.unwrap();
+
+            if capabilities <= MAX_CAPABILITIES {
+                handle.await.unwrap().unwrap();
+            } else {
+                let err = handle.await.unwrap().unwrap_err();
+                assert!(matches!(
+                    err,
+                    P2PStreamError::HandshakeError(P2PHandshakeError::TooManyCapabilities(count))
+                        if count == capabilities
+                ));
+                let err = p2p_stream.next().await.unwrap().unwrap_err();
+                assert!(matches!(
+                    err,
+                    P2PStreamError::Disconnected(DisconnectReason::ProtocolBreach)
+                ));
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_handshake_disconnect() {
         // create a p2p stream and server, then confirm that the two are authed
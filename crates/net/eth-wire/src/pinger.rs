@@ -1,24 +1,44 @@
-use crate::errors::PingerError;
+use crate::{
+    clock::{SharedClock, SystemClock},
+    errors::PingerError,
+};
+use rand::Rng;
 use std::{
+    future::Future,
     pin::Pin,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::time::{Instant, Interval, Sleep};
+use tokio::time::Sleep;
 use tokio_stream::Stream;
 
+/// The default number of pongs without a ping that are ignored before they're treated as an error.
+///
+/// The count decays by one per ping interval, so this limits the rate of unsolicited pongs.
+pub const DEFAULT_MAX_UNSOLICITED_PONGS: u32 = 8;
+
 /// The pinger is a state machine that is created with a maximum number of pongs that can be
 /// missed.
 #[derive(Debug)]
 pub(crate) struct Pinger {
-    /// The timer used for the next ping.
-    ping_interval: Interval,
-    /// The timer used for the next ping.
-    timeout_timer: Pin<Box<Sleep>>,
+    /// The source of the current time, which decides whether a deadline passed.
+    clock: SharedClock,
+    /// The interval between pings.
+    ping_interval: Duration,
+    /// When the next ping is due.
+    next_ping: Instant,
     /// The timeout duration for each ping.
     timeout: Duration,
+    /// When the outstanding ping times out.
+    timeout_deadline: Instant,
+    /// Wakes the task at the next deadline.
+    timer: Pin<Box<Sleep>>,
     /// Keeps track of the state
     state: PingState,
+    /// Number of pongs received without an outstanding ping, decays by one per ping interval.
+    unsolicited_pongs: u32,
+    /// Number of unsolicited pongs that are ignored before [`Self::on_pong`] returns an error.
+    max_unsolicited_pongs: u32,
 }
 
 // === impl Pinger ===
@@ -27,36 +47,108 @@ impl Pinger {
     /// Creates a new [`Pinger`] with the given ping interval duration,
     /// and timeout duration.
     pub(crate) fn new(ping_interval: Duration, timeout_duration: Duration) -> Self {
-        let now = Instant::now();
-        let timeout_timer = tokio::time::sleep(timeout_duration);
+        Self::with_clock(ping_interval, timeout_duration, SystemClock::shared())
+    }
+
+    /// Creates a new [`Pinger`] like [`Self::new`], that reads the time from the given clock.
+    pub(crate) fn with_clock(
+        ping_interval: Duration,
+        timeout_duration: Duration,
+        clock: SharedClock,
+    ) -> Self {
+        let now = clock.now();
         Self {
+            clock,
             state: PingState::Ready,
-            ping_interval: tokio::time::interval_at(now + ping_interval, ping_interval),
-            timeout_timer: Box::pin(timeout_timer),
+            ping_interval,
+            next_ping: now + ping_interval,
             timeout: timeout_duration,
+            timeout_deadline: now + timeout_duration,
+            timer: Box::pin(tokio::time::sleep(ping_interval)),
+            unsolicited_pongs: 0,
+            max_unsolicited_pongs: DEFAULT_MAX_UNSOLICITED_PONGS,
         }
     }
 
+    /// Returns the clock of the pinger.
+    pub(crate) fn clock(&self) -> SharedClock {
+        self.clock.clone()
+    }
+
+    /// Sets the clock of the pinger, the pending deadlines restart at the time of the new clock.
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        let now = clock.now();
+        self.clock = clock;
+        self.next_ping = now + self.ping_interval;
+        self.timeout_deadline = now + self.timeout;
+    }
+
+    /// Sets the number of unsolicited pongs that are ignored before [`Self::on_pong`] returns an
+    /// error.
+    pub(crate) const fn with_max_unsolicited_pongs(mut self, max_unsolicited_pongs: u32) -> Self {
+        self.max_unsolicited_pongs = max_unsolicited_pongs;
+        self
+    }
+
+    /// See [`Self::with_max_unsolicited_pongs`].
+    pub(crate) fn set_max_unsolicited_pongs(&mut self, max_unsolicited_pongs: u32) {
+        self.max_unsolicited_pongs = max_unsolicited_pongs;
+    }
+
+    /// Returns the number of unsolicited pongs that are ignored.
+    pub(crate) const fn max_unsolicited_pongs(&self) -> u32 {
+        self.max_unsolicited_pongs
+    }
+
+    /// Randomizes the ping interval by up to `jitter`, a fraction of the interval in `[0, 1)`.
+    ///
+    /// For example, with a jitter of `0.2` the interval is between 80% and 120% of the
+    /// configured interval. This keeps the pings of sessions that were started at the same time
+    /// from firing in lockstep.
+    pub(crate) fn with_jitter(mut self, jitter: f64) -> Self {
+        let jitter = jitter.clamp(0.0, 0.99);
+        if jitter > 0.0 {
+            let factor = rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter);
+            self.ping_interval = self.ping_interval.mul_f64(factor);
+            self.next_ping = self.clock.now() + self.ping_interval;
+        }
+        self
+    }
+
     /// Mark a pong as received, and transition the pinger to the `Ready` state if it was in the
     /// `WaitingForPong` state. Unsets the sleep timer.
+    ///
+    /// A pong received in the `Ready` state doesn't match any ping. These are counted and ignored,
+    /// until more than the configured maximum were received.
     pub(crate) fn on_pong(&mut self) -> Result<(), PingerError> {
         match self.state {
-            PingState::Ready => Err(PingerError::UnexpectedPong),
+            PingState::Ready => {
+                self.unsolicited_pongs += 1;
+                if self.unsolicited_pongs > self.max_unsolicited_pongs {
+                    return Err(PingerError::UnexpectedPong)
+                }
+                Ok(())
+            }
             PingState::WaitingForPong => {
                 self.state = PingState::Ready;
-                self.ping_interval.reset();
+                self.next_ping = self.clock.now() + self.ping_interval;
                 Ok(())
             }
             PingState::TimedOut => {
                 // if we receive a pong after timeout then we also reset the state, since the
                 // connection was kept alive after timeout
                 self.state = PingState::Ready;
-                self.ping_interval.reset();
+                self.next_ping = self.clock.now() + self.ping_interval;
                 Ok(())
             }
         }
     }
 
+    /// Returns the number of pongs received without an outstanding ping.
+    pub(crate) const fn unsolicited_pongs(&self) -> u32 {
+        self.unsolicited_pongs
+    }
+
     /// Returns the current state of the pinger.
     pub(crate) const fn state(&self) -> PingState {
         self.state
@@ -68,27 +160,50 @@ impl Pinger {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<Result<PingerEvent, PingerError>> {
-        match self.state() {
-            PingState::Ready => {
-                if self.ping_interval.poll_tick(cx).is_ready() {
-                    self.timeout_timer.as_mut().reset(Instant::now() + self.timeout);
-                    self.state = PingState::WaitingForPong;
-                    return Poll::Ready(Ok(PingerEvent::Ping))
+        let mut timer_elapsed = false;
+        loop {
+            let now = self.clock.now();
+            let deadline = match self.state() {
+                PingState::Ready => {
+                    if now >= self.next_ping {
+                        self.next_ping = now + self.ping_interval;
+                        self.timeout_deadline = now + self.timeout;
+                        self.state = PingState::WaitingForPong;
+                        // occasional unsolicited pongs are forgiven over time
+                        self.unsolicited_pongs = self.unsolicited_pongs.saturating_sub(1);
+                        return Poll::Ready(Ok(PingerEvent::Ping))
+                    }
+                    self.next_ping
                 }
-            }
-            PingState::WaitingForPong => {
-                if self.timeout_timer.is_elapsed() {
-                    self.state = PingState::TimedOut;
-                    return Poll::Ready(Ok(PingerEvent::Timeout))
+                PingState::WaitingForPong => {
+                    if now >= self.timeout_deadline {
+                        self.state = PingState::TimedOut;
+                        return Poll::Ready(Ok(PingerEvent::Timeout))
+                    }
+                    self.timeout_deadline
                 }
+                PingState::TimedOut => {
+                    // we treat continuous calls while in timeout as pending, since the connection
+                    // is not yet terminated
+                    return Poll::Pending
+                }
+            };
+
+            // the timer elapsed but the clock is behind, which only happens with a clock that
+            // isn't the system clock, the caller polls again after advancing it
+            if timer_elapsed {
+                return Poll::Pending
             }
-            PingState::TimedOut => {
-                // we treat continuous calls while in timeout as pending, since the connection is
-                // not yet terminated
+
+            let deadline = tokio::time::Instant::from_std(deadline);
+            if self.timer.deadline() != deadline {
+                self.timer.as_mut().reset(deadline);
+            }
+            if self.timer.as_mut().poll(cx).is_pending() {
                 return Poll::Pending
             }
-        };
-        Poll::Pending
+            timer_elapsed = true;
+        }
     }
 }
 
@@ -127,21 +242,87 @@ pub(crate) enum PingerEvent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use futures::StreamExt;
 
+    fn poll(pinger: &mut Pinger) -> Poll<PingerEvent> {
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        pinger.poll_ping(&mut cx).map(Result::unwrap)
+    }
+
     #[tokio::test]
     async fn test_ping_timeout() {
+        let clock = MockClock::new();
         let interval = Duration::from_millis(300);
+        let timeout = Duration::from_millis(20);
         // we should wait for the interval to elapse and receive a pong before the timeout elapses
+        let mut pinger = Pinger::with_clock(interval, timeout, clock.shared());
+        assert!(poll(&mut pinger).is_pending());
+        clock.advance(interval);
+        assert_eq!(poll(&mut pinger), Poll::Ready(PingerEvent::Ping));
+        pinger.on_pong().unwrap();
+
+        clock.advance(interval - Duration::from_millis(1));
+        assert!(poll(&mut pinger).is_pending());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(poll(&mut pinger), Poll::Ready(PingerEvent::Ping));
+
+        clock.advance(timeout);
+        assert_eq!(poll(&mut pinger), Poll::Ready(PingerEvent::Timeout));
+        assert!(poll(&mut pinger).is_pending());
+        pinger.on_pong().unwrap();
+
+        assert!(poll(&mut pinger).is_pending());
+        clock.advance(interval);
+        assert_eq!(poll(&mut pinger), Poll::Ready(PingerEvent::Ping));
+    }
+
+    #[tokio::test]
+    async fn test_ping_system_clock() {
+        let interval = Duration::from_millis(30);
         let mut pinger = Pinger::new(interval, Duration::from_millis(20));
         assert_eq!(pinger.next().await.unwrap().unwrap(), PingerEvent::Ping);
+        assert_eq!(pinger.next().await.unwrap().unwrap(), PingerEvent::Timeout);
         pinger.on_pong().unwrap();
         assert_eq!(pinger.next().await.unwrap().unwrap(), PingerEvent::Ping);
+    }
 
-        tokio::time::sleep(interval).await;
-        assert_eq!(pinger.next().await.unwrap().unwrap(), PingerEvent::Timeout);
+    #[tokio::test]
+    async fn test_unsolicited_pongs() {
+        let interval = Duration::from_millis(50);
+        let mut pinger =
+            Pinger::new(interval, Duration::from_secs(1)).with_max_unsolicited_pongs(2);
+
+        // pongs without a ping are ignored, but counted
         pinger.on_pong().unwrap();
+        pinger.on_pong().unwrap();
+        assert_eq!(pinger.unsolicited_pongs(), 2);
+        assert_eq!(pinger.state(), PingState::Ready);
 
+        // the count decays per ping interval, a pong that answers a ping isn't unsolicited
         assert_eq!(pinger.next().await.unwrap().unwrap(), PingerEvent::Ping);
+        pinger.on_pong().unwrap();
+        assert_eq!(pinger.unsolicited_pongs(), 1);
+
+        pinger.on_pong().unwrap();
+        assert!(matches!(pinger.on_pong(), Err(PingerError::UnexpectedPong)));
+        assert_eq!(pinger.unsolicited_pongs(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_ping_interval_jitter() {
+        let interval = Duration::from_secs(60);
+        let jitter = 0.2;
+
+        let first = Pinger::new(interval, Duration::from_secs(15)).with_jitter(jitter);
+        let second = Pinger::new(interval, Duration::from_secs(15)).with_jitter(jitter);
+        assert_ne!(first.ping_interval, second.ping_interval);
+        for pinger in [first, second] {
+            assert!(pinger.ping_interval >= interval.mul_f64(1.0 - jitter));
+            assert!(pinger.ping_interval <= interval.mul_f64(1.0 + jitter));
+        }
+
+        let pinger = Pinger::new(interval, Duration::from_secs(15)).with_jitter(0.0);
+        assert_eq!(pinger.ping_interval, interval);
     }
 }
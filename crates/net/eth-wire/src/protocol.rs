@@ -2,6 +2,12 @@
 
 use crate::{Capability, EthMessageID, EthVersion};
 
+/// The number of messages of the `snap/1` protocol, `GetAccountRange` (0x00) through `TrieNodes`
+/// (0x07).
+///
+/// See also <https://github.com/ethereum/devp2p/blob/master/caps/snap.md>
+pub const SNAP_1_MESSAGES: u8 = 8;
+
 /// Type that represents a [Capability] and the number of messages it uses.
 ///
 /// Only the [Capability] is shared with the remote peer, assuming both parties know the number of
@@ -45,6 +51,13 @@ impl Protocol {
         Self::eth(EthVersion::Eth68)
     }
 
+    /// Returns the `snap/1` capability.
+    ///
+    /// Note: snap runs side by side with eth and is only usable if eth is shared as well.
+    pub const fn snap_1() -> Self {
+        Self::new(Capability::snap_1(), SNAP_1_MESSAGES)
+    }
+
     /// Consumes the type and returns a tuple of the [Capability] and number of messages.
     #[inline]
     pub(crate) fn split(self) -> (Capability, u8) {
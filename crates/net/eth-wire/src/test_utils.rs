@@ -60,6 +60,58 @@ pub async fn connect_passthrough(
     p2p_stream
 }
 
+/// Wires two streams over an in-memory duplex, without TCP or ECIES.
+pub mod duplex {
+    use super::*;
+    use crate::{EthStream, UnauthedEthStream};
+    use tokio::io::DuplexStream;
+
+    /// Size of the in-memory buffer of each direction of the duplex.
+    const DUPLEX_BUFFER_SIZE: usize = 1024 * 1024;
+
+    pub type DuplexP2PStream = P2PStream<Framed<DuplexStream, LengthDelimitedCodec>>;
+
+    pub type DuplexEthStream = EthStream<DuplexP2PStream>;
+
+    /// Returns two [`P2PStream`]s connected to each other that completed the `p2p` handshake with
+    /// the given hellos.
+    pub async fn p2p_pair(
+        local_hello: HelloMessageWithProtocols,
+        remote_hello: HelloMessageWithProtocols,
+    ) -> (DuplexP2PStream, DuplexP2PStream) {
+        let (local, remote) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+        let local = UnauthedP2PStream::new(LengthDelimitedCodec::default().framed(local));
+        let remote = UnauthedP2PStream::new(LengthDelimitedCodec::default().framed(remote));
+
+        let (local, remote) =
+            tokio::try_join!(local.handshake(local_hello), remote.handshake(remote_hello)).unwrap();
+        (local.0, remote.0)
+    }
+
+    /// Returns two [`EthStream`]s connected to each other that completed the `p2p` and `eth`
+    /// handshakes with the given status.
+    pub async fn eth_pair_with_status(
+        status: Status,
+        fork_filter: ForkFilter,
+    ) -> (DuplexEthStream, DuplexEthStream) {
+        let (local, remote) = p2p_pair(eth_hello().0, eth_hello().0).await;
+
+        let (local, remote) = tokio::try_join!(
+            UnauthedEthStream::new(local).handshake(status, fork_filter.clone()),
+            UnauthedEthStream::new(remote).handshake(status, fork_filter),
+        )
+        .unwrap();
+        (local.0, remote.0)
+    }
+
+    /// Returns two [`EthStream`]s connected to each other that completed the `p2p` and `eth`
+    /// handshakes, see [`eth_handshake`].
+    pub async fn eth_pair() -> (DuplexEthStream, DuplexEthStream) {
+        let (status, fork_filter) = eth_handshake();
+        eth_pair_with_status(status, fork_filter).await
+    }
+}
+
 /// A Rplx subprotocol for testing
 pub mod proto {
     use super::*;
@@ -0,0 +1,107 @@
+//! Decoding tests for the corpus of synthetic handshake messages in the forms of other clients in
+//! `testdata/client_messages`, see its `manifest`.
+
+use alloy_primitives::hex;
+use alloy_rlp::Decodable;
+use reth_eth_wire::{
+    decode_with_form, DisconnectReason, EthMessage, EthVersion, P2PMessage, P2PMessageID,
+    ProtocolMessage, MAX_RESERVED_MESSAGE_ID,
+};
+use std::{collections::HashSet, fs, path::PathBuf};
+
+/// An entry of the corpus manifest.
+#[derive(Debug)]
+struct Entry {
+    fixture: String,
+    message: String,
+    expected: String,
+}
+
+fn corpus_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/client_messages")
+}
+
+fn read_manifest() -> Vec<Entry> {
+    let manifest = fs::read_to_string(corpus_dir().join("manifest")).unwrap();
+    manifest
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut columns = line.split_whitespace();
+            let mut next = || columns.next().unwrap_or_else(|| panic!("malformed: {line}"));
+            let (fixture, message, _client, expected) = (next(), next(), next(), next());
+            Entry {
+                fixture: fixture.to_string(),
+                message: message.to_string(),
+                expected: expected.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Decodes the message of the entry, returning the label of the reason for disconnects.
+fn decode(entry: &Entry, bytes: &[u8]) -> Result<Option<DisconnectReason>, String> {
+    match entry.message.as_str() {
+        "disconnect" => {
+            let (id, payload) = bytes.split_first().ok_or("empty message")?;
+            assert_eq!(*id, P2PMessageID::Disconnect as u8, "{}", entry.fixture);
+            let (reason, _form) = decode_with_form(payload).map_err(|err| err.to_string())?;
+            Ok(Some(reason))
+        }
+        "hello" => match P2PMessage::decode(&mut &bytes[..]).map_err(|err| err.to_string())? {
+            P2PMessage::Hello(_) => Ok(None),
+            msg => Err(format!("unexpected message {msg:?}")),
+        },
+        "status" => {
+            // the id of the first eth message directly follows the reserved p2p message ids, it's
+            // normalized like the `P2PStream` does
+            let (id, payload) = bytes.split_first().ok_or("empty message")?;
+            let id = id.checked_sub(MAX_RESERVED_MESSAGE_ID + 1).ok_or("reserved message id")?;
+            let normalized = [&[id][..], payload].concat();
+            let msg = ProtocolMessage::decode_message(EthVersion::Eth68, &mut &normalized[..])
+                .map_err(|err| err.to_string())?;
+            match msg.message {
+                EthMessage::Status(_) => Ok(None),
+                msg => Err(format!("unexpected message {msg:?}")),
+            }
+        }
+        message => panic!("unknown message {message} of {}", entry.fixture),
+    }
+}
+
+#[test]
+fn decode_client_messages() {
+    let entries = read_manifest();
+    assert!(!entries.is_empty());
+
+    for entry in &entries {
+        let data = fs::read_to_string(corpus_dir().join(&entry.fixture)).unwrap();
+        let bytes = hex::decode(data.trim()).unwrap();
+
+        let decoded = decode(entry, &bytes);
+        match entry.expected.split_once(':') {
+            Some(("ok", reason)) => {
+                let expected = DisconnectReason::from_label(reason).unwrap();
+                assert_eq!(decoded, Ok(Some(expected)), "{}", entry.fixture);
+            }
+            None if entry.expected == "ok" => {
+                assert_eq!(decoded, Ok(None), "{}", entry.fixture);
+            }
+            None if entry.expected == "err" => {
+                assert!(decoded.is_err(), "{} decoded: {decoded:?}", entry.fixture);
+            }
+            _ => panic!("unknown outcome {} of {}", entry.expected, entry.fixture),
+        }
+    }
+}
+
+#[test]
+fn client_messages_are_listed() {
+    let listed = read_manifest().into_iter().map(|entry| entry.fixture).collect::<HashSet<_>>();
+    for entry in fs::read_dir(corpus_dir()).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        if name != "manifest" {
+            assert!(listed.contains(&name), "{name} is missing from the manifest");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Fuzzing of the `Hello` decoder and the capability negotiation that follows it.
+
+#![allow(missing_docs)]
+
+use alloy_rlp::Decodable;
+use proptest::prelude::*;
+use proptest_arbitrary_interop::arb;
+use reth_eth_wire::{
+    capability::SharedCapabilities, protocol::Protocol, Capability, EthVersion, HelloMessage,
+    P2PMessage, ProtocolVersion, DEFAULT_MAX_CAPABILITIES, MAX_RESERVED_MESSAGE_ID,
+};
+use reth_network_peers::PeerId;
+use serde::{Deserialize, Serialize};
+use test_fuzz::test_fuzz;
+
+/// The capability names a [`HelloInput`] picks from.
+///
+/// These are few on purpose, so that generated hellos frequently contain duplicates and
+/// capabilities that are shared with the local protocols.
+const CAPABILITY_NAMES: [&str; 5] = ["eth", "snap", "les", "nodedata", "wit"];
+
+/// A semi-valid `Hello`: always well formed, but with arbitrary capabilities.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, arbitrary::Arbitrary)]
+struct HelloInput {
+    client_version: String,
+    /// Pairs of an index into [`CAPABILITY_NAMES`] and a version.
+    capabilities: Vec<(u8, u8)>,
+    port: u16,
+}
+
+impl HelloInput {
+    fn into_hello(self) -> HelloMessage {
+        HelloMessage {
+            protocol_version: ProtocolVersion::V5,
+            client_version: self.client_version,
+            capabilities: self
+                .capabilities
+                .into_iter()
+                .map(|(name, version)| {
+                    let name = CAPABILITY_NAMES[name as usize % CAPABILITY_NAMES.len()];
+                    Capability::new_static(name, version as usize)
+                })
+                .collect(),
+            port: self.port,
+            id: PeerId::ZERO,
+        }
+    }
+}
+
+/// Encoded `Hello`s modelled on the ones sent by geth and nethermind, used as seeds.
+fn seeds() -> Vec<Vec<u8>> {
+    let geth = HelloMessage {
+        protocol_version: ProtocolVersion::V5,
+        client_version: "Geth/v1.14.8-stable-a9523b64/linux-amd64/go1.22.6".to_string(),
+        capabilities: vec![EthVersion::Eth68.into(), Capability::snap_1()],
+        port: 30303,
+        id: PeerId::repeat_byte(0x11),
+    };
+    let nethermind = HelloMessage {
+        protocol_version: ProtocolVersion::V5,
+        client_version: "Nethermind/v1.28.0+9c4816c2/linux-x64/dotnet8.0.8".to_string(),
+        capabilities: vec![
+            EthVersion::Eth66.into(),
+            EthVersion::Eth67.into(),
+            EthVersion::Eth68.into(),
+            Capability::new_static("nodedata", 1),
+            Capability::snap_1(),
+        ],
+        port: 30303,
+        id: PeerId::repeat_byte(0x22),
+    };
+    [geth, nethermind]
+        .into_iter()
+        .map(|hello| alloy_rlp::encode(P2PMessage::Hello(hello)))
+        .collect()
+}
+
+/// The protocols we negotiate against.
+fn local_protocols() -> Vec<Protocol> {
+    vec![
+        Protocol::eth_68(),
+        Protocol::eth_67(),
+        Protocol::snap_1(),
+        Protocol::new(Capability::new_static("nodedata", 1), 2),
+    ]
+}
+
+/// Decoding arbitrary bytes as a `Hello` must never panic.
+fn decode_hello(bytes: &[u8]) {
+    let _ = HelloMessage::decode(&mut &bytes[..]);
+    let _ = HelloMessage::decode_with_max_capabilities(&mut &bytes[..], DEFAULT_MAX_CAPABILITIES);
+    if let Ok(P2PMessage::Hello(hello)) = P2PMessage::decode(&mut &bytes[..]) {
+        let _ = SharedCapabilities::try_new(local_protocols(), hello.capabilities);
+    }
+}
+
+/// A semi-valid `Hello` survives the wire encoding, and negotiating its capabilities either fails
+/// or yields contiguous message id ranges.
+fn negotiate_hello(input: HelloInput) {
+    let hello = input.into_hello();
+    let encoded = alloy_rlp::encode(P2PMessage::Hello(hello.clone()));
+    let decoded = P2PMessage::decode(&mut &encoded[..]).unwrap();
+    assert_eq!(decoded, P2PMessage::Hello(hello.clone()));
+
+    if let Ok(shared) = SharedCapabilities::try_new(local_protocols(), hello.capabilities) {
+        let mut next = MAX_RESERVED_MESSAGE_ID as usize + 1;
+        for cap in shared.iter_caps() {
+            assert_eq!(cap.message_id_offset() as usize, next, "{shared:?}");
+            next += cap.num_messages() as usize;
+        }
+        assert!(next <= u8::MAX as usize + 1, "{shared:?}");
+    }
+}
+
+#[allow(non_snake_case)]
+#[test_fuzz]
+fn fuzz_HelloMessage_decode(bytes: Vec<u8>) {
+    decode_hello(&bytes)
+}
+
+#[allow(non_snake_case)]
+#[test_fuzz]
+fn fuzz_HelloMessage_capabilities(input: HelloInput) {
+    negotiate_hello(input)
+}
+
+#[test]
+fn hello_seeds() {
+    for seed in seeds() {
+        let P2PMessage::Hello(hello) = P2PMessage::decode(&mut &seed[..]).unwrap() else {
+            panic!("seed is not a hello")
+        };
+        let shared = SharedCapabilities::try_new(local_protocols(), hello.capabilities).unwrap();
+        assert_eq!(shared.eth_version().unwrap(), EthVersion::Eth68);
+        fuzz_HelloMessage_decode(seed);
+    }
+    fuzz_HelloMessage_capabilities(HelloInput {
+        client_version: "reth/v1.0.8".to_string(),
+        capabilities: vec![(0, 68), (0, 68), (1, 1), (2, 4)],
+        port: 30303,
+    });
+}
+
+proptest! {
+    #[test]
+    fn hello_decode_arbitrary_bytes(bytes in any::<Vec<u8>>()) {
+        decode_hello(&bytes);
+    }
+
+    #[test]
+    fn hello_decode_mutated_seeds(
+        seed in 0..2usize,
+        mutations in proptest::collection::vec((any::<usize>(), any::<u8>()), 1..8),
+        truncate in any::<Option<usize>>(),
+    ) {
+        let mut bytes = seeds().swap_remove(seed);
+        for (index, byte) in mutations {
+            let len = bytes.len();
+            bytes[index % len] = byte;
+        }
+        if let Some(len) = truncate {
+            bytes.truncate(len % (bytes.len() + 1));
+        }
+        decode_hello(&bytes);
+    }
+
+    #[test]
+    fn hello_capabilities(input in arb::<HelloInput>()) {
+        negotiate_hello(input);
+    }
+}
@@ -5,7 +5,7 @@ use std::{fmt, net::SocketAddr, sync::Arc};
 use reth_eth_wire_types::{
     message::RequestPair, BlockBodies, BlockHeaders, Capabilities, DisconnectReason, EthMessage,
     EthVersion, GetBlockBodies, GetBlockHeaders, GetNodeData, GetPooledTransactions, GetReceipts,
-    NodeData, PooledTransactions, Receipts, Status,
+    Initiator, NodeData, PooledTransactions, Receipts, Status,
 };
 use reth_ethereum_forks::ForkId;
 use reth_network_p2p::error::{RequestError, RequestResult};
@@ -15,6 +15,8 @@ use reth_tokio_util::EventStream;
 use tokio::sync::{mpsc, oneshot};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
+use crate::PeerInfo;
+
 /// Provides event subscription for the network.
 #[auto_impl::auto_impl(&, Arc)]
 pub trait NetworkEventListenerProvider: Send + Sync {
@@ -62,6 +64,25 @@ pub enum NetworkEvent {
     PeerRemoved(PeerId),
 }
 
+/// Events about peers joining or leaving the active peer set.
+///
+/// Unlike [`NetworkEvent`], this carries the full [`PeerInfo`] of an established session and
+/// which side closed a session.
+#[derive(Debug, Clone)]
+pub enum PeerEvent {
+    /// Established a new session with a peer.
+    SessionEstablished(Box<PeerInfo>),
+    /// Closed the session with a peer.
+    SessionClosed {
+        /// The identifier of the peer.
+        peer_id: PeerId,
+        /// The reason of the disconnect, if a disconnect message was exchanged.
+        reason: Option<DisconnectReason>,
+        /// Which side closed the session.
+        initiator: Initiator,
+    },
+}
+
 /// Events produced by the `Discovery` manager.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DiscoveryEvent {
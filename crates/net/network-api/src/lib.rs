@@ -29,13 +29,17 @@ pub use reth_network_types::{PeerKind, Reputation, ReputationChangeKind};
 pub use downloaders::BlockDownloaderProvider;
 pub use error::NetworkError;
 pub use events::{
-    DiscoveredEvent, DiscoveryEvent, NetworkEvent, NetworkEventListenerProvider, PeerRequest,
-    PeerRequestSender,
+    DiscoveredEvent, DiscoveryEvent, NetworkEvent, NetworkEventListenerProvider, PeerEvent,
+    PeerRequest, PeerRequestSender,
 };
 
 use std::{future::Future, net::SocketAddr, sync::Arc, time::Instant};
 
-use reth_eth_wire_types::{capability::Capabilities, DisconnectReason, EthVersion, Status};
+use alloy_primitives::B256;
+
+use reth_eth_wire_types::{
+    capability::Capabilities, DisconnectReason, EthVersion, NegotiatedEth, Status,
+};
 use reth_network_peers::NodeRecord;
 
 /// The `PeerId` type.
@@ -232,12 +236,29 @@ pub struct PeerInfo {
     pub eth_version: EthVersion,
     /// The Status message the peer sent for the `eth` handshake
     pub status: Arc<Status>,
+    /// The hash of the best block of the peer.
+    ///
+    /// This is the block from the peer's [`Status`] unless the peer announced a newer block since.
+    pub best_block: B256,
     /// The timestamp when the session to that peer has been established.
     pub session_established: Instant,
+    /// The moving average of the compression ratio of the messages received from the peer, the
+    /// decompressed size divided by the compressed size.
+    ///
+    /// A ratio at or below `1` indicates a peer that sends incompressible payloads. This is `None`
+    /// if no message was received yet.
+    pub compression_ratio: Option<f64>,
     /// The peer's connection kind
     pub kind: PeerKind,
 }
 
+impl PeerInfo {
+    /// Returns the parameters agreed on with the peer in the `eth` handshake.
+    pub fn negotiated(&self) -> NegotiatedEth {
+        NegotiatedEth::new(self.eth_version, &self.status)
+    }
+}
+
 /// The direction of the connection.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
@@ -29,4 +29,4 @@ pub use peers::{
     state::PeerConnectionState,
     ConnectionsConfig, Peer, PeersConfig,
 };
-pub use session::{SessionLimits, SessionsConfig};
+pub use session::{RequestTimeoutConfig, ServingPolicy, SessionLimits, SessionsConfig};
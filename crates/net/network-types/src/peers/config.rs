@@ -103,6 +103,18 @@ pub struct ConnectionsConfig {
     /// Maximum allowed concurrent outbound dials.
     #[cfg_attr(feature = "serde", serde(default))]
     pub max_concurrent_outbound_dials: usize,
+    /// Maximum allowed outbound connections, pending or established, to peers in the same subnet
+    /// (`/24` for IPv4, `/64` for IPv6).
+    ///
+    /// Trusted and static peers are exempt. No limit if `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_outbound_per_subnet: Option<usize>,
+    /// Maximum allowed concurrent outbound dials to peers with the same IP address.
+    ///
+    /// Discovery can return many records of a single host, this spreads the dials to it out.
+    /// Trusted and static peers are exempt. No limit if `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_concurrent_dials_per_ip: Option<usize>,
 }
 
 impl Default for ConnectionsConfig {
@@ -111,6 +123,8 @@ impl Default for ConnectionsConfig {
             max_outbound: DEFAULT_MAX_COUNT_PEERS_OUTBOUND as usize,
             max_inbound: DEFAULT_MAX_COUNT_PEERS_INBOUND as usize,
             max_concurrent_outbound_dials: DEFAULT_MAX_COUNT_CONCURRENT_OUTBOUND_DIALS,
+            max_outbound_per_subnet: None,
+            max_concurrent_dials_per_ip: None,
         }
     }
 }
@@ -222,6 +236,18 @@ impl PeersConfig {
         self
     }
 
+    /// Maximum allowed outbound connections to peers in the same subnet.
+    pub const fn with_max_outbound_per_subnet(mut self, max_outbound_per_subnet: usize) -> Self {
+        self.connection_info.max_outbound_per_subnet = Some(max_outbound_per_subnet);
+        self
+    }
+
+    /// Maximum allowed concurrent outbound dials to peers with the same IP address.
+    pub const fn with_max_concurrent_dials_per_ip(mut self, max_dials_per_ip: usize) -> Self {
+        self.connection_info.max_concurrent_dials_per_ip = Some(max_dials_per_ip);
+        self
+    }
+
     /// Nodes to always connect to.
     pub fn with_trusted_nodes(mut self, nodes: Vec<TrustedPeer>) -> Self {
         self.trusted_nodes = nodes;
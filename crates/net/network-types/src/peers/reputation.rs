@@ -21,6 +21,9 @@ const TIMEOUT_REPUTATION_CHANGE: i32 = 4 * REPUTATION_UNIT;
 /// The reputation change to apply to a peer that sent a bad message.
 const BAD_MESSAGE_REPUTATION_CHANGE: i32 = 16 * REPUTATION_UNIT;
 
+/// The reputation change to apply to a peer that sent an incorrect response to a request.
+const BAD_RESPONSE_REPUTATION_CHANGE: i32 = 16 * REPUTATION_UNIT;
+
 /// The reputation change applies to a peer that has sent a transaction (full or hash) that we
 /// already know about and have already previously received from that peer.
 ///
@@ -62,6 +65,12 @@ pub enum ReputationChangeKind {
     BadBlock,
     /// Peer sent a bad transaction message. E.g. Transactions which weren't recoverable.
     BadTransactions,
+    /// Peer sent an incorrect response to a request, e.g. headers that don't chain, bodies that
+    /// don't match their headers or blocks outside the requested range.
+    ///
+    /// Peers that accumulate these are disconnected as useless, even if they complete the
+    /// handshake and respond in time.
+    BadResponse,
     /// Peer sent a bad announcement message, e.g. invalid transaction type for the configured
     /// network.
     BadAnnouncement,
@@ -112,6 +121,8 @@ pub struct ReputationChangeWeights {
     pub bad_block: Reputation,
     /// Weight for [`ReputationChangeKind::BadTransactions`]
     pub bad_transactions: Reputation,
+    /// Weight for [`ReputationChangeKind::BadResponse`]
+    pub bad_response: Reputation,
     /// Weight for [`ReputationChangeKind::AlreadySeenTransaction`]
     pub already_seen_transactions: Reputation,
     /// Weight for [`ReputationChangeKind::Timeout`]
@@ -134,6 +145,7 @@ impl ReputationChangeWeights {
         Self {
             bad_block: 0,
             bad_transactions: 0,
+            bad_response: 0,
             already_seen_transactions: 0,
             bad_message: 0,
             timeout: 0,
@@ -151,6 +163,7 @@ impl ReputationChangeWeights {
             ReputationChangeKind::BadMessage => self.bad_message.into(),
             ReputationChangeKind::BadBlock => self.bad_block.into(),
             ReputationChangeKind::BadTransactions => self.bad_transactions.into(),
+            ReputationChangeKind::BadResponse => self.bad_response.into(),
             ReputationChangeKind::AlreadySeenTransaction => self.already_seen_transactions.into(),
             ReputationChangeKind::Timeout => self.timeout.into(),
             ReputationChangeKind::BadProtocol => self.bad_protocol.into(),
@@ -168,6 +181,7 @@ impl Default for ReputationChangeWeights {
         Self {
             bad_block: BAD_MESSAGE_REPUTATION_CHANGE,
             bad_transactions: BAD_MESSAGE_REPUTATION_CHANGE,
+            bad_response: BAD_RESPONSE_REPUTATION_CHANGE,
             already_seen_transactions: ALREADY_SEEN_TRANSACTION_REPUTATION_CHANGE,
             bad_message: BAD_MESSAGE_REPUTATION_CHANGE,
             timeout: TIMEOUT_REPUTATION_CHANGE,
@@ -49,6 +49,12 @@ impl PeerConnectionState {
         matches!(self, Self::Idle)
     }
 
+    /// Returns true if this is an outgoing connection, either established or still being dialed.
+    #[inline]
+    pub const fn is_outgoing(&self) -> bool {
+        matches!(self, Self::Out | Self::PendingOut | Self::DisconnectingOut)
+    }
+
     /// Returns true if there's currently an outbound dial to that peer.
     #[inline]
     pub const fn is_pending_out(&self) -> bool {
@@ -8,6 +8,17 @@ use std::time::Duration;
 /// This represents the amount of time we wait for a response until we consider it timed out.
 pub const INITIAL_REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
 
+/// Default lower bound of the adaptive request timeout, see [`RequestTimeoutConfig`].
+pub const MIN_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Default number of round-trip times of a peer after which a request times out, see
+/// [`RequestTimeoutConfig`].
+pub const REQUEST_TIMEOUT_RTT_SCALING: u32 = 3;
+
+/// Default margin that's added to the scaled round-trip time of a peer, see
+/// [`RequestTimeoutConfig`].
+pub const REQUEST_TIMEOUT_MARGIN: Duration = Duration::from_secs(1);
+
 /// Default timeout after which a pending session attempt is considered failed.
 pub const PENDING_SESSION_TIMEOUT: Duration = Duration::from_secs(20);
 
@@ -42,6 +53,9 @@ pub struct SessionsConfig {
     /// The maximum initial time we wait for a response from the peer before we timeout a request
     /// _internally_.
     pub initial_internal_request_timeout: Duration,
+    /// How the request timeout adapts to the round-trip time of the peer once requests were
+    /// answered.
+    pub request_timeout: RequestTimeoutConfig,
     /// The amount of time we continue to wait for a response from the peer, even if we timed it
     /// out internally (`initial_internal_request_timeout`). Timeouts are not penalized but the
     /// session directly, however if a peer fails to respond at all (within
@@ -50,6 +64,30 @@ pub struct SessionsConfig {
     pub protocol_breach_request_timeout: Duration,
     /// The timeout after which a pending session attempt is considered failed.
     pub pending_session_timeout: Duration,
+    /// The window after which a peer that neither answered any of our requests nor sent us any
+    /// blocks or transactions is disconnected with `UselessPeer`.
+    ///
+    /// By default, useless peers are not disconnected.
+    pub useless_peer_timeout: Option<Duration>,
+    /// The requests of peers that are served.
+    ///
+    /// By default, all requests are served.
+    pub serving_policy: ServingPolicy,
+    /// Whether requests of peers are served at all.
+    ///
+    /// If disabled, all `eth` requests are answered with empty responses regardless of the
+    /// [`ServingPolicy`], for nodes that only sync from others. `GetPooledTransactions` requests
+    /// are exempt: the node keeps announcing transaction hashes to its peers, so it has to serve
+    /// the announced transactions.
+    ///
+    /// By default, requests are served.
+    pub serve_requests: bool,
+    /// The maximum number of session handshakes whose CPU heavy `ECIES` crypto runs concurrently
+    /// on the blocking thread pool.
+    ///
+    /// The socket I/O of the handshakes always runs on the network's task executor. By default,
+    /// the crypto runs inline as well.
+    pub handshake_threads: Option<usize>,
 }
 
 impl Default for SessionsConfig {
@@ -67,8 +105,13 @@ impl Default for SessionsConfig {
             session_event_buffer: DEFAULT_SESSION_EVENT_BUFFER_SIZE,
             limits: Default::default(),
             initial_internal_request_timeout: INITIAL_REQUEST_TIMEOUT,
+            request_timeout: RequestTimeoutConfig::default(),
             protocol_breach_request_timeout: PROTOCOL_BREACH_REQUEST_TIMEOUT,
             pending_session_timeout: PENDING_SESSION_TIMEOUT,
+            useless_peer_timeout: None,
+            serving_policy: ServingPolicy::serve_all(),
+            serve_requests: true,
+            handshake_threads: None,
         }
     }
 }
@@ -84,6 +127,40 @@ impl SessionsConfig {
         self
     }
 
+    /// Sets the window after which peers that weren't useful during it are disconnected.
+    pub const fn with_useless_peer_timeout(mut self, timeout: Duration) -> Self {
+        self.useless_peer_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how the request timeout adapts to the round-trip time of the peer.
+    pub const fn with_request_timeout(mut self, request_timeout: RequestTimeoutConfig) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets the requests of peers that are served.
+    pub const fn with_serving_policy(mut self, serving_policy: ServingPolicy) -> Self {
+        self.serving_policy = serving_policy;
+        self
+    }
+
+    /// Sets whether requests of peers are served at all.
+    ///
+    /// If `false`, no requests but `GetPooledTransactions` are served, regardless of the
+    /// [`ServingPolicy`].
+    pub const fn with_serve_requests(mut self, serve_requests: bool) -> Self {
+        self.serve_requests = serve_requests;
+        self
+    }
+
+    /// Sets the maximum number of session handshakes whose crypto runs concurrently on the
+    /// blocking thread pool.
+    pub const fn with_handshake_threads(mut self, threads: usize) -> Self {
+        self.handshake_threads = Some(threads);
+        self
+    }
+
     /// Helper function to set the buffer size for the bounded communication channel between the
     /// manager and its sessions for events emitted by the sessions.
     ///
@@ -102,6 +179,145 @@ impl SessionsConfig {
     }
 }
 
+/// Bounds of the request timeout of a session, which adapts to the round-trip time of the peer.
+///
+/// The timeout is a multiple of the moving average of the peer's round-trip time plus a margin,
+/// clamped to `[min, max]`. This gives slow but healthy peers enough time to answer, while requests
+/// to fast peers that stall time out early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RequestTimeoutConfig {
+    /// The lower bound of the timeout.
+    pub min: Duration,
+    /// The upper bound of the timeout.
+    pub max: Duration,
+    /// The number of round-trip times after which a request times out.
+    pub rtt_scaling: u32,
+    /// The margin added to the scaled round-trip time.
+    pub margin: Duration,
+}
+
+impl RequestTimeoutConfig {
+    /// Sets the lower bound of the timeout.
+    pub const fn with_min(mut self, min: Duration) -> Self {
+        self.min = min;
+        self
+    }
+
+    /// Sets the upper bound of the timeout.
+    pub const fn with_max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Sets the number of round-trip times after which a request times out.
+    pub const fn with_rtt_scaling(mut self, rtt_scaling: u32) -> Self {
+        self.rtt_scaling = rtt_scaling;
+        self
+    }
+
+    /// Sets the margin added to the scaled round-trip time.
+    pub const fn with_margin(mut self, margin: Duration) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Returns the request timeout of a peer with the given moving average of the round-trip
+    /// time.
+    ///
+    /// If the bounds are inverted, the upper bound takes precedence.
+    pub fn timeout(&self, rtt: Duration) -> Duration {
+        rtt.saturating_mul(self.rtt_scaling).saturating_add(self.margin).max(self.min).min(self.max)
+    }
+
+    /// Returns the round-trip time for which the given request timeout applies.
+    ///
+    /// A session seeds the moving average of the round-trip time with this, so that the first
+    /// answered requests only gradually move the timeout away from its initial value.
+    pub fn rtt_for_timeout(&self, timeout: Duration) -> Duration {
+        let timeout = timeout.saturating_sub(self.margin);
+        timeout.checked_div(self.rtt_scaling).unwrap_or(timeout)
+    }
+}
+
+impl Default for RequestTimeoutConfig {
+    fn default() -> Self {
+        Self {
+            min: MIN_REQUEST_TIMEOUT,
+            max: INITIAL_REQUEST_TIMEOUT,
+            rtt_scaling: REQUEST_TIMEOUT_RTT_SCALING,
+            margin: REQUEST_TIMEOUT_MARGIN,
+        }
+    }
+}
+
+/// The `eth` requests of peers that a session serves.
+///
+/// Requests that aren't served are answered right away with an empty response, without looking up
+/// any data. A node that only syncs can use this to save the resources spent on serving others,
+/// while a dedicated server node serves everything, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ServingPolicy {
+    /// Whether `GetBlockHeaders` requests are served.
+    pub block_headers: bool,
+    /// Whether `GetBlockBodies` requests are served.
+    pub block_bodies: bool,
+    /// Whether `GetPooledTransactions` requests are served.
+    pub pooled_transactions: bool,
+    /// Whether `GetReceipts` requests are served.
+    pub receipts: bool,
+}
+
+impl ServingPolicy {
+    /// Returns a policy that serves all requests.
+    pub const fn serve_all() -> Self {
+        Self { block_headers: true, block_bodies: true, pooled_transactions: true, receipts: true }
+    }
+
+    /// Returns a policy that serves no requests.
+    pub const fn serve_none() -> Self {
+        Self {
+            block_headers: false,
+            block_bodies: false,
+            pooled_transactions: false,
+            receipts: false,
+        }
+    }
+
+    /// Sets whether `GetBlockHeaders` requests are served.
+    pub const fn with_block_headers(mut self, serve: bool) -> Self {
+        self.block_headers = serve;
+        self
+    }
+
+    /// Sets whether `GetBlockBodies` requests are served.
+    pub const fn with_block_bodies(mut self, serve: bool) -> Self {
+        self.block_bodies = serve;
+        self
+    }
+
+    /// Sets whether `GetPooledTransactions` requests are served.
+    pub const fn with_pooled_transactions(mut self, serve: bool) -> Self {
+        self.pooled_transactions = serve;
+        self
+    }
+
+    /// Sets whether `GetReceipts` requests are served.
+    pub const fn with_receipts(mut self, serve: bool) -> Self {
+        self.receipts = serve;
+        self
+    }
+}
+
+impl Default for ServingPolicy {
+    fn default() -> Self {
+        Self::serve_all()
+    }
+}
+
 /// Limits for sessions.
 ///
 /// By default, no session limits will be enforced
@@ -158,4 +374,24 @@ mod tests {
         let expected_ration = config.session_event_buffer / (DEFAULT_MAX_PEERS * 2);
         assert_eq!(default_ration, expected_ration);
     }
+
+    #[test]
+    fn request_timeout_within_bounds() {
+        let config = RequestTimeoutConfig::default()
+            .with_min(Duration::from_secs(1))
+            .with_max(Duration::from_secs(10))
+            .with_rtt_scaling(3)
+            .with_margin(Duration::from_millis(500));
+
+        assert_eq!(config.timeout(Duration::from_secs(1)), Duration::from_millis(3500));
+        assert_eq!(config.rtt_for_timeout(Duration::from_millis(3500)), Duration::from_secs(1));
+        // timeouts within the margin map to a zero round-trip time
+        assert_eq!(config.rtt_for_timeout(Duration::from_millis(200)), Duration::ZERO);
+        // fast peers are clamped to the lower bound
+        assert_eq!(config.timeout(Duration::from_millis(10)), Duration::from_secs(1));
+        assert_eq!(config.timeout(Duration::ZERO), Duration::from_secs(1));
+        // slow peers are clamped to the upper bound
+        assert_eq!(config.timeout(Duration::from_secs(30)), Duration::from_secs(10));
+        assert_eq!(config.timeout(Duration::MAX), Duration::from_secs(10));
+    }
 }
@@ -6,7 +6,9 @@ use reth_chainspec::{ChainSpecProvider, EthChainSpec, Hardforks};
 use reth_discv4::{Discv4Config, Discv4ConfigBuilder, NatResolver, DEFAULT_DISCOVERY_ADDRESS};
 use reth_discv5::NetworkStackId;
 use reth_dns_discovery::DnsDiscoveryConfig;
-use reth_eth_wire::{HelloMessage, HelloMessageWithProtocols, Status};
+use reth_eth_wire::{
+    HelloMessage, HelloMessageWithProtocols, Status, SubprotocolDisconnectReasons,
+};
 use reth_network_peers::{mainnet_nodes, pk2id, sepolia_nodes, PeerId, TrustedPeer};
 use reth_network_types::{PeersConfig, SessionsConfig};
 use reth_primitives::{ForkFilter, Head};
@@ -15,6 +17,7 @@ use reth_tasks::{TaskSpawner, TokioTaskExecutor};
 use secp256k1::SECP256K1;
 
 use crate::{
+    discovery::DiscoveryBackend,
     error::NetworkError,
     import::{BlockImport, ProofOfStakeBlockImport},
     transactions::TransactionsManagerConfig,
@@ -50,6 +53,8 @@ pub struct NetworkConfig<C> {
     pub discovery_v4_config: Option<Discv4Config>,
     /// How to set up discovery version 5.
     pub discovery_v5_config: Option<reth_discv5::Config>,
+    /// Additional sources of nodes to connect to.
+    pub discovery_backends: Vec<Box<dyn DiscoveryBackend>>,
     /// Address to listen for incoming connections
     pub listener_addr: SocketAddr,
     /// How to instantiate peer manager.
@@ -77,6 +82,8 @@ pub struct NetworkConfig<C> {
     pub hello_message: HelloMessageWithProtocols,
     /// Additional protocols to announce and handle in `RLPx`
     pub extra_protocols: RlpxSubProtocols,
+    /// Interprets the codes of subprotocol specific disconnects of peers, for logging
+    pub subprotocol_disconnect_reasons: SubprotocolDisconnectReasons,
     /// Whether to disable transaction gossip
     pub tx_gossip_disabled: bool,
     /// How to instantiate transactions manager.
@@ -163,6 +170,8 @@ pub struct NetworkConfigBuilder {
     discovery_v4_builder: Option<Discv4ConfigBuilder>,
     /// How to set up discovery version 5.
     discovery_v5_builder: Option<reth_discv5::ConfigBuilder>,
+    /// Additional sources of nodes to connect to.
+    discovery_backends: Vec<Box<dyn DiscoveryBackend>>,
     /// All boot nodes to start network discovery with.
     boot_nodes: HashSet<TrustedPeer>,
     /// Address to use for discovery
@@ -181,6 +190,8 @@ pub struct NetworkConfigBuilder {
     hello_message: Option<HelloMessageWithProtocols>,
     /// The executor to use for spawning tasks.
     extra_protocols: RlpxSubProtocols,
+    /// Interprets the codes of subprotocol specific disconnects of peers.
+    subprotocol_disconnect_reasons: SubprotocolDisconnectReasons,
     /// Head used to start set for the fork filter and status.
     head: Option<Head>,
     /// Whether tx gossip is disabled
@@ -207,6 +218,7 @@ impl NetworkConfigBuilder {
             dns_discovery_config: Some(Default::default()),
             discovery_v4_builder: Some(Default::default()),
             discovery_v5_builder: None,
+            discovery_backends: Vec::new(),
             boot_nodes: Default::default(),
             discovery_addr: None,
             listener_addr: None,
@@ -216,6 +228,7 @@ impl NetworkConfigBuilder {
             executor: None,
             hello_message: None,
             extra_protocols: Default::default(),
+            subprotocol_disconnect_reasons: Default::default(),
             head: None,
             tx_gossip_disabled: false,
             block_import: None,
@@ -364,6 +377,14 @@ impl NetworkConfigBuilder {
         self
     }
 
+    /// Adds a source of nodes to connect to, in addition to the configured discovery protocols.
+    ///
+    /// See also [`StaticDiscovery`](crate::StaticDiscovery).
+    pub fn add_discovery_backend(mut self, backend: impl DiscoveryBackend) -> Self {
+        self.discovery_backends.push(Box::new(backend));
+        self
+    }
+
     /// Sets the dns discovery config to use.
     pub fn dns_discovery(mut self, config: DnsDiscoveryConfig) -> Self {
         self.dns_discovery_config = Some(config);
@@ -441,6 +462,13 @@ impl NetworkConfigBuilder {
         self
     }
 
+    /// Sets the mappings that interpret the disconnect codes of `RLPx` sub-protocols, which are
+    /// used to log why a peer disconnected with a subprotocol specific reason.
+    pub fn subprotocol_disconnect_reasons(mut self, reasons: SubprotocolDisconnectReasons) -> Self {
+        self.subprotocol_disconnect_reasons = reasons;
+        self
+    }
+
     /// Sets whether tx gossip is disabled.
     pub const fn disable_tx_gossip(mut self, disable_tx_gossip: bool) -> Self {
         self.tx_gossip_disabled = disable_tx_gossip;
@@ -482,6 +510,7 @@ impl NetworkConfigBuilder {
             mut dns_discovery_config,
             discovery_v4_builder,
             mut discovery_v5_builder,
+            discovery_backends,
             boot_nodes,
             discovery_addr,
             listener_addr,
@@ -491,6 +520,7 @@ impl NetworkConfigBuilder {
             executor,
             hello_message,
             extra_protocols,
+            subprotocol_disconnect_reasons,
             head,
             tx_gossip_disabled,
             block_import,
@@ -547,6 +577,7 @@ impl NetworkConfigBuilder {
             dns_discovery_config,
             discovery_v4_config: discovery_v4_builder.map(|builder| builder.build()),
             discovery_v5_config: discovery_v5_builder.map(|builder| builder.build()),
+            discovery_backends,
             discovery_v4_addr: discovery_addr.unwrap_or(DEFAULT_DISCOVERY_ADDRESS),
             listener_addr,
             peers_config: peers_config.unwrap_or_default(),
@@ -558,6 +589,7 @@ impl NetworkConfigBuilder {
             status,
             hello_message,
             extra_protocols,
+            subprotocol_disconnect_reasons,
             fork_filter,
             tx_gossip_disabled,
             transactions_manager_config,
@@ -2,13 +2,13 @@
 
 use std::{
     collections::VecDeque,
+    fmt,
     net::{IpAddr, SocketAddr},
     pin::Pin,
     sync::Arc,
     task::{ready, Context, Poll},
 };
 
-use enr::Enr;
 use futures::StreamExt;
 use reth_discv4::{DiscoveryUpdate, Discv4, Discv4Config};
 use reth_discv5::{DiscoveredPeer, Discv5};
@@ -34,10 +34,143 @@ use crate::{
 /// Default is 10 000 peers.
 pub const DEFAULT_MAX_CAPACITY_DISCOVERED_PEERS_CACHE: u32 = 10_000;
 
-/// An abstraction over the configured discovery protocol.
+/// An update from a [`DiscoveryBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoveryBackendUpdate {
+    /// A node was discovered, with the [`ForkId`] it advertises if it's known.
+    Discovered(NodeRecord, Option<ForkId>),
+    /// The [`ForkId`] advertised by a discovered node.
+    EnrForkId(PeerId, ForkId),
+    /// A previously discovered node was removed.
+    Removed(PeerId),
+}
+
+/// A source of candidate peers, e.g. a discovery protocol, a DNS tree or a static list.
+///
+/// discv4, discv5 and DNS discovery are backends as well. The discovered nodes of all backends
+/// are handed to the peers manager, which dials them.
+pub trait DiscoveryBackend: fmt::Debug + Send + Unpin + 'static {
+    /// Polls for the next update.
+    ///
+    /// Returns `Poll::Ready(None)` once the backend won't discover any more nodes, it's not polled
+    /// again after that.
+    fn poll_next_update(&mut self, cx: &mut Context<'_>) -> Poll<Option<DiscoveryBackendUpdate>>;
+}
+
+/// A [`DiscoveryBackend`] that yields a fixed list of nodes.
 ///
-/// Listens for new discovered nodes and emits events for discovered nodes and their
-/// address.
+/// Together with disabled discovery, see
+/// [`NetworkConfigBuilder::disable_discovery`](crate::NetworkConfigBuilder::disable_discovery),
+/// this only connects to the given nodes.
+#[derive(Debug, Clone, Default)]
+pub struct StaticDiscovery {
+    nodes: VecDeque<NodeRecord>,
+}
+
+impl StaticDiscovery {
+    /// Creates a new backend that yields the given nodes.
+    pub fn new(nodes: impl IntoIterator<Item = NodeRecord>) -> Self {
+        Self { nodes: nodes.into_iter().collect() }
+    }
+}
+
+impl DiscoveryBackend for StaticDiscovery {
+    fn poll_next_update(&mut self, _cx: &mut Context<'_>) -> Poll<Option<DiscoveryBackendUpdate>> {
+        Poll::Ready(
+            self.nodes.pop_front().map(|record| DiscoveryBackendUpdate::Discovered(record, None)),
+        )
+    }
+}
+
+/// The KAD table updates of the discv4 service.
+#[derive(Debug)]
+struct Discv4Backend {
+    updates: ReceiverStream<DiscoveryUpdate>,
+    /// Unpacked updates of a [`DiscoveryUpdate::Batch`].
+    queued: VecDeque<DiscoveryUpdate>,
+}
+
+impl DiscoveryBackend for Discv4Backend {
+    fn poll_next_update(&mut self, cx: &mut Context<'_>) -> Poll<Option<DiscoveryBackendUpdate>> {
+        loop {
+            let update = match self.queued.pop_front() {
+                Some(update) => update,
+                None => match ready!(self.updates.poll_next_unpin(cx)) {
+                    Some(update) => update,
+                    None => return Poll::Ready(None),
+                },
+            };
+            let update = match update {
+                DiscoveryUpdate::Added(record) | DiscoveryUpdate::DiscoveredAtCapacity(record) => {
+                    DiscoveryBackendUpdate::Discovered(record, None)
+                }
+                DiscoveryUpdate::EnrForkId(node, fork_id) => {
+                    DiscoveryBackendUpdate::EnrForkId(node.id, fork_id)
+                }
+                DiscoveryUpdate::Removed(peer_id) => DiscoveryBackendUpdate::Removed(peer_id),
+                DiscoveryUpdate::Batch(updates) => {
+                    self.queued.extend(updates);
+                    continue
+                }
+            };
+            return Poll::Ready(Some(update))
+        }
+    }
+}
+
+/// The KAD table updates of the discv5 service.
+#[derive(Debug)]
+struct Discv5Backend {
+    discv5: Discv5,
+    updates: ReceiverStream<discv5::Event>,
+}
+
+impl DiscoveryBackend for Discv5Backend {
+    fn poll_next_update(&mut self, cx: &mut Context<'_>) -> Poll<Option<DiscoveryBackendUpdate>> {
+        while let Some(update) = ready!(self.updates.poll_next_unpin(cx)) {
+            if let Some(DiscoveredPeer { node_record, fork_id }) =
+                self.discv5.on_discv5_update(update)
+            {
+                return Poll::Ready(Some(DiscoveryBackendUpdate::Discovered(node_record, fork_id)))
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// The nodes resolved by the DNS discovery service.
+///
+/// Resolved nodes are also added to the discv4 and discv5 tables.
+#[derive(Debug)]
+struct DnsBackend {
+    updates: ReceiverStream<DnsNodeRecordUpdate>,
+    discv4: Option<Discv4>,
+    discv5: Option<Discv5>,
+}
+
+impl DiscoveryBackend for DnsBackend {
+    fn poll_next_update(&mut self, cx: &mut Context<'_>) -> Poll<Option<DiscoveryBackendUpdate>> {
+        let Some(update) = ready!(self.updates.poll_next_unpin(cx)) else {
+            return Poll::Ready(None)
+        };
+        if let Some(discv4) = &self.discv4 {
+            discv4.add_node(update.node_record);
+        }
+        if let Some(discv5) = &self.discv5 {
+            if let Err(err) = discv5.add_node(update.enr) {
+                trace!(target: "net::discovery",
+                    %err,
+                    "failed adding node discovered by dns to discv5"
+                );
+            }
+        }
+        Poll::Ready(Some(DiscoveryBackendUpdate::Discovered(update.node_record, update.fork_id)))
+    }
+}
+
+/// An abstraction over the configured discovery protocols.
+///
+/// Polls the [`DiscoveryBackend`]s and emits events for discovered nodes and their address.
 #[derive(Debug)]
 pub struct Discovery {
     /// All nodes discovered via discovery protocol.
@@ -48,20 +181,16 @@ pub struct Discovery {
     local_enr: NodeRecord,
     /// Handler to interact with the Discovery v4 service
     discv4: Option<Discv4>,
-    /// All KAD table updates from the discv4 service.
-    discv4_updates: Option<ReceiverStream<DiscoveryUpdate>>,
     /// The handle to the spawned discv4 service
     _discv4_service: Option<JoinHandle<()>>,
     /// Handler to interact with the Discovery v5 service
     discv5: Option<Discv5>,
-    /// All KAD table updates from the discv5 service.
-    discv5_updates: Option<ReceiverStream<discv5::Event>>,
     /// Handler to interact with the DNS discovery service
     _dns_discovery: Option<DnsDiscoveryHandle>,
-    /// Updates from the DNS discovery service.
-    dns_discovery_updates: Option<ReceiverStream<DnsNodeRecordUpdate>>,
     /// The handle to the spawned DNS discovery service
     _dns_disc_service: Option<JoinHandle<()>>,
+    /// Sources of discovered nodes, removed once they're exhausted.
+    backends: Vec<Box<dyn DiscoveryBackend>>,
     /// Events buffered until polled.
     queued_events: VecDeque<DiscoveryEvent>,
     /// List of listeners subscribed to discovery events.
@@ -123,22 +252,40 @@ impl Discovery {
                 (None, None, None)
             };
 
+        let mut backends: Vec<Box<dyn DiscoveryBackend>> = Vec::new();
+        if let Some(updates) = discv4_updates {
+            backends.push(Box::new(Discv4Backend { updates, queued: VecDeque::new() }));
+        }
+        if let (Some(discv5), Some(updates)) = (discv5.clone(), discv5_updates) {
+            backends.push(Box::new(Discv5Backend { discv5, updates }));
+        }
+        if let Some(updates) = dns_discovery_updates {
+            backends.push(Box::new(DnsBackend {
+                updates,
+                discv4: discv4.clone(),
+                discv5: discv5.clone(),
+            }));
+        }
+
         Ok(Self {
             discovery_listeners: Default::default(),
             local_enr,
             discv4,
-            discv4_updates,
             _discv4_service,
             discv5,
-            discv5_updates,
             discovered_nodes: LruMap::new(DEFAULT_MAX_CAPACITY_DISCOVERED_PEERS_CACHE),
             queued_events: Default::default(),
             _dns_disc_service,
             _dns_discovery,
-            dns_discovery_updates,
+            backends,
         })
     }
 
+    /// Adds a source of nodes, that's polled alongside the configured discovery protocols.
+    pub(crate) fn add_backend(&mut self, backend: Box<dyn DiscoveryBackend>) {
+        self.backends.push(backend);
+    }
+
     /// Registers a listener for receiving [`DiscoveryEvent`] updates.
     pub(crate) fn add_listener(&mut self, tx: mpsc::UnboundedSender<DiscoveryEvent>) {
         self.discovery_listeners.push(tx);
@@ -189,27 +336,11 @@ impl Discovery {
         self.local_enr.id // local discv4 and discv5 have same id, since signed with same secret key
     }
 
-    /// Add a node to the discv4 table.
-    pub(crate) fn add_discv4_node(&self, node: NodeRecord) {
-        if let Some(discv4) = &self.discv4 {
-            discv4.add_node(node);
-        }
-    }
-
     /// Returns discv5 handle.
     pub fn discv5(&self) -> Option<Discv5> {
         self.discv5.clone()
     }
 
-    /// Add a node to the discv4 table.
-    pub(crate) fn add_discv5_node(&self, enr: Enr<SecretKey>) -> Result<(), NetworkError> {
-        if let Some(discv5) = &self.discv5 {
-            discv5.add_node(enr).map_err(NetworkError::Discv5Error)?;
-        }
-
-        Ok(())
-    }
-
     /// Processes an incoming [`NodeRecord`] update from a discovery service
     fn on_node_record_update(&mut self, record: NodeRecord, fork_id: Option<ForkId>) {
         let peer_id = record.id;
@@ -226,22 +357,17 @@ impl Discovery {
             })
     }
 
-    fn on_discv4_update(&mut self, update: DiscoveryUpdate) {
+    fn on_backend_update(&mut self, update: DiscoveryBackendUpdate) {
         match update {
-            DiscoveryUpdate::Added(record) | DiscoveryUpdate::DiscoveredAtCapacity(record) => {
-                self.on_node_record_update(record, None);
+            DiscoveryBackendUpdate::Discovered(record, fork_id) => {
+                self.on_node_record_update(record, fork_id)
             }
-            DiscoveryUpdate::EnrForkId(node, fork_id) => {
-                self.queued_events.push_back(DiscoveryEvent::EnrForkId(node.id, fork_id))
+            DiscoveryBackendUpdate::EnrForkId(peer_id, fork_id) => {
+                self.queued_events.push_back(DiscoveryEvent::EnrForkId(peer_id, fork_id))
             }
-            DiscoveryUpdate::Removed(peer_id) => {
+            DiscoveryBackendUpdate::Removed(peer_id) => {
                 self.discovered_nodes.remove(&peer_id);
             }
-            DiscoveryUpdate::Batch(updates) => {
-                for update in updates {
-                    self.on_discv4_update(update);
-                }
-            }
         }
     }
 
@@ -253,39 +379,16 @@ impl Discovery {
                 return Poll::Ready(event)
             }
 
-            // drain the discv4 update stream
-            while let Some(Poll::Ready(Some(update))) =
-                self.discv4_updates.as_mut().map(|updates| updates.poll_next_unpin(cx))
-            {
-                self.on_discv4_update(update)
-            }
-
-            // drain the discv5 update stream
-            while let Some(Poll::Ready(Some(update))) =
-                self.discv5_updates.as_mut().map(|updates| updates.poll_next_unpin(cx))
-            {
-                if let Some(discv5) = self.discv5.as_mut() {
-                    if let Some(DiscoveredPeer { node_record, fork_id }) =
-                        discv5.on_discv5_update(update)
-                    {
-                        self.on_node_record_update(node_record, fork_id);
-                    }
+            // drain the backends, exhausted backends are removed
+            let mut backends = std::mem::take(&mut self.backends);
+            backends.retain_mut(|backend| loop {
+                match backend.poll_next_update(cx) {
+                    Poll::Ready(Some(update)) => self.on_backend_update(update),
+                    Poll::Ready(None) => break false,
+                    Poll::Pending => break true,
                 }
-            }
-
-            // drain the dns update stream
-            while let Some(Poll::Ready(Some(update))) =
-                self.dns_discovery_updates.as_mut().map(|updates| updates.poll_next_unpin(cx))
-            {
-                self.add_discv4_node(update.node_record);
-                if let Err(err) = self.add_discv5_node(update.enr) {
-                    trace!(target: "net::discovery",
-                        %err,
-                        "failed adding node discovered by dns to discv5"
-                    );
-                }
-                self.on_node_record_update(update.node_record, update.fork_id);
-            }
+            });
+            self.backends = backends;
 
             if self.queued_events.is_empty() {
                 return Poll::Pending
@@ -320,17 +423,31 @@ impl Discovery {
                 id: PeerId::random(),
             },
             discv4: Default::default(),
-            discv4_updates: Default::default(),
             discv5: None,
-            discv5_updates: None,
             queued_events: Default::default(),
             _discv4_service: Default::default(),
             _dns_discovery: None,
-            dns_discovery_updates: None,
             _dns_disc_service: None,
+            backends: Vec::new(),
             discovery_listeners: Default::default(),
         }
     }
+
+    /// Add a node to the discv4 table.
+    pub(crate) fn add_discv4_node(&self, node: NodeRecord) {
+        if let Some(discv4) = &self.discv4 {
+            discv4.add_node(node);
+        }
+    }
+
+    /// Add a node to the discv5 table.
+    pub(crate) fn add_discv5_node(&self, enr: enr::Enr<SecretKey>) -> Result<(), NetworkError> {
+        if let Some(discv5) = &self.discv5 {
+            discv5.add_node(enr).map_err(NetworkError::Discv5Error)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -338,7 +455,10 @@ mod tests {
     use super::*;
     use rand::thread_rng;
     use secp256k1::SECP256K1;
-    use std::net::{Ipv4Addr, SocketAddrV4};
+    use std::{
+        future::poll_fn,
+        net::{Ipv4Addr, SocketAddrV4},
+    };
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_discovery_setup() {
@@ -357,10 +477,86 @@ mod tests {
         .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_static_discovery() {
+        let nodes: Vec<_> = (1..=3)
+            .map(|port| NodeRecord::new(SocketAddr::from(([127, 0, 0, 1], port)), PeerId::random()))
+            .collect();
+        let mut discovery = Discovery::noop();
+        discovery.add_backend(Box::new(StaticDiscovery::new(nodes.clone())));
+
+        for node in nodes {
+            let event = poll_fn(|cx| discovery.poll(cx)).await;
+            let DiscoveryEvent::NewNode(DiscoveredEvent::EventQueued { peer_id, addr, fork_id }) =
+                event
+            else {
+                panic!("unexpected event {event:?}")
+            };
+            assert_eq!(peer_id, node.id);
+            assert_eq!(addr.tcp(), node.tcp_addr());
+            assert_eq!(fork_id, None);
+        }
+
+        // the exhausted backend is removed
+        assert!(poll_fn(|cx| Poll::Ready(discovery.poll(cx))).await.is_pending());
+        assert!(discovery.backends.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_discv4_backend_unpacks_batches() {
+        let (tx, rx) = mpsc::channel(1);
+        let mut backend = Discv4Backend { updates: rx.into(), queued: VecDeque::new() };
+        let node = NodeRecord::new(SocketAddr::from(([127, 0, 0, 1], 30303)), PeerId::random());
+        let removed = PeerId::random();
+        tx.send(DiscoveryUpdate::Batch(vec![
+            DiscoveryUpdate::Added(node),
+            DiscoveryUpdate::Removed(removed),
+        ]))
+        .await
+        .unwrap();
+        drop(tx);
+
+        let mut updates = Vec::new();
+        while let Some(update) = poll_fn(|cx| backend.poll_next_update(cx)).await {
+            updates.push(update);
+        }
+        assert_eq!(
+            updates,
+            vec![
+                DiscoveryBackendUpdate::Discovered(node, None),
+                DiscoveryBackendUpdate::Removed(removed),
+            ]
+        );
+    }
+
     use reth_discv4::Discv4ConfigBuilder;
     use reth_discv5::{enr::EnrCombinedKeyWrapper, enr_to_discv4_id};
+    use reth_primitives::ForkHash;
     use tracing::trace;
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_update_fork_id() {
+        let secret_key = SecretKey::new(&mut thread_rng());
+        let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+        let fork_id = ForkId { hash: ForkHash([0xfc, 0x64, 0xec, 0x04]), next: 1_150_000 };
+        let discv4_config = Discv4ConfigBuilder::default()
+            .external_ip_resolver(None)
+            .add_eip868_pair("eth", EnrForkIdEntry::from(fork_id))
+            .build();
+        let discovery =
+            Discovery::new(addr, addr, secret_key, Some(discv4_config), None, None).await.unwrap();
+        let discv4 = discovery.discv4().unwrap();
+        let enr = discv4.local_enr().await.unwrap();
+
+        // a fork activation updates the fork id advertised in the ENR and increments its sequence
+        let next_fork_id = ForkId { hash: ForkHash([0x97, 0xc2, 0xc3, 0x4c]), next: 1_920_000 };
+        discovery.update_fork_id(next_fork_id);
+        let updated = discv4.local_enr().await.unwrap();
+        assert_eq!(updated.seq(), enr.seq() + 1);
+        let entry = updated.get_decodable::<EnrForkIdEntry>(b"eth").unwrap().unwrap();
+        assert_eq!(ForkId::from(entry), next_fork_id);
+    }
+
     async fn start_discovery_node(udp_port_discv4: u16, udp_port_discv5: u16) -> Discovery {
         let secret_key = SecretKey::new(&mut thread_rng());
 
@@ -6,7 +6,7 @@ use reth_dns_discovery::resolver::ResolveError;
 use reth_ecies::ECIESErrorImpl;
 use reth_eth_wire::{
     errors::{EthHandshakeError, EthStreamError, P2PHandshakeError, P2PStreamError},
-    DisconnectReason,
+    DisconnectReason, Initiator,
 };
 use reth_network_types::BackoffKind;
 
@@ -106,15 +106,23 @@ pub(crate) trait SessionError: fmt::Debug + fmt::Display {
     /// In case of `Some(BackoffKind)` will temporarily prevent additional
     /// connection attempts.
     fn should_backoff(&self) -> Option<BackoffKind>;
+
+    /// Returns the [`DisconnectReason`] if the error is a disconnect message.
+    fn disconnect_reason(&self) -> Option<DisconnectReason>;
+
+    /// Returns true if the session ended because of the local node rather than the peer, see
+    /// [`DisconnectReason::is_our_fault`].
+    ///
+    /// The peer's reputation is only slashed if this returns `false`.
+    fn is_our_fault(&self) -> bool;
 }
 
 impl SessionError for EthStreamError {
     fn merits_discovery_ban(&self) -> bool {
         match self {
-            Self::P2PStreamError(P2PStreamError::HandshakeError(
-                P2PHandshakeError::HelloNotInHandshake |
-                P2PHandshakeError::NonHelloMessageInHandshake,
-            )) => true,
+            Self::P2PStreamError(P2PStreamError::HandshakeError(err)) => {
+                p2p_handshake_merits_discovery_ban(err)
+            }
             Self::EthHandshakeError(err) => !matches!(err, EthHandshakeError::NoResponse),
             _ => false,
         }
@@ -122,85 +130,97 @@ impl SessionError for EthStreamError {
 
     fn is_fatal_protocol_error(&self) -> bool {
         match self {
-            Self::P2PStreamError(err) => {
-                matches!(
-                    err,
-                    P2PStreamError::HandshakeError(
-                        P2PHandshakeError::NoSharedCapabilities |
-                            P2PHandshakeError::HelloNotInHandshake |
-                            P2PHandshakeError::NonHelloMessageInHandshake |
-                            P2PHandshakeError::Disconnected(
-                                DisconnectReason::UselessPeer |
-                                    DisconnectReason::IncompatibleP2PProtocolVersion |
-                                    DisconnectReason::ProtocolBreach
-                            )
-                    ) | P2PStreamError::UnknownReservedMessageId(_) |
-                        P2PStreamError::EmptyProtocolMessage |
-                        P2PStreamError::ParseSharedCapability(_) |
-                        P2PStreamError::CapabilityNotShared |
-                        P2PStreamError::Disconnected(
-                            DisconnectReason::UselessPeer |
-                                DisconnectReason::IncompatibleP2PProtocolVersion |
-                                DisconnectReason::ProtocolBreach
-                        ) |
-                        P2PStreamError::MismatchedProtocolVersion { .. }
-                )
-            }
+            Self::P2PStreamError(err) => is_fatal_p2p_error(err),
             Self::EthHandshakeError(err) => !matches!(err, EthHandshakeError::NoResponse),
             _ => false,
         }
     }
 
     fn should_backoff(&self) -> Option<BackoffKind> {
-        if let Some(err) = self.as_io() {
+        let reason = if self.as_ecies().is_some() {
+            // failures of the ECIES framing are handled like the disconnect we'd send because of
+            // them, so corrupted frames are transport errors rather than a protocol breach
+            Some(self.recommended_disconnect_reason())
+        } else if let Some(err) = self.as_io() {
             return err.should_backoff()
-        }
-
-        if let Some(err) = self.as_disconnected() {
-            return match err {
-                DisconnectReason::TooManyPeers |
-                DisconnectReason::AlreadyConnected |
-                DisconnectReason::PingTimeout |
-                DisconnectReason::DisconnectRequested |
-                DisconnectReason::TcpSubsystemError => Some(BackoffKind::Low),
+        } else {
+            self.as_disconnected()
+        };
 
-                DisconnectReason::ProtocolBreach |
-                DisconnectReason::UselessPeer |
-                DisconnectReason::IncompatibleP2PProtocolVersion |
-                DisconnectReason::NullNodeIdentity |
-                DisconnectReason::ClientQuitting |
-                DisconnectReason::UnexpectedHandshakeIdentity |
-                DisconnectReason::ConnectedToSelf |
-                DisconnectReason::SubprotocolSpecific => {
-                    // These are considered fatal, and are handled by the
-                    // [`SessionError::is_fatal_protocol_error`]
-                    Some(BackoffKind::High)
-                }
-            }
+        if let Some(reason) = reason {
+            return Some(disconnect_backoff(reason))
         }
 
         // This only checks for a subset of error variants, the counterpart of
         // [`SessionError::is_fatal_protocol_error`]
         match self {
             // timeouts
-            Self::EthHandshakeError(EthHandshakeError::NoResponse) |
-            Self::P2PStreamError(
-                P2PStreamError::HandshakeError(P2PHandshakeError::NoResponse) |
-                P2PStreamError::PingTimeout,
-            ) => Some(BackoffKind::Low),
-            // malformed messages
-            Self::P2PStreamError(
-                P2PStreamError::Rlp(_) |
-                P2PStreamError::UnknownReservedMessageId(_) |
-                P2PStreamError::UnknownDisconnectReason(_) |
-                P2PStreamError::MessageTooBig { .. } |
-                P2PStreamError::EmptyProtocolMessage |
-                P2PStreamError::PingerError(_) |
-                P2PStreamError::Snap(_),
-            ) => Some(BackoffKind::Medium),
+            Self::EthHandshakeError(EthHandshakeError::NoResponse) => Some(BackoffKind::Low),
+            Self::P2PStreamError(err) => p2p_error_backoff(err),
+            _ => None,
+        }
+    }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.as_disconnected()
+    }
+
+    fn is_our_fault(&self) -> bool {
+        match self.as_disconnected() {
+            Some(reason) => reason.is_our_fault(Initiator::Remote),
+            None => self.recommended_disconnect_reason().is_our_fault(Initiator::Local),
+        }
+    }
+}
+
+impl SessionError for reth_eth_wire::errors::SessionError {
+    fn merits_discovery_ban(&self) -> bool {
+        match self {
+            Self::P2PHandshake(err) => p2p_handshake_merits_discovery_ban(err),
+            Self::EthHandshake(err) => !matches!(err, EthHandshakeError::NoResponse),
+            Self::Eth(err) => err.merits_discovery_ban(),
+            _ => false,
+        }
+    }
+
+    fn is_fatal_protocol_error(&self) -> bool {
+        match self {
+            Self::P2PHandshake(err) => is_fatal_p2p_handshake_error(err),
+            Self::EthHandshake(err) => !matches!(err, EthHandshakeError::NoResponse),
+            Self::P2P(err) => is_fatal_p2p_error(err),
+            Self::Eth(err) => err.is_fatal_protocol_error(),
+            _ => false,
+        }
+    }
+
+    fn should_backoff(&self) -> Option<BackoffKind> {
+        if let Some(reason) = self.as_disconnected() {
+            return Some(disconnect_backoff(reason))
+        }
+
+        match self {
+            // see the ECIES handling of `EthStreamError`
+            Self::Ecies(_) => Some(disconnect_backoff(self.recommended_disconnect_reason())),
+            Self::Io(err) => err.should_backoff(),
+            Self::P2PHandshake(P2PHandshakeError::NoResponse) |
+            Self::EthHandshake(EthHandshakeError::NoResponse) => Some(BackoffKind::Low),
+            Self::Decode(_) => Some(BackoffKind::Medium),
+            Self::P2P(err) => p2p_error_backoff(err),
+            Self::Eth(err) => err.should_backoff(),
             _ => None,
         }
     }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.as_disconnected()
+    }
+
+    fn is_our_fault(&self) -> bool {
+        match self.as_disconnected() {
+            Some(reason) => reason.is_our_fault(Initiator::Remote),
+            None => self.recommended_disconnect_reason().is_our_fault(Initiator::Local),
+        }
+    }
 }
 
 impl SessionError for PendingSessionHandshakeError {
@@ -247,6 +267,17 @@ impl SessionError for PendingSessionHandshakeError {
             Self::Timeout => Some(BackoffKind::Medium),
         }
     }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        self.as_disconnected()
+    }
+
+    fn is_our_fault(&self) -> bool {
+        match self {
+            Self::Eth(eth) => eth.is_our_fault(),
+            Self::Ecies(_) | Self::Timeout => false,
+        }
+    }
 }
 
 impl SessionError for io::Error {
@@ -271,11 +302,110 @@ impl SessionError for io::Error {
             _ => Some(BackoffKind::Medium),
         }
     }
+
+    fn disconnect_reason(&self) -> Option<DisconnectReason> {
+        None
+    }
+
+    fn is_our_fault(&self) -> bool {
+        false
+    }
+}
+
+/// Returns true if the failed `p2p` handshake means the peer isn't part of the gossip network.
+const fn p2p_handshake_merits_discovery_ban(err: &P2PHandshakeError) -> bool {
+    matches!(
+        err,
+        P2PHandshakeError::HelloNotInHandshake | P2PHandshakeError::NonHelloMessageInHandshake
+    )
+}
+
+/// Returns true if the failed `p2p` handshake means we'll never be able to connect to the peer.
+const fn is_fatal_p2p_handshake_error(err: &P2PHandshakeError) -> bool {
+    matches!(
+        err,
+        P2PHandshakeError::NoSharedCapabilities |
+            P2PHandshakeError::TooManyDuplicateCapabilities(_) |
+            P2PHandshakeError::TooManyCapabilities(_) |
+            P2PHandshakeError::NullNodeIdentity |
+            P2PHandshakeError::HelloNotInHandshake |
+            P2PHandshakeError::NonHelloMessageInHandshake |
+            P2PHandshakeError::Disconnected(
+                DisconnectReason::UselessPeer |
+                    DisconnectReason::IncompatibleP2PProtocolVersion |
+                    DisconnectReason::ProtocolBreach
+            )
+    )
+}
+
+/// Returns true if the error of the `p2p` stream means we'll never be able to connect to the peer.
+const fn is_fatal_p2p_error(err: &P2PStreamError) -> bool {
+    match err {
+        P2PStreamError::HandshakeError(err) => is_fatal_p2p_handshake_error(err),
+        _ => matches!(
+            err,
+            P2PStreamError::UnknownReservedMessageId(_) |
+                P2PStreamError::EmptyProtocolMessage |
+                P2PStreamError::ParseSharedCapability(_) |
+                P2PStreamError::CapabilityNotShared |
+                P2PStreamError::Disconnected(
+                    DisconnectReason::UselessPeer |
+                        DisconnectReason::IncompatibleP2PProtocolVersion |
+                        DisconnectReason::ProtocolBreach
+                ) |
+                P2PStreamError::MismatchedProtocolVersion { .. }
+        ),
+    }
+}
+
+/// Returns the backoff for a session that ended with the given [`DisconnectReason`].
+const fn disconnect_backoff(reason: DisconnectReason) -> BackoffKind {
+    match reason {
+        DisconnectReason::TooManyPeers |
+        DisconnectReason::AlreadyConnected |
+        DisconnectReason::PingTimeout |
+        DisconnectReason::DisconnectRequested |
+        DisconnectReason::TcpSubsystemError => BackoffKind::Low,
+
+        DisconnectReason::ProtocolBreach |
+        DisconnectReason::UselessPeer |
+        DisconnectReason::IncompatibleP2PProtocolVersion |
+        DisconnectReason::NullNodeIdentity |
+        DisconnectReason::ClientQuitting |
+        DisconnectReason::UnexpectedHandshakeIdentity |
+        DisconnectReason::ConnectedToSelf |
+        DisconnectReason::SubprotocolSpecific => {
+            // These are considered fatal, and are handled by the
+            // [`SessionError::is_fatal_protocol_error`]
+            BackoffKind::High
+        }
+    }
+}
+
+/// Returns the backoff for an error of the `p2p` stream that isn't a disconnect, if any.
+const fn p2p_error_backoff(err: &P2PStreamError) -> Option<BackoffKind> {
+    match err {
+        // timeouts
+        P2PStreamError::HandshakeError(P2PHandshakeError::NoResponse) |
+        P2PStreamError::PingTimeout => Some(BackoffKind::Low),
+        // malformed messages
+        P2PStreamError::Rlp(_) |
+        P2PStreamError::UnknownReservedMessageId(_) |
+        P2PStreamError::UnknownDisconnectReason(_) |
+        P2PStreamError::MessageTooBig { .. } |
+        P2PStreamError::SubprotocolMessageTooBig { .. } |
+        P2PStreamError::ReceiveBufferFull { .. } |
+        P2PStreamError::EmptyProtocolMessage |
+        P2PStreamError::PingerError(_) |
+        P2PStreamError::Snap(_) => Some(BackoffKind::Medium),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use reth_ecies::ECIESError;
     use std::net::{Ipv4Addr, SocketAddrV4};
 
     #[test]
@@ -304,6 +434,73 @@ mod tests {
         assert_eq!(err.should_backoff(), Some(BackoffKind::Low));
     }
 
+    #[test]
+    fn test_ecies_failure_backoff() {
+        let corrupted = EthStreamError::from(io::Error::from(ECIESError::from(
+            ECIESErrorImpl::TagCheckBodyFailed,
+        )));
+        assert_eq!(corrupted.recommended_disconnect_reason(), DisconnectReason::TcpSubsystemError);
+        assert_eq!(corrupted.should_backoff(), Some(BackoffKind::Low));
+        assert!(!corrupted.is_fatal_protocol_error());
+
+        let garbage =
+            EthStreamError::from(io::Error::from(ECIESError::from(ECIESErrorImpl::InvalidHeader)));
+        assert_eq!(garbage.recommended_disconnect_reason(), DisconnectReason::ProtocolBreach);
+        assert_eq!(garbage.should_backoff(), Some(BackoffKind::High));
+
+        // plain IO errors aren't affected
+        let refused = EthStreamError::from(io::Error::from(ErrorKind::ConnectionRefused));
+        assert_eq!(refused.should_backoff(), Some(BackoffKind::High));
+        let reset = EthStreamError::from(io::Error::from(ErrorKind::ConnectionReset));
+        assert_eq!(reset.should_backoff(), Some(BackoffKind::Low));
+    }
+
+    #[test]
+    fn test_flattened_session_error_matches_stream_error() {
+        let errors = || {
+            vec![
+                EthStreamError::P2PStreamError(P2PStreamError::HandshakeError(
+                    P2PHandshakeError::Disconnected(DisconnectReason::UselessPeer),
+                )),
+                EthStreamError::P2PStreamError(P2PStreamError::HandshakeError(
+                    P2PHandshakeError::HelloNotInHandshake,
+                )),
+                EthStreamError::P2PStreamError(P2PStreamError::HandshakeError(
+                    P2PHandshakeError::NoResponse,
+                )),
+                EthStreamError::P2PStreamError(P2PStreamError::Disconnected(
+                    DisconnectReason::TooManyPeers,
+                )),
+                EthStreamError::P2PStreamError(P2PStreamError::PingTimeout),
+                EthStreamError::P2PStreamError(P2PStreamError::CapabilityNotShared),
+                EthStreamError::P2PStreamError(P2PStreamError::SendBufferFull),
+                EthStreamError::EthHandshakeError(EthHandshakeError::NoResponse),
+                EthStreamError::EthHandshakeError(EthHandshakeError::NonStatusMessageInHandshake),
+                EthStreamError::from(io::Error::from(ECIESError::from(
+                    ECIESErrorImpl::TagCheckBodyFailed,
+                ))),
+                EthStreamError::from(io::Error::from(ErrorKind::ConnectionRefused)),
+                EthStreamError::MessageTooBig(usize::MAX),
+                EthStreamError::StreamTimeout,
+            ]
+        };
+
+        for (stream_err, err) in errors()
+            .into_iter()
+            .zip(errors().into_iter().map(reth_eth_wire::errors::SessionError::from))
+        {
+            assert_eq!(err.merits_discovery_ban(), stream_err.merits_discovery_ban(), "{err:?}");
+            assert_eq!(
+                err.is_fatal_protocol_error(),
+                stream_err.is_fatal_protocol_error(),
+                "{err:?}"
+            );
+            assert_eq!(err.should_backoff(), stream_err.should_backoff(), "{err:?}");
+            assert_eq!(err.disconnect_reason(), stream_err.disconnect_reason(), "{err:?}");
+            assert_eq!(err.is_our_fault(), stream_err.is_our_fault(), "{err:?}");
+        }
+    }
+
     #[test]
     fn test_address_in_use_message() {
         let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 1234));
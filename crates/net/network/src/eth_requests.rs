@@ -84,6 +84,8 @@ where
     C: BlockReader + HeaderProvider + ReceiptProvider,
 {
     /// Returns the list of requested headers
+    ///
+    /// The response is empty if the anchor is unknown.
     fn get_headers_response(&self, request: GetBlockHeaders) -> Vec<Header> {
         let GetBlockHeaders { start_block, limit, skip, direction } = request;
 
@@ -175,9 +177,8 @@ where
                 if bodies.len() >= MAX_BODIES_SERVE || total_bytes > SOFT_RESPONSE_LIMIT {
                     break
                 }
-            } else {
-                break
             }
+            // the bodies we don't have are omitted, the rest stay in the requested order
         }
 
         let _ = response.send(Ok(BlockBodies(bodies)));
@@ -210,9 +211,9 @@ where
                 if receipts.len() >= MAX_RECEIPTS_SERVE || total_bytes > SOFT_RESPONSE_LIMIT {
                     break
                 }
-            } else {
-                break
             }
+            // the receipts of blocks we don't have are omitted, the rest stay in the requested
+            // order
         }
 
         let _ = response.send(Ok(Receipts(receipts)));
@@ -317,3 +318,75 @@ pub enum IncomingEthRequest {
         response: oneshot::Sender<RequestResult<Receipts>>,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+    use reth_provider::test_utils::MockEthProvider;
+    use tokio::sync::mpsc;
+
+    /// Returns a handler serving a chain of `len` headers, and the hashes of the headers.
+    fn handler_with_chain(len: u64) -> (EthRequestHandler<MockEthProvider>, Vec<B256>) {
+        let provider = MockEthProvider::default();
+        let mut hashes = Vec::new();
+        let mut parent_hash = B256::ZERO;
+        for number in 0..len {
+            let header = Header { number, parent_hash, ..Default::default() };
+            parent_hash = header.hash_slow();
+            provider.add_header(parent_hash, header);
+            hashes.push(parent_hash);
+        }
+
+        let (peers_tx, _) = mpsc::unbounded_channel();
+        let (_, requests_rx) = mpsc::channel(1);
+        (EthRequestHandler::new(provider, PeersHandle::new(peers_tx), requests_rx), hashes)
+    }
+
+    fn numbers(headers: Vec<Header>) -> Vec<u64> {
+        headers.into_iter().map(|header| header.number).collect()
+    }
+
+    #[test]
+    fn test_headers_by_known_hash() {
+        let (handler, hashes) = handler_with_chain(10);
+
+        let headers = handler.get_headers_response(GetBlockHeaders {
+            start_block: hashes[3].into(),
+            limit: 3,
+            skip: 1,
+            direction: HeadersDirection::Rising,
+        });
+        assert_eq!(numbers(headers), [3, 5, 7]);
+
+        let headers = handler.get_headers_response(GetBlockHeaders {
+            start_block: hashes[5].into(),
+            limit: 3,
+            skip: 0,
+            direction: HeadersDirection::Falling,
+        });
+        assert_eq!(numbers(headers), [5, 4, 3]);
+
+        // the walk ends at the tip
+        let headers = handler.get_headers_response(GetBlockHeaders {
+            start_block: hashes[8].into(),
+            limit: 5,
+            skip: 0,
+            direction: HeadersDirection::Rising,
+        });
+        assert_eq!(numbers(headers), [8, 9]);
+    }
+
+    #[test]
+    fn test_headers_by_unknown_hash() {
+        let (handler, _) = handler_with_chain(10);
+
+        let headers = handler.get_headers_response(GetBlockHeaders {
+            start_block: B256::repeat_byte(0xab).into(),
+            limit: 3,
+            skip: 0,
+            direction: HeadersDirection::Rising,
+        });
+        assert!(headers.is_empty());
+    }
+}
@@ -20,7 +20,10 @@ use reth_network_types::ReputationChangeKind;
 use reth_primitives::Header;
 use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
-use crate::{fetch::DownloadRequest, flattened_response::FlattenedResponse};
+use crate::{
+    fetch::{disconnects::SyncDisconnects, DownloadRequest, SyncDisconnectsSnapshot},
+    flattened_response::FlattenedResponse,
+};
 
 #[cfg_attr(doc, aquamarine::aquamarine)]
 /// Front-end API for fetching data from the network.
@@ -37,6 +40,21 @@ pub struct FetchClient {
     pub(crate) peers_handle: PeersHandle,
     /// Number of active peer sessions the node's currently handling.
     pub(crate) num_active_peers: Arc<AtomicUsize>,
+    /// Disconnects of the peers used for syncing.
+    pub(crate) disconnects: Arc<SyncDisconnects>,
+}
+
+impl FetchClient {
+    /// Returns the disconnects of sync peers, by initiator and reason, since the start of the
+    /// current sync session.
+    pub fn sync_disconnects(&self) -> SyncDisconnectsSnapshot {
+        self.disconnects.snapshot()
+    }
+
+    /// Returns the disconnects of sync peers of the current sync session and starts a new one.
+    pub fn reset_sync_disconnects(&self) -> SyncDisconnectsSnapshot {
+        self.disconnects.reset()
+    }
 }
 
 impl DownloadClient for FetchClient {
@@ -44,6 +62,10 @@ impl DownloadClient for FetchClient {
         self.peers_handle.reputation_change(peer_id, ReputationChangeKind::BadMessage);
     }
 
+    fn report_bad_response(&self, peer_id: PeerId) {
+        self.peers_handle.reputation_change(peer_id, ReputationChangeKind::BadResponse);
+    }
+
     fn num_connected_peers(&self) -> usize {
         self.num_active_peers.load(Ordering::Relaxed)
     }
@@ -0,0 +1,68 @@
+//! Classification of the disconnects of the peers used for syncing.
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use reth_eth_wire::{DisconnectReason, Initiator};
+
+/// Counts the disconnects of sync peers by [`Initiator`] and [`DisconnectReason`].
+///
+/// This is shared between the [`StateFetcher`](super::StateFetcher), which records the
+/// disconnects, and the [`FetchClient`](super::FetchClient), which reads them.
+#[derive(Debug, Default)]
+pub(crate) struct SyncDisconnects {
+    counts: Mutex<HashMap<(Initiator, Option<DisconnectReason>), u64>>,
+}
+
+impl SyncDisconnects {
+    /// Records a disconnect of a sync peer.
+    pub(crate) fn record(&self, initiator: Initiator, reason: Option<DisconnectReason>) {
+        *self.counts.lock().entry((initiator, reason)).or_default() += 1;
+    }
+
+    /// Returns the disconnects recorded since the start of the current sync session.
+    pub(crate) fn snapshot(&self) -> SyncDisconnectsSnapshot {
+        SyncDisconnectsSnapshot { counts: self.counts.lock().clone() }
+    }
+
+    /// Returns the disconnects recorded since the start of the current sync session and starts a
+    /// new one.
+    pub(crate) fn reset(&self) -> SyncDisconnectsSnapshot {
+        SyncDisconnectsSnapshot { counts: std::mem::take(&mut *self.counts.lock()) }
+    }
+}
+
+/// The distribution of the disconnects of sync peers during a sync session.
+///
+/// Disconnects initiated by the remote tell whether peers drop us, e.g. with
+/// [`DisconnectReason::UselessPeer`] when we're on a bad fork, while disconnects initiated locally
+/// are the peers we dropped, e.g. because their requests timed out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncDisconnectsSnapshot {
+    counts: HashMap<(Initiator, Option<DisconnectReason>), u64>,
+}
+
+impl SyncDisconnectsSnapshot {
+    /// Returns the number of disconnects by the given initiator with the given reason.
+    ///
+    /// A `None` reason counts the disconnects without a reason, e.g. when the connection was
+    /// dropped without a `Disconnect` message.
+    pub fn count(&self, initiator: Initiator, reason: Option<DisconnectReason>) -> u64 {
+        self.counts.get(&(initiator, reason)).copied().unwrap_or_default()
+    }
+
+    /// Returns the number of disconnects by the given initiator, regardless of the reason.
+    pub fn total_by(&self, initiator: Initiator) -> u64 {
+        self.counts.iter().filter(|((i, _), _)| *i == initiator).map(|(_, count)| count).sum()
+    }
+
+    /// Returns the total number of disconnects.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns an iterator over the number of disconnects per initiator and reason.
+    pub fn iter(&self) -> impl Iterator<Item = (Initiator, Option<DisconnectReason>, u64)> + '_ {
+        self.counts.iter().map(|((initiator, reason), count)| (*initiator, *reason, *count))
+    }
+}
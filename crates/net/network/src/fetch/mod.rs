@@ -1,8 +1,12 @@
 //! Fetch data from the network.
 
 mod client;
+mod disconnects;
+mod multiplexer;
 
 pub use client::FetchClient;
+pub use disconnects::SyncDisconnectsSnapshot;
+pub use multiplexer::RequestMultiplexer;
 
 use std::{
     collections::{HashMap, VecDeque},
@@ -15,10 +19,12 @@ use std::{
 
 use alloy_primitives::B256;
 use futures::StreamExt;
-use reth_eth_wire::{GetBlockBodies, GetBlockHeaders};
+use reth_eth_wire::{
+    message::EthMessageID, DisconnectReason, GetBlockBodies, GetBlockHeaders, Initiator,
+};
 use reth_network_api::test_utils::PeersHandle;
 use reth_network_p2p::{
-    error::{EthResponseValidator, PeerRequestResult, RequestError, RequestResult},
+    error::{EthResponseValidator, PeerRequestResult, RequestError, RequestResult, SessionEnd},
     headers::client::HeadersRequest,
     priority::Priority,
 };
@@ -27,8 +33,10 @@ use reth_network_types::ReputationChangeKind;
 use reth_primitives::{BlockBody, Header};
 use tokio::sync::{mpsc, mpsc::UnboundedSender, oneshot};
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::debug;
 
-use crate::message::BlockRequest;
+use crate::{message::BlockRequest, session::RequestLatencies};
+use disconnects::SyncDisconnects;
 
 /// Manages data fetching operations.
 ///
@@ -56,6 +64,8 @@ pub struct StateFetcher {
     download_requests_rx: UnboundedReceiverStream<DownloadRequest>,
     /// Sender for download requests, used to detach a [`FetchClient`]
     download_requests_tx: UnboundedSender<DownloadRequest>,
+    /// Disconnects of the peers available for requests, shared with the [`FetchClient`]
+    disconnects: Arc<SyncDisconnects>,
 }
 
 // === impl StateSyncer ===
@@ -72,6 +82,7 @@ impl StateFetcher {
             queued_requests: Default::default(),
             download_requests_rx: UnboundedReceiverStream::new(download_requests_rx),
             download_requests_tx,
+            disconnects: Default::default(),
         }
     }
 
@@ -82,6 +93,7 @@ impl StateFetcher {
         best_hash: B256,
         best_number: u64,
         timeout: Arc<AtomicU64>,
+        latencies: Arc<RequestLatencies>,
     ) {
         self.peers.insert(
             peer_id,
@@ -90,6 +102,7 @@ impl StateFetcher {
                 best_hash,
                 best_number,
                 timeout,
+                latencies,
                 last_response_likely_bad: false,
             },
         );
@@ -100,14 +113,30 @@ impl StateFetcher {
     ///
     /// Invoked when an active session was closed.
     ///
-    /// This cancels also inflight request and sends an error to the receiver.
-    pub(crate) fn on_session_closed(&mut self, peer: &PeerId) {
+    /// This cancels also inflight request and sends an error to the receiver, which carries how
+    /// the session ended if that's known.
+    pub(crate) fn on_session_closed(&mut self, peer: &PeerId, end: Option<SessionEnd>) {
         self.peers.remove(peer);
+        let err = end.map_or(RequestError::ConnectionDropped, RequestError::Disconnected);
         if let Some(req) = self.inflight_headers_requests.remove(peer) {
-            let _ = req.response.send(Err(RequestError::ConnectionDropped));
+            let _ = req.response.send(Err(err.clone()));
         }
         if let Some(req) = self.inflight_bodies_requests.remove(peer) {
-            let _ = req.response.send(Err(RequestError::ConnectionDropped));
+            let _ = req.response.send(Err(err));
+        }
+    }
+
+    /// Records the disconnect of a peer that's available for requests.
+    ///
+    /// Invoked before [`Self::on_session_closed`] if it's known who closed the session and why.
+    pub(crate) fn on_peer_disconnected(
+        &self,
+        peer: &PeerId,
+        initiator: Initiator,
+        reason: Option<DisconnectReason>,
+    ) {
+        if self.peers.contains_key(peer) {
+            self.disconnects.record(initiator, reason);
         }
     }
 
@@ -132,10 +161,10 @@ impl StateFetcher {
         }
     }
 
-    /// Returns the _next_ idle peer that's ready to accept a request,
+    /// Returns the _next_ idle peer that's ready to accept a request of the given type,
     /// prioritizing those with the lowest timeout/latency and those that recently responded with
     /// adequate data.
-    fn next_best_peer(&self) -> Option<PeerId> {
+    fn next_best_peer(&self, request: EthMessageID) -> Option<PeerId> {
         let mut idle = self.peers.iter().filter(|(_, peer)| peer.state.is_idle());
 
         let mut best_peer = idle.next()?;
@@ -148,7 +177,7 @@ impl StateFetcher {
             }
 
             // replace best peer if this peer has better rtt
-            if maybe_better.1.timeout() < best_peer.1.timeout() &&
+            if maybe_better.1.is_faster_than(best_peer.1, request) &&
                 !maybe_better.1.last_response_likely_bad
             {
                 best_peer = maybe_better;
@@ -165,7 +194,10 @@ impl StateFetcher {
             return PollAction::NoRequests
         }
 
-        let Some(peer_id) = self.next_best_peer() else { return PollAction::NoPeersAvailable };
+        let request_type = self.queued_requests.front().expect("not empty").message_id();
+        let Some(peer_id) = self.next_best_peer(request_type) else {
+            return PollAction::NoPeersAvailable
+        };
 
         let request = self.queued_requests.pop_front().expect("not empty");
         let request = self.prepare_block_request(peer_id, request);
@@ -262,11 +294,29 @@ impl StateFetcher {
         peer_id: PeerId,
         res: RequestResult<Vec<Header>>,
     ) -> Option<BlockResponseOutcome> {
-        let is_error = res.is_err();
-        let maybe_reputation_change = res.reputation_change_err();
-
         let resp = self.inflight_headers_requests.remove(&peer_id);
 
+        // reject headers that don't satisfy the request, e.g. that start at another block
+        let mut is_invalid_response = false;
+        let res = match (res, &resp) {
+            (Ok(headers), Some(resp)) => match resp.request.validate_response(&headers) {
+                Ok(()) => Ok(headers),
+                Err(err) => {
+                    debug!(target: "net::fetch", ?peer_id, %err, "invalid headers response");
+                    is_invalid_response = true;
+                    Err(RequestError::BadResponse)
+                }
+            },
+            (res, _) => res,
+        };
+
+        let is_error = res.is_err();
+        let maybe_reputation_change = if is_invalid_response {
+            Some(ReputationChangeKind::BadResponse)
+        } else {
+            res.reputation_change_err()
+        };
+
         let is_likely_bad_response =
             resp.as_ref().is_some_and(|r| res.is_likely_bad_headers_response(&r.request));
 
@@ -320,6 +370,7 @@ impl StateFetcher {
             request_tx: self.download_requests_tx.clone(),
             peers_handle: self.peers_handle.clone(),
             num_active_peers: Arc::clone(&self.num_active_peers),
+            disconnects: Arc::clone(&self.disconnects),
         }
     }
 }
@@ -342,6 +393,8 @@ struct Peer {
     best_number: u64,
     /// Tracks the current timeout value we use for the peer.
     timeout: Arc<AtomicU64>,
+    /// The round-trip latencies of the requests sent to the peer, per request type.
+    latencies: Arc<RequestLatencies>,
     /// Tracks whether the peer has recently responded with a likely bad response.
     ///
     /// This is used to de-rank the peer if there are other peers available.
@@ -355,6 +408,18 @@ impl Peer {
     fn timeout(&self) -> u64 {
         self.timeout.load(Ordering::Relaxed)
     }
+
+    /// Returns `true` if this peer is expected to answer a request of the given type faster than
+    /// the other peer.
+    ///
+    /// This compares the latencies of previous requests of the same type if both peers answered
+    /// one, and the timeouts otherwise.
+    fn is_faster_than(&self, other: &Self, request: EthMessageID) -> bool {
+        match (self.latencies.ewma(request), other.latencies.ewma(request)) {
+            (Some(latency), Some(other_latency)) => latency < other_latency,
+            _ => self.timeout() < other.timeout(),
+        }
+    }
 }
 
 /// Tracks the state of an individual peer
@@ -431,6 +496,14 @@ impl DownloadRequest {
         }
     }
 
+    /// Returns the id of the eth message of this request.
+    const fn message_id(&self) -> EthMessageID {
+        match self {
+            Self::GetBlockHeaders { .. } => EthMessageID::GetBlockHeaders,
+            Self::GetBlockBodies { .. } => EthMessageID::GetBlockBodies,
+        }
+    }
+
     /// Returns the requested priority of this request
     const fn get_priority(&self) -> &Priority {
         match self {
@@ -474,7 +547,7 @@ mod tests {
     use crate::{peers::PeersManager, PeersConfig};
     use alloy_primitives::B512;
     use reth_primitives::SealedHeader;
-    use std::future::poll_fn;
+    use std::{future::poll_fn, time::Duration};
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_poll_fetcher() {
@@ -503,20 +576,119 @@ mod tests {
         // Add a few random peers
         let peer1 = B512::random();
         let peer2 = B512::random();
-        fetcher.new_active_peer(peer1, B256::random(), 1, Arc::new(AtomicU64::new(1)));
-        fetcher.new_active_peer(peer2, B256::random(), 2, Arc::new(AtomicU64::new(1)));
+        fetcher.new_active_peer(
+            peer1,
+            B256::random(),
+            1,
+            Arc::new(AtomicU64::new(1)),
+            Default::default(),
+        );
+        fetcher.new_active_peer(
+            peer2,
+            B256::random(),
+            2,
+            Arc::new(AtomicU64::new(1)),
+            Default::default(),
+        );
 
-        let first_peer = fetcher.next_best_peer().unwrap();
+        let first_peer = fetcher.next_best_peer(EthMessageID::GetBlockBodies).unwrap();
         assert!(first_peer == peer1 || first_peer == peer2);
         // Pending disconnect for first_peer
         fetcher.on_pending_disconnect(&first_peer);
         // first_peer now isn't idle, so we should get other peer
-        let second_peer = fetcher.next_best_peer().unwrap();
+        let second_peer = fetcher.next_best_peer(EthMessageID::GetBlockBodies).unwrap();
         assert!(first_peer == peer1 || first_peer == peer2);
         assert_ne!(first_peer, second_peer);
         // without idle peers, returns None
         fetcher.on_pending_disconnect(&second_peer);
-        assert_eq!(fetcher.next_best_peer(), None);
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), None);
+    }
+
+    #[tokio::test]
+    async fn test_peer_prioritization_by_latency() {
+        let manager = PeersManager::new(PeersConfig::default());
+        let mut fetcher = StateFetcher::new(manager.handle(), Default::default());
+        let fast_headers = B512::random();
+        let slow_headers = B512::random();
+        let fast_headers_latencies = Arc::new(RequestLatencies::default());
+        let slow_headers_latencies = Arc::new(RequestLatencies::default());
+        // the peer with the fast headers responses has the higher timeout
+        fetcher.new_active_peer(
+            fast_headers,
+            B256::random(),
+            1,
+            Arc::new(AtomicU64::new(50)),
+            Arc::clone(&fast_headers_latencies),
+        );
+        fetcher.new_active_peer(
+            slow_headers,
+            B256::random(),
+            1,
+            Arc::new(AtomicU64::new(10)),
+            Arc::clone(&slow_headers_latencies),
+        );
+
+        for _ in 0..5 {
+            fast_headers_latencies.record(EthMessageID::GetBlockHeaders, Duration::from_millis(20));
+            slow_headers_latencies
+                .record(EthMessageID::GetBlockHeaders, Duration::from_millis(300));
+        }
+        slow_headers_latencies.record(EthMessageID::GetBlockBodies, Duration::from_millis(10));
+
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockHeaders), Some(fast_headers));
+        // without body latencies of both peers, the timeout decides
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), Some(slow_headers));
+    }
+
+    #[tokio::test]
+    async fn test_sync_peer_disconnects() {
+        let manager = PeersManager::new(PeersConfig::default());
+        let mut fetcher = StateFetcher::new(manager.handle(), Default::default());
+        let client = fetcher.client();
+
+        let peers: Vec<_> = (0..6).map(|_| B512::random()).collect();
+        for peer in &peers {
+            fetcher.new_active_peer(
+                *peer,
+                B256::random(),
+                1,
+                Arc::new(AtomicU64::new(1)),
+                Default::default(),
+            );
+        }
+
+        let disconnects = [
+            (Initiator::Remote, Some(DisconnectReason::UselessPeer)),
+            (Initiator::Remote, Some(DisconnectReason::UselessPeer)),
+            (Initiator::Remote, Some(DisconnectReason::TooManyPeers)),
+            (Initiator::Remote, None),
+            (Initiator::Local, Some(DisconnectReason::DisconnectRequested)),
+            (Initiator::Local, Some(DisconnectReason::DisconnectRequested)),
+        ];
+        for (peer, (initiator, reason)) in peers.iter().zip(disconnects) {
+            fetcher.on_peer_disconnected(peer, initiator, reason);
+            fetcher.on_session_closed(peer, None);
+        }
+        // peers that are no longer available for requests are not counted again
+        fetcher.on_peer_disconnected(&peers[0], Initiator::Remote, None);
+
+        let snapshot = client.sync_disconnects();
+        assert_eq!(snapshot.total(), 6);
+        assert_eq!(snapshot.total_by(Initiator::Remote), 4);
+        assert_eq!(snapshot.total_by(Initiator::Local), 2);
+        assert_eq!(snapshot.count(Initiator::Remote, Some(DisconnectReason::UselessPeer)), 2);
+        assert_eq!(snapshot.count(Initiator::Remote, Some(DisconnectReason::TooManyPeers)), 1);
+        assert_eq!(snapshot.count(Initiator::Remote, None), 1);
+        assert_eq!(
+            snapshot.count(Initiator::Local, Some(DisconnectReason::DisconnectRequested)),
+            2
+        );
+        assert_eq!(snapshot.count(Initiator::Local, Some(DisconnectReason::UselessPeer)), 0);
+        assert_eq!(snapshot.iter().count(), 4);
+
+        // resetting starts a new sync session
+        assert_eq!(client.reset_sync_disconnects(), snapshot);
+        assert_eq!(client.sync_disconnects(), SyncDisconnectsSnapshot::default());
     }
 
     #[tokio::test]
@@ -530,18 +702,36 @@ mod tests {
 
         let peer2_timeout = Arc::new(AtomicU64::new(300));
 
-        fetcher.new_active_peer(peer1, B256::random(), 1, Arc::new(AtomicU64::new(30)));
-        fetcher.new_active_peer(peer2, B256::random(), 2, Arc::clone(&peer2_timeout));
-        fetcher.new_active_peer(peer3, B256::random(), 3, Arc::new(AtomicU64::new(50)));
+        fetcher.new_active_peer(
+            peer1,
+            B256::random(),
+            1,
+            Arc::new(AtomicU64::new(30)),
+            Default::default(),
+        );
+        fetcher.new_active_peer(
+            peer2,
+            B256::random(),
+            2,
+            Arc::clone(&peer2_timeout),
+            Default::default(),
+        );
+        fetcher.new_active_peer(
+            peer3,
+            B256::random(),
+            3,
+            Arc::new(AtomicU64::new(50)),
+            Default::default(),
+        );
 
         // Must always get peer1 (lowest timeout)
-        assert_eq!(fetcher.next_best_peer(), Some(peer1));
-        assert_eq!(fetcher.next_best_peer(), Some(peer1));
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), Some(peer1));
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), Some(peer1));
         // peer2's timeout changes below peer1's
         peer2_timeout.store(10, Ordering::Relaxed);
         // Then we get peer 2 always (now lowest)
-        assert_eq!(fetcher.next_best_peer(), Some(peer2));
-        assert_eq!(fetcher.next_best_peer(), Some(peer2));
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), Some(peer2));
+        assert_eq!(fetcher.next_best_peer(EthMessageID::GetBlockBodies), Some(peer2));
     }
 
     #[tokio::test]
@@ -600,6 +790,7 @@ mod tests {
             Default::default(),
             Default::default(),
             Default::default(),
+            Default::default(),
         );
 
         let (req, header) = request_pair();
@@ -625,4 +816,91 @@ mod tests {
 
         assert!(fetcher.peers[&peer_id].state.is_idle());
     }
+
+    #[tokio::test]
+    async fn test_header_response_validation() {
+        let manager = PeersManager::new(PeersConfig::default());
+        let mut fetcher = StateFetcher::new(manager.handle(), Default::default());
+        let peer_id = B512::random();
+        fetcher.new_active_peer(
+            peer_id,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        let headers = |numbers: &[u64]| {
+            numbers
+                .iter()
+                .map(|&number| Header { number, ..Default::default() })
+                .collect::<Vec<_>>()
+        };
+        let request =
+            HeadersRequest { start: 10u64.into(), limit: 3, direction: Default::default() };
+
+        let mut respond = |headers: Vec<Header>| {
+            let (tx, mut rx) = oneshot::channel();
+            fetcher
+                .inflight_headers_requests
+                .insert(peer_id, Request { request: request.clone(), response: tx });
+            let outcome = fetcher.on_block_headers_response(peer_id, Ok(headers));
+            (outcome, rx.try_recv().unwrap())
+        };
+
+        // a correct response is delegated
+        let (outcome, res) = respond(headers(&[10, 11, 12]));
+        assert_eq!(outcome, None);
+        assert_eq!(res.unwrap().into_data(), headers(&[10, 11, 12]));
+
+        // a response that starts at another block is rejected and the peer is reported
+        let (outcome, res) = respond(headers(&[11, 12, 13]));
+        assert_eq!(
+            outcome,
+            Some(BlockResponseOutcome::BadResponse(peer_id, ReputationChangeKind::BadResponse))
+        );
+        assert_eq!(res.unwrap_err(), RequestError::BadResponse);
+
+        // a response in the wrong direction is rejected and the peer is reported
+        let (outcome, res) = respond(headers(&[10, 9, 8]));
+        assert_eq!(
+            outcome,
+            Some(BlockResponseOutcome::BadResponse(peer_id, ReputationChangeKind::BadResponse))
+        );
+        assert_eq!(res.unwrap_err(), RequestError::BadResponse);
+    }
+
+    #[tokio::test]
+    async fn test_session_end_fails_inflight_requests() {
+        let manager = PeersManager::new(PeersConfig::default());
+        let mut fetcher = StateFetcher::new(manager.handle(), Default::default());
+        let peer_id = B512::random();
+        fetcher.new_active_peer(
+            peer_id,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        let (headers_tx, headers_rx) = oneshot::channel();
+        let headers =
+            HeadersRequest { start: 0u64.into(), limit: 1, direction: Default::default() };
+        fetcher
+            .inflight_headers_requests
+            .insert(peer_id, Request { request: headers, response: headers_tx });
+        let (bodies_tx, bodies_rx) = oneshot::channel();
+        fetcher
+            .inflight_bodies_requests
+            .insert(peer_id, Request { request: vec![B256::random()], response: bodies_tx });
+
+        let end = SessionEnd::remote(DisconnectReason::UselessPeer);
+        fetcher.on_session_closed(&peer_id, Some(end));
+
+        let err = headers_rx.await.unwrap().unwrap_err();
+        assert_eq!(err, RequestError::Disconnected(end));
+        assert_eq!(err.as_session_end().unwrap().initiator, Initiator::Remote);
+        assert_eq!(bodies_rx.await.unwrap().unwrap_err(), RequestError::Disconnected(end));
+        assert!(!fetcher.peers.contains_key(&peer_id));
+    }
 }
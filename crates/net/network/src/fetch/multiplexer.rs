@@ -0,0 +1,517 @@
+//! Load balancing of eth requests across a pool of peer sessions.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use reth_eth_wire::{BlockBodies, BlockHeaders, GetBlockBodies, GetBlockHeaders};
+use reth_network_api::{PeerRequest, PeerRequestSender};
+use reth_network_p2p::error::{PeerRequestResult, RequestError, RequestResult};
+use reth_network_peers::{PeerId, WithPeerId};
+use tokio::sync::{mpsc::error::TrySendError, oneshot};
+
+/// Default time to wait for the response of a peer before trying another peer.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of peers a request is sent to before giving up.
+const DEFAULT_MAX_ATTEMPTS: usize = 3;
+
+/// Default number of consecutive timeouts after which a peer is deprioritized.
+const DEFAULT_MAX_CONSECUTIVE_TIMEOUTS: u32 = 3;
+
+/// Default duration a peer is deprioritized for.
+const DEFAULT_DEPRIORITIZATION: Duration = Duration::from_secs(30);
+
+/// Weight of the latest outcome in a peer's timeout rate.
+const TIMEOUT_RATE_WEIGHT: f64 = 0.25;
+
+/// Routes eth requests across a pool of peer sessions.
+///
+/// Every request is sent to the least loaded healthy peer: the peer with the fewest inflight
+/// requests through this multiplexer, then the lowest recent timeout rate. Among equally good peers
+/// the least recently used one is picked, so that requests are spread evenly.
+///
+/// If a peer fails to respond, the request is retried on another peer, up to
+/// [`RequestMultiplexer::with_max_attempts`] peers. Peers that time out repeatedly are
+/// deprioritized for a while and are only used if no other peer is available.
+///
+/// The multiplexer is cheap to clone, clones share the same pool.
+#[derive(Debug, Clone)]
+pub struct RequestMultiplexer {
+    /// The pool of peers, shared between clones.
+    inner: Arc<Mutex<MultiplexerInner>>,
+    /// Time to wait for the response of a peer.
+    request_timeout: Duration,
+    /// Number of peers a request is sent to before giving up.
+    max_attempts: usize,
+    /// Number of consecutive timeouts after which a peer is deprioritized.
+    max_consecutive_timeouts: u32,
+    /// Duration a peer is deprioritized for.
+    deprioritization: Duration,
+}
+
+// === impl RequestMultiplexer ===
+
+impl RequestMultiplexer {
+    /// Creates a new multiplexer with an empty pool.
+    pub fn new() -> Self {
+        Self {
+            inner: Default::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            max_consecutive_timeouts: DEFAULT_MAX_CONSECUTIVE_TIMEOUTS,
+            deprioritization: DEFAULT_DEPRIORITIZATION,
+        }
+    }
+
+    /// Sets the time to wait for the response of a peer before trying another peer.
+    pub const fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets the number of peers a request is sent to before giving up.
+    pub const fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Sets the number of consecutive timeouts after which a peer is deprioritized, and for how
+    /// long.
+    pub const fn with_deprioritization(
+        mut self,
+        max_consecutive_timeouts: u32,
+        deprioritization: Duration,
+    ) -> Self {
+        self.max_consecutive_timeouts = max_consecutive_timeouts;
+        self.deprioritization = deprioritization;
+        self
+    }
+
+    /// Adds the session of a peer to the pool.
+    ///
+    /// If the peer is already in the pool, its session is replaced.
+    pub fn add_peer(&self, sender: PeerRequestSender) {
+        let mut inner = self.inner.lock();
+        let last_used = inner.next_use();
+        inner.peers.insert(sender.peer_id, MultiplexedPeer::new(sender, last_used));
+    }
+
+    /// Removes the session of a peer from the pool.
+    pub fn remove_peer(&self, peer_id: &PeerId) {
+        self.inner.lock().peers.remove(peer_id);
+    }
+
+    /// Returns the number of peers in the pool.
+    pub fn num_peers(&self) -> usize {
+        self.inner.lock().peers.len()
+    }
+
+    /// Returns the number of requests currently inflight on the given peer.
+    pub fn inflight_requests(&self, peer_id: &PeerId) -> Option<usize> {
+        self.inner.lock().peers.get(peer_id).map(|peer| peer.inflight)
+    }
+
+    /// Returns `true` if the given peer is currently deprioritized because it timed out
+    /// repeatedly.
+    pub fn is_deprioritized(&self, peer_id: &PeerId) -> bool {
+        self.inner
+            .lock()
+            .peers
+            .get(peer_id)
+            .is_some_and(|peer| peer.is_deprioritized(Instant::now()))
+    }
+
+    /// Sends a `GetBlockHeaders` request to the least loaded peer.
+    pub async fn get_block_headers(
+        &self,
+        request: GetBlockHeaders,
+    ) -> PeerRequestResult<BlockHeaders> {
+        self.send_request(|response| PeerRequest::GetBlockHeaders { request, response }).await
+    }
+
+    /// Sends a `GetBlockBodies` request to the least loaded peer.
+    pub async fn get_block_bodies(
+        &self,
+        request: GetBlockBodies,
+    ) -> PeerRequestResult<BlockBodies> {
+        self.send_request(|response| PeerRequest::GetBlockBodies {
+            request: request.clone(),
+            response,
+        })
+        .await
+    }
+
+    /// Sends the request created by `make_request` to the least loaded peer, and to other peers
+    /// if it fails.
+    ///
+    /// `make_request` is called once per attempt.
+    pub async fn send_request<T>(
+        &self,
+        make_request: impl Fn(oneshot::Sender<RequestResult<T>>) -> PeerRequest,
+    ) -> PeerRequestResult<T> {
+        let mut tried = HashSet::new();
+        let mut last_err = RequestError::ChannelClosed;
+
+        while tried.len() < self.max_attempts {
+            let Some(sender) = self.inner.lock().acquire(&tried) else { break };
+            let peer_id = sender.peer_id;
+            tried.insert(peer_id);
+            let inflight = InflightGuard { inner: &self.inner, peer_id };
+
+            let (tx, rx) = oneshot::channel();
+            match sender.try_send(make_request(tx)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    last_err = RequestError::ChannelFull;
+                    continue
+                }
+                Err(TrySendError::Closed(_)) => {
+                    self.remove_peer(&peer_id);
+                    last_err = RequestError::ChannelClosed;
+                    continue
+                }
+            }
+
+            let outcome = match tokio::time::timeout(self.request_timeout, rx).await {
+                Ok(Ok(res)) => res,
+                Ok(Err(_)) => Err(RequestError::ConnectionDropped),
+                Err(_) => Err(RequestError::Timeout),
+            };
+
+            drop(inflight);
+            let mut inner = self.inner.lock();
+            match outcome {
+                Ok(response) => {
+                    inner.on_response(&peer_id);
+                    return Ok(WithPeerId::new(peer_id, response))
+                }
+                Err(RequestError::Timeout) => {
+                    inner.on_timeout(
+                        &peer_id,
+                        self.max_consecutive_timeouts,
+                        Instant::now() + self.deprioritization,
+                    );
+                    last_err = RequestError::Timeout;
+                }
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+impl Default for RequestMultiplexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The pool of peers of a [`RequestMultiplexer`].
+#[derive(Debug, Default)]
+struct MultiplexerInner {
+    /// All peers requests can be routed to.
+    peers: HashMap<PeerId, MultiplexedPeer>,
+    /// Monotonic counter used to order peers by their last use.
+    uses: u64,
+}
+
+impl MultiplexerInner {
+    /// Returns the next value of the use counter.
+    fn next_use(&mut self) -> u64 {
+        self.uses += 1;
+        self.uses
+    }
+
+    /// Selects the least loaded peer that's not in `exclude` and marks a request inflight on it.
+    ///
+    /// Peers with a closed session are removed from the pool.
+    fn acquire(&mut self, exclude: &HashSet<PeerId>) -> Option<PeerRequestSender> {
+        self.peers.retain(|_, peer| !peer.sender.to_session_tx.is_closed());
+
+        let now = Instant::now();
+        let peer_id = self
+            .peers
+            .iter()
+            .filter(|(peer_id, _)| !exclude.contains(*peer_id))
+            .min_by(|(_, a), (_, b)| {
+                a.is_deprioritized(now)
+                    .cmp(&b.is_deprioritized(now))
+                    .then(a.inflight.cmp(&b.inflight))
+                    .then(a.timeout_rate.total_cmp(&b.timeout_rate))
+                    .then(a.last_used.cmp(&b.last_used))
+            })
+            .map(|(peer_id, _)| *peer_id)?;
+
+        let last_used = self.next_use();
+        let peer = self.peers.get_mut(&peer_id)?;
+        peer.inflight += 1;
+        peer.last_used = last_used;
+        Some(peer.sender.clone())
+    }
+
+    /// Marks a request of the peer as no longer inflight.
+    fn release(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.inflight = peer.inflight.saturating_sub(1);
+        }
+    }
+
+    /// Records that the peer responded.
+    fn on_response(&mut self, peer_id: &PeerId) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.consecutive_timeouts = 0;
+            peer.deprioritized_until = None;
+            peer.update_timeout_rate(false);
+        }
+    }
+
+    /// Records that the peer timed out, and deprioritizes it until `until` if it timed out too
+    /// often in a row.
+    fn on_timeout(&mut self, peer_id: &PeerId, max_consecutive_timeouts: u32, until: Instant) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.consecutive_timeouts += 1;
+            peer.update_timeout_rate(true);
+            if peer.consecutive_timeouts >= max_consecutive_timeouts {
+                peer.consecutive_timeouts = 0;
+                peer.deprioritized_until = Some(until);
+            }
+        }
+    }
+}
+
+/// Marks a request as inflight on a peer until it's dropped.
+///
+/// This releases the request on every path, including when the
+/// [`RequestMultiplexer::send_request`] future is dropped while it waits for the response.
+struct InflightGuard<'a> {
+    inner: &'a Mutex<MultiplexerInner>,
+    peer_id: PeerId,
+}
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        self.inner.lock().release(&self.peer_id);
+    }
+}
+
+/// A peer in the pool of a [`RequestMultiplexer`].
+#[derive(Debug)]
+struct MultiplexedPeer {
+    /// The session of the peer.
+    sender: PeerRequestSender,
+    /// Number of requests currently inflight.
+    inflight: usize,
+    /// Moving average of the share of requests that timed out.
+    timeout_rate: f64,
+    /// Number of timeouts since the last response.
+    consecutive_timeouts: u32,
+    /// Set if the peer timed out repeatedly.
+    deprioritized_until: Option<Instant>,
+    /// Value of the use counter when the peer was last selected.
+    last_used: u64,
+}
+
+impl MultiplexedPeer {
+    const fn new(sender: PeerRequestSender, last_used: u64) -> Self {
+        Self {
+            sender,
+            inflight: 0,
+            timeout_rate: 0.0,
+            consecutive_timeouts: 0,
+            deprioritized_until: None,
+            last_used,
+        }
+    }
+
+    fn is_deprioritized(&self, now: Instant) -> bool {
+        self.deprioritized_until.is_some_and(|until| until > now)
+    }
+
+    fn update_timeout_rate(&mut self, timed_out: bool) {
+        let outcome = if timed_out { 1.0 } else { 0.0 };
+        self.timeout_rate += TIMEOUT_RATE_WEIGHT * (outcome - self.timeout_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B512;
+    use reth_eth_wire::HeadersDirection;
+    use tokio::sync::mpsc;
+
+    /// Spawns a session that responds to every headers request, and returns the peer id and the
+    /// number of served requests.
+    fn responsive_peer(multiplexer: &RequestMultiplexer) -> (PeerId, Arc<Mutex<usize>>) {
+        let peer_id = B512::random();
+        let (tx, mut rx) = mpsc::channel(8);
+        multiplexer.add_peer(PeerRequestSender::new(peer_id, tx));
+        let served = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&served);
+        tokio::spawn(async move {
+            while let Some(request) = rx.recv().await {
+                if let PeerRequest::GetBlockHeaders { response, .. } = request {
+                    *counter.lock() += 1;
+                    let _ = response.send(Ok(BlockHeaders::default()));
+                }
+            }
+        });
+        (peer_id, served)
+    }
+
+    /// Adds a session that accepts requests but never responds.
+    fn unresponsive_peer(
+        multiplexer: &RequestMultiplexer,
+    ) -> (PeerId, mpsc::Receiver<PeerRequest>) {
+        let peer_id = B512::random();
+        let (tx, rx) = mpsc::channel(8);
+        multiplexer.add_peer(PeerRequestSender::new(peer_id, tx));
+        (peer_id, rx)
+    }
+
+    fn headers_request() -> GetBlockHeaders {
+        GetBlockHeaders {
+            start_block: 0u64.into(),
+            limit: 1,
+            skip: 0,
+            direction: HeadersDirection::Rising,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_distribution_under_equal_load() {
+        let multiplexer = RequestMultiplexer::new();
+        let peers: Vec<_> = (0..3).map(|_| responsive_peer(&multiplexer)).collect();
+
+        let mut order = Vec::new();
+        for _ in 0..9 {
+            let response = multiplexer.get_block_headers(headers_request()).await.unwrap();
+            order.push(response.peer_id());
+        }
+
+        // peers are used in turns
+        let first_round = &order[..3];
+        assert_eq!(first_round, &peers.iter().map(|(peer_id, _)| *peer_id).collect::<Vec<_>>());
+        assert_eq!(&order[3..6], first_round);
+        assert_eq!(&order[6..], first_round);
+        for (peer_id, served) in &peers {
+            assert_eq!(*served.lock(), 3);
+            assert_eq!(multiplexer.inflight_requests(peer_id), Some(0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_spread() {
+        let multiplexer = RequestMultiplexer::new();
+        let (_, mut rx1) = unresponsive_peer(&multiplexer);
+        let (_, mut rx2) = unresponsive_peer(&multiplexer);
+
+        let requests: Vec<_> = (0..4)
+            .map(|_| {
+                let multiplexer = multiplexer.clone();
+                tokio::spawn(async move { multiplexer.get_block_headers(headers_request()).await })
+            })
+            .collect();
+
+        // each peer receives half of the concurrent requests
+        for rx in [&mut rx1, &mut rx2] {
+            for _ in 0..2 {
+                let PeerRequest::GetBlockHeaders { response, .. } = rx.recv().await.unwrap() else {
+                    unreachable!()
+                };
+                response.send(Ok(BlockHeaders::default())).unwrap();
+            }
+        }
+        for request in requests {
+            request.await.unwrap().unwrap();
+        }
+        assert!(rx1.try_recv().is_err());
+        assert!(rx2.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_timeout() {
+        let multiplexer = RequestMultiplexer::new()
+            .with_request_timeout(Duration::from_millis(50))
+            .with_deprioritization(1, Duration::from_secs(60));
+        // added first, so it's selected first
+        let (slow, _rx) = unresponsive_peer(&multiplexer);
+        let (fast, served) = responsive_peer(&multiplexer);
+
+        let response = multiplexer.get_block_headers(headers_request()).await.unwrap();
+        assert_eq!(response.peer_id(), fast);
+        assert!(multiplexer.is_deprioritized(&slow));
+        assert!(!multiplexer.is_deprioritized(&fast));
+
+        // the slow peer was used least recently, but it's deprioritized
+        let response = multiplexer.get_block_headers(headers_request()).await.unwrap();
+        assert_eq!(response.peer_id(), fast);
+        assert_eq!(*served.lock(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_closed_session() {
+        let multiplexer = RequestMultiplexer::new();
+        let (closed, rx) = unresponsive_peer(&multiplexer);
+        let (open, _) = responsive_peer(&multiplexer);
+        drop(rx);
+
+        let response = multiplexer.get_block_headers(headers_request()).await.unwrap();
+        assert_eq!(response.peer_id(), open);
+        assert_eq!(multiplexer.num_peers(), 1);
+        assert_eq!(multiplexer.inflight_requests(&closed), None);
+    }
+
+    #[tokio::test]
+    async fn test_full_session_is_reported_as_busy() {
+        let multiplexer = RequestMultiplexer::new();
+        let peer_id = B512::random();
+        let (tx, _rx) = mpsc::channel(1);
+        let (response, _) = oneshot::channel();
+        tx.try_send(PeerRequest::GetBlockHeaders { request: headers_request(), response }).unwrap();
+        multiplexer.add_peer(PeerRequestSender::new(peer_id, tx));
+
+        let err = multiplexer.get_block_headers(headers_request()).await.unwrap_err();
+        assert_eq!(err, RequestError::ChannelFull);
+        assert!(err.is_retryable());
+        // the session is still alive
+        assert_eq!(multiplexer.num_peers(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_is_released() {
+        let multiplexer = RequestMultiplexer::new();
+        let (peer_id, mut rx) = unresponsive_peer(&multiplexer);
+
+        let request = {
+            let multiplexer = multiplexer.clone();
+            tokio::spawn(async move { multiplexer.get_block_headers(headers_request()).await })
+        };
+        let _request = rx.recv().await.unwrap();
+        assert_eq!(multiplexer.inflight_requests(&peer_id), Some(1));
+
+        // dropping the pending request doesn't leak the inflight request
+        request.abort();
+        assert!(request.await.unwrap_err().is_cancelled());
+        assert_eq!(multiplexer.inflight_requests(&peer_id), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let multiplexer = RequestMultiplexer::new()
+            .with_request_timeout(Duration::from_millis(20))
+            .with_max_attempts(2);
+        let _peers: Vec<_> = (0..3).map(|_| unresponsive_peer(&multiplexer)).collect();
+
+        let err = multiplexer.get_block_headers(headers_request()).await.unwrap_err();
+        assert_eq!(err, RequestError::Timeout);
+
+        let err = RequestMultiplexer::new().get_block_headers(headers_request()).await.unwrap_err();
+        assert_eq!(err, RequestError::ChannelClosed);
+    }
+}
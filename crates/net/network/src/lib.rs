@@ -143,20 +143,21 @@ pub use reth_network_api::{
     NetworkEventListenerProvider, NetworkInfo, PeerRequest, PeerRequestSender, Peers, PeersInfo,
 };
 pub use reth_network_p2p::sync::{NetworkSyncUpdater, SyncState};
-pub use reth_network_types::{PeersConfig, SessionsConfig};
+pub use reth_network_types::{PeersConfig, ServingPolicy, SessionsConfig};
 pub use session::{
-    ActiveSessionHandle, ActiveSessionMessage, Direction, EthRlpxConnection, PeerInfo,
-    PendingSessionEvent, PendingSessionHandle, PendingSessionHandshakeError, SessionCommand,
-    SessionEvent, SessionId, SessionManager,
+    ActiveSessionHandle, ActiveSessionMessage, DialError, Direction, EthRlpxConnection,
+    HandshakeTiming, LatencyHistogram, PeerInfo, PendingSessionEvent, PendingSessionHandle,
+    PendingSessionHandshakeError, RequestLatencies, SessionCommand, SessionEvent, SessionId,
+    SessionManager, SessionRegistry,
 };
 
 pub use builder::NetworkBuilder;
 pub use config::{NetworkConfig, NetworkConfigBuilder};
-pub use discovery::Discovery;
-pub use fetch::FetchClient;
+pub use discovery::{Discovery, DiscoveryBackend, DiscoveryBackendUpdate, StaticDiscovery};
+pub use fetch::{FetchClient, RequestMultiplexer, SyncDisconnectsSnapshot};
 pub use flattened_response::FlattenedResponse;
 pub use manager::NetworkManager;
-pub use metrics::TxTypesCounter;
+pub use metrics::{SessionLifetimeStats, TxTypesCounter};
 pub use network::{NetworkHandle, NetworkProtocols};
 pub use swarm::NetworkConnectionState;
 pub use transactions::{FilterAnnouncement, MessageFilter, ValidateTx68};
@@ -29,11 +29,12 @@ use std::{
 
 use futures::{Future, StreamExt};
 use parking_lot::Mutex;
-use reth_eth_wire::{capability::CapabilityMessage, Capabilities, DisconnectReason};
+use reth_eth_wire::{capability::CapabilityMessage, Capabilities, DisconnectReason, Initiator};
 use reth_fs_util::{self as fs, FsPathError};
 use reth_metrics::common::mpsc::UnboundedMeteredSender;
 use reth_network_api::{
-    test_utils::PeersHandle, EthProtocolInfo, NetworkEvent, NetworkStatus, PeerInfo, PeerRequest,
+    test_utils::PeersHandle, EthProtocolInfo, NetworkEvent, NetworkStatus, PeerEvent, PeerInfo,
+    PeerRequest,
 };
 use reth_network_peers::{NodeRecord, PeerId};
 use reth_network_types::ReputationChangeKind;
@@ -87,6 +88,8 @@ pub struct NetworkManager {
     block_import: Box<dyn BlockImport>,
     /// Sender for high level network events.
     event_sender: EventSender<NetworkEvent>,
+    /// Sender for peer set changes.
+    peer_event_sender: EventSender<PeerEvent>,
     /// Sender half to send events to the
     /// [`TransactionsManager`](crate::transactions::TransactionsManager) task, if configured.
     to_transactions_manager: Option<UnboundedMeteredSender<NetworkTransactionEvent>>,
@@ -173,6 +176,7 @@ impl NetworkManager {
             discovery_v4_addr,
             mut discovery_v4_config,
             mut discovery_v5_config,
+            discovery_backends,
             listener_addr,
             peers_config,
             sessions_config,
@@ -186,6 +190,7 @@ impl NetworkManager {
             fork_filter,
             dns_discovery_config,
             extra_protocols,
+            subprotocol_disconnect_reasons,
             tx_gossip_disabled,
             transactions_manager_config: _,
         } = config;
@@ -215,7 +220,7 @@ impl NetworkManager {
             discv5.extend_unsigned_boot_nodes(resolved_boot_nodes)
         }
 
-        let discovery = Discovery::new(
+        let mut discovery = Discovery::new(
             listener_addr,
             discovery_v4_addr,
             secret_key,
@@ -224,6 +229,9 @@ impl NetworkManager {
             dns_discovery_config,
         )
         .await?;
+        for backend in discovery_backends {
+            discovery.add_backend(backend);
+        }
         // need to retrieve the addr here since provided port could be `0`
         let local_peer_id = discovery.local_id();
         let discv4 = discovery.discv4();
@@ -231,7 +239,7 @@ impl NetworkManager {
 
         let num_active_peers = Arc::new(AtomicUsize::new(0));
 
-        let sessions = SessionManager::new(
+        let mut sessions = SessionManager::new(
             secret_key,
             sessions_config,
             executor,
@@ -240,6 +248,7 @@ impl NetworkManager {
             fork_filter,
             extra_protocols,
         );
+        sessions.set_subprotocol_disconnect_reasons(subprotocol_disconnect_reasons);
 
         let state = NetworkState::new(
             crate::state::BlockNumReader::new(client),
@@ -253,6 +262,7 @@ impl NetworkManager {
         let (to_manager_tx, from_handle_rx) = mpsc::unbounded_channel();
 
         let event_sender: EventSender<NetworkEvent> = Default::default();
+        let peer_event_sender: EventSender<PeerEvent> = Default::default();
 
         let handle = NetworkHandle::new(
             Arc::clone(&num_active_peers),
@@ -267,6 +277,7 @@ impl NetworkManager {
             discv4,
             discv5,
             event_sender.clone(),
+            peer_event_sender.clone(),
         );
 
         Ok(Self {
@@ -275,6 +286,7 @@ impl NetworkManager {
             from_handle_rx: UnboundedReceiverStream::new(from_handle_rx),
             block_import,
             event_sender,
+            peer_event_sender,
             to_transactions_manager: None,
             to_eth_request_handler: None,
             num_active_peers,
@@ -585,8 +597,11 @@ impl NetworkManager {
             NetworkHandleMessage::RemovePeer(peer_id, kind) => {
                 self.swarm.state_mut().remove_peer_kind(peer_id, kind);
             }
-            NetworkHandleMessage::DisconnectPeer(peer_id, reason) => {
-                self.swarm.sessions_mut().disconnect(peer_id, reason);
+            NetworkHandleMessage::DisconnectPeer(peer_id, reason, tx) => {
+                let connected = self.swarm.sessions_mut().disconnect(peer_id, reason);
+                if let Some(tx) = tx {
+                    let _ = tx.send(connected);
+                }
             }
             NetworkHandleMessage::ConnectPeer(peer_id, kind, addr) => {
                 self.swarm.state_mut().add_and_connect(peer_id, kind, addr);
@@ -617,6 +632,7 @@ impl NetworkManager {
             }
             NetworkHandleMessage::StatusUpdate { head } => {
                 if let Some(transition) = self.swarm.sessions_mut().on_status_update(head) {
+                    debug!(target: "net", ?transition, "fork activated, updating fork id");
                     self.swarm.state_mut().update_fork_id(transition.current);
                 }
             }
@@ -633,6 +649,18 @@ impl NetworkManager {
                 let peer_ids = self.swarm.state().peers().peers_by_kind(kind);
                 let _ = tx.send(self.get_peer_infos_by_ids(peer_ids));
             }
+            NetworkHandleMessage::GetSharedCapabilities(peer_id, tx) => {
+                let shared_capabilities = self
+                    .swarm
+                    .sessions()
+                    .active_sessions()
+                    .get(&peer_id)
+                    .map(|session| Arc::clone(&session.shared_capabilities));
+                let _ = tx.send(shared_capabilities);
+            }
+            NetworkHandleMessage::GetPeersWithCapability(cap, tx) => {
+                let _ = tx.send(self.swarm.sessions().peers_with_capability(&cap));
+            }
             NetworkHandleMessage::AddRlpxSubProtocol(proto) => self.add_rlpx_sub_protocol(proto),
             NetworkHandleMessage::GetTransactionsHandle(tx) => {
                 if let Some(ref tx_inner) = self.to_transactions_manager {
@@ -706,6 +734,10 @@ impl NetworkManager {
 
                 self.update_active_connection_metrics();
 
+                if let Some(info) = self.get_peer_info_by_id(peer_id) {
+                    self.peer_event_sender.notify(PeerEvent::SessionEstablished(Box::new(info)));
+                }
+
                 self.event_sender.notify(NetworkEvent::SessionEstablished {
                     peer_id,
                     remote_addr,
@@ -726,7 +758,7 @@ impl NetworkManager {
                 self.event_sender.notify(NetworkEvent::PeerRemoved(peer_id));
                 self.metrics.tracked_peers.set(self.swarm.state().peers().num_known_peers() as f64);
             }
-            SwarmEvent::SessionClosed { peer_id, remote_addr, error } => {
+            SwarmEvent::SessionClosed { peer_id, remote_addr, error, local_reason } => {
                 let total_active = self.num_active_peers.fetch_sub(1, Ordering::Relaxed) - 1;
                 self.metrics.connected_peers.set(total_active as f64);
                 trace!(
@@ -766,6 +798,16 @@ impl NetworkManager {
                             .saturating_sub(1)
                             as f64,
                     );
+                // a graceful close is initiated by us, unless the peer just closed the connection
+                let (peer_event_reason, initiator) = match local_reason {
+                    Some(local_reason) if error.is_none() => (Some(local_reason), Initiator::Local),
+                    _ => (reason, Initiator::Remote),
+                };
+                self.peer_event_sender.notify(PeerEvent::SessionClosed {
+                    peer_id,
+                    reason: peer_event_reason,
+                    initiator,
+                });
                 self.event_sender.notify(NetworkEvent::SessionClosed { peer_id, reason });
             }
             SwarmEvent::IncomingPendingSessionClosed { remote_addr, error } => {
@@ -889,11 +931,10 @@ impl NetworkManager {
             .active_sessions()
             .iter()
             .filter_map(|(&peer_id, session)| {
-                self.swarm
-                    .state()
-                    .peers()
-                    .peer_by_id(peer_id)
-                    .map(|(record, kind)| session.peer_info(&record, kind))
+                let state = self.swarm.state();
+                state.peers().peer_by_id(peer_id).map(|(record, kind)| {
+                    session.peer_info(&record, kind, state.best_hash(&peer_id))
+                })
             })
             .collect()
     }
@@ -903,11 +944,11 @@ impl NetworkManager {
     /// Returns `None` if there's no active session to the peer.
     fn get_peer_info_by_id(&self, peer_id: PeerId) -> Option<PeerInfo> {
         self.swarm.sessions().active_sessions().get(&peer_id).and_then(|session| {
-            self.swarm
-                .state()
+            let state = self.swarm.state();
+            state
                 .peers()
                 .peer_by_id(peer_id)
-                .map(|(record, kind)| session.peer_info(&record, kind))
+                .map(|(record, kind)| session.peer_info(&record, kind, state.best_hash(&peer_id)))
         })
     }
 
@@ -12,8 +12,9 @@ use alloy_primitives::{Bytes, B256};
 use futures::FutureExt;
 use reth_eth_wire::{
     capability::RawCapabilityMessage, message::RequestPair, BlockBodies, BlockHeaders, EthMessage,
-    GetBlockBodies, GetBlockHeaders, NewBlock, NewBlockHashes, NewPooledTransactionHashes,
-    NodeData, PooledTransactions, Receipts, SharedTransactions, Transactions,
+    EthVersion, GetBlockBodies, GetBlockHeaders, NewBlock, NewBlockHashes,
+    NewPooledTransactionHashes, NodeData, PooledTransactions, Receipts, Receipts69,
+    SharedTransactions, Transactions,
 };
 use reth_network_api::PeerRequest;
 use reth_network_p2p::error::{RequestError, RequestResult};
@@ -155,8 +156,10 @@ pub enum PeerResponseResult {
 // === impl PeerResponseResult ===
 
 impl PeerResponseResult {
-    /// Converts this response into an [`EthMessage`]
-    pub fn try_into_message(self, id: u64) -> RequestResult<EthMessage> {
+    /// Converts this response into an [`EthMessage`] for a session with the given version.
+    ///
+    /// Receipts are sent without the logs bloom to `eth/69` peers.
+    pub fn try_into_message(self, id: u64, version: EthVersion) -> RequestResult<EthMessage> {
         macro_rules! to_message {
             ($response:ident, $item:ident, $request_id:ident) => {
                 match $response {
@@ -181,6 +184,10 @@ impl PeerResponseResult {
             Self::NodeData(resp) => {
                 to_message!(resp, NodeData, id)
             }
+            Self::Receipts(resp) if version >= EthVersion::Eth69 => resp.map(|receipts| {
+                let request = RequestPair { request_id: id, message: Receipts(receipts) };
+                EthMessage::Receipts69(request.map(Receipts69::from))
+            }),
             Self::Receipts(resp) => {
                 to_message!(resp, Receipts, id)
             }
@@ -203,3 +210,47 @@ impl PeerResponseResult {
         self.err().is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{hex, Log};
+    use reth_primitives::{Receipt, TxType};
+
+    #[allow(clippy::needless_update)]
+    fn receipts() -> Vec<Vec<ReceiptWithBloom>> {
+        let log = Log::new_unchecked(
+            hex!("0000000000000000000000000000000000000011").into(),
+            vec![hex!("000000000000000000000000000000000000000000000000000000000000dead").into()],
+            hex!("0100ff")[..].into(),
+        );
+        let receipt = Receipt {
+            tx_type: TxType::Eip1559,
+            success: true,
+            cumulative_gas_used: 21_000,
+            logs: vec![log],
+            ..Default::default()
+        };
+        vec![vec![receipt.with_bloom()]]
+    }
+
+    #[test]
+    fn test_receipts_message_by_version() {
+        let resp = PeerResponseResult::Receipts(Ok(receipts()));
+        let msg = resp.try_into_message(1, EthVersion::Eth68).unwrap();
+        assert_eq!(
+            msg,
+            EthMessage::Receipts(RequestPair { request_id: 1, message: Receipts(receipts()) })
+        );
+
+        let resp = PeerResponseResult::Receipts(Ok(receipts()));
+        let msg = resp.try_into_message(1, EthVersion::Eth69).unwrap();
+        let EthMessage::Receipts69(RequestPair { request_id: 1, message }) = msg else {
+            panic!("unexpected message: {msg:?}")
+        };
+        assert_eq!(message.0, vec![vec![receipts()[0][0].receipt.clone()]]);
+
+        // the receiving side recomputes the same blooms
+        assert_eq!(Receipts::from(message), Receipts(receipts()));
+    }
+}
@@ -5,6 +5,11 @@ use reth_metrics::{
     Metrics,
 };
 use reth_primitives::TxType;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 /// Scope for monitoring transactions sent from the manager to the tx manager
 pub(crate) const NETWORK_POOL_TRANSACTIONS_SCOPE: &str = "network.pool.transactions";
@@ -85,6 +90,17 @@ pub struct SessionManagerMetrics {
     pub(crate) total_dial_successes: Counter,
     /// Number of dropped outgoing peer messages.
     pub(crate) total_outgoing_peer_messages_dropped: Counter,
+    /// Time in seconds to establish the TCP connection of outgoing sessions.
+    pub(crate) handshake_tcp_duration: Histogram,
+    /// Time in seconds of the `ECIES` handshake of established sessions.
+    pub(crate) handshake_ecies_duration: Histogram,
+    /// Time in seconds of the `Hello` exchange of established sessions.
+    pub(crate) handshake_hello_duration: Histogram,
+    /// Time in seconds of the `Status` exchange of established sessions.
+    pub(crate) handshake_status_duration: Histogram,
+    /// Moving average of the compression ratio of the messages received in a session, recorded
+    /// when the session is closed.
+    pub(crate) session_compression_ratio: Histogram,
 }
 
 /// Metrics for the [`TransactionsManager`](crate::transactions::TransactionsManager).
@@ -96,6 +112,9 @@ pub struct TransactionsManagerMetrics {
     pub(crate) propagated_transactions: Counter,
     /// Total number of reported bad transactions
     pub(crate) reported_bad_transactions: Counter,
+    /// Total number of [`GetPooledTransactions`](reth_eth_wire::GetPooledTransactions) requests
+    /// answered with an empty response, because the peer exceeded its rate limit.
+    pub(crate) rate_limited_get_pooled_transactions_requests: Counter,
 
     /* -- Freq txns already marked as seen by peer -- */
     /// Total number of messages from a peer, announcing transactions that have already been
@@ -308,6 +327,89 @@ impl DisconnectMetrics {
     }
 }
 
+/// Number of slots needed to index [`DisconnectCounts`] by the reason's wire code.
+const DISCONNECT_REASON_SLOTS: usize = DisconnectReason::SubprotocolSpecific as usize + 1;
+
+/// Total number of disconnects per [`DisconnectReason`] since start.
+///
+/// Unlike [`DisconnectMetrics`], which are pushed to the metrics recorder, these counts can be
+/// read back at any time via [`DisconnectCounts::snapshot`].
+#[derive(Debug, Default)]
+pub(crate) struct DisconnectCounts {
+    /// Counters indexed by the wire code of the reason.
+    counts: [AtomicU64; DISCONNECT_REASON_SLOTS],
+}
+
+impl DisconnectCounts {
+    /// Increments the counter for the given disconnect reason
+    pub(crate) fn increment(&self, reason: DisconnectReason) {
+        self.counts[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns a copy of the current count of every [`DisconnectReason`].
+    ///
+    /// This only reads the counters and does not reset them.
+    pub(crate) fn snapshot(&self) -> HashMap<DisconnectReason, u64> {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter_map(|(code, count)| {
+                let reason = DisconnectReason::try_from(code as u8).ok()?;
+                Some((reason, count.load(Ordering::Relaxed)))
+            })
+            .collect()
+    }
+}
+
+/// Metrics for the lifetime of sessions, labeled by the category of the reason they were closed
+/// with.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "network.sessions")]
+pub struct SessionLifetimeMetrics {
+    /// Time in seconds from the session being established until it was closed.
+    pub(crate) lifetime: Histogram,
+}
+
+/// Summary of the lifetimes of closed sessions that ended with the same reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionLifetimeStats {
+    /// Number of sessions closed.
+    pub count: u64,
+    /// Sum of the lifetimes of all sessions.
+    pub total: Duration,
+    /// The longest lifetime of a session.
+    pub max: Duration,
+}
+
+/// Records how long sessions lasted, by the [`DisconnectReason`] they were closed with.
+///
+/// Each lifetime is recorded in the [`SessionLifetimeMetrics`] histogram labeled with the
+/// [`DisconnectReason::category`], sessions closed without a reason, e.g. on an IO error, are
+/// labeled `unknown`.
+#[derive(Debug, Default)]
+pub(crate) struct SessionLifetimes {
+    by_reason: HashMap<Option<DisconnectReason>, (SessionLifetimeMetrics, SessionLifetimeStats)>,
+}
+
+impl SessionLifetimes {
+    /// Records the lifetime of a session that was closed with the given reason.
+    pub(crate) fn record(&mut self, reason: Option<DisconnectReason>, lifetime: Duration) {
+        let (metrics, stats) = self.by_reason.entry(reason).or_insert_with(|| {
+            let category = reason.map_or("unknown", |reason| reason.category());
+            (SessionLifetimeMetrics::new_with_labels(&[("category", category)]), Default::default())
+        });
+        metrics.lifetime.record(lifetime.as_secs_f64());
+        stats.count += 1;
+        stats.total += lifetime;
+        stats.max = stats.max.max(lifetime);
+    }
+
+    /// Returns a copy of the lifetime summary of every reason sessions were closed with.
+    pub(crate) fn snapshot(&self) -> HashMap<Option<DisconnectReason>, SessionLifetimeStats> {
+        self.by_reason.iter().map(|(reason, (_, stats))| (*reason, *stats)).collect()
+    }
+}
+
 /// Metrics for the `EthRequestHandler`
 #[derive(Metrics)]
 #[metrics(scope = "network")]
@@ -406,3 +508,59 @@ impl AnnouncedTxTypesMetrics {
         self.eip7702.record(tx_types_counter.eip7702 as f64);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disconnect_counts_snapshot() {
+        let counts = DisconnectCounts::default();
+        counts.increment(DisconnectReason::TooManyPeers);
+        counts.increment(DisconnectReason::TooManyPeers);
+        counts.increment(DisconnectReason::UselessPeer);
+        counts.increment(DisconnectReason::SubprotocolSpecific);
+
+        let snapshot = counts.snapshot();
+        assert_eq!(snapshot.len(), 13);
+        assert_eq!(snapshot[&DisconnectReason::TooManyPeers], 2);
+        assert_eq!(snapshot[&DisconnectReason::UselessPeer], 1);
+        assert_eq!(snapshot[&DisconnectReason::SubprotocolSpecific], 1);
+        assert_eq!(snapshot[&DisconnectReason::PingTimeout], 0);
+
+        // taking a snapshot doesn't reset the counters
+        counts.increment(DisconnectReason::UselessPeer);
+        let snapshot = counts.snapshot();
+        assert_eq!(snapshot[&DisconnectReason::TooManyPeers], 2);
+        assert_eq!(snapshot[&DisconnectReason::UselessPeer], 2);
+    }
+
+    #[test]
+    fn session_lifetimes_by_reason() {
+        let mut lifetimes = SessionLifetimes::default();
+        lifetimes.record(Some(DisconnectReason::ProtocolBreach), Duration::from_millis(10));
+        lifetimes.record(Some(DisconnectReason::ClientQuitting), Duration::from_secs(60));
+        lifetimes.record(Some(DisconnectReason::ClientQuitting), Duration::from_secs(30));
+        lifetimes.record(None, Duration::from_secs(1));
+
+        let snapshot = lifetimes.snapshot();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(
+            snapshot[&Some(DisconnectReason::ProtocolBreach)],
+            SessionLifetimeStats {
+                count: 1,
+                total: Duration::from_millis(10),
+                max: Duration::from_millis(10)
+            }
+        );
+        assert_eq!(
+            snapshot[&Some(DisconnectReason::ClientQuitting)],
+            SessionLifetimeStats {
+                count: 2,
+                total: Duration::from_secs(90),
+                max: Duration::from_secs(60)
+            }
+        );
+        assert_eq!(snapshot[&None].count, 1);
+    }
+}
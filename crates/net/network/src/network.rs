@@ -11,12 +11,15 @@ use enr::Enr;
 use parking_lot::Mutex;
 use reth_discv4::Discv4;
 use reth_discv5::Discv5;
-use reth_eth_wire::{DisconnectReason, NewBlock, NewPooledTransactionHashes, SharedTransactions};
+use reth_eth_wire::{
+    capability::SharedCapabilities, Capability, DisconnectReason, NewBlock,
+    NewPooledTransactionHashes, SharedTransactions,
+};
 use reth_network_api::{
     test_utils::{PeersHandle, PeersHandleProvider},
     BlockDownloaderProvider, DiscoveryEvent, NetworkError, NetworkEvent,
-    NetworkEventListenerProvider, NetworkInfo, NetworkStatus, PeerInfo, PeerRequest, Peers,
-    PeersInfo,
+    NetworkEventListenerProvider, NetworkInfo, NetworkStatus, PeerEvent, PeerInfo, PeerRequest,
+    Peers, PeersInfo,
 };
 use reth_network_p2p::{
     sync::{NetworkSyncUpdater, SyncState, SyncStateProvider},
@@ -65,6 +68,7 @@ impl NetworkHandle {
         discv4: Option<Discv4>,
         discv5: Option<Discv5>,
         event_sender: EventSender<NetworkEvent>,
+        peer_event_sender: EventSender<PeerEvent>,
     ) -> Self {
         let inner = NetworkInner {
             num_active_peers,
@@ -81,6 +85,7 @@ impl NetworkHandle {
             discv4,
             discv5,
             event_sender,
+            peer_event_sender,
         };
         Self { inner: Arc::new(inner) }
     }
@@ -99,6 +104,14 @@ impl NetworkHandle {
         &self.inner.network_mode
     }
 
+    /// Returns a new [`PeerEvent`] stream.
+    ///
+    /// The stream yields an event whenever a session is established or closed. A subscriber that
+    /// falls behind misses the oldest events instead of blocking the network.
+    pub fn peer_events(&self) -> EventStream<PeerEvent> {
+        self.inner.peer_event_sender.new_listener()
+    }
+
     /// Sends a [`NetworkHandleMessage`] to the manager
     pub(crate) fn send_message(&self, msg: NetworkHandleMessage) {
         let _ = self.inner.to_manager_tx.send(msg);
@@ -145,6 +158,47 @@ impl NetworkHandle {
         rx.await.unwrap()
     }
 
+    /// Returns the capabilities negotiated with the given peer.
+    ///
+    /// Returns `None` if there's no active session to the peer.
+    pub async fn shared_capabilities(
+        &self,
+        peer_id: PeerId,
+    ) -> Result<Option<Arc<SharedCapabilities>>, NetworkError> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.manager().send(NetworkHandleMessage::GetSharedCapabilities(peer_id, tx));
+        Ok(rx.await?)
+    }
+
+    /// Returns all connected peers that negotiated the given capability, e.g. to only schedule
+    /// snap requests to peers that support `snap/1`.
+    pub async fn peers_with_capability(
+        &self,
+        cap: Capability,
+    ) -> Result<Vec<PeerId>, NetworkError> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.manager().send(NetworkHandleMessage::GetPeersWithCapability(cap, tx));
+        Ok(rx.await?)
+    }
+
+    /// Gracefully disconnects the active session with the given peer, with the given reason.
+    ///
+    /// Unlike [`Peers::disconnect_peer_with_reason`], this reports whether the peer was connected:
+    /// returns `false` if there's no active session with the peer.
+    pub async fn disconnect_active_peer(
+        &self,
+        peer_id: PeerId,
+        reason: DisconnectReason,
+    ) -> Result<bool, NetworkError> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.manager().send(NetworkHandleMessage::DisconnectPeer(
+            peer_id,
+            Some(reason),
+            Some(tx),
+        ));
+        Ok(rx.await?)
+    }
+
     /// Send message to gracefully shutdown node.
     ///
     /// This will disconnect all active and pending sessions and prevent
@@ -298,13 +352,13 @@ impl Peers for NetworkHandle {
     /// Sends a message to the [`NetworkManager`](crate::NetworkManager)  to disconnect an existing
     /// connection to the given peer.
     fn disconnect_peer(&self, peer: PeerId) {
-        self.send_message(NetworkHandleMessage::DisconnectPeer(peer, None))
+        self.send_message(NetworkHandleMessage::DisconnectPeer(peer, None, None))
     }
 
     /// Sends a message to the [`NetworkManager`](crate::NetworkManager)  to disconnect an existing
     /// connection to the given peer using the provided reason
     fn disconnect_peer_with_reason(&self, peer: PeerId, reason: DisconnectReason) {
-        self.send_message(NetworkHandleMessage::DisconnectPeer(peer, Some(reason)))
+        self.send_message(NetworkHandleMessage::DisconnectPeer(peer, Some(reason), None))
     }
 
     /// Sends a message to the [`NetworkManager`](crate::NetworkManager) to connect to the given
@@ -432,6 +486,8 @@ struct NetworkInner {
     discv5: Option<Discv5>,
     /// Sender for high level network events.
     event_sender: EventSender<NetworkEvent>,
+    /// Sender for peer set changes.
+    peer_event_sender: EventSender<PeerEvent>,
 }
 
 /// Provides access to modify the network's additional protocol handlers.
@@ -450,7 +506,9 @@ pub(crate) enum NetworkHandleMessage {
     /// Removes a peer from the peerset corresponding to the given kind.
     RemovePeer(PeerId, PeerKind),
     /// Disconnects a connection to a peer if it exists, optionally providing a disconnect reason.
-    DisconnectPeer(PeerId, Option<DisconnectReason>),
+    ///
+    /// If a sender is provided, it's notified whether the peer was connected.
+    DisconnectPeer(PeerId, Option<DisconnectReason>, Option<oneshot::Sender<bool>>),
     /// Broadcasts an event to announce a new block to all nodes.
     AnnounceBlock(NewBlock, B256),
     /// Sends a list of transactions to the given peer.
@@ -493,6 +551,10 @@ pub(crate) enum NetworkHandleMessage {
     GetPeerInfoById(PeerId, oneshot::Sender<Option<PeerInfo>>),
     /// Gets `PeerInfo` for a specific peer kind via a oneshot sender.
     GetPeerInfosByPeerKind(PeerKind, oneshot::Sender<Vec<PeerInfo>>),
+    /// Gets the capabilities negotiated with a specific peer via a oneshot sender.
+    GetSharedCapabilities(PeerId, oneshot::Sender<Option<Arc<SharedCapabilities>>>),
+    /// Gets the peers that negotiated the given capability via a oneshot sender.
+    GetPeersWithCapability(Capability, oneshot::Sender<Vec<PeerId>>),
     /// Gets the reputation for a specific peer via a oneshot sender.
     GetReputationById(PeerId, oneshot::Sender<Option<Reputation>>),
     /// Retrieves the `TransactionsHandle` via a oneshot sender.
@@ -4,13 +4,13 @@ use std::{
     collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     fmt::Display,
     io::{self},
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     task::{Context, Poll},
     time::Duration,
 };
 
 use futures::StreamExt;
-use reth_eth_wire::{errors::EthStreamError, DisconnectReason};
+use reth_eth_wire::DisconnectReason;
 use reth_net_banlist::BanList;
 use reth_network_api::test_utils::{PeerCommand, PeersHandle};
 use reth_network_peers::{NodeRecord, PeerId};
@@ -66,8 +66,11 @@ pub struct PeersManager {
     connection_info: ConnectionInfo,
     /// Tracks unwanted ips/peer ids.
     ban_list: BanList,
-    /// Tracks currently backed off peers.
-    backed_off_peers: HashMap<PeerId, std::time::Instant>,
+    /// Tracks recently disconnected peers that are currently backed off.
+    ///
+    /// This is keyed by peer id and outlives the peer's entry in [`Self::peers`], so a peer that
+    /// is removed and rediscovered during its cooldown is not redialed.
+    backed_off_peers: HashMap<PeerId, BackedOffPeer>,
     /// Interval at which to check for peers to unban and release from the backoff map.
     release_interval: Interval,
     /// How long to ban bad peers.
@@ -354,12 +357,20 @@ impl PeersManager {
     }
 
     /// Temporarily puts the peer in timeout by inserting it into the backedoff peers set
-    fn backoff_peer_until(&mut self, peer_id: PeerId, until: std::time::Instant) {
-        trace!(target: "net::peers", ?peer_id, "backing off");
+    fn backoff_peer_until(
+        &mut self,
+        peer_id: PeerId,
+        until: std::time::Instant,
+        reason: Option<DisconnectReason>,
+    ) {
+        trace!(target: "net::peers", ?peer_id, ?reason, "backing off");
 
         if let Some(peer) = self.peers.get_mut(&peer_id) {
             peer.backed_off = true;
-            self.backed_off_peers.insert(peer_id, until);
+            self.backed_off_peers.insert(
+                peer_id,
+                BackedOffPeer { reason, disconnected_at: std::time::Instant::now(), until },
+            );
         }
     }
 
@@ -440,10 +451,14 @@ impl PeersManager {
             }
             ReputationChangeOutcome::Unban => self.unban_peer(*peer_id),
             ReputationChangeOutcome::DisconnectAndBan => {
-                self.queued_actions.push_back(PeerAction::Disconnect {
-                    peer_id: *peer_id,
-                    reason: Some(DisconnectReason::DisconnectRequested),
-                });
+                // a peer that serves incorrect responses is of no use to us
+                let reason = if matches!(rep, ReputationChangeKind::BadResponse) {
+                    DisconnectReason::UselessPeer
+                } else {
+                    DisconnectReason::DisconnectRequested
+                };
+                self.queued_actions
+                    .push_back(PeerAction::Disconnect { peer_id: *peer_id, reason: Some(reason) });
                 self.ban_peer(*peer_id);
             }
         }
@@ -505,12 +520,12 @@ impl PeersManager {
     /// Called when an _active_ session to a peer was forcefully dropped due to an error.
     ///
     /// Depending on whether the error is fatal, the peer will be removed from the peer set
-    /// otherwise its reputation is slashed.
+    /// otherwise its reputation is slashed, unless the session ended because of us.
     pub(crate) fn on_active_session_dropped(
         &mut self,
         remote_addr: &SocketAddr,
         peer_id: &PeerId,
-        err: &EthStreamError,
+        err: &reth_eth_wire::errors::SessionError,
     ) {
         self.on_connection_failure(remote_addr, peer_id, err, ReputationChangeKind::Dropped)
     }
@@ -587,8 +602,9 @@ impl PeersManager {
                     // connections, so we will hold off on attempting any new connections for a
                     // while
                     backoff_until = Some(backoff_time);
-                } else {
-                    // If the error was not a backoff error, we reduce the peer's reputation
+                } else if !err.is_our_fault() {
+                    // If the error was not a backoff error and caused by the peer, we reduce the
+                    // peer's reputation
                     let reputation_change = self.reputation_weights.change(reputation_change);
                     peer.reputation = peer.reputation.saturating_add(reputation_change.as_i32());
                 };
@@ -609,7 +625,7 @@ impl PeersManager {
                 self.queued_actions.push_back(PeerAction::PeerRemoved(peer_id));
             } else if let Some(backoff_until) = backoff_until {
                 // otherwise, backoff the peer if marked as such
-                self.backoff_peer_until(*peer_id, backoff_until);
+                self.backoff_peer_until(*peer_id, backoff_until, err.disconnect_reason());
             }
         }
 
@@ -697,6 +713,11 @@ impl PeersManager {
                 trace!(target: "net::peers", ?peer_id, addr=?addr.tcp(), "discovered new node");
                 let mut peer = Peer::with_kind(addr, kind);
                 peer.fork_id = fork_id;
+                // the peer may have been removed and rediscovered while backed off
+                if let Some(backed_off) = self.backed_off_peers.get(&peer_id) {
+                    trace!(target: "net::peers", ?peer_id, reason=?backed_off.reason, disconnected=?backed_off.disconnected_at.elapsed(), "rediscovered backed off node");
+                    peer.backed_off = true;
+                }
                 entry.insert(peer);
                 self.queued_actions.push_back(PeerAction::PeerAdded(peer_id));
             }
@@ -789,6 +810,24 @@ impl PeersManager {
         self.trusted_peer_ids.remove(&peer_id);
     }
 
+    /// Returns the number of outbound connections, pending or established, per subnet.
+    fn outbound_subnets(&self) -> HashMap<IpAddr, usize> {
+        let mut subnets = HashMap::new();
+        for peer in self.peers.values().filter(|peer| peer.state.is_outgoing()) {
+            *subnets.entry(subnet(peer.addr.tcp().ip())).or_default() += 1;
+        }
+        subnets
+    }
+
+    /// Returns the number of pending outbound dials per IP address.
+    fn pending_dials_per_ip(&self) -> HashMap<IpAddr, usize> {
+        let mut dials = HashMap::new();
+        for peer in self.peers.values().filter(|peer| peer.state.is_pending_out()) {
+            *dials.entry(peer.addr.tcp().ip()).or_default() += 1;
+        }
+        dials
+    }
+
     /// Returns the idle peer with the highest reputation.
     ///
     /// Peers that are `trusted` or `static`, see [`PeerKind`], are prioritized as long as they're
@@ -797,34 +836,64 @@ impl PeersManager {
     /// If `trusted_nodes_only` is enabled, see [`PeersConfig`], then this will only consider
     /// `trusted` peers.
     ///
+    /// Among peers with the same reputation, the one in the subnet with the fewest outbound
+    /// connections is preferred. Peers in a subnet that's already at the configured
+    /// `max_outbound_per_subnet` are skipped, as are peers whose IP address is already at the
+    /// configured `max_concurrent_dials_per_ip`.
+    ///
     /// Returns `None` if no peer is available.
-    fn best_unconnected(&mut self) -> Option<(PeerId, &mut Peer)> {
-        let mut unconnected = self.peers.iter_mut().filter(|(_, peer)| {
+    fn best_unconnected(
+        &mut self,
+        outbound_subnets: &HashMap<IpAddr, usize>,
+        pending_dials: &HashMap<IpAddr, usize>,
+    ) -> Option<(PeerId, &mut Peer)> {
+        let max_per_subnet = self.connection_info.config.max_outbound_per_subnet;
+        let max_dials_per_ip = self.connection_info.config.max_concurrent_dials_per_ip;
+        let subnet_count =
+            |peer: &Peer| outbound_subnets.get(&subnet(peer.addr.tcp().ip())).copied().unwrap_or(0);
+        let dials_count = |peer: &Peer| pending_dials.get(&peer.addr.tcp().ip()).copied();
+
+        let unconnected = self.peers.iter_mut().filter(|(_, peer)| {
             !peer.is_backed_off() &&
                 !peer.is_banned() &&
                 peer.state.is_unconnected() &&
                 (!self.trusted_nodes_only || peer.is_trusted())
         });
 
-        // keep track of the best peer, if there's one
-        let mut best_peer = unconnected.next()?;
-
-        if best_peer.1.is_trusted() || best_peer.1.is_static() {
-            return Some((*best_peer.0, best_peer.1))
-        }
+        // keep track of the best peer and the number of outbound connections in its subnet
+        let mut best_peer: Option<(&PeerId, &mut Peer, usize)> = None;
 
-        for maybe_better in unconnected {
+        for (peer_id, peer) in unconnected {
             // if the peer is trusted or static, return it immediately
-            if maybe_better.1.is_trusted() || maybe_better.1.is_static() {
-                return Some((*maybe_better.0, maybe_better.1))
+            if peer.is_trusted() || peer.is_static() {
+                return Some((*peer_id, peer))
+            }
+
+            // skip peers that wouldn't add to the diversity of our outbound connections
+            let count = subnet_count(peer);
+            if max_per_subnet.is_some_and(|max| count >= max) {
+                continue
+            }
+
+            // skip peers on a host that's already being dialed too often
+            if max_dials_per_ip.is_some_and(|max| dials_count(peer).unwrap_or(0) >= max) {
+                continue
             }
 
-            // otherwise we keep track of the best peer using the reputation
-            if maybe_better.1.reputation > best_peer.1.reputation {
-                best_peer = maybe_better;
+            // otherwise we keep track of the best peer using the reputation, preferring less
+            // crowded subnets
+            let is_better = match &best_peer {
+                Some((_, best, best_count)) => {
+                    (peer.reputation, std::cmp::Reverse(count)) >
+                        (best.reputation, std::cmp::Reverse(*best_count))
+                }
+                None => true,
+            };
+            if is_better {
+                best_peer = Some((peer_id, peer, count));
             }
         }
-        Some((*best_peer.0, best_peer.1))
+        best_peer.map(|(peer_id, peer, _)| (*peer_id, peer))
     }
 
     /// If there's capacity for new outbound connections, this will queue new
@@ -840,16 +909,22 @@ impl PeersManager {
             return
         }
 
+        let mut outbound_subnets = self.outbound_subnets();
+        let mut pending_dials = self.pending_dials_per_ip();
+
         // as long as there are slots available fill them with the best peers
         while self.connection_info.has_out_capacity() {
             let action = {
-                let (peer_id, peer) = match self.best_unconnected() {
+                let (peer_id, peer) = match self.best_unconnected(&outbound_subnets, &pending_dials)
+                {
                     Some(peer) => peer,
                     _ => break,
                 };
 
                 trace!(target: "net::peers", ?peer_id, addr=?peer.addr, "schedule outbound connection");
 
+                *outbound_subnets.entry(subnet(peer.addr.tcp().ip())).or_default() += 1;
+                *pending_dials.entry(peer.addr.tcp().ip()).or_default() += 1;
                 peer.state = PeerConnectionState::PendingOut;
                 PeerAction::Connect { peer_id, remote_addr: peer.addr.tcp() }
             };
@@ -917,8 +992,8 @@ impl PeersManager {
 
                 // clear the backoff list of expired backoffs, and mark the relevant peers as
                 // ready to be dialed
-                self.backed_off_peers.retain(|peer_id, until| {
-                    if now > *until {
+                self.backed_off_peers.retain(|peer_id, backed_off| {
+                    if now > backed_off.until {
                         if let Some(peer) = self.peers.get_mut(peer_id) {
                             peer.backed_off = false;
                         }
@@ -945,6 +1020,17 @@ impl Default for PeersManager {
     }
 }
 
+/// A recently disconnected peer that must not be redialed until its backoff elapsed.
+#[derive(Debug, Clone, Copy)]
+struct BackedOffPeer {
+    /// The reason of the last disconnect, if the peer was disconnected with a disconnect message.
+    reason: Option<DisconnectReason>,
+    /// When the peer was disconnected.
+    disconnected_at: std::time::Instant,
+    /// Until when the peer is backed off.
+    until: std::time::Instant,
+}
+
 /// Tracks stats about connected nodes
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ConnectionInfo {
@@ -1021,6 +1107,22 @@ impl ConnectionInfo {
     }
 }
 
+/// Returns the subnet of the given address, used to diversify outbound connections.
+///
+/// This is the `/24` prefix of an IPv4 address and the `/64` prefix of an IPv6 address.
+fn subnet(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            Ipv4Addr::new(a, b, c, 0).into()
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, d, ..] = ip.segments();
+            Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0).into()
+        }
+    }
+}
+
 /// Actions the peer manager can trigger.
 #[derive(Debug)]
 pub enum PeerAction {
@@ -1095,6 +1197,7 @@ impl Display for InboundConnectionError {
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashSet,
         future::{poll_fn, Future},
         io,
         net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -1116,7 +1219,7 @@ mod tests {
     };
     use url::Host;
 
-    use super::PeersManager;
+    use super::{subnet, PeersManager};
     use crate::{
         error::SessionError,
         peers::{
@@ -1289,7 +1392,8 @@ mod tests {
             &peer,
             &EthStreamError::P2PStreamError(P2PStreamError::Disconnected(
                 DisconnectReason::TooManyPeers,
-            )),
+            ))
+            .into(),
         );
 
         poll_fn(|cx| {
@@ -1314,6 +1418,80 @@ mod tests {
         assert!(!peers.peers.get(&peer).unwrap().is_backed_off());
     }
 
+    #[tokio::test]
+    async fn test_no_reputation_slash_if_our_fault() {
+        let peer = PeerId::random();
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 1, 2)), 8008);
+
+        let mut peers = PeersManager::new(PeersConfig::test());
+        peers.add_peer(peer, PeerAddr::from_tcp(socket_addr), None);
+
+        assert!(matches!(event!(peers), PeerAction::PeerAdded(peer_id) if peer_id == peer));
+        assert!(matches!(event!(peers), PeerAction::Connect { peer_id, .. } if peer_id == peer));
+
+        // our send buffer overflowed, the peer isn't at fault
+        peers.on_active_session_dropped(
+            &socket_addr,
+            &peer,
+            &EthStreamError::P2PStreamError(P2PStreamError::SendBufferFull).into(),
+        );
+        assert_eq!(peers.get_reputation(&peer), Some(DEFAULT_REPUTATION));
+
+        // the peer sent an oversized message
+        peers.on_active_session_dropped(
+            &socket_addr,
+            &peer,
+            &EthStreamError::MessageTooBig(0).into(),
+        );
+        assert!(peers.get_reputation(&peer).unwrap() < DEFAULT_REPUTATION);
+    }
+
+    #[tokio::test]
+    async fn test_no_redial_of_rediscovered_backed_off_peer() {
+        let peer = PeerId::random();
+        let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 1, 2)), 8008);
+
+        let mut peers = PeersManager::new(PeersConfig::test());
+        peers.add_peer(peer, PeerAddr::from_tcp(socket_addr), None);
+
+        assert!(matches!(event!(peers), PeerAction::PeerAdded(peer_id) if peer_id == peer));
+        assert!(matches!(event!(peers), PeerAction::Connect { peer_id, .. } if peer_id == peer));
+
+        peers.on_active_session_dropped(
+            &socket_addr,
+            &peer,
+            &EthStreamError::P2PStreamError(P2PStreamError::Disconnected(
+                DisconnectReason::TooManyPeers,
+            ))
+            .into(),
+        );
+
+        let backed_off = *peers.backed_off_peers.get(&peer).unwrap();
+        assert_eq!(backed_off.reason, Some(DisconnectReason::TooManyPeers));
+        assert!(backed_off.disconnected_at <= std::time::Instant::now());
+        assert!(backed_off.until > backed_off.disconnected_at);
+
+        // the peer is dropped from discovery and immediately rediscovered
+        peers.remove_peer(peer);
+        assert!(matches!(event!(peers), PeerAction::PeerRemoved(peer_id) if peer_id == peer));
+        peers.add_peer(peer, PeerAddr::from_tcp(socket_addr), None);
+        assert!(matches!(event!(peers), PeerAction::PeerAdded(peer_id) if peer_id == peer));
+
+        // still within the cooldown, so the peer must not be dialed
+        peers.fill_outbound_slots();
+        poll_fn(|cx| {
+            assert!(peers.poll(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await;
+        assert!(peers.peers.get(&peer).unwrap().is_backed_off());
+
+        tokio::time::sleep(peers.backoff_durations.low).await;
+
+        assert!(matches!(event!(peers), PeerAction::Connect { peer_id, .. } if peer_id == peer));
+        assert!(!peers.backed_off_peers.contains_key(&peer));
+    }
+
     #[tokio::test]
     async fn test_backoff_on_no_response() {
         let peer = PeerId::random();
@@ -1450,7 +1628,8 @@ mod tests {
             &peer,
             &EthStreamError::P2PStreamError(P2PStreamError::Disconnected(
                 DisconnectReason::UselessPeer,
-            )),
+            ))
+            .into(),
         );
 
         match event!(peers) {
@@ -1837,6 +2016,44 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_bad_responses_disconnect_useless_peer() {
+        let mut peers = PeersManager::default();
+        let good = PeerId::random();
+        let bad = PeerId::random();
+        for (peer, port) in [(good, 8008), (bad, 8009)] {
+            let socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 1, 2)), port);
+            peers.add_peer(peer, PeerAddr::from_tcp(socket_addr), None);
+            peers.on_incoming_pending_session(socket_addr.ip()).unwrap();
+            peers.on_incoming_session_established(peer, socket_addr);
+        }
+        peers.queued_actions.clear();
+
+        // the good peer sent a single incorrect response among the correct ones, the bad peer only
+        // sends incorrect responses
+        peers.apply_reputation_change(&good, ReputationChangeKind::BadResponse);
+        let mut bad_responses = 0;
+        while !peers.peers[&bad].is_banned() {
+            assert!(peers.queued_actions.is_empty());
+            peers.apply_reputation_change(&bad, ReputationChangeKind::BadResponse);
+            bad_responses += 1;
+        }
+        assert!(bad_responses > 1);
+        assert!(!peers.peers[&good].is_banned());
+
+        match event!(peers) {
+            PeerAction::Disconnect { peer_id, reason } => {
+                assert_eq!(peer_id, bad);
+                assert_eq!(reason, Some(DisconnectReason::UselessPeer));
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+        assert!(peers.queued_actions.iter().all(|action| !matches!(
+            action,
+            PeerAction::Disconnect { .. } | PeerAction::DisconnectBannedIncoming { .. }
+        )));
+    }
+
     #[tokio::test]
     async fn test_reputation_management() {
         let peer = PeerId::random();
@@ -2429,6 +2646,111 @@ mod tests {
         assert_eq!(dials, peer_manager.connection_info.config.max_concurrent_outbound_dials);
     }
 
+    #[tokio::test]
+    async fn test_dials_diverse_subnets() {
+        let config =
+            PeersConfig::default().with_max_concurrent_dials(2).with_max_outbound_per_subnet(1);
+        let mut peer_manager = PeersManager::new(config);
+
+        let peer_at = |a, b, c, d| PeerAddr::from_tcp(SocketAddr::from(([a, b, c, d], 30303)));
+        let crowded = [PeerId::random(), PeerId::random()];
+        peer_manager.add_peer(crowded[0], peer_at(10, 0, 0, 1), None);
+        peer_manager.add_peer(crowded[1], peer_at(10, 0, 0, 2), None);
+        let diverse = [PeerId::random(), PeerId::random()];
+        peer_manager.add_peer(diverse[0], peer_at(10, 0, 1, 1), None);
+        peer_manager.add_peer(diverse[1], peer_at(10, 0, 2, 1), None);
+        let backed_off = PeerId::random();
+        peer_manager.add_peer(backed_off, peer_at(10, 0, 3, 1), None);
+        peer_manager.peers.get_mut(&backed_off).unwrap().backed_off = true;
+
+        let mut dialed = HashSet::new();
+        loop {
+            peer_manager.fill_outbound_slots();
+            let pending = peer_manager
+                .peers
+                .iter()
+                .filter(|(_, peer)| peer.state.is_pending_out())
+                .map(|(peer_id, _)| *peer_id)
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                break
+            }
+
+            // never more dials in flight than configured
+            assert!(pending.len() <= 2);
+            assert_eq!(peer_manager.connection_info.num_pending_out, pending.len());
+
+            // completing the dials frees the slots
+            for peer_id in pending {
+                peer_manager.on_active_outgoing_established(peer_id);
+                dialed.insert(peer_id);
+            }
+            assert_eq!(peer_manager.connection_info.num_pending_out, 0);
+        }
+
+        // one peer per subnet, excluding the backed off peer
+        assert_eq!(dialed.len(), 3);
+        assert!(dialed.contains(&diverse[0]) && dialed.contains(&diverse[1]));
+        assert_eq!(crowded.iter().filter(|peer_id| dialed.contains(*peer_id)).count(), 1);
+        assert!(!dialed.contains(&backed_off));
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_dials_per_ip() {
+        let config = PeersConfig::default().with_max_concurrent_dials_per_ip(2);
+        let mut peer_manager = PeersManager::new(config);
+
+        // many records of a single host behind different ports
+        let host = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let mut same_host = HashSet::new();
+        for port in 30303..30313 {
+            let peer_id = PeerId::random();
+            peer_manager.add_peer(peer_id, PeerAddr::from_tcp(SocketAddr::new(host, port)), None);
+            same_host.insert(peer_id);
+        }
+        let other_host = PeerId::random();
+        peer_manager.add_peer(
+            other_host,
+            PeerAddr::from_tcp(SocketAddr::from(([10, 0, 1, 1], 30303))),
+            None,
+        );
+
+        let mut dialed = HashSet::new();
+        loop {
+            peer_manager.fill_outbound_slots();
+            let pending = peer_manager
+                .peers
+                .iter()
+                .filter(|(_, peer)| peer.state.is_pending_out())
+                .map(|(peer_id, _)| *peer_id)
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                break
+            }
+
+            // never more dials to the host in flight than configured
+            let host_dials = pending.iter().filter(|peer_id| same_host.contains(*peer_id)).count();
+            assert!(host_dials <= 2, "{host_dials} dials to the same host");
+
+            // completing the dials frees the host's dial slots
+            for peer_id in pending {
+                peer_manager.on_active_outgoing_established(peer_id);
+                dialed.insert(peer_id);
+            }
+        }
+
+        // all records are dialed eventually, spread out over several rounds
+        assert!(dialed.contains(&other_host));
+        assert!(same_host.is_subset(&dialed));
+    }
+
+    #[test]
+    fn test_subnet() {
+        let subnet_of = |ip: &str| subnet(ip.parse().unwrap()).to_string();
+        assert_eq!(subnet_of("10.0.1.42"), "10.0.1.0");
+        assert_eq!(subnet_of("2001:db8:1:2:3:4:5:6"), "2001:db8:1:2::");
+    }
+
     #[tokio::test]
     async fn test_max_num_of_pending_dials() {
         let config = PeersConfig::default();
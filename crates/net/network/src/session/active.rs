@@ -12,16 +12,18 @@ use std::{
 };
 
 use futures::{stream::Fuse, SinkExt, StreamExt};
+use parking_lot::Mutex;
 use reth_eth_wire::{
-    errors::{EthHandshakeError, EthStreamError, P2PStreamError},
+    clock::SharedClock,
+    errors::{EthHandshakeError, EthStreamError, P2PStreamError, PingerError, SessionError},
     message::{EthBroadcastMessage, RequestPair},
-    Capabilities, DisconnectP2P, DisconnectReason, EthMessage,
+    Capabilities, DisconnectP2P, DisconnectReason, EthMessage, EthMessageID, Receipts,
 };
 use reth_metrics::common::mpsc::MeteredPollSender;
 use reth_network_api::PeerRequest;
-use reth_network_p2p::error::RequestError;
+use reth_network_p2p::error::{RequestError, SessionEnd};
 use reth_network_peers::PeerId;
-use reth_network_types::session::config::INITIAL_REQUEST_TIMEOUT;
+use reth_network_types::{RequestTimeoutConfig, ServingPolicy};
 use rustc_hash::FxHashMap;
 use tokio::{
     sync::{mpsc::error::TrySendError, oneshot},
@@ -29,27 +31,19 @@ use tokio::{
 };
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::PollSender;
-use tracing::{debug, trace};
+use tracing::{debug, debug_span, trace, trace_span, Span};
 
 use crate::{
     message::{NewBlockMessage, PeerMessage, PeerResponse, PeerResponseResult},
     session::{
         conn::EthRlpxConnection,
         handle::{ActiveSessionMessage, SessionCommand},
-        SessionId,
+        RequestLatencies, SessionId,
     },
 };
 
-// Constants for timeout updating.
-
-/// Minimum timeout value
-const MINIMUM_TIMEOUT: Duration = Duration::from_secs(2);
-/// Maximum timeout value
-const MAXIMUM_TIMEOUT: Duration = INITIAL_REQUEST_TIMEOUT;
-/// How much the new measurements affect the current timeout (X percent)
+/// How much a new RTT measurement affects the moving average of the RTT (X percent)
 const SAMPLE_IMPACT: f64 = 0.1;
-/// Amount of RTTs before timeout
-const TIMEOUT_SCALING: u32 = 3;
 
 /// The type that advances an established session by listening for incoming messages (from local
 /// node or read from connection) and emitting events back to the
@@ -90,6 +84,11 @@ pub(crate) struct ActiveSession {
     pub(crate) queued_outgoing: VecDeque<OutgoingMessage>,
     /// The maximum time we wait for a response from a peer.
     pub(crate) internal_request_timeout: Arc<AtomicU64>,
+    /// How the request timeout adapts to the round-trip time of the peer.
+    pub(crate) request_timeout: RequestTimeoutConfig,
+    /// The moving average of the round-trip time of the answered requests, seeded with the
+    /// round-trip time of the initial request timeout.
+    pub(crate) rtt: Duration,
     /// Interval when to check for timed out requests.
     pub(crate) internal_request_timeout_interval: Interval,
     /// If an [`ActiveSession`] does not receive a response at all within this duration then it is
@@ -97,6 +96,26 @@ pub(crate) struct ActiveSession {
     pub(crate) protocol_breach_request_timeout: Duration,
     /// Used to reserve a slot to guarantee that the termination message is delivered
     pub(crate) terminate_message: Option<(PollSender<ActiveSessionMessage>, ActiveSessionMessage)>,
+    /// The reason we're disconnecting the peer with, if we started the disconnect.
+    pub(crate) disconnect_reason: Option<DisconnectReason>,
+    /// Tracks whether the peer is useful, if useless peers should be disconnected.
+    pub(crate) usefulness: Option<UsefulnessTracker>,
+    /// The requests of the peer that are served, the others are answered with empty responses.
+    pub(crate) serving_policy: ServingPolicy,
+    /// Whether requests of the peer are served at all, if not all but `GetPooledTransactions`
+    /// are answered with empty responses regardless of the `serving_policy`.
+    pub(crate) serve_requests: bool,
+    /// The round-trip latencies of the requests sent to the peer, per request type.
+    pub(crate) latencies: Arc<RequestLatencies>,
+    /// The moving average of the compression ratio of the received messages, shared with the
+    /// session's handle.
+    pub(crate) compression_ratio: Arc<Mutex<Option<f64>>>,
+    /// When the peer last answered one of our requests, including late answers.
+    pub(crate) last_answered_request: Option<Instant>,
+    /// The source of the current time for request deadlines and latencies.
+    pub(crate) clock: SharedClock,
+    /// The span all processing of the session is entered in, see [`session_span`].
+    pub(crate) span: Span,
 }
 
 impl ActiveSession {
@@ -106,10 +125,18 @@ impl ActiveSession {
     }
 
     /// Returns the next request id
+    ///
+    /// Ids wrap around, which can't happen in practice, but an id that's still in
+    /// `inflight_requests` is never reused, so a response is always correlated with the right
+    /// request.
     fn next_id(&mut self) -> u64 {
-        let id = self.next_id;
-        self.next_id += 1;
-        id
+        loop {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            if !self.inflight_requests.contains_key(&id) {
+                return id
+            }
+        }
     }
 
     /// Shrinks the capacity of the internal buffers.
@@ -118,6 +145,28 @@ impl ActiveSession {
         self.queued_outgoing.shrink_to_fit();
     }
 
+    /// Returns the span in which a message exchanged with the remote peer is processed.
+    ///
+    /// The span carries the message name and, for requests and responses, the request id, so the
+    /// lifecycle of a single request can be followed across the session. The peer id and the
+    /// negotiated versions are inherited from the [`session_span`].
+    ///
+    /// Fields are only evaluated if the span is enabled.
+    fn message_span(
+        &self,
+        direction: &'static str,
+        message_id: EthMessageID,
+        request_id: Option<u64>,
+    ) -> Span {
+        trace_span!(
+            target: "net::session",
+            "eth_message",
+            direction,
+            message = ?message_id,
+            request_id,
+        )
+    }
+
     /// Handle a message read from the connection.
     ///
     /// Returns an error if the message is considered to be in violation of the protocol.
@@ -126,13 +175,36 @@ impl ActiveSession {
         /// This creates a new channel and tries to send the sender half to the session while
         /// storing the receiver half internally so the pending response can be polled.
         macro_rules! on_request {
+            ($req:ident, $resp_item:ident, $req_item:ident, $served:ident) => {{
+                let served = self.serve_requests && self.serving_policy.$served;
+                on_request!(@serve served, $req, $resp_item, $req_item)
+            }};
             ($req:ident, $resp_item:ident, $req_item:ident) => {{
+                on_request!(@serve self.serve_requests, $req, $resp_item, $req_item)
+            }};
+            (@serve $served:expr, $req:ident, $resp_item:ident, $req_item:ident) => {{
+                if $served {
+                    on_request!(@delegate $req, $resp_item, $req_item)
+                } else {
+                    // answer right away without delegating the request
+                    let RequestPair { request_id, .. } = $req;
+                    self.queued_outgoing.push_back(
+                        EthMessage::$resp_item(RequestPair {
+                            request_id,
+                            message: Default::default(),
+                        })
+                        .into(),
+                    );
+                    OnIncomingMessageOutcome::Ok
+                }
+            }};
+            (@delegate $req:ident, $resp_item:ident, $req_item:ident) => {{
                 let RequestPair { request_id, message: request } = $req;
                 let (tx, response) = oneshot::channel();
                 let received = ReceivedRequest {
                     request_id,
                     rx: PeerResponse::$resp_item { response },
-                    received: Instant::now(),
+                    received: self.clock.now(),
                 };
                 self.received_requests_from_remote.push(received);
                 self.try_emit_request(PeerMessage::EthRequest(PeerRequest::$req_item {
@@ -151,19 +223,23 @@ impl ActiveSession {
                 if let Some(req) = self.inflight_requests.remove(&request_id) {
                     match req.request {
                         RequestState::Waiting(PeerRequest::$item { response, .. }) => {
+                            if let Some(usefulness) = &mut self.usefulness {
+                                usefulness.on_answered_request();
+                            }
                             let _ = response.send(Ok(message));
-                            self.update_request_timeout(req.timestamp, Instant::now());
+                            self.on_request_answered(EthMessageID::$item, req.timestamp);
                         }
                         RequestState::Waiting(request) => {
                             request.send_bad_response();
                         }
                         RequestState::TimedOut => {
                             // request was already timed out internally
-                            self.update_request_timeout(req.timestamp, Instant::now());
+                            self.on_request_answered(EthMessageID::$item, req.timestamp);
                         }
                     }
                 } else {
-                    // we received a response to a request we never sent
+                    // we received a response to a request we never sent, or to a timed out request
+                    // that was already evicted
                     self.on_bad_message();
                 }
 
@@ -171,11 +247,28 @@ impl ActiveSession {
             }};
         }
 
+        if matches!(
+            msg,
+            EthMessage::NewBlockHashes(_) |
+                EthMessage::NewBlock(_) |
+                EthMessage::Transactions(_) |
+                EthMessage::NewPooledTransactionHashes66(_) |
+                EthMessage::NewPooledTransactionHashes68(_)
+        ) {
+            if let Some(usefulness) = &mut self.usefulness {
+                usefulness.on_gossip();
+            }
+        }
+
         match msg {
-            message @ EthMessage::Status(_) => OnIncomingMessageOutcome::BadMessage {
-                error: EthStreamError::EthHandshakeError(EthHandshakeError::StatusNotInHandshake),
-                message,
-            },
+            message @ (EthMessage::Status(_) | EthMessage::StatusEth69(_)) => {
+                OnIncomingMessageOutcome::BadMessage {
+                    error: EthStreamError::EthHandshakeError(
+                        EthHandshakeError::StatusNotInHandshake,
+                    ),
+                    message,
+                }
+            }
             EthMessage::NewBlockHashes(msg) => {
                 self.try_emit_broadcast(PeerMessage::NewBlockHashes(msg)).into()
             }
@@ -204,19 +297,22 @@ impl ActiveSession {
                 self.try_emit_broadcast(PeerMessage::PooledTransactions(msg.into())).into()
             }
             EthMessage::GetBlockHeaders(req) => {
-                on_request!(req, BlockHeaders, GetBlockHeaders)
+                on_request!(req, BlockHeaders, GetBlockHeaders, block_headers)
             }
             EthMessage::BlockHeaders(resp) => {
                 on_response!(resp, GetBlockHeaders)
             }
             EthMessage::GetBlockBodies(req) => {
-                on_request!(req, BlockBodies, GetBlockBodies)
+                on_request!(req, BlockBodies, GetBlockBodies, block_bodies)
             }
             EthMessage::BlockBodies(resp) => {
                 on_response!(resp, GetBlockBodies)
             }
             EthMessage::GetPooledTransactions(req) => {
-                on_request!(req, PooledTransactions, GetPooledTransactions)
+                // transactions are still announced if requests aren't served, so the peer has to
+                // be able to fetch them
+                let served = self.serving_policy.pooled_transactions;
+                on_request!(@serve served, req, PooledTransactions, GetPooledTransactions)
             }
             EthMessage::PooledTransactions(resp) => {
                 on_response!(resp, GetPooledTransactions)
@@ -228,11 +324,16 @@ impl ActiveSession {
                 on_response!(resp, GetNodeData)
             }
             EthMessage::GetReceipts(req) => {
-                on_request!(req, Receipts, GetReceipts)
+                on_request!(req, Receipts, GetReceipts, receipts)
             }
             EthMessage::Receipts(resp) => {
                 on_response!(resp, GetReceipts)
             }
+            EthMessage::Receipts69(resp) => {
+                // eth/69 receipts don't include the logs bloom, so it's recomputed here
+                let resp = resp.map(Receipts::from);
+                on_response!(resp, GetReceipts)
+            }
         }
     }
 
@@ -243,7 +344,7 @@ impl ActiveSession {
         self.queued_outgoing.push_back(msg.into());
         let req = InflightRequest {
             request: RequestState::Waiting(request),
-            timestamp: Instant::now(),
+            timestamp: self.clock.now(),
             deadline,
         };
         self.inflight_requests.insert(request_id, req);
@@ -281,7 +382,7 @@ impl ActiveSession {
 
     /// Returns the deadline timestamp at which the request times out
     fn request_deadline(&self) -> Instant {
-        Instant::now() +
+        self.clock.now() +
             Duration::from_millis(self.internal_request_timeout.load(Ordering::Relaxed))
     }
 
@@ -289,7 +390,7 @@ impl ActiveSession {
     ///
     /// This will queue the response to be sent to the peer
     fn handle_outgoing_response(&mut self, id: u64, resp: PeerResponseResult) {
-        match resp.try_into_message(id) {
+        match resp.try_into_message(id, self.conn.version()) {
             Ok(msg) => {
                 self.queued_outgoing.push_back(msg.into());
             }
@@ -360,12 +461,25 @@ impl ActiveSession {
         let _ = sender.try_send(ActiveSessionMessage::BadMessage { peer_id: self.remote_peer_id });
     }
 
+    /// Notify the manager that the peer is in breach of the protocol.
+    fn on_protocol_breach(&self) {
+        let Some(sender) = self.to_session_manager.inner().get_ref() else { return };
+        let _ =
+            sender.try_send(ActiveSessionMessage::ProtocolBreach { peer_id: self.remote_peer_id });
+    }
+
     /// Report back that this session has been closed.
     fn emit_disconnect(&mut self, cx: &mut Context<'_>) -> Poll<()> {
         trace!(target: "net::session", remote_peer_id=?self.remote_peer_id, "emitting disconnect");
+        if let Some(reason) = self.disconnect_reason {
+            // we disconnected, so the peer won't respond anymore
+            self.fail_inflight_requests(RequestError::Disconnected(SessionEnd::local(reason)));
+        }
         let msg = ActiveSessionMessage::Disconnected {
             peer_id: self.remote_peer_id,
+            session_id: self.session_id,
             remote_addr: self.remote_addr,
+            reason: self.disconnect_reason,
         };
 
         self.terminate_message = Some((self.to_session_manager.inner().clone(), msg));
@@ -373,9 +487,15 @@ impl ActiveSession {
     }
 
     /// Report back that this session has been closed due to an error
-    fn close_on_error(&mut self, error: EthStreamError, cx: &mut Context<'_>) -> Poll<()> {
+    fn close_on_error(&mut self, error: impl Into<SessionError>, cx: &mut Context<'_>) -> Poll<()> {
+        let error = error.into();
+        if let Some(reason) = error.as_disconnected() {
+            // the peer won't respond anymore, so there's no point in waiting for the timeout
+            self.fail_inflight_requests(RequestError::Disconnected(SessionEnd::remote(reason)));
+        }
         let msg = ActiveSessionMessage::ClosedOnConnectionError {
             peer_id: self.remote_peer_id,
+            session_id: self.session_id,
             remote_addr: self.remote_addr,
             error,
         };
@@ -385,6 +505,7 @@ impl ActiveSession {
 
     /// Starts the disconnect process
     fn start_disconnect(&mut self, reason: DisconnectReason) -> Result<(), EthStreamError> {
+        self.disconnect_reason = Some(reason);
         self.conn
             .inner_mut()
             .start_disconnect(reason)
@@ -415,6 +536,16 @@ impl ActiveSession {
         }
     }
 
+    /// Fails all requests that are still waiting for a response from the peer with the given
+    /// error.
+    fn fail_inflight_requests(&mut self, err: RequestError) {
+        for (_, req) in self.inflight_requests.drain() {
+            if let RequestState::Waiting(request) = req.request {
+                request.send_err_response(err.clone());
+            }
+        }
+    }
+
     /// Checks for _internally_ timed out requests.
     ///
     /// If a requests misses its deadline, then it is timed out internally.
@@ -423,28 +554,53 @@ impl ActiveSession {
     ///
     /// Returns `true` if a peer missed the `protocol_breach_request_timeout`, in which case the
     /// session should be terminated.
+    ///
+    /// Requests that timed out are kept until the `protocol_breach_request_timeout`, so a late
+    /// response still updates the request timeout. After that they're evicted if the peer answered
+    /// any request since, because then the response was lost instead of the peer being stuck.
     #[must_use]
     fn check_timed_out_requests(&mut self, now: Instant) -> bool {
-        for (id, req) in &mut self.inflight_requests {
-            if req.is_timed_out(now) {
-                if req.is_waiting() {
-                    debug!(target: "net::session", ?id, remote_peer_id=?self.remote_peer_id, "timed out outgoing request");
-                    req.timeout();
-                } else if now - req.timestamp > self.protocol_breach_request_timeout {
-                    return true
-                }
+        let mut breach = false;
+        let last_answered_request = self.last_answered_request;
+        self.inflight_requests.retain(|id, req| {
+            if !req.is_timed_out(now) {
+                return true
             }
-        }
+            if req.is_waiting() {
+                debug!(target: "net::session", ?id, remote_peer_id=?self.remote_peer_id, "timed out outgoing request");
+                req.timeout();
+                return true
+            }
+            if now - req.timestamp <= self.protocol_breach_request_timeout {
+                return true
+            }
+            let answered_since =
+                last_answered_request.is_some_and(|answered| answered >= req.timestamp);
+            if !answered_since {
+                breach = true;
+                return true
+            }
+            trace!(target: "net::session", ?id, remote_peer_id=?self.remote_peer_id, "evicting timed out request");
+            false
+        });
 
-        false
+        breach
     }
 
-    /// Updates the request timeout with a request's timestamps
+    /// Records the latency of an answered request and updates the request timeout.
+    fn on_request_answered(&mut self, request: EthMessageID, sent: Instant) {
+        let received = self.clock.now();
+        self.last_answered_request = Some(received);
+        self.latencies.record(request, received.saturating_duration_since(sent));
+        self.update_request_timeout(sent, received);
+    }
+
+    /// Updates the moving average of the RTT with a request's timestamps and adapts the request
+    /// timeout to it
     fn update_request_timeout(&mut self, sent: Instant, received: Instant) {
-        let elapsed = received.saturating_duration_since(sent);
+        self.rtt = update_rtt(self.rtt, received.saturating_duration_since(sent));
 
-        let current = Duration::from_millis(self.internal_request_timeout.load(Ordering::Relaxed));
-        let request_timeout = calculate_new_timeout(current, elapsed);
+        let request_timeout = self.request_timeout.timeout(self.rtt);
         self.internal_request_timeout.store(request_timeout.as_millis() as u64, Ordering::Relaxed);
         self.internal_request_timeout_interval = tokio::time::interval(request_timeout);
     }
@@ -474,6 +630,7 @@ impl Future for ActiveSession {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
+        let _span = this.span.clone().entered();
 
         // if the session is terminate we have to send the termination message before we can close
         if let Some(terminate) = this.poll_terminate_message(cx) {
@@ -554,8 +711,19 @@ impl Future for ActiveSession {
                 if let Some(msg) = this.queued_outgoing.pop_front() {
                     progress = true;
                     let res = match msg {
-                        OutgoingMessage::Eth(msg) => this.conn.start_send_unpin(msg),
-                        OutgoingMessage::Broadcast(msg) => this.conn.start_send_broadcast(msg),
+                        OutgoingMessage::Eth(msg) => {
+                            let _span = this
+                                .message_span("outbound", msg.message_id(), msg.request_id())
+                                .entered();
+                            trace!(target: "net::session", "sending eth message");
+                            this.conn.start_send_unpin(msg)
+                        }
+                        OutgoingMessage::Broadcast(msg) => {
+                            let _span =
+                                this.message_span("outbound", msg.message_id(), None).entered();
+                            trace!(target: "net::session", "sending eth broadcast");
+                            this.conn.start_send_broadcast(msg)
+                        }
                     };
                     if let Err(err) = res {
                         debug!(target: "net::session", %err, remote_peer_id=?this.remote_peer_id, "failed to send message");
@@ -606,7 +774,12 @@ impl Future for ActiveSession {
                     Poll::Ready(Some(res)) => {
                         match res {
                             Ok(msg) => {
-                                trace!(target: "net::session", msg_id=?msg.message_id(), remote_peer_id=?this.remote_peer_id, "received eth message");
+                                *this.compression_ratio.lock() =
+                                    this.conn.inner().compression_ratio();
+                                let _span = this
+                                    .message_span("inbound", msg.message_id(), msg.request_id())
+                                    .entered();
+                                trace!(target: "net::session", "received eth message");
                                 // decode and handle message
                                 match this.on_incoming_message(msg) {
                                     OnIncomingMessageOutcome::Ok => {
@@ -625,7 +798,17 @@ impl Future for ActiveSession {
                                 }
                             }
                             Err(err) => {
-                                debug!(target: "net::session", %err, remote_peer_id=?this.remote_peer_id, "failed to receive message");
+                                let err = SessionError::from(err);
+                                debug!(target: "net::session", %err, reason=%err.recommended_disconnect_reason(), remote_peer_id=?this.remote_peer_id, "failed to receive message");
+                                if matches!(
+                                    err,
+                                    SessionError::P2P(P2PStreamError::PingerError(
+                                        PingerError::UnexpectedPong
+                                    ))
+                                ) {
+                                    // the peer keeps sending pongs without a ping
+                                    this.on_protocol_breach();
+                                }
                                 return this.close_on_error(err, cx)
                             }
                         }
@@ -638,9 +821,16 @@ impl Future for ActiveSession {
             }
         }
 
+        if let Some(usefulness) = &mut this.usefulness {
+            if usefulness.poll_useless(cx).is_ready() {
+                debug!(target: "net::session", remote_peer_id=?this.remote_peer_id, "disconnecting useless peer");
+                return this.try_disconnect(DisconnectReason::UselessPeer, cx)
+            }
+        }
+
         while this.internal_request_timeout_interval.poll_tick(cx).is_ready() {
             // check for timed out requests
-            if this.check_timed_out_requests(Instant::now()) {
+            if this.check_timed_out_requests(this.clock.now()) {
                 if let Poll::Ready(Ok(_)) = this.to_session_manager.poll_reserve(cx) {
                     let msg = ActiveSessionMessage::ProtocolBreach { peer_id: this.remote_peer_id };
                     this.pending_message_to_session = Some(msg);
@@ -654,6 +844,55 @@ impl Future for ActiveSession {
     }
 }
 
+/// Tracks whether a peer is useful, i.e. whether it answers our requests or sends us new blocks
+/// and transactions.
+///
+/// Keep-alive pings are handled by the `p2p` stream and don't count.
+#[derive(Debug)]
+pub(crate) struct UsefulnessTracker {
+    /// Fires at the end of each window.
+    window: Interval,
+    /// Responses to our requests received during the current window.
+    answered_requests: u64,
+    /// Block and transaction announcements received during the current window.
+    received_gossip: u64,
+}
+
+impl UsefulnessTracker {
+    /// Creates a new tracker with the given window, starting now.
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window: tokio::time::interval_at(tokio::time::Instant::now() + window, window),
+            answered_requests: 0,
+            received_gossip: 0,
+        }
+    }
+
+    /// Records a response to one of our requests.
+    fn on_answered_request(&mut self) {
+        self.answered_requests += 1;
+    }
+
+    /// Records a block or transaction announcement.
+    fn on_gossip(&mut self) {
+        self.received_gossip += 1;
+    }
+
+    /// Resolves at the end of a window in which the peer wasn't useful.
+    ///
+    /// Windows in which the peer was useful start a new window.
+    fn poll_useless(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        while self.window.poll_tick(cx).is_ready() {
+            if self.answered_requests == 0 && self.received_gossip == 0 {
+                return Poll::Ready(())
+            }
+            self.answered_requests = 0;
+            self.received_gossip = 0;
+        }
+        Poll::Pending
+    }
+}
+
 /// Tracks a request received from the peer
 pub(crate) struct ReceivedRequest {
     /// Protocol Identifier
@@ -747,40 +986,124 @@ impl From<EthBroadcastMessage> for OutgoingMessage {
     }
 }
 
-/// Calculates a new timeout using an updated estimation of the RTT
+/// Returns the moving average of the RTT updated with a new measurement
 #[inline]
-fn calculate_new_timeout(current_timeout: Duration, estimated_rtt: Duration) -> Duration {
-    let new_timeout = estimated_rtt.mul_f64(SAMPLE_IMPACT) * TIMEOUT_SCALING;
-
+fn update_rtt(rtt: Duration, sample: Duration) -> Duration {
     // this dampens sudden changes by taking a weighted mean of the old and new values
-    let smoothened_timeout = current_timeout.mul_f64(1.0 - SAMPLE_IMPACT) + new_timeout;
+    rtt.mul_f64(1.0 - SAMPLE_IMPACT) + sample.mul_f64(SAMPLE_IMPACT)
+}
 
-    smoothened_timeout.clamp(MINIMUM_TIMEOUT, MAXIMUM_TIMEOUT)
+/// Returns the span in which all processing of the session with the given peer happens.
+///
+/// The span carries the peer id and the negotiated `eth` and, if shared, `snap` versions, which
+/// all events emitted by the session inherit, so the lifecycle of a single peer can be followed
+/// across modules.
+pub(crate) fn session_span(peer_id: PeerId, conn: &EthRlpxConnection) -> Span {
+    debug_span!(
+        target: "net::session",
+        "session",
+        %peer_id,
+        eth_version = %conn.version(),
+        snap_version = conn.inner().shared_capabilities().snap().map(|cap| cap.version()),
+    )
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::session::{handle::PendingSessionEvent, start_pending_incoming_session};
+    use alloy_eips::BlockHashOrNumber;
+    use alloy_primitives::B256;
+    use futures::FutureExt;
     use reth_chainspec::MAINNET;
     use reth_ecies::stream::ECIESStream;
     use reth_eth_wire::{
-        EthStream, GetBlockBodies, HelloMessageWithProtocols, P2PStream, Status, StatusBuilder,
-        UnauthedEthStream, UnauthedP2PStream,
+        clock::{Clock, MockClock, SystemClock},
+        BlockBodies, BlockHashNumber, BlockHeaders, EthStream, GetBlockBodies, GetBlockHeaders,
+        GetPooledTransactions, GetReceipts, HeadersDirection, HelloMessageWithProtocols,
+        NewBlockHashes, P2PStream, Status, StatusBuilder, UnauthedEthStream, UnauthedP2PStream,
+        DEFAULT_MAX_UNSOLICITED_PONGS,
     };
     use reth_network_peers::pk2id;
-    use reth_network_types::session::config::PROTOCOL_BREACH_REQUEST_TIMEOUT;
-    use reth_primitives::{EthereumHardfork, ForkFilter};
+    use reth_network_types::session::config::{
+        INITIAL_REQUEST_TIMEOUT, PROTOCOL_BREACH_REQUEST_TIMEOUT,
+    };
+    use reth_primitives::{BlockBody, EthereumHardfork, ForkFilter, Header};
     use secp256k1::{SecretKey, SECP256K1};
+    use std::{collections::HashMap, sync::Mutex};
     use tokio::{
         net::{TcpListener, TcpStream},
         sync::mpsc,
     };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Event, Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::Context as LayerContext, prelude::*, registry::LookupSpan, Layer,
+    };
 
     /// Returns a testing `HelloMessage` and new secretkey
     fn eth_hello(server_key: &SecretKey) -> HelloMessageWithProtocols {
         HelloMessageWithProtocols::builder(pk2id(&server_key.public_key(SECP256K1))).build()
     }
 
+    /// Records the fields of every `eth_message` span that is created.
+    #[derive(Clone, Default)]
+    struct MessageSpans(Arc<Mutex<Vec<HashMap<&'static str, String>>>>);
+
+    #[derive(Default)]
+    struct SpanFields(HashMap<&'static str, String>);
+
+    impl Visit for SpanFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for MessageSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: LayerContext<'_, S>) {
+            if attrs.metadata().name() != "eth_message" {
+                return
+            }
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+
+    /// Records the fields of the enclosing `session` span of every event emitted within one.
+    #[derive(Clone, Default)]
+    struct SessionEvents(Arc<Mutex<Vec<HashMap<&'static str, String>>>>);
+
+    impl<S> Layer<S> for SessionEvents
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+            if attrs.metadata().name() != "session" {
+                return
+            }
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+            let Some(scope) = ctx.event_scope(event) else { return };
+            for span in scope {
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    self.0.lock().unwrap().push(fields.0.clone());
+                }
+            }
+        }
+    }
+
     struct SessionBuilder {
         _remote_capabilities: Arc<Capabilities>,
         active_session_tx: mpsc::Sender<ActiveSessionMessage>,
@@ -848,6 +1171,8 @@ mod tests {
                 self.status,
                 self.fork_filter.clone(),
                 Default::default(),
+                None,
+                SystemClock::shared(),
             ));
 
             let mut stream = ReceiverStream::new(pending_sessions_rx);
@@ -867,6 +1192,8 @@ mod tests {
 
                     self.to_sessions.push(commands_to_session);
 
+                    let span = session_span(peer_id, &conn);
+
                     ActiveSession {
                         next_id: 0,
                         remote_peer_id: peer_id,
@@ -890,8 +1217,20 @@ mod tests {
                         internal_request_timeout: Arc::new(AtomicU64::new(
                             INITIAL_REQUEST_TIMEOUT.as_millis() as u64,
                         )),
+                        request_timeout: Default::default(),
+                        rtt: RequestTimeoutConfig::default()
+                            .rtt_for_timeout(INITIAL_REQUEST_TIMEOUT),
                         protocol_breach_request_timeout: PROTOCOL_BREACH_REQUEST_TIMEOUT,
                         terminate_message: None,
+                        disconnect_reason: None,
+                        usefulness: None,
+                        serving_policy: Default::default(),
+                        serve_requests: true,
+                        latencies: Default::default(),
+                        compression_ratio: Default::default(),
+                        last_answered_request: None,
+                        clock: SystemClock::shared(),
+                        span,
                     }
                 }
                 ev => {
@@ -1011,6 +1350,37 @@ mod tests {
         rx.await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_compression_ratio_shared_with_handle() {
+        reth_tracing::init_test_tracing();
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // repeated announcements compress well
+            let hashes = vec![BlockHashNumber { hash: B256::ZERO, number: 0 }; 100];
+            client_stream.send(EthMessage::NewBlockHashes(NewBlockHashes(hashes))).await.unwrap();
+            let _ = tokio::time::timeout(Duration::from_secs(5), client_stream.next()).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let session = builder.connect_incoming(incoming).await;
+        let compression_ratio = Arc::clone(&session.compression_ratio);
+        assert_eq!(*compression_ratio.lock(), None);
+        tokio::spawn(session);
+
+        match builder.active_session_rx.next().await.unwrap() {
+            ActiveSessionMessage::ValidMessage {
+                message: PeerMessage::NewBlockHashes(_), ..
+            } => {}
+            ev => unreachable!("{ev:?}"),
+        }
+        assert!(compression_ratio.lock().unwrap() > 1.0);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_request_timeout() {
         reth_tracing::init_test_tracing();
@@ -1053,6 +1423,354 @@ mod tests {
         }
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_request_timeout_mock_clock() {
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |client_stream| async move {
+            let _client_stream = client_stream;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        let clock = MockClock::new();
+        session.clock = clock.shared();
+        // check often, timeouts are only decided by the clock
+        session.internal_request_timeout_interval =
+            tokio::time::interval(Duration::from_millis(10));
+        let (tx, mut rx) = oneshot::channel();
+        let req = PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx };
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(req, deadline);
+        tokio::spawn(session);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+
+        clock.advance(INITIAL_REQUEST_TIMEOUT);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(rx.await.unwrap().unwrap_err(), RequestError::Timeout);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_slow_response_after_fast_response() {
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |client_stream| async move {
+            let _client_stream = client_stream;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        let clock = MockClock::new();
+        session.clock = clock.shared();
+
+        // a small request is answered right away
+        let (tx, _rx) = oneshot::channel();
+        let request_id = session.next_id;
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(
+            PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx },
+            deadline,
+        );
+        clock.advance(Duration::from_millis(10));
+        let response =
+            EthMessage::BlockBodies(RequestPair { request_id, message: Default::default() });
+        assert!(matches!(session.on_incoming_message(response), OnIncomingMessageOutcome::Ok));
+
+        // a single sample only moves the timeout a bit
+        assert!(session.request_deadline() - clock.now() > INITIAL_REQUEST_TIMEOUT * 4 / 5);
+
+        // a large request that takes a while to be answered doesn't time out
+        let (tx, rx) = oneshot::channel();
+        let request_id = session.next_id;
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(
+            PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx },
+            deadline,
+        );
+        clock.advance(Duration::from_secs(10));
+        assert!(!session.check_timed_out_requests(clock.now()));
+        let response = EthMessage::BlockBodies(RequestPair {
+            request_id,
+            message: BlockBodies(vec![BlockBody::default(); 1000]),
+        });
+        assert!(matches!(session.on_incoming_message(response), OnIncomingMessageOutcome::Ok));
+        assert_eq!(rx.await.unwrap().unwrap().0.len(), 1000);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_timed_out_requests_evicted() {
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |client_stream| async move {
+            let _client_stream = client_stream;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        let breach_timeout = session.protocol_breach_request_timeout;
+
+        let mut receivers = Vec::new();
+        for _ in 0..10 {
+            // many requests are lost, only the last one of each round is answered
+            let mut last_id = 0;
+            for _ in 0..100 {
+                let (tx, rx) = oneshot::channel();
+                let req =
+                    PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx };
+                last_id = session.next_id;
+                session.on_internal_peer_request(req, Instant::now());
+                receivers.push(rx);
+            }
+            let sent = Instant::now();
+            assert!(!session.check_timed_out_requests(sent + Duration::from_millis(1)));
+            assert_eq!(session.inflight_requests.len(), 100);
+
+            let response = EthMessage::BlockBodies(RequestPair {
+                request_id: last_id,
+                message: Default::default(),
+            });
+            assert!(matches!(session.on_incoming_message(response), OnIncomingMessageOutcome::Ok));
+
+            // the peer is responsive, so the lost responses aren't a protocol breach
+            assert!(!session.check_timed_out_requests(sent + breach_timeout * 2));
+            assert!(session.inflight_requests.is_empty());
+        }
+
+        for rx in receivers {
+            assert_eq!(rx.await.unwrap().unwrap_err(), RequestError::Timeout);
+        }
+
+        // a late response to a timed out request that wasn't evicted yet is accepted
+        let timed_out_id = session.next_id;
+        let (tx, _rx) = oneshot::channel();
+        session.on_internal_peer_request(
+            PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx },
+            Instant::now(),
+        );
+        assert!(!session.check_timed_out_requests(Instant::now() + Duration::from_millis(1)));
+        let late = EthMessage::BlockBodies(RequestPair {
+            request_id: timed_out_id,
+            message: Default::default(),
+        });
+        assert!(matches!(session.on_incoming_message(late), OnIncomingMessageOutcome::Ok));
+        assert!(builder.active_session_rx.next().now_or_never().is_none());
+
+        // a response to an evicted request, a duplicate response, or a response to a request that
+        // was never sent is a bad message
+        for request_id in [0, timed_out_id, session.next_id] {
+            let response =
+                EthMessage::BlockBodies(RequestPair { request_id, message: Default::default() });
+            assert!(matches!(session.on_incoming_message(response), OnIncomingMessageOutcome::Ok));
+            match builder.active_session_rx.next().await.unwrap() {
+                ActiveSessionMessage::BadMessage { .. } => {}
+                ev => unreachable!("{ev:?}"),
+            }
+        }
+
+        // ids of pending requests are skipped when the ids wrap around
+        let pending_id = session.next_id;
+        let (tx, _rx) = oneshot::channel();
+        session.on_internal_peer_request(
+            PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx },
+            Instant::now(),
+        );
+        session.next_id = u64::MAX;
+        assert_eq!(session.next_id(), u64::MAX);
+        session.next_id = pending_id;
+        assert_eq!(session.next_id(), pending_id + 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_disconnect_useless_peer() {
+        reth_tracing::init_test_tracing();
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let window = Duration::from_millis(500);
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // the peer only pings, without reading the pongs, until it's disconnected
+            for _ in 0..40 {
+                client_stream.inner_mut().send_ping();
+                if client_stream.flush().await.is_err() {
+                    break
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        session.usefulness = Some(UsefulnessTracker::new(window));
+        tokio::spawn(session);
+
+        let msg = builder.active_session_rx.next().await.unwrap();
+        match msg {
+            ActiveSessionMessage::Disconnected { reason, .. } => {
+                assert_eq!(reason, Some(DisconnectReason::UselessPeer));
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_unsolicited_pongs_reported() {
+        reth_tracing::init_test_tracing();
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            for _ in 0..=DEFAULT_MAX_UNSOLICITED_PONGS {
+                client_stream.inner_mut().send_pong();
+            }
+            client_stream.flush().await.unwrap();
+            let _ = client_stream.next().await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let session = builder.connect_incoming(incoming).await;
+        tokio::spawn(session);
+
+        let msg = builder.active_session_rx.next().await.unwrap();
+        assert!(matches!(msg, ActiveSessionMessage::ProtocolBreach { .. }), "{msg:?}");
+        let msg = builder.active_session_rx.next().await.unwrap();
+        match msg {
+            ActiveSessionMessage::ClosedOnConnectionError { error, .. } => {
+                assert!(matches!(
+                    error,
+                    SessionError::P2P(P2PStreamError::PingerError(PingerError::UnexpectedPong))
+                ));
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_disconnect_fails_inflight_requests() {
+        reth_tracing::init_test_tracing();
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let expected_disconnect = DisconnectReason::TooManyPeers;
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // receive the request but disconnect instead of responding
+            let msg = client_stream.next().await.unwrap().unwrap();
+            assert!(matches!(msg, EthMessage::GetBlockHeaders(_)), "{msg:?}");
+            client_stream.into_inner().disconnect(expected_disconnect).await.unwrap();
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+
+        let (tx, rx) = oneshot::channel();
+        let req = PeerRequest::GetBlockHeaders {
+            request: GetBlockHeaders {
+                start_block: BlockHashOrNumber::Number(0),
+                limit: 1,
+                skip: 0,
+                direction: HeadersDirection::Rising,
+            },
+            response: tx,
+        };
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(req, deadline);
+        tokio::spawn(session);
+
+        // the request fails well before it would time out
+        let err = tokio::time::timeout(INITIAL_REQUEST_TIMEOUT / 2, rx)
+            .await
+            .expect("request should fail promptly")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err, RequestError::Disconnected(SessionEnd::remote(expected_disconnect)));
+
+        let msg = builder.active_session_rx.next().await.unwrap();
+        match msg {
+            ActiveSessionMessage::ClosedOnConnectionError { error, .. } => {
+                assert_eq!(error.as_disconnected(), Some(expected_disconnect));
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_disconnect_fails_inflight_requests() {
+        reth_tracing::init_test_tracing();
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let expected_disconnect = DisconnectReason::UselessPeer;
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // the disconnect clears the queued request, so the peer only sees the disconnect
+            let err = client_stream.next().await.unwrap().unwrap_err();
+            assert_eq!(err.as_disconnected(), Some(expected_disconnect));
+        });
+        let client = tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+
+        let (tx, rx) = oneshot::channel();
+        let req = PeerRequest::GetBlockHeaders {
+            request: GetBlockHeaders {
+                start_block: BlockHashOrNumber::Number(0),
+                limit: 1,
+                skip: 0,
+                direction: HeadersDirection::Rising,
+            },
+            response: tx,
+        };
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(req, deadline);
+        session.start_disconnect(expected_disconnect).unwrap();
+        tokio::spawn(session);
+
+        let err = tokio::time::timeout(INITIAL_REQUEST_TIMEOUT / 2, rx)
+            .await
+            .expect("request should fail promptly")
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err, RequestError::Disconnected(SessionEnd::local(expected_disconnect)));
+        client.await.unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_keep_alive() {
         let mut builder = SessionBuilder::default();
@@ -1137,19 +1855,321 @@ mod tests {
         }
     }
 
+    // This tests that a request/response exchange is processed within spans that carry the peer,
+    // the eth version, the message and the request id.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_message_spans() {
+        let spans = MessageSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            let request_id = match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::GetBlockBodies(RequestPair { request_id, .. }) => request_id,
+                msg => unreachable!("{msg:?}"),
+            };
+            client_stream
+                .send(EthMessage::BlockBodies(RequestPair {
+                    request_id,
+                    message: BlockBodies(vec![]),
+                }))
+                .await
+                .unwrap();
+            let _ = tokio::time::timeout(Duration::from_secs(100), client_stream.next()).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        let (tx, rx) = oneshot::channel();
+        let req = PeerRequest::GetBlockBodies { request: GetBlockBodies(vec![]), response: tx };
+        let deadline = session.request_deadline();
+        session.on_internal_peer_request(req, deadline);
+        tokio::task::spawn(session);
+
+        rx.await.unwrap().unwrap();
+
+        let spans = spans.0.lock().unwrap();
+        let find = |direction: &str, message: &str| {
+            spans
+                .iter()
+                .find(|fields| {
+                    fields.get("direction").map(String::as_str) == Some(direction) &&
+                        fields.get("message").map(String::as_str) == Some(message)
+                })
+                .cloned()
+                .unwrap_or_else(|| panic!("missing {direction} {message} span"))
+        };
+
+        for fields in [find("outbound", "GetBlockBodies"), find("inbound", "BlockBodies")] {
+            assert_eq!(fields["request_id"], "0");
+        }
+    }
+
+    // This tests that the events emitted while a session processes messages carry the peer and
+    // the negotiated versions of the session.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_session_span() {
+        let events = SessionEvents::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            client_stream
+                .send(EthMessage::GetBlockHeaders(RequestPair {
+                    request_id: 1,
+                    message: GetBlockHeaders {
+                        start_block: BlockHashOrNumber::Number(1),
+                        limit: 1,
+                        skip: 0,
+                        direction: Default::default(),
+                    },
+                }))
+                .await
+                .unwrap();
+            let _ = tokio::time::timeout(Duration::from_secs(100), client_stream.next()).await;
+        });
+        tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let session = builder.connect_incoming(incoming).await;
+        let peer_id = session.remote_peer_id;
+        let version = session.conn.version();
+        tokio::task::spawn(session);
+
+        // the request is processed by the session and forwarded to the manager
+        match builder.active_session_rx.next().await.unwrap() {
+            ActiveSessionMessage::ValidMessage { message: PeerMessage::EthRequest(_), .. } => {}
+            ev => unreachable!("{ev:?}"),
+        }
+
+        let events = events.0.lock().unwrap();
+        assert!(!events.is_empty());
+        for fields in events.iter() {
+            assert_eq!(fields["peer_id"], peer_id.to_string());
+            assert_eq!(fields["eth_version"], version.to_string());
+            // snap isn't shared with the peer
+            assert!(!fields.contains_key("snap_version"));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serving_policy() {
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let header = Header { number: 1, ..Default::default() };
+        let expected_header = header.clone();
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // receipts are not served, so the request is answered with an empty response
+            client_stream
+                .send(EthMessage::GetReceipts(RequestPair {
+                    request_id: 1,
+                    message: GetReceipts(vec![B256::random()]),
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::Receipts(RequestPair { request_id: 1, message }) => {
+                    assert!(message.0.is_empty())
+                }
+                msg => unreachable!("{msg:?}"),
+            }
+
+            // headers are still served
+            client_stream
+                .send(EthMessage::GetBlockHeaders(RequestPair {
+                    request_id: 2,
+                    message: GetBlockHeaders {
+                        start_block: BlockHashOrNumber::Number(1),
+                        limit: 1,
+                        skip: 0,
+                        direction: HeadersDirection::Rising,
+                    },
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::BlockHeaders(RequestPair { request_id: 2, message }) => {
+                    assert_eq!(message.0, vec![expected_header])
+                }
+                msg => unreachable!("{msg:?}"),
+            }
+        });
+        let client = tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        session.serving_policy = ServingPolicy::serve_all().with_receipts(false);
+        tokio::spawn(session);
+
+        // only the headers request is delegated
+        match builder.active_session_rx.next().await.unwrap() {
+            ActiveSessionMessage::ValidMessage {
+                message: PeerMessage::EthRequest(PeerRequest::GetBlockHeaders { response, .. }),
+                ..
+            } => {
+                response.send(Ok(BlockHeaders(vec![header]))).unwrap();
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+
+        client.await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_requests_disabled() {
+        let mut builder = SessionBuilder::default();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_addr = listener.local_addr().unwrap();
+
+        let hash = B256::random();
+        let fut = builder.with_client_stream(local_addr, move |mut client_stream| async move {
+            // all requests are answered with empty responses
+            client_stream
+                .send(EthMessage::GetBlockHeaders(RequestPair {
+                    request_id: 1,
+                    message: GetBlockHeaders {
+                        start_block: BlockHashOrNumber::Number(1),
+                        limit: 1,
+                        skip: 0,
+                        direction: HeadersDirection::Rising,
+                    },
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::BlockHeaders(RequestPair { request_id: 1, message }) => {
+                    assert!(message.0.is_empty())
+                }
+                msg => unreachable!("{msg:?}"),
+            }
+
+            client_stream
+                .send(EthMessage::GetBlockBodies(RequestPair {
+                    request_id: 2,
+                    message: GetBlockBodies(vec![B256::random()]),
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::BlockBodies(RequestPair { request_id: 2, message }) => {
+                    assert!(message.0.is_empty())
+                }
+                msg => unreachable!("{msg:?}"),
+            }
+
+            client_stream
+                .send(EthMessage::GetReceipts(RequestPair {
+                    request_id: 3,
+                    message: GetReceipts(vec![B256::random()]),
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::Receipts(RequestPair { request_id: 3, message }) => {
+                    assert!(message.0.is_empty())
+                }
+                msg => unreachable!("{msg:?}"),
+            }
+
+            // except for the announced transactions
+            client_stream
+                .send(EthMessage::GetPooledTransactions(RequestPair {
+                    request_id: 4,
+                    message: GetPooledTransactions(vec![hash]),
+                }))
+                .await
+                .unwrap();
+            match client_stream.next().await.unwrap().unwrap() {
+                EthMessage::PooledTransactions(RequestPair { request_id: 4, .. }) => {}
+                msg => unreachable!("{msg:?}"),
+            }
+        });
+        let client = tokio::task::spawn(fut);
+
+        let (incoming, _) = listener.accept().await.unwrap();
+        let mut session = builder.connect_incoming(incoming).await;
+        // overrides the policy that serves everything
+        session.serving_policy = ServingPolicy::serve_all();
+        session.serve_requests = false;
+        tokio::spawn(session);
+
+        // only the pooled transactions request is delegated
+        match builder.active_session_rx.next().await.unwrap() {
+            ActiveSessionMessage::ValidMessage {
+                message:
+                    PeerMessage::EthRequest(PeerRequest::GetPooledTransactions { request, response }),
+                ..
+            } => {
+                assert_eq!(request.0, vec![hash]);
+                response.send(Ok(Default::default())).unwrap();
+            }
+            ev => unreachable!("{ev:?}"),
+        }
+
+        client.await.unwrap();
+        assert!(builder.active_session_rx.next().now_or_never().is_none());
+    }
+
     #[test]
     fn timeout_calculation_sanity_tests() {
         let rtt = Duration::from_secs(5);
-        // timeout for an RTT of `rtt`
-        let timeout = rtt * TIMEOUT_SCALING;
 
-        // if rtt hasn't changed, timeout shouldn't change
-        assert_eq!(calculate_new_timeout(timeout, rtt), timeout);
+        // if rtt hasn't changed, the average shouldn't change
+        assert_eq!(update_rtt(rtt, rtt), rtt);
+
+        // if rtt changed, the average should change less than it
+        assert!(update_rtt(rtt, rtt / 2) < rtt);
+        assert!(update_rtt(rtt, rtt / 2) > rtt / 2);
+        assert!(update_rtt(rtt, rtt * 2) > rtt);
+        assert!(update_rtt(rtt, rtt * 2) < rtt * 2);
+    }
+
+    #[test]
+    fn adaptive_timeout_tracks_rtt() {
+        let config = RequestTimeoutConfig::default()
+            .with_min(Duration::from_millis(500))
+            .with_max(Duration::from_secs(10))
+            .with_rtt_scaling(3)
+            .with_margin(Duration::from_millis(100));
+        let mut rtt = config.rtt_for_timeout(config.max);
+        let mut feed = |sample: Duration, count: usize| {
+            for _ in 0..count {
+                rtt = update_rtt(rtt, sample);
+            }
+            config.timeout(rtt)
+        };
+
+        // a single fast response barely moves the timeout
+        let timeout = feed(Duration::from_millis(10), 1);
+        assert!(timeout > Duration::from_secs(9) && timeout < config.max);
+
+        // a distant but healthy peer gets more time than the fast ones
+        let timeout = feed(Duration::from_secs(1), 100);
+        assert!(timeout >= Duration::from_millis(3100) && timeout < Duration::from_millis(3110));
+
+        // the timeout follows the peer once it gets faster
+        let timeout = feed(Duration::from_millis(200), 100);
+        assert!(timeout >= Duration::from_millis(700) && timeout < Duration::from_millis(710));
+
+        // a fast peer is clamped to the lower bound, so it fails fast once it stalls
+        assert_eq!(feed(Duration::from_millis(5), 100), config.min);
 
-        // if rtt changed, the new timeout should change less than it
-        assert!(calculate_new_timeout(timeout, rtt / 2) < timeout);
-        assert!(calculate_new_timeout(timeout, rtt / 2) > timeout / 2);
-        assert!(calculate_new_timeout(timeout, rtt * 2) > timeout);
-        assert!(calculate_new_timeout(timeout, rtt * 2) < timeout * 2);
+        // a very slow peer is clamped to the upper bound
+        assert_eq!(feed(Duration::from_secs(30), 100), config.max);
     }
 }
@@ -2,10 +2,14 @@
 
 use std::{io, net::SocketAddr, sync::Arc, time::Instant};
 
+use parking_lot::Mutex;
+
+use alloy_primitives::B256;
 use reth_ecies::ECIESError;
 use reth_eth_wire::{
-    capability::CapabilityMessage, errors::EthStreamError, Capabilities, DisconnectReason,
-    EthVersion, Status,
+    capability::{CapabilityMessage, SharedCapabilities, SharedCapability},
+    errors::SessionError,
+    Capabilities, Capability, DisconnectReason, EthVersion, NegotiatedEth, Status,
 };
 use reth_network_api::PeerInfo;
 use reth_network_peers::{NodeRecord, PeerId};
@@ -17,7 +21,7 @@ use tokio::sync::{
 
 use crate::{
     message::PeerMessage,
-    session::{conn::EthRlpxConnection, Direction, SessionId},
+    session::{conn::EthRlpxConnection, Direction, HandshakeTiming, RequestLatencies, SessionId},
     PendingSessionHandshakeError,
 };
 
@@ -67,6 +71,8 @@ pub struct ActiveSessionHandle {
     pub(crate) established: Instant,
     /// Announced capabilities of the peer.
     pub(crate) capabilities: Arc<Capabilities>,
+    /// Capabilities negotiated with the peer.
+    pub(crate) shared_capabilities: Arc<SharedCapabilities>,
     /// Sender half of the command channel used send commands _to_ the spawned session
     pub(crate) commands_to_session: mpsc::Sender<SessionCommand>,
     /// The client's name and version
@@ -77,6 +83,11 @@ pub struct ActiveSessionHandle {
     pub(crate) local_addr: Option<SocketAddr>,
     /// The Status message the peer sent for the `eth` handshake
     pub(crate) status: Arc<Status>,
+    /// The round-trip latencies of the requests sent to the peer, updated by the session.
+    pub(crate) latencies: Arc<RequestLatencies>,
+    /// The moving average of the compression ratio of the messages received from the peer,
+    /// updated by the session.
+    pub(crate) compression_ratio: Arc<Mutex<Option<f64>>>,
 }
 
 // === impl ActiveSessionHandle ===
@@ -112,6 +123,11 @@ impl ActiveSessionHandle {
         self.version
     }
 
+    /// Returns the parameters agreed on with the peer in the `eth` handshake.
+    pub fn negotiated(&self) -> NegotiatedEth {
+        NegotiatedEth::new(self.version, &self.status)
+    }
+
     /// Returns the identifier of the remote peer.
     pub const fn remote_id(&self) -> PeerId {
         self.remote_id
@@ -127,6 +143,16 @@ impl ActiveSessionHandle {
         self.capabilities.clone()
     }
 
+    /// Returns the capabilities negotiated with the peer, with their message id offsets.
+    pub fn shared_capabilities(&self) -> &[SharedCapability] {
+        &self.shared_capabilities
+    }
+
+    /// Returns `true` if the given capability was negotiated with the peer, in that version.
+    pub fn supports_capability(&self, cap: &Capability) -> bool {
+        self.shared_capabilities.contains(cap)
+    }
+
     /// Returns the client's name and version.
     pub fn client_version(&self) -> Arc<str> {
         self.client_version.clone()
@@ -137,8 +163,28 @@ impl ActiveSessionHandle {
         self.remote_addr
     }
 
+    /// Returns the round-trip latencies of the requests sent to the peer, per request type.
+    pub fn request_latencies(&self) -> &RequestLatencies {
+        &self.latencies
+    }
+
+    /// Returns the moving average of the compression ratio of the messages received from the
+    /// peer, or `None` if no message was received yet.
+    ///
+    /// See also [`P2PStream::compression_ratio`](reth_eth_wire::P2PStream::compression_ratio).
+    pub fn compression_ratio(&self) -> Option<f64> {
+        *self.compression_ratio.lock()
+    }
+
     /// Extracts the [`PeerInfo`] from the session handle.
-    pub(crate) fn peer_info(&self, record: &NodeRecord, kind: PeerKind) -> PeerInfo {
+    ///
+    /// If the best block of the peer isn't known, the block of the peer's `Status` is used.
+    pub(crate) fn peer_info(
+        &self,
+        record: &NodeRecord,
+        kind: PeerKind,
+        best_block: Option<B256>,
+    ) -> PeerInfo {
         PeerInfo {
             remote_id: self.remote_id,
             direction: self.direction,
@@ -149,8 +195,10 @@ impl ActiveSessionHandle {
             capabilities: self.capabilities.clone(),
             client_version: self.client_version.clone(),
             eth_version: self.version,
+            best_block: best_block.unwrap_or(self.status.blockhash),
             status: self.status.clone(),
             session_established: self.established,
+            compression_ratio: self.compression_ratio(),
             kind,
         }
     }
@@ -184,6 +232,8 @@ pub enum PendingSessionEvent {
         direction: Direction,
         /// The remote node's user agent, usually containing the client name and version
         client_id: String,
+        /// How long each phase of the handshake took
+        timing: HandshakeTiming,
     },
     /// Handshake unsuccessful, session was disconnected.
     Disconnected {
@@ -240,17 +290,23 @@ pub enum ActiveSessionMessage {
     Disconnected {
         /// The remote node's public key
         peer_id: PeerId,
+        /// The id of the session.
+        session_id: SessionId,
         /// The remote node's socket address
         remote_addr: SocketAddr,
+        /// The reason we disconnected the peer with, `None` if the peer closed the connection.
+        reason: Option<DisconnectReason>,
     },
     /// Session was closed due an error
     ClosedOnConnectionError {
         /// The remote node's public key
         peer_id: PeerId,
+        /// The id of the session.
+        session_id: SessionId,
         /// The remote node's socket address
         remote_addr: SocketAddr,
         /// The error that caused the session to close
-        error: EthStreamError,
+        error: SessionError,
     },
     /// A session received a valid message via `RLPx`.
     ValidMessage {
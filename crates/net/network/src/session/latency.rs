@@ -0,0 +1,192 @@
+//! Round-trip latencies of the requests sent to a peer.
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reth_eth_wire::message::EthMessageID;
+
+/// Number of buckets of a [`LatencyHistogram`].
+///
+/// Bucket `i` counts latencies below `2^i` milliseconds, the last bucket also counts all larger
+/// latencies.
+const NUM_BUCKETS: usize = 18;
+
+/// Number of samples after which the counts of a [`LatencyHistogram`] are halved, so that older
+/// samples fade out.
+const WINDOW: u32 = 256;
+
+/// Impact of a new sample on the moving average.
+const SAMPLE_IMPACT: f64 = 0.1;
+
+/// Number of request types whose latencies are tracked, see [`request_index`].
+const NUM_REQUEST_TYPES: usize = 5;
+
+/// A histogram of request latencies with exponentially sized buckets, and a moving average.
+///
+/// The memory is bounded regardless of the number of samples. Once the histogram holds
+/// [`WINDOW`] samples, all counts are halved, so the percentiles mostly reflect recent requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Number of samples per bucket.
+    buckets: [u32; NUM_BUCKETS],
+    /// Number of samples in all buckets.
+    samples: u32,
+    /// Exponentially weighted moving average of all samples.
+    ewma: Option<Duration>,
+}
+
+impl LatencyHistogram {
+    /// Records the round-trip latency of a request.
+    pub fn record(&mut self, latency: Duration) {
+        let millis = latency.as_millis();
+        let bucket = (u128::BITS - millis.leading_zeros()) as usize;
+        self.buckets[bucket.min(NUM_BUCKETS - 1)] += 1;
+        self.samples += 1;
+
+        if self.samples >= WINDOW {
+            self.buckets.iter_mut().for_each(|count| *count /= 2);
+            self.samples = self.buckets.iter().sum();
+        }
+
+        self.ewma = Some(self.ewma.map_or(latency, |ewma| {
+            ewma.mul_f64(1.0 - SAMPLE_IMPACT) + latency.mul_f64(SAMPLE_IMPACT)
+        }));
+    }
+
+    /// Returns the moving average of the latency, or `None` if nothing was recorded.
+    pub const fn ewma(&self) -> Option<Duration> {
+        self.ewma
+    }
+
+    /// Returns the number of samples in the histogram.
+    pub const fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Returns an upper bound of the given percentile, in `[0, 1]`, of the latencies in the
+    /// histogram, or `None` if it's empty.
+    ///
+    /// This is the upper bound of the bucket the percentile falls into, so it's at most twice the
+    /// actual latency.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples == 0 {
+            return None
+        }
+        let rank = ((self.samples as f64 * percentile.clamp(0.0, 1.0)).ceil() as u32).max(1);
+        let mut seen = 0;
+        let bucket = self
+            .buckets
+            .iter()
+            .position(|count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(NUM_BUCKETS - 1);
+        Some(Duration::from_millis(1 << bucket))
+    }
+}
+
+/// The [`LatencyHistogram`]s of the requests sent to a peer, per request type.
+///
+/// This is updated by the session and shared with the [`StateFetcher`](crate::fetch::StateFetcher)
+/// and the session's [`ActiveSessionHandle`](super::ActiveSessionHandle).
+#[derive(Debug, Default)]
+pub struct RequestLatencies {
+    histograms: Mutex<[LatencyHistogram; NUM_REQUEST_TYPES]>,
+}
+
+impl RequestLatencies {
+    /// Records the round-trip latency of a request of the given type.
+    ///
+    /// Latencies of messages that aren't requests are ignored.
+    pub fn record(&self, request: EthMessageID, latency: Duration) {
+        if let Some(index) = request_index(request) {
+            self.histograms.lock()[index].record(latency);
+        }
+    }
+
+    /// Returns a copy of the histogram of the given request type.
+    pub fn histogram(&self, request: EthMessageID) -> Option<LatencyHistogram> {
+        request_index(request).map(|index| self.histograms.lock()[index].clone())
+    }
+
+    /// Returns the moving average of the latency of the given request type, or `None` if no
+    /// request of this type was answered yet.
+    pub fn ewma(&self, request: EthMessageID) -> Option<Duration> {
+        request_index(request).and_then(|index| self.histograms.lock()[index].ewma())
+    }
+}
+
+/// Returns the index of the histogram of a request type.
+const fn request_index(request: EthMessageID) -> Option<usize> {
+    match request {
+        EthMessageID::GetBlockHeaders => Some(0),
+        EthMessageID::GetBlockBodies => Some(1),
+        EthMessageID::GetPooledTransactions => Some(2),
+        EthMessageID::GetNodeData => Some(3),
+        EthMessageID::GetReceipts => Some(4),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.percentile(0.5), None);
+        assert_eq!(histogram.ewma(), None);
+
+        // 9 fast requests and a slow one
+        for _ in 0..9 {
+            histogram.record(Duration::from_millis(20));
+        }
+        histogram.record(Duration::from_millis(900));
+
+        assert_eq!(histogram.samples(), 10);
+        assert_eq!(histogram.percentile(0.5), Some(Duration::from_millis(32)));
+        assert_eq!(histogram.percentile(0.9), Some(Duration::from_millis(32)));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_millis(1024)));
+
+        // the average moves towards the slow sample, but stays close to the fast ones
+        let ewma = histogram.ewma().unwrap();
+        assert!(ewma > Duration::from_millis(20) && ewma < Duration::from_millis(200), "{ewma:?}");
+
+        // latencies beyond the largest bucket are counted in the last bucket
+        histogram.record(Duration::from_secs(1000));
+        assert_eq!(histogram.percentile(1.0), Some(Duration::from_millis(1 << (NUM_BUCKETS - 1))));
+    }
+
+    #[test]
+    fn test_latency_histogram_window() {
+        let mut histogram = LatencyHistogram::default();
+        for _ in 0..WINDOW {
+            histogram.record(Duration::from_millis(500));
+        }
+        assert!(histogram.samples() < WINDOW);
+
+        // recent fast requests dominate once the slow ones were halved a few times
+        for _ in 0..4 * WINDOW {
+            histogram.record(Duration::from_millis(3));
+        }
+        assert!(histogram.samples() < WINDOW);
+        assert_eq!(histogram.percentile(0.9), Some(Duration::from_millis(4)));
+        assert!(histogram.ewma().unwrap() < Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_request_latencies() {
+        let latencies = RequestLatencies::default();
+        latencies.record(EthMessageID::GetBlockHeaders, Duration::from_millis(10));
+        latencies.record(EthMessageID::GetBlockBodies, Duration::from_millis(100));
+        latencies.record(EthMessageID::Status, Duration::from_millis(1));
+
+        assert_eq!(latencies.ewma(EthMessageID::GetBlockHeaders), Some(Duration::from_millis(10)));
+        assert_eq!(latencies.ewma(EthMessageID::GetBlockBodies), Some(Duration::from_millis(100)));
+        assert_eq!(latencies.ewma(EthMessageID::GetReceipts), None);
+        assert_eq!(latencies.histogram(EthMessageID::Status), None);
+        assert_eq!(latencies.histogram(EthMessageID::GetBlockHeaders).unwrap().samples(), 1);
+    }
+}
@@ -4,17 +4,24 @@ mod active;
 mod conn;
 mod counter;
 mod handle;
+mod latency;
+mod pool;
+mod registry;
+mod timing;
 
 pub use conn::EthRlpxConnection;
 pub use handle::{
     ActiveSessionHandle, ActiveSessionMessage, PendingSessionEvent, PendingSessionHandle,
     SessionCommand,
 };
+pub use latency::{LatencyHistogram, RequestLatencies};
+pub use registry::SessionRegistry;
+pub use timing::HandshakeTiming;
 
 pub use reth_network_api::{Direction, PeerInfo};
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     future::Future,
     net::SocketAddr,
     sync::{atomic::AtomicU64, Arc},
@@ -26,14 +33,17 @@ use counter::SessionCounter;
 use futures::{future::Either, io, FutureExt, StreamExt};
 use reth_ecies::{stream::ECIESStream, ECIESError};
 use reth_eth_wire::{
-    capability::CapabilityMessage, errors::EthStreamError, multiplex::RlpxProtocolMultiplexer,
-    Capabilities, DisconnectReason, EthVersion, HelloMessageWithProtocols, Status,
-    UnauthedEthStream, UnauthedP2PStream,
+    capability::CapabilityMessage,
+    clock::{SharedClock, SystemClock},
+    errors::{EthStreamError, SessionError},
+    multiplex::RlpxProtocolMultiplexer,
+    Capabilities, Capability, DisconnectReason, EthVersion, HelloMessageWithProtocols, Status,
+    SubprotocolDisconnectReasons, UnauthedEthStream, UnauthedP2PStream,
 };
 use reth_metrics::common::mpsc::MeteredPollSender;
 use reth_network_api::PeerRequestSender;
 use reth_network_peers::PeerId;
-use reth_network_types::SessionsConfig;
+use reth_network_types::{RequestTimeoutConfig, ServingPolicy, SessionsConfig};
 use reth_primitives::{ForkFilter, ForkId, ForkTransition, Head};
 use reth_tasks::TaskSpawner;
 use rustc_hash::FxHashMap;
@@ -49,9 +59,13 @@ use tracing::{debug, instrument, trace};
 
 use crate::{
     message::PeerMessage,
-    metrics::SessionManagerMetrics,
+    metrics::{DisconnectCounts, SessionLifetimeStats, SessionLifetimes, SessionManagerMetrics},
     protocol::{IntoRlpxSubProtocol, RlpxSubProtocolHandlers, RlpxSubProtocols},
-    session::active::ActiveSession,
+    session::{
+        active::{session_span, ActiveSession, UsefulnessTracker},
+        pool::HandshakePool,
+        timing::HandshakeTimer,
+    },
 };
 
 /// Internal identifier for active sessions.
@@ -69,11 +83,19 @@ pub struct SessionManager {
     ///  The maximum initial time an [`ActiveSession`] waits for a response from the peer before it
     /// responds to an _internal_ request with a `TimeoutError`
     initial_internal_request_timeout: Duration,
+    /// How the request timeout of an [`ActiveSession`] adapts to the round-trip time of the peer.
+    request_timeout: RequestTimeoutConfig,
     /// If an [`ActiveSession`] does not receive a response at all within this duration then it is
     /// considered a protocol violation and the session will initiate a drop.
     protocol_breach_request_timeout: Duration,
     /// The timeout after which a pending session attempt is considered failed.
     pending_session_timeout: Duration,
+    /// The window after which an [`ActiveSession`] disconnects a peer that wasn't useful.
+    useless_peer_timeout: Option<Duration>,
+    /// The requests of peers that an [`ActiveSession`] serves.
+    serving_policy: ServingPolicy,
+    /// Whether an [`ActiveSession`] serves requests of peers at all.
+    serve_requests: bool,
     /// The secret key used for authenticating sessions.
     secret_key: SecretKey,
     /// The `Status` message to send to peers.
@@ -86,13 +108,21 @@ pub struct SessionManager {
     session_command_buffer: usize,
     /// The executor for spawned tasks.
     executor: Box<dyn TaskSpawner>,
+    /// The dedicated pool the `ECIES` crypto of pending sessions runs on, if configured.
+    ///
+    /// Without one, the crypto runs inline on the tasks of the pending sessions.
+    handshake_pool: Option<HandshakePool>,
     /// All pending session that are currently handshaking, exchanging `Hello`s.
     ///
     /// Events produced during the authentication phase are reported to this manager. Once the
     /// session is authenticated, it can be moved to the `active_session` set.
     pending_sessions: FxHashMap<SessionId, PendingSessionHandle>,
+    /// The pending outgoing sessions, keyed by the peer they're dialing.
+    ///
+    /// This is used to skip dialing a peer that is already being dialed.
+    dialing: FxHashMap<PeerId, SessionId>,
     /// All active sessions that are ready to exchange messages.
-    active_sessions: HashMap<PeerId, ActiveSessionHandle>,
+    active_sessions: SessionRegistry,
     /// The original Sender half of the [`PendingSessionEvent`] channel.
     ///
     /// When a new (pending) session is created, the corresponding [`PendingSessionHandle`] will
@@ -109,8 +139,19 @@ pub struct SessionManager {
     active_session_rx: ReceiverStream<ActiveSessionMessage>,
     /// Additional `RLPx` sub-protocols to be used by the session manager.
     extra_protocols: RlpxSubProtocols,
+    /// Interprets the codes of subprotocol specific disconnects, set on the stream of every
+    /// established session.
+    subprotocol_disconnect_reasons: SubprotocolDisconnectReasons,
     /// Metrics for the session manager.
     metrics: SessionManagerMetrics,
+    /// Total number of disconnects per reason since start.
+    disconnect_counts: DisconnectCounts,
+    /// Lifetimes of closed sessions per disconnect reason.
+    session_lifetimes: SessionLifetimes,
+    /// Events that are returned before polling the sessions again.
+    queued_events: VecDeque<SessionEvent>,
+    /// Time source of the handshake timings and the sessions.
+    clock: SharedClock,
 }
 
 // === impl SessionManager ===
@@ -130,27 +171,39 @@ impl SessionManager {
         let (pending_sessions_tx, pending_sessions_rx) = mpsc::channel(config.session_event_buffer);
         let (active_session_tx, active_session_rx) = mpsc::channel(config.session_event_buffer);
         let active_session_tx = PollSender::new(active_session_tx);
+        let handshake_pool = config.handshake_threads.map(HandshakePool::new);
 
         Self {
             next_id: 0,
             counter: SessionCounter::new(config.limits),
             initial_internal_request_timeout: config.initial_internal_request_timeout,
+            request_timeout: config.request_timeout,
             protocol_breach_request_timeout: config.protocol_breach_request_timeout,
             pending_session_timeout: config.pending_session_timeout,
+            useless_peer_timeout: config.useless_peer_timeout,
+            serving_policy: config.serving_policy,
+            serve_requests: config.serve_requests,
             secret_key,
             status,
             hello_message,
             fork_filter,
             session_command_buffer: config.session_command_buffer,
             executor,
+            handshake_pool,
             pending_sessions: Default::default(),
+            dialing: Default::default(),
             active_sessions: Default::default(),
             pending_sessions_tx,
             pending_session_rx: ReceiverStream::new(pending_sessions_rx),
             active_session_tx: MeteredPollSender::new(active_session_tx, "network_active_session"),
             active_session_rx: ReceiverStream::new(active_session_rx),
             extra_protocols,
+            subprotocol_disconnect_reasons: Default::default(),
             metrics: Default::default(),
+            disconnect_counts: Default::default(),
+            session_lifetimes: Default::default(),
+            queued_events: Default::default(),
+            clock: SystemClock::shared(),
         }
     }
 
@@ -178,10 +231,34 @@ impl SessionManager {
     }
 
     /// Returns a borrowed reference to the active sessions.
-    pub const fn active_sessions(&self) -> &HashMap<PeerId, ActiveSessionHandle> {
+    pub const fn active_sessions(&self) -> &SessionRegistry {
         &self.active_sessions
     }
 
+    /// Returns the peers of all active sessions that negotiated the given capability.
+    pub fn peers_with_capability(&self, cap: &Capability) -> Vec<PeerId> {
+        self.active_sessions
+            .iter()
+            .filter(|(_, session)| session.supports_capability(cap))
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+
+    /// Returns the number of disconnects per [`DisconnectReason`] since start.
+    ///
+    /// This is a copy of the current counts, taking it doesn't affect the counters.
+    pub fn disconnect_counts(&self) -> HashMap<DisconnectReason, u64> {
+        self.disconnect_counts.snapshot()
+    }
+
+    /// Returns how long the closed sessions lasted, by the [`DisconnectReason`] they were closed
+    /// with.
+    ///
+    /// Sessions that were closed without a disconnect reason are recorded under `None`.
+    pub fn session_lifetimes(&self) -> HashMap<Option<DisconnectReason>, SessionLifetimeStats> {
+        self.session_lifetimes.snapshot()
+    }
+
     /// Returns the session hello message.
     pub fn hello_message(&self) -> HelloMessageWithProtocols {
         self.hello_message.clone()
@@ -192,6 +269,15 @@ impl SessionManager {
         self.extra_protocols.push(protocol)
     }
 
+    /// Sets the mappings that interpret the disconnect codes of subprotocols on the streams of
+    /// established sessions.
+    pub(crate) fn set_subprotocol_disconnect_reasons(
+        &mut self,
+        reasons: SubprotocolDisconnectReasons,
+    ) {
+        self.subprotocol_disconnect_reasons = reasons;
+    }
+
     /// Returns the number of currently pending connections.
     #[inline]
     pub(crate) fn num_pending_connections(&self) -> usize {
@@ -263,6 +349,8 @@ impl SessionManager {
                 status,
                 fork_filter,
                 extra_handlers,
+                self.handshake_pool.clone(),
+                Arc::clone(&self.clock),
             ),
         ));
 
@@ -276,54 +364,69 @@ impl SessionManager {
     }
 
     /// Starts a new pending session from the local node to the given remote node.
-    pub fn dial_outbound(&mut self, remote_addr: SocketAddr, remote_peer_id: PeerId) {
-        // The error can be dropped because no dial will be made if it would exceed the limit
-        if self.counter.ensure_pending_outbound().is_ok() {
-            let session_id = self.next_id();
-            let (disconnect_tx, disconnect_rx) = oneshot::channel();
-            let pending_events = self.pending_sessions_tx.clone();
-            let secret_key = self.secret_key;
-            let hello_message = self.hello_message.clone();
-            let fork_filter = self.fork_filter.clone();
-            let status = self.status;
-            let extra_handlers = self.extra_protocols.on_outgoing(remote_addr, remote_peer_id);
-            self.spawn(pending_session_with_timeout(
-                self.pending_session_timeout,
+    ///
+    /// No new session is started if the remote node is already being dialed, or if it would exceed
+    /// the pending outbound session limit.
+    pub fn dial_outbound(
+        &mut self,
+        remote_addr: SocketAddr,
+        remote_peer_id: PeerId,
+    ) -> Result<SessionId, DialError> {
+        if let Some(session_id) = self.dialing.get(&remote_peer_id) {
+            return Err(DialError::AlreadyDialing(*session_id))
+        }
+        self.counter.ensure_pending_outbound()?;
+
+        let session_id = self.next_id();
+        let (disconnect_tx, disconnect_rx) = oneshot::channel();
+        let pending_events = self.pending_sessions_tx.clone();
+        let secret_key = self.secret_key;
+        let hello_message = self.hello_message.clone();
+        let fork_filter = self.fork_filter.clone();
+        let status = self.status;
+        let extra_handlers = self.extra_protocols.on_outgoing(remote_addr, remote_peer_id);
+        self.spawn(pending_session_with_timeout(
+            self.pending_session_timeout,
+            session_id,
+            remote_addr,
+            Direction::Outgoing(remote_peer_id),
+            pending_events.clone(),
+            start_pending_outbound_session(
+                disconnect_rx,
+                pending_events,
                 session_id,
                 remote_addr,
-                Direction::Outgoing(remote_peer_id),
-                pending_events.clone(),
-                start_pending_outbound_session(
-                    disconnect_rx,
-                    pending_events,
-                    session_id,
-                    remote_addr,
-                    remote_peer_id,
-                    secret_key,
-                    hello_message,
-                    status,
-                    fork_filter,
-                    extra_handlers,
-                ),
-            ));
-
-            let handle = PendingSessionHandle {
-                disconnect_tx: Some(disconnect_tx),
-                direction: Direction::Outgoing(remote_peer_id),
-            };
-            self.pending_sessions.insert(session_id, handle);
-            self.counter.inc_pending_outbound();
-        }
+                remote_peer_id,
+                secret_key,
+                hello_message,
+                status,
+                fork_filter,
+                extra_handlers,
+                self.handshake_pool.clone(),
+                Arc::clone(&self.clock),
+            ),
+        ));
+
+        let handle = PendingSessionHandle {
+            disconnect_tx: Some(disconnect_tx),
+            direction: Direction::Outgoing(remote_peer_id),
+        };
+        self.pending_sessions.insert(session_id, handle);
+        self.dialing.insert(remote_peer_id, session_id);
+        self.counter.inc_pending_outbound();
+        Ok(session_id)
     }
 
     /// Initiates a shutdown of the channel.
     ///
     /// This will trigger the disconnect on the session task to gracefully terminate. The result
     /// will be picked up by the receiver.
-    pub fn disconnect(&self, node: PeerId, reason: Option<DisconnectReason>) {
-        if let Some(session) = self.active_sessions.get(&node) {
-            session.disconnect(reason);
-        }
+    ///
+    /// Returns `false` if there's no active session with the peer.
+    pub fn disconnect(&self, node: PeerId, reason: Option<DisconnectReason>) -> bool {
+        let Some(session) = self.active_sessions.get(&node) else { return false };
+        session.disconnect(reason);
+        true
     }
 
     /// Initiates a shutdown of all sessions.
@@ -331,7 +434,7 @@ impl SessionManager {
     /// It will trigger the disconnect on all the session tasks to gracefully terminate. The result
     /// will be picked by the receiver.
     pub fn disconnect_all(&self, reason: Option<DisconnectReason>) {
-        for session in self.active_sessions.values() {
+        for session in self.active_sessions.handles() {
             session.disconnect(reason);
         }
     }
@@ -364,6 +467,11 @@ impl SessionManager {
     /// Removes the [`PendingSessionHandle`] if it exists.
     fn remove_pending_session(&mut self, id: &SessionId) -> Option<PendingSessionHandle> {
         let session = self.pending_sessions.remove(id)?;
+        if let Direction::Outgoing(peer_id) = session.direction {
+            if self.dialing.get(&peer_id) == Some(id) {
+                self.dialing.remove(&peer_id);
+            }
+        }
         self.counter.dec_pending(&session.direction);
         Some(session)
     }
@@ -372,6 +480,9 @@ impl SessionManager {
     fn remove_active_session(&mut self, id: &PeerId) -> Option<ActiveSessionHandle> {
         let session = self.active_sessions.remove(id)?;
         self.counter.dec_active(&session.direction);
+        if let Some(ratio) = session.compression_ratio() {
+            self.metrics.session_compression_ratio.record(ratio);
+        }
         Some(session)
     }
 
@@ -379,48 +490,82 @@ impl SessionManager {
     ///
     /// Active sessions are prioritized.
     pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<SessionEvent> {
+        if let Some(event) = self.queued_events.pop_front() {
+            return Poll::Ready(event)
+        }
+
         // Poll events from active sessions
-        match self.active_session_rx.poll_next_unpin(cx) {
-            Poll::Pending => {}
-            Poll::Ready(None) => {
-                unreachable!("Manager holds both channel halves.")
+        loop {
+            let event = match self.active_session_rx.poll_next_unpin(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None) => {
+                    unreachable!("Manager holds both channel halves.")
+                }
+                Poll::Ready(Some(event)) => event,
+            };
+
+            // a session that was replaced by another session to the same peer is already removed
+            if let ActiveSessionMessage::Disconnected { peer_id, session_id, .. } |
+            ActiveSessionMessage::ClosedOnConnectionError { peer_id, session_id, .. } = &event
+            {
+                if self.active_sessions.get(peer_id).map(|s| s.session_id) != Some(*session_id) {
+                    trace!(
+                        target: "net::session",
+                        ?peer_id,
+                        ?session_id,
+                        "closed replaced session."
+                    );
+                    continue
+                }
             }
-            Poll::Ready(Some(event)) => {
-                return match event {
-                    ActiveSessionMessage::Disconnected { peer_id, remote_addr } => {
-                        trace!(
-                            target: "net::session",
-                            ?peer_id,
-                            "gracefully disconnected active session."
-                        );
-                        self.remove_active_session(&peer_id);
-                        Poll::Ready(SessionEvent::Disconnected { peer_id, remote_addr })
-                    }
-                    ActiveSessionMessage::ClosedOnConnectionError {
-                        peer_id,
-                        remote_addr,
-                        error,
-                    } => {
-                        trace!(target: "net::session", ?peer_id, %error,"closed session.");
-                        self.remove_active_session(&peer_id);
-                        Poll::Ready(SessionEvent::SessionClosedOnConnectionError {
-                            remote_addr,
-                            peer_id,
-                            error,
-                        })
-                    }
-                    ActiveSessionMessage::ValidMessage { peer_id, message } => {
-                        Poll::Ready(SessionEvent::ValidMessage { peer_id, message })
+
+            return match event {
+                ActiveSessionMessage::Disconnected { peer_id, remote_addr, reason, .. } => {
+                    trace!(
+                        target: "net::session",
+                        ?peer_id,
+                        "gracefully disconnected active session."
+                    );
+                    if let Some(session) = self.remove_active_session(&peer_id) {
+                        self.session_lifetimes.record(reason, session.established.elapsed());
                     }
-                    ActiveSessionMessage::InvalidMessage { peer_id, capabilities, message } => {
-                        Poll::Ready(SessionEvent::InvalidMessage { peer_id, message, capabilities })
+                    // the session sent the disconnect with this reason
+                    if let Some(reason) = reason {
+                        self.disconnect_counts.increment(reason);
                     }
-                    ActiveSessionMessage::BadMessage { peer_id } => {
-                        Poll::Ready(SessionEvent::BadMessage { peer_id })
+                    Poll::Ready(SessionEvent::Disconnected { peer_id, remote_addr, reason })
+                }
+                ActiveSessionMessage::ClosedOnConnectionError {
+                    peer_id,
+                    remote_addr,
+                    error,
+                    ..
+                } => {
+                    trace!(target: "net::session", ?peer_id, %error,"closed session.");
+                    let reason = error.as_disconnected();
+                    if let Some(session) = self.remove_active_session(&peer_id) {
+                        self.session_lifetimes.record(reason, session.established.elapsed());
                     }
-                    ActiveSessionMessage::ProtocolBreach { peer_id } => {
-                        Poll::Ready(SessionEvent::ProtocolBreach { peer_id })
+                    if let Some(reason) = reason {
+                        self.disconnect_counts.increment(reason);
                     }
+                    Poll::Ready(SessionEvent::SessionClosedOnConnectionError {
+                        remote_addr,
+                        peer_id,
+                        error,
+                    })
+                }
+                ActiveSessionMessage::ValidMessage { peer_id, message } => {
+                    Poll::Ready(SessionEvent::ValidMessage { peer_id, message })
+                }
+                ActiveSessionMessage::InvalidMessage { peer_id, capabilities, message } => {
+                    Poll::Ready(SessionEvent::InvalidMessage { peer_id, message, capabilities })
+                }
+                ActiveSessionMessage::BadMessage { peer_id } => {
+                    Poll::Ready(SessionEvent::BadMessage { peer_id })
+                }
+                ActiveSessionMessage::ProtocolBreach { peer_id } => {
+                    Poll::Ready(SessionEvent::ProtocolBreach { peer_id })
                 }
             }
         }
@@ -438,16 +583,40 @@ impl SessionManager {
                 local_addr,
                 peer_id,
                 capabilities,
-                conn,
+                mut conn,
                 status,
                 direction,
                 client_id,
+                timing,
             } => {
                 // move from pending to established.
                 self.remove_pending_session(&session_id);
 
-                // If there's already a session to the peer then we disconnect right away
-                if self.active_sessions.contains_key(&peer_id) {
+                // If there's already a session to the peer then one of them is closed, both sides
+                // agree on which one
+                let mut replaced = None;
+                if let Some(active) = self.active_sessions.get(&peer_id) {
+                    let local_id = self.hello_message.id;
+                    if keeps_connection(local_id, peer_id, direction) &&
+                        !keeps_connection(local_id, peer_id, active.direction)
+                    {
+                        trace!(
+                            target: "net::session",
+                            ?session_id,
+                            ?remote_addr,
+                            ?peer_id,
+                            ?direction,
+                            "already connected, replacing active session"
+                        );
+                        let reason = DisconnectReason::AlreadyConnected;
+                        let active = self.remove_active_session(&peer_id).expect("exists");
+                        active.disconnect(Some(reason));
+                        self.session_lifetimes.record(Some(reason), active.established.elapsed());
+                        self.disconnect_counts.increment(reason);
+                        replaced = Some(active.remote_addr);
+                    }
+                }
+                if self.active_sessions.contains(&peer_id) {
                     trace!(
                         target: "net::session",
                         ?session_id,
@@ -480,8 +649,16 @@ impl SessionManager {
                     self.initial_internal_request_timeout.as_millis() as u64,
                 ));
 
+                conn.inner_mut().set_subprotocol_disconnect_reasons(
+                    self.subprotocol_disconnect_reasons.clone(),
+                );
+
                 // negotiated version
                 let version = conn.version();
+                let shared_capabilities = Arc::new(conn.inner().shared_capabilities().clone());
+                let latencies = Arc::new(RequestLatencies::default());
+                let compression_ratio = Arc::default();
+                let span = session_span(peer_id, &conn);
 
                 let session = ActiveSession {
                     next_id: 0,
@@ -501,8 +678,21 @@ impl SessionManager {
                         self.initial_internal_request_timeout,
                     ),
                     internal_request_timeout: Arc::clone(&timeout),
+                    request_timeout: self.request_timeout,
+                    rtt: self
+                        .request_timeout
+                        .rtt_for_timeout(self.initial_internal_request_timeout),
                     protocol_breach_request_timeout: self.protocol_breach_request_timeout,
                     terminate_message: None,
+                    disconnect_reason: None,
+                    usefulness: self.useless_peer_timeout.map(UsefulnessTracker::new),
+                    serving_policy: self.serving_policy,
+                    serve_requests: self.serve_requests,
+                    latencies: Arc::clone(&latencies),
+                    compression_ratio: Arc::clone(&compression_ratio),
+                    last_answered_request: None,
+                    clock: Arc::clone(&self.clock),
+                    span,
                 };
 
                 self.spawn(session);
@@ -516,6 +706,9 @@ impl SessionManager {
                     version,
                     established: Instant::now(),
                     capabilities: Arc::clone(&capabilities),
+                    shared_capabilities,
+                    latencies: Arc::clone(&latencies),
+                    compression_ratio,
                     commands_to_session,
                     client_version: Arc::clone(&client_version),
                     remote_addr,
@@ -527,9 +720,25 @@ impl SessionManager {
 
                 if direction.is_outgoing() {
                     self.metrics.total_dial_successes.increment(1);
+                    self.metrics.handshake_tcp_duration.record(timing.tcp.as_secs_f64());
                 }
+                self.metrics.handshake_ecies_duration.record(timing.ecies.as_secs_f64());
+                self.metrics.handshake_hello_duration.record(timing.hello.as_secs_f64());
+                self.metrics.handshake_status_duration.record(timing.status.as_secs_f64());
+                debug!(
+                    target: "net::session",
+                    ?session_id,
+                    ?peer_id,
+                    ?direction,
+                    tcp = ?timing.tcp,
+                    ecies = ?timing.ecies,
+                    hello = ?timing.hello,
+                    status = ?timing.status,
+                    total = ?timing.total(),
+                    "session established"
+                );
 
-                Poll::Ready(SessionEvent::SessionEstablished {
+                let established = SessionEvent::SessionEstablished {
                     peer_id,
                     remote_addr,
                     client_version,
@@ -539,7 +748,20 @@ impl SessionManager {
                     messages,
                     direction,
                     timeout,
-                })
+                    latencies,
+                    timing,
+                };
+                if let Some(replaced_addr) = replaced {
+                    // the replaced session is closed before the new one is reported
+                    self.queued_events.push_back(established);
+                    return Poll::Ready(SessionEvent::Disconnected {
+                        peer_id,
+                        remote_addr: replaced_addr,
+                        reason: Some(DisconnectReason::AlreadyConnected),
+                    })
+                }
+
+                Poll::Ready(established)
             }
             PendingSessionEvent::Disconnected { remote_addr, session_id, direction, error } => {
                 trace!(
@@ -550,6 +772,9 @@ impl SessionManager {
                     "disconnected pending session"
                 );
                 self.remove_pending_session(&session_id);
+                if let Some(reason) = error.as_ref().and_then(|err| err.as_disconnected()) {
+                    self.disconnect_counts.increment(reason);
+                }
                 match direction {
                     Direction::Incoming => {
                         Poll::Ready(SessionEvent::IncomingPendingSessionClosed {
@@ -612,6 +837,18 @@ impl SessionManager {
     }
 }
 
+/// Returns `true` if the connection in the given direction is kept when the local node and the
+/// peer are connected more than once, e.g. because they dialed each other simultaneously.
+///
+/// The node with the numerically smaller id keeps the connection it dialed, so both nodes
+/// independently agree on which connection survives.
+pub(crate) fn keeps_connection(local_id: PeerId, peer_id: PeerId, direction: Direction) -> bool {
+    match direction {
+        Direction::Outgoing(_) => local_id < peer_id,
+        Direction::Incoming => peer_id < local_id,
+    }
+}
+
 /// Events produced by the [`SessionManager`]
 #[derive(Debug)]
 pub enum SessionEvent {
@@ -638,6 +875,10 @@ pub enum SessionEvent {
         /// The maximum time that the session waits for a response from the peer before timing out
         /// the connection
         timeout: Arc<AtomicU64>,
+        /// The round-trip latencies of the requests sent to the peer
+        latencies: Arc<RequestLatencies>,
+        /// How long each phase of the handshake took
+        timing: HandshakeTiming,
     },
     /// The peer was already connected with another session.
     AlreadyConnected {
@@ -706,7 +947,7 @@ pub enum SessionEvent {
         /// The socket we were connected to.
         remote_addr: SocketAddr,
         /// The error that caused the session to close
-        error: EthStreamError,
+        error: SessionError,
     },
     /// Active session was gracefully disconnected.
     Disconnected {
@@ -714,6 +955,8 @@ pub enum SessionEvent {
         peer_id: PeerId,
         /// The remote node's socket address that we were connected to
         remote_addr: SocketAddr,
+        /// The reason we disconnected the peer with, `None` if the peer closed the connection.
+        reason: Option<DisconnectReason>,
     },
 }
 
@@ -747,6 +990,17 @@ impl PendingSessionHandshakeError {
 #[error("session limit reached {0}")]
 pub struct ExceedsSessionLimit(pub(crate) u32);
 
+/// The error returned when no outgoing session is started for a dial.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DialError {
+    /// The peer is already being dialed by the pending session with the given id.
+    #[error("already dialing peer in session {0:?}")]
+    AlreadyDialing(SessionId),
+    /// The pending outbound session limit has been reached.
+    #[error(transparent)]
+    ExceedsSessionLimit(#[from] ExceedsSessionLimit),
+}
+
 /// Starts a pending session authentication with a timeout.
 pub(crate) async fn pending_session_with_timeout<F>(
     timeout: Duration,
@@ -785,6 +1039,8 @@ pub(crate) async fn start_pending_incoming_session(
     status: Status,
     fork_filter: ForkFilter,
     extra_handlers: RlpxSubProtocolHandlers,
+    handshake_pool: Option<HandshakePool>,
+    clock: SharedClock,
 ) {
     authenticate(
         disconnect_rx,
@@ -798,6 +1054,8 @@ pub(crate) async fn start_pending_incoming_session(
         status,
         fork_filter,
         extra_handlers,
+        handshake_pool,
+        HandshakeTimer::start(clock),
     )
     .await
 }
@@ -816,7 +1074,10 @@ async fn start_pending_outbound_session(
     status: Status,
     fork_filter: ForkFilter,
     extra_handlers: RlpxSubProtocolHandlers,
+    handshake_pool: Option<HandshakePool>,
+    clock: SharedClock,
 ) {
+    let mut timer = HandshakeTimer::start(clock);
     let stream = match TcpStream::connect(remote_addr).await {
         Ok(stream) => {
             if let Err(err) = stream.set_nodelay(true) {
@@ -836,6 +1097,7 @@ async fn start_pending_outbound_session(
             return
         }
     };
+    timer.tcp_connected();
     authenticate(
         disconnect_rx,
         events,
@@ -848,6 +1110,8 @@ async fn start_pending_outbound_session(
         status,
         fork_filter,
         extra_handlers,
+        handshake_pool,
+        timer,
     )
     .await
 }
@@ -866,22 +1130,27 @@ async fn authenticate(
     status: Status,
     fork_filter: ForkFilter,
     extra_handlers: RlpxSubProtocolHandlers,
+    handshake_pool: Option<HandshakePool>,
+    mut timer: HandshakeTimer,
 ) {
     let local_addr = stream.local_addr().ok();
-    let stream = match get_eciess_stream(stream, secret_key, direction).await {
-        Ok(stream) => stream,
-        Err(error) => {
-            let _ = events
-                .send(PendingSessionEvent::EciesAuthError {
-                    remote_addr,
-                    session_id,
-                    error,
-                    direction,
-                })
-                .await;
-            return
-        }
-    };
+    let stream =
+        match get_eciess_stream(stream, secret_key, direction, handshake_pool.as_ref()).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                let _ = events
+                    .send(PendingSessionEvent::EciesAuthError {
+                        remote_addr,
+                        session_id,
+                        error,
+                        direction,
+                    })
+                    .await;
+                return
+            }
+        };
+
+    timer.ecies_completed();
 
     let unauthed = UnauthedP2PStream::new(stream);
 
@@ -895,6 +1164,7 @@ async fn authenticate(
         status,
         fork_filter,
         extra_handlers,
+        timer,
     )
     .boxed();
 
@@ -917,16 +1187,25 @@ async fn authenticate(
 
 /// Returns an [`ECIESStream`] if it can be built. If not, send a
 /// [`PendingSessionEvent::EciesAuthError`] and returns `None`
+///
+/// If a [`HandshakePool`] is given, the crypto of the handshake runs on it.
 async fn get_eciess_stream<Io: AsyncRead + AsyncWrite + Unpin>(
     stream: Io,
     secret_key: SecretKey,
     direction: Direction,
+    handshake_pool: Option<&HandshakePool>,
 ) -> Result<ECIESStream<Io>, ECIESError> {
-    match direction {
-        Direction::Incoming => ECIESStream::incoming(stream, secret_key).await,
-        Direction::Outgoing(remote_peer_id) => {
+    match (direction, handshake_pool) {
+        (Direction::Incoming, None) => ECIESStream::incoming(stream, secret_key).await,
+        (Direction::Incoming, Some(pool)) => {
+            ECIESStream::incoming_with_executor(stream, secret_key, pool).await
+        }
+        (Direction::Outgoing(remote_peer_id), None) => {
             ECIESStream::connect(stream, secret_key, remote_peer_id).await
         }
+        (Direction::Outgoing(remote_peer_id), Some(pool)) => {
+            ECIESStream::connect_with_executor(stream, secret_key, remote_peer_id, pool).await
+        }
     }
 }
 
@@ -947,6 +1226,7 @@ async fn authenticate_stream(
     mut status: Status,
     fork_filter: ForkFilter,
     mut extra_handlers: RlpxSubProtocolHandlers,
+    mut timer: HandshakeTimer,
 ) -> PendingSessionEvent {
     // Add extra protocols to the hello message
     extra_handlers.retain(|handler| hello.try_add_protocol(handler.protocol()).is_ok());
@@ -964,6 +1244,8 @@ async fn authenticate_stream(
         }
     };
 
+    timer.hello_exchanged();
+
     // Ensure we negotiated mandatory eth protocol
     let eth_version = match p2p_stream.shared_capabilities().eth_version() {
         Ok(version) => version,
@@ -1036,5 +1318,405 @@ async fn authenticate_stream(
         conn,
         direction,
         client_id: their_hello.client_version,
+        timing: timer.status_exchanged(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reth_chainspec::MAINNET;
+    use reth_eth_wire::{clock::Clock, StatusBuilder};
+    use reth_network_peers::pk2id;
+    use reth_primitives::EthereumHardfork;
+    use reth_tasks::TokioTaskExecutor;
+    use secp256k1::SECP256K1;
+    use std::{
+        future::poll_fn,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+    use tokio::net::TcpListener;
+
+    fn session_manager() -> (SessionManager, PeerId) {
+        session_manager_with_config(SessionsConfig::default())
+    }
+
+    fn session_manager_with_config(config: SessionsConfig) -> (SessionManager, PeerId) {
+        let (secret_key, pk) = SECP256K1.generate_keypair(&mut rand::thread_rng());
+        let peer_id = pk2id(&pk);
+        let manager = SessionManager::new(
+            secret_key,
+            config,
+            Box::new(TokioTaskExecutor::default()),
+            StatusBuilder::default().build(),
+            HelloMessageWithProtocols::builder(peer_id).build(),
+            MAINNET.hardfork_fork_filter(EthereumHardfork::Frontier).unwrap(),
+            Default::default(),
+        );
+        (manager, peer_id)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_negotiated_eth() {
+        let (mut local, local_id) = session_manager();
+        let (mut remote, remote_id) = session_manager();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+        local.dial_outbound(remote_addr, remote_id).unwrap();
+        let (incoming, addr) = listener.accept().await.unwrap();
+        remote.on_incoming(incoming, addr).unwrap();
+
+        let (mut local_status, mut remote_status) = (None, None);
+        while local_status.is_none() || remote_status.is_none() {
+            tokio::select! {
+                ev = poll_fn(|cx| local.poll(cx)) => {
+                    if let SessionEvent::SessionEstablished { status, .. } = ev {
+                        local_status = Some(status);
+                    }
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    if let SessionEvent::SessionEstablished { status, .. } = ev {
+                        remote_status = Some(status);
+                    }
+                }
+            }
+        }
+
+        // each side records the parameters of the status the other side sent
+        for (manager, peer, peer_id, status) in [
+            (&local, &remote, remote_id, local_status.unwrap()),
+            (&remote, &local, local_id, remote_status.unwrap()),
+        ] {
+            assert_eq!(*status, peer.status());
+            let handle = manager.active_sessions.get(&peer_id).unwrap();
+            let negotiated = handle.negotiated();
+            assert_eq!(negotiated.version as u8, status.version);
+            assert_eq!(negotiated.version, handle.version());
+            assert_eq!(negotiated.network_id, status.chain);
+            assert_eq!(negotiated.genesis, status.genesis);
+            assert_eq!(negotiated.fork_id, status.forkid);
+        }
+    }
+
+    /// A clock that moves forward by a fixed step whenever it's read.
+    #[derive(Debug)]
+    struct SteppingClock {
+        start: Instant,
+        step: Duration,
+        reads: AtomicU32,
+    }
+
+    impl Clock for SteppingClock {
+        fn now(&self) -> Instant {
+            self.start + self.step * self.reads.fetch_add(1, Ordering::SeqCst)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_handshake_timing() {
+        let (mut local, _) = session_manager();
+        let (mut remote, remote_id) = session_manager();
+        // every phase of the handshake lasts exactly one step
+        let step = Duration::from_millis(25);
+        for manager in [&mut local, &mut remote] {
+            manager.clock =
+                Arc::new(SteppingClock { start: Instant::now(), step, reads: AtomicU32::new(0) });
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+        local.dial_outbound(remote_addr, remote_id).unwrap();
+        let (incoming, addr) = listener.accept().await.unwrap();
+        remote.on_incoming(incoming, addr).unwrap();
+
+        let (mut local_timing, mut remote_timing) = (None, None);
+        while local_timing.is_none() || remote_timing.is_none() {
+            tokio::select! {
+                ev = poll_fn(|cx| local.poll(cx)) => {
+                    if let SessionEvent::SessionEstablished { timing, .. } = ev {
+                        local_timing = Some(timing);
+                    }
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    if let SessionEvent::SessionEstablished { timing, .. } = ev {
+                        remote_timing = Some(timing);
+                    }
+                }
+            }
+        }
+
+        let local_timing = local_timing.unwrap();
+        assert_eq!(
+            local_timing,
+            HandshakeTiming { tcp: step, ecies: step, hello: step, status: step }
+        );
+        assert_eq!(local_timing.total(), step * 4);
+
+        // the connection of the incoming session was already established
+        let remote_timing = remote_timing.unwrap();
+        assert_eq!(
+            remote_timing,
+            HandshakeTiming { tcp: Duration::ZERO, ecies: step, hello: step, status: step }
+        );
+        assert_eq!(remote_timing.total(), step * 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_session_lifetime_recorded_on_disconnect() {
+        let (mut local, _) = session_manager();
+        let (mut remote, remote_id) = session_manager();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+        let start = Instant::now();
+        local.dial_outbound(remote_addr, remote_id).unwrap();
+        let (incoming, addr) = listener.accept().await.unwrap();
+        remote.on_incoming(incoming, addr).unwrap();
+
+        let (mut local_established, mut remote_established) = (false, false);
+        while !(local_established && remote_established) {
+            tokio::select! {
+                ev = poll_fn(|cx| local.poll(cx)) => {
+                    local_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    remote_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+            }
+        }
+
+        let lifetime = Duration::from_millis(200);
+        tokio::time::sleep(lifetime).await;
+        local.disconnect(remote_id, Some(DisconnectReason::ClientQuitting));
+
+        let (mut local_closed, mut remote_closed) = (false, false);
+        while !(local_closed && remote_closed) {
+            tokio::select! {
+                ev = poll_fn(|cx| local.poll(cx)) => {
+                    local_closed |= matches!(ev, SessionEvent::Disconnected { .. });
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    remote_closed |=
+                        matches!(ev, SessionEvent::SessionClosedOnConnectionError { .. });
+                }
+            }
+        }
+        let max_lifetime = start.elapsed();
+
+        // both sides record the lifetime under the reason the session was closed with
+        for manager in [&local, &remote] {
+            let lifetimes = manager.session_lifetimes();
+            assert_eq!(lifetimes.len(), 1);
+            let stats = lifetimes[&Some(DisconnectReason::ClientQuitting)];
+            assert_eq!(stats.count, 1);
+            assert!(stats.total >= lifetime && stats.total <= max_lifetime, "{stats:?}");
+
+            // the disconnect is counted by the side that sent it and the side that received it
+            let counts = manager.disconnect_counts();
+            assert_eq!(counts[&DisconnectReason::ClientQuitting], 1);
+            assert_eq!(counts.values().sum::<u64>(), 1);
+        }
+    }
+
+    #[test]
+    fn test_keeps_connection_tie_break() {
+        let (mut a, mut b) = (PeerId::random(), PeerId::random());
+        if b < a {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        // the connection dialed by `a`, the node with the smaller id, is kept by both
+        assert!(keeps_connection(a, b, Direction::Outgoing(b)));
+        assert!(keeps_connection(b, a, Direction::Incoming));
+
+        // the connection dialed by `b` is dropped by both
+        assert!(!keeps_connection(b, a, Direction::Outgoing(a)));
+        assert!(!keeps_connection(a, b, Direction::Incoming));
+    }
+
+    /// Connects `a` to `b` and then `b` to `a`, and asserts that both sides keep the connection
+    /// dialed by the node with the smaller id and drop the other one with `AlreadyConnected`.
+    async fn assert_duplicate_connection_tie_break(a_dials_kept: bool) {
+        let ((mut a, mut a_id), (mut b, mut b_id)) = (session_manager(), session_manager());
+        if (a_id < b_id) != a_dials_kept {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut a_id, &mut b_id);
+        }
+        let a_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let b_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        // first connection, dialed by `a`
+        a.dial_outbound(b_listener.local_addr().unwrap(), b_id).unwrap();
+        let (incoming, addr) = b_listener.accept().await.unwrap();
+        b.on_incoming(incoming, addr).unwrap();
+        let (mut a_established, mut b_established) = (false, false);
+        while !(a_established && b_established) {
+            tokio::select! {
+                ev = poll_fn(|cx| a.poll(cx)) => {
+                    a_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+                ev = poll_fn(|cx| b.poll(cx)) => {
+                    b_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+            }
+        }
+
+        // second connection, dialed by `b`
+        b.dial_outbound(a_listener.local_addr().unwrap(), a_id).unwrap();
+        let (incoming, addr) = a_listener.accept().await.unwrap();
+        a.on_incoming(incoming, addr).unwrap();
+
+        // each side either drops the second connection or replaces the first one with it
+        let on_event = |ev: SessionEvent, replaced: &mut bool, settled: &mut bool| match ev {
+            SessionEvent::AlreadyConnected { .. } => *settled = true,
+            SessionEvent::Disconnected { reason, .. } => {
+                assert_eq!(reason, Some(DisconnectReason::AlreadyConnected));
+                *replaced = true;
+            }
+            // the peer closed the first connection before the second one was established here
+            SessionEvent::SessionClosedOnConnectionError { error, .. } => {
+                assert_eq!(error.as_disconnected(), Some(DisconnectReason::AlreadyConnected));
+                *replaced = true;
+            }
+            SessionEvent::SessionEstablished { .. } => {
+                assert!(*replaced);
+                *settled = true;
+            }
+            ev => unreachable!("unexpected event {ev:?}"),
+        };
+        let (mut a_replaced, mut b_replaced) = (false, false);
+        let (mut a_settled, mut b_settled) = (false, false);
+        while !(a_settled && b_settled) {
+            tokio::select! {
+                ev = poll_fn(|cx| a.poll(cx)) => on_event(ev, &mut a_replaced, &mut a_settled),
+                ev = poll_fn(|cx| b.poll(cx)) => on_event(ev, &mut b_replaced, &mut b_settled),
+            }
+        }
+        assert_eq!(a_replaced, !a_dials_kept);
+        assert_eq!(b_replaced, !a_dials_kept);
+
+        // the closed session is counted by each side that replaced it
+        for (manager, replaced) in [(&a, a_replaced), (&b, b_replaced)] {
+            if replaced {
+                let counts = manager.disconnect_counts();
+                assert_eq!(counts.get(&DisconnectReason::AlreadyConnected), Some(&1), "{counts:?}");
+            }
+        }
+
+        // the closed connection doesn't affect the kept one
+        let _ = tokio::time::timeout(Duration::from_millis(200), async {
+            tokio::select! {
+                ev = poll_fn(|cx| a.poll(cx)) => unreachable!("unexpected event {ev:?}"),
+                ev = poll_fn(|cx| b.poll(cx)) => unreachable!("unexpected event {ev:?}"),
+            }
+        })
+        .await;
+        let a_direction = a.active_sessions().get(&b_id).unwrap().direction();
+        let b_direction = b.active_sessions().get(&a_id).unwrap().direction();
+        assert_eq!(a_direction.is_outgoing(), a_dials_kept);
+        assert_eq!(b_direction.is_incoming(), a_dials_kept);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_dials_deduplicated() {
+        let (mut local, _) = session_manager();
+        let (mut remote, remote_id) = session_manager();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+        let session_id = local.dial_outbound(remote_addr, remote_id).unwrap();
+
+        // the second dial to the same peer is short-circuited
+        let res = local.dial_outbound(remote_addr, remote_id);
+        assert!(matches!(res, Err(DialError::AlreadyDialing(id)) if id == session_id), "{res:?}");
+        assert_eq!(local.num_pending_connections(), 1);
+
+        let (incoming, addr) = listener.accept().await.unwrap();
+        remote.on_incoming(incoming, addr).unwrap();
+
+        let (mut local_established, mut remote_established) = (false, false);
+        while !(local_established && remote_established) {
+            tokio::select! {
+                ev = poll_fn(|cx| local.poll(cx)) => {
+                    local_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    remote_established |= matches!(ev, SessionEvent::SessionEstablished { .. });
+                }
+            }
+        }
+
+        // only a single connection was made
+        let accept = tokio::time::timeout(Duration::from_millis(100), listener.accept()).await;
+        assert!(accept.is_err());
+        assert_eq!(local.active_sessions.get(&remote_id).unwrap().session_id(), session_id);
+
+        // the completed dial clears the entry
+        assert!(local.dialing.is_empty());
+        assert_eq!(local.num_pending_connections(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_duplicate_connection_tie_break() {
+        reth_tracing::init_test_tracing();
+        assert_duplicate_connection_tie_break(true).await;
+        assert_duplicate_connection_tie_break(false).await;
+    }
+
+    /// Establishes sessions from `num_peers` dialers with a single listener, all using the given
+    /// config.
+    async fn establish_concurrent_sessions(config: SessionsConfig, num_peers: usize) {
+        let (mut remote, remote_id) = session_manager_with_config(config.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+
+        let (established_tx, mut established_rx) = mpsc::unbounded_channel();
+        for _ in 0..num_peers {
+            let established_tx = established_tx.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                let (mut local, _) = session_manager_with_config(config);
+                local.dial_outbound(remote_addr, remote_id).unwrap();
+                loop {
+                    if let SessionEvent::SessionEstablished { .. } =
+                        poll_fn(|cx| local.poll(cx)).await
+                    {
+                        let _ = established_tx.send(());
+                    }
+                }
+            });
+        }
+
+        let (mut local_established, mut remote_established) = (0, 0);
+        while local_established < num_peers || remote_established < num_peers {
+            tokio::select! {
+                conn = listener.accept() => {
+                    let (incoming, addr) = conn.unwrap();
+                    remote.on_incoming(incoming, addr).unwrap();
+                }
+                ev = poll_fn(|cx| remote.poll(cx)) => {
+                    if let SessionEvent::SessionEstablished { .. } = ev {
+                        remote_established += 1;
+                    }
+                }
+                Some(()) = established_rx.recv() => local_established += 1,
+            }
+        }
+
+        assert_eq!(remote.active_sessions().len(), num_peers);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_handshake_pool_concurrent_sessions() {
+        reth_tracing::init_test_tracing();
+        let num_peers = 32;
+
+        let config = SessionsConfig::default().with_handshake_threads(2);
+        let (manager, _) = session_manager_with_config(config.clone());
+        assert!(manager.handshake_pool.is_some());
+
+        establish_concurrent_sessions(SessionsConfig::default(), num_peers).await;
+        establish_concurrent_sessions(config, num_peers).await;
     }
 }
@@ -0,0 +1,82 @@
+//! A dedicated pool for the crypto of session handshakes.
+
+use std::{io, sync::Arc};
+
+use reth_ecies::stream::HandshakeExecutor;
+use tokio::sync::Semaphore;
+
+/// A bounded pool that runs the CPU heavy `ECIES` crypto of pending sessions.
+///
+/// The key agreement and the decryption of the auth and ack messages are run on tokio's blocking
+/// threads, at most `threads` at a time. This keeps a burst of incoming connections from starving
+/// the tasks of the active sessions. The socket I/O of the handshake stays on the network's task
+/// executor.
+#[derive(Debug, Clone)]
+pub(crate) struct HandshakePool {
+    /// Bounds the number of handshake steps that run concurrently.
+    permits: Arc<Semaphore>,
+}
+
+impl HandshakePool {
+    /// Creates a new pool that runs at most the given number of handshake steps at once, at least
+    /// one.
+    pub(crate) fn new(threads: usize) -> Self {
+        Self { permits: Arc::new(Semaphore::new(threads.max(1))) }
+    }
+}
+
+impl HandshakeExecutor for HandshakePool {
+    async fn execute<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let _permit = self.permits.acquire().await.map_err(io::Error::other)?;
+        tokio::task::spawn_blocking(f).await.map_err(io::Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn runs_off_the_calling_thread() {
+        let pool = HandshakePool::new(1);
+        let caller = thread::current().id();
+        let worker = pool.execute(|| thread::current().id()).await.unwrap();
+        assert_ne!(worker, caller);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bounds_concurrent_steps() {
+        let pool = HandshakePool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let steps = (0..8).map(|_| {
+            let (pool, running, max_running) =
+                (pool.clone(), Arc::clone(&running), Arc::clone(&max_running));
+            tokio::spawn(async move {
+                pool.execute(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+                .await
+                .unwrap()
+            })
+        });
+        for step in futures::future::join_all(steps).await {
+            step.unwrap();
+        }
+
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,171 @@
+//! Registry of the active sessions.
+
+use std::collections::{hash_map, HashMap};
+
+use reth_network_peers::PeerId;
+
+use super::ActiveSessionHandle;
+
+/// Keeps track of all active sessions, keyed by the [`PeerId`] of the remote peer.
+///
+/// There is at most one active session per peer. Lookups by peer id are `O(1)` and iterating over
+/// all sessions is linear in the number of sessions.
+///
+/// The iteration order is arbitrary, but stable for as long as the registry is not modified.
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    /// The handles of all active sessions.
+    sessions: HashMap<PeerId, ActiveSessionHandle>,
+}
+
+// === impl SessionRegistry ===
+
+impl SessionRegistry {
+    /// Inserts the handle of the session with the given peer.
+    ///
+    /// Returns the handle of the previous session with the peer, if any.
+    pub(crate) fn insert(
+        &mut self,
+        peer_id: PeerId,
+        handle: ActiveSessionHandle,
+    ) -> Option<ActiveSessionHandle> {
+        self.sessions.insert(peer_id, handle)
+    }
+
+    /// Removes the handle of the session with the given peer.
+    pub(crate) fn remove(&mut self, peer_id: &PeerId) -> Option<ActiveSessionHandle> {
+        self.sessions.remove(peer_id)
+    }
+
+    /// Returns the handle of the session with the given peer.
+    pub fn get(&self, peer_id: &PeerId) -> Option<&ActiveSessionHandle> {
+        self.sessions.get(peer_id)
+    }
+
+    /// Returns a mutable reference to the handle of the session with the given peer.
+    pub(crate) fn get_mut(&mut self, peer_id: &PeerId) -> Option<&mut ActiveSessionHandle> {
+        self.sessions.get_mut(peer_id)
+    }
+
+    /// Returns `true` if there's an active session with the given peer.
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.sessions.contains_key(peer_id)
+    }
+
+    /// Returns the number of active sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Returns `true` if there are no active sessions.
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Returns an iterator over the peers and the handles of all active sessions.
+    pub fn iter(&self) -> hash_map::Iter<'_, PeerId, ActiveSessionHandle> {
+        self.sessions.iter()
+    }
+
+    /// Returns an iterator over the peers of all active sessions.
+    pub fn peer_ids(&self) -> hash_map::Keys<'_, PeerId, ActiveSessionHandle> {
+        self.sessions.keys()
+    }
+
+    /// Returns an iterator over the handles of all active sessions.
+    pub fn handles(&self) -> hash_map::Values<'_, PeerId, ActiveSessionHandle> {
+        self.sessions.values()
+    }
+}
+
+impl<'a> IntoIterator for &'a SessionRegistry {
+    type Item = (&'a PeerId, &'a ActiveSessionHandle);
+    type IntoIter = hash_map::Iter<'a, PeerId, ActiveSessionHandle>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionId;
+    use reth_eth_wire::{capability::SharedCapabilities, Capability, EthVersion, Status};
+    use reth_network_api::Direction;
+    use std::{collections::HashSet, sync::Arc, time::Instant};
+    use tokio::sync::mpsc;
+
+    fn handle(peer_id: PeerId, session_id: usize) -> ActiveSessionHandle {
+        let status = Status::default();
+        let version = EthVersion::Eth68;
+        let (commands_to_session, _) = mpsc::channel(1);
+        ActiveSessionHandle {
+            direction: Direction::Incoming,
+            session_id: SessionId(session_id),
+            version,
+            remote_id: peer_id,
+            established: Instant::now(),
+            capabilities: Arc::new(vec![Capability::from(version)].into()),
+            shared_capabilities: Arc::new(
+                SharedCapabilities::try_new(vec![version.into()], vec![Capability::from(version)])
+                    .unwrap(),
+            ),
+            commands_to_session,
+            client_version: Arc::from("reth"),
+            remote_addr: "127.0.0.1:30303".parse().unwrap(),
+            local_addr: None,
+            status: Arc::new(status),
+            latencies: Default::default(),
+            compression_ratio: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut registry = SessionRegistry::default();
+        assert!(registry.is_empty());
+
+        let peer_id = PeerId::random();
+        assert!(registry.insert(peer_id, handle(peer_id, 0)).is_none());
+        assert!(registry.contains(&peer_id));
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get(&peer_id).unwrap().session_id(), SessionId(0));
+        assert!(registry.get(&PeerId::random()).is_none());
+
+        // replacing the session of a peer returns the previous one
+        let previous = registry.insert(peer_id, handle(peer_id, 1)).unwrap();
+        assert_eq!(previous.session_id(), SessionId(0));
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get_mut(&peer_id).unwrap().session_id(), SessionId(1));
+
+        assert_eq!(registry.remove(&peer_id).unwrap().session_id(), SessionId(1));
+        assert!(registry.remove(&peer_id).is_none());
+        assert!(!registry.contains(&peer_id));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut registry = SessionRegistry::default();
+        let peers = (0..16).map(|_| PeerId::random()).collect::<Vec<_>>();
+        for (session_id, peer_id) in peers.iter().enumerate() {
+            registry.insert(*peer_id, handle(*peer_id, session_id));
+        }
+
+        // every session is yielded exactly once, under the peer it was inserted for
+        assert_eq!(registry.iter().count(), peers.len());
+        for (peer_id, handle) in &registry {
+            assert_eq!(handle.remote_id(), *peer_id);
+        }
+        assert_eq!(
+            registry.peer_ids().copied().collect::<HashSet<_>>(),
+            peers.iter().copied().collect()
+        );
+
+        // the order is stable while the registry is not modified
+        let order = registry.peer_ids().copied().collect::<Vec<_>>();
+        assert_eq!(registry.handles().map(|handle| handle.remote_id()).collect::<Vec<_>>(), order);
+        assert_eq!(registry.peer_ids().copied().collect::<Vec<_>>(), order);
+    }
+}
@@ -0,0 +1,108 @@
+//! Durations of the phases of a session handshake.
+
+use std::time::{Duration, Instant};
+
+use reth_eth_wire::clock::SharedClock;
+
+/// How long each phase of the handshake of an established session took.
+///
+/// This shows whether establishing sessions is bound by the network or by the crypto of the
+/// `ECIES` handshake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandshakeTiming {
+    /// Time to establish the TCP connection.
+    ///
+    /// This is zero for incoming sessions, their connection was already accepted.
+    pub tcp: Duration,
+    /// Time of the `ECIES` handshake.
+    pub ecies: Duration,
+    /// Time of the `Hello` exchange of the `p2p` handshake.
+    pub hello: Duration,
+    /// Time of the `Status` exchange of the `eth` handshake.
+    pub status: Duration,
+}
+
+impl HandshakeTiming {
+    /// Returns the duration of the entire handshake.
+    pub fn total(&self) -> Duration {
+        self.tcp + self.ecies + self.hello + self.status
+    }
+}
+
+/// Measures the phases of a handshake, each phase lasts until the next one starts.
+#[derive(Debug)]
+pub(crate) struct HandshakeTimer {
+    clock: SharedClock,
+    /// When the current phase started.
+    phase_start: Instant,
+    timing: HandshakeTiming,
+}
+
+impl HandshakeTimer {
+    /// Starts measuring the first phase of the handshake.
+    pub(crate) fn start(clock: SharedClock) -> Self {
+        let phase_start = clock.now();
+        Self { clock, phase_start, timing: HandshakeTiming::default() }
+    }
+
+    /// Ends the current phase and returns its duration.
+    fn lap(&mut self) -> Duration {
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.phase_start);
+        self.phase_start = now;
+        elapsed
+    }
+
+    /// Marks the TCP connection as established.
+    pub(crate) fn tcp_connected(&mut self) {
+        self.timing.tcp = self.lap();
+    }
+
+    /// Marks the `ECIES` handshake as completed.
+    pub(crate) fn ecies_completed(&mut self) {
+        self.timing.ecies = self.lap();
+    }
+
+    /// Marks the `Hello` messages as exchanged.
+    pub(crate) fn hello_exchanged(&mut self) {
+        self.timing.hello = self.lap();
+    }
+
+    /// Marks the `Status` messages as exchanged and returns the timing of all phases.
+    pub(crate) fn status_exchanged(mut self) -> HandshakeTiming {
+        self.timing.status = self.lap();
+        self.timing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reth_eth_wire::clock::MockClock;
+
+    #[test]
+    fn test_handshake_timer() {
+        let clock = MockClock::new();
+        let mut timer = HandshakeTimer::start(clock.shared());
+
+        clock.advance(Duration::from_millis(30));
+        timer.tcp_connected();
+        clock.advance(Duration::from_millis(5));
+        timer.ecies_completed();
+        clock.advance(Duration::from_millis(40));
+        timer.hello_exchanged();
+        clock.advance(Duration::from_millis(45));
+        let timing = timer.status_exchanged();
+
+        assert_eq!(
+            timing,
+            HandshakeTiming {
+                tcp: Duration::from_millis(30),
+                ecies: Duration::from_millis(5),
+                hello: Duration::from_millis(40),
+                status: Duration::from_millis(45),
+            }
+        );
+        assert_eq!(timing.total(), Duration::from_millis(120));
+    }
+}
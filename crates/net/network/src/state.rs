@@ -14,8 +14,11 @@ use std::{
 
 use alloy_primitives::B256;
 use rand::seq::SliceRandom;
-use reth_eth_wire::{BlockHashNumber, Capabilities, DisconnectReason, NewBlockHashes, Status};
+use reth_eth_wire::{
+    BlockHashNumber, Capabilities, DisconnectReason, Initiator, NewBlockHashes, Status,
+};
 use reth_network_api::{DiscoveredEvent, DiscoveryEvent, PeerRequest, PeerRequestSender};
+use reth_network_p2p::error::SessionEnd;
 use reth_network_peers::PeerId;
 use reth_network_types::{PeerAddr, PeerKind};
 use reth_primitives::ForkId;
@@ -28,6 +31,7 @@ use crate::{
     fetch::{BlockResponseOutcome, FetchAction, StateFetcher},
     message::{BlockRequest, NewBlockMessage, PeerResponse, PeerResponseResult},
     peers::{PeerAction, PeersManager},
+    session::RequestLatencies,
     FetchClient,
 };
 
@@ -135,6 +139,11 @@ impl NetworkState {
         self.active_peers.len()
     }
 
+    /// Returns the hash of the best block the active peer is known to have.
+    pub(crate) fn best_hash(&self, peer_id: &PeerId) -> Option<B256> {
+        self.active_peers.get(peer_id).map(|peer| peer.best_hash)
+    }
+
     /// Event hook for an activated session for the peer.
     ///
     /// Returns `Ok` if the session is valid, returns an `Err` if the session is not accepted and
@@ -146,13 +155,20 @@ impl NetworkState {
         status: Arc<Status>,
         request_tx: PeerRequestSender,
         timeout: Arc<AtomicU64>,
+        latencies: Arc<RequestLatencies>,
     ) {
         debug_assert!(!self.active_peers.contains_key(&peer), "Already connected; not possible");
 
         // find the corresponding block number
         let block_number =
             self.client.block_number(status.blockhash).ok().flatten().unwrap_or_default();
-        self.state_fetcher.new_active_peer(peer, status.blockhash, block_number, timeout);
+        self.state_fetcher.new_active_peer(
+            peer,
+            status.blockhash,
+            block_number,
+            timeout,
+            latencies,
+        );
 
         self.active_peers.insert(
             peer,
@@ -169,9 +185,22 @@ impl NetworkState {
     /// Event hook for a disconnected session for the given peer.
     ///
     /// This will remove the peer from the available set of peers and close all inflight requests.
-    pub(crate) fn on_session_closed(&mut self, peer: PeerId) {
+    pub(crate) fn on_session_closed(&mut self, peer: PeerId, end: Option<SessionEnd>) {
         self.active_peers.remove(&peer);
-        self.state_fetcher.on_session_closed(&peer);
+        self.state_fetcher.on_session_closed(&peer, end);
+    }
+
+    /// Event hook for a session that was closed by the given side, for the given reason, if any.
+    ///
+    /// This records the disconnect before the peer is removed, see [`Self::on_session_closed`].
+    pub(crate) fn on_session_disconnected(
+        &mut self,
+        peer: PeerId,
+        initiator: Initiator,
+        reason: Option<DisconnectReason>,
+    ) {
+        self.state_fetcher.on_peer_disconnected(&peer, initiator, reason);
+        self.on_session_closed(peer, reason.map(|reason| SessionEnd::new(initiator, reason)));
     }
 
     /// Starts propagating the new block to peers that haven't reported the block yet.
@@ -269,8 +298,15 @@ impl NetworkState {
 
     /// Invoked for a `NewBlockHashes` broadcast message.
     pub(crate) fn on_new_block_hashes(&mut self, peer_id: PeerId, hashes: Vec<BlockHashNumber>) {
-        // Mark the blocks as seen
         if let Some(peer) = self.active_peers.get_mut(&peer_id) {
+            // update peer block info with the highest announced block
+            if let Some(latest) = hashes.iter().max_by_key(|b| b.number) {
+                if self.state_fetcher.update_peer_block(&peer_id, latest.hash, latest.number) {
+                    peer.best_hash = latest.hash;
+                }
+            }
+
+            // Mark the blocks as seen
             peer.blocks.extend(hashes.into_iter().map(|b| b.hash));
         }
     }
@@ -467,7 +503,7 @@ impl NetworkState {
             }
 
             for peer in closed_sessions {
-                self.on_session_closed(peer)
+                self.on_session_closed(peer, None)
             }
 
             for (peer_id, resp) in received_responses {
@@ -552,7 +588,9 @@ mod tests {
     };
 
     use alloy_primitives::B256;
-    use reth_eth_wire::{BlockBodies, Capabilities, Capability, EthVersion};
+    use reth_eth_wire::{
+        BlockBodies, BlockHashNumber, Capabilities, Capability, EthVersion, Status,
+    };
     use reth_network_api::PeerRequestSender;
     use reth_network_p2p::{bodies::client::BodiesClient, error::RequestError};
     use reth_network_peers::PeerId;
@@ -604,6 +642,7 @@ mod tests {
             Arc::default(),
             peer_tx,
             Arc::new(AtomicU64::new(1)),
+            Arc::default(),
         );
 
         assert!(state.active_peers.contains_key(&peer_id));
@@ -643,4 +682,36 @@ mod tests {
         assert!(resp.is_err());
         assert_eq!(resp.unwrap_err(), RequestError::ConnectionDropped);
     }
+
+    #[tokio::test]
+    async fn test_best_block_updated_by_announcement() {
+        let mut state = state();
+
+        let peer_id = PeerId::random();
+        let (tx, _session_rx) = mpsc::channel(1);
+        let peer_tx = PeerRequestSender::new(peer_id, tx);
+
+        let status = Status { blockhash: B256::random(), ..Default::default() };
+        state.on_session_activated(
+            peer_id,
+            capabilities(),
+            Arc::new(status),
+            peer_tx,
+            Arc::new(AtomicU64::new(1)),
+            Arc::default(),
+        );
+        assert_eq!(state.best_hash(&peer_id), Some(status.blockhash));
+
+        // the highest announced block becomes the best block, regardless of the order
+        let latest = BlockHashNumber { hash: B256::random(), number: 10 };
+        let older = BlockHashNumber { hash: B256::random(), number: 5 };
+        state.on_new_block_hashes(peer_id, vec![latest.clone(), older.clone()]);
+        assert_eq!(state.best_hash(&peer_id), Some(latest.hash));
+        assert!(state.active_peers[&peer_id].blocks.contains(&older.hash));
+
+        // an announcement of an older block doesn't regress the best block
+        let stale = BlockHashNumber { hash: B256::random(), number: 7 };
+        state.on_new_block_hashes(peer_id, vec![stale]);
+        assert_eq!(state.best_hash(&peer_id), Some(latest.hash));
+    }
 }
@@ -8,7 +8,8 @@ use std::{
 
 use futures::Stream;
 use reth_eth_wire::{
-    capability::CapabilityMessage, errors::EthStreamError, Capabilities, EthVersion, Status,
+    capability::CapabilityMessage, errors::SessionError, Capabilities, DisconnectReason,
+    EthVersion, Initiator, Status,
 };
 use reth_network_api::PeerRequestSender;
 use reth_network_peers::PeerId;
@@ -19,7 +20,9 @@ use crate::{
     message::PeerMessage,
     peers::InboundConnectionError,
     protocol::IntoRlpxSubProtocol,
-    session::{Direction, PendingSessionHandshakeError, SessionEvent, SessionId, SessionManager},
+    session::{
+        DialError, Direction, PendingSessionHandshakeError, SessionEvent, SessionId, SessionManager,
+    },
     state::{NetworkState, StateAction},
 };
 
@@ -103,7 +106,11 @@ impl Swarm {
 
 impl Swarm {
     /// Triggers a new outgoing connection to the given node
-    pub(crate) fn dial_outbound(&mut self, remote_addr: SocketAddr, remote_id: PeerId) {
+    pub(crate) fn dial_outbound(
+        &mut self,
+        remote_addr: SocketAddr,
+        remote_id: PeerId,
+    ) -> Result<SessionId, DialError> {
         self.sessions.dial_outbound(remote_addr, remote_id)
     }
 
@@ -123,6 +130,8 @@ impl Swarm {
                 messages,
                 direction,
                 timeout,
+                latencies,
+                ..
             } => {
                 self.state.on_session_activated(
                     peer_id,
@@ -130,6 +139,7 @@ impl Swarm {
                     status.clone(),
                     messages.clone(),
                     timeout,
+                    latencies,
                 );
                 Some(SwarmEvent::SessionEstablished {
                     peer_id,
@@ -159,13 +169,29 @@ impl Swarm {
             SessionEvent::OutgoingPendingSessionClosed { remote_addr, peer_id, error } => {
                 Some(SwarmEvent::OutgoingPendingSessionClosed { remote_addr, peer_id, error })
             }
-            SessionEvent::Disconnected { peer_id, remote_addr } => {
-                self.state.on_session_closed(peer_id);
-                Some(SwarmEvent::SessionClosed { peer_id, remote_addr, error: None })
+            SessionEvent::Disconnected { peer_id, remote_addr, reason } => {
+                // a graceful close without a local reason was initiated by the peer
+                let initiator = if reason.is_some() { Initiator::Local } else { Initiator::Remote };
+                self.state.on_session_disconnected(peer_id, initiator, reason);
+                Some(SwarmEvent::SessionClosed {
+                    peer_id,
+                    remote_addr,
+                    error: None,
+                    local_reason: reason,
+                })
             }
             SessionEvent::SessionClosedOnConnectionError { peer_id, remote_addr, error } => {
-                self.state.on_session_closed(peer_id);
-                Some(SwarmEvent::SessionClosed { peer_id, remote_addr, error: Some(error) })
+                // only a disconnect message means the peer ended the session, any other error was
+                // detected by us
+                let reason = error.as_disconnected();
+                let initiator = if reason.is_some() { Initiator::Remote } else { Initiator::Local };
+                self.state.on_session_disconnected(peer_id, initiator, reason);
+                Some(SwarmEvent::SessionClosed {
+                    peer_id,
+                    remote_addr,
+                    error: Some(error),
+                    local_reason: None,
+                })
             }
             SessionEvent::OutgoingConnectionError { remote_addr, peer_id, error } => {
                 Some(SwarmEvent::OutgoingConnectionError { peer_id, remote_addr, error })
@@ -227,7 +253,13 @@ impl Swarm {
     fn on_state_action(&mut self, event: StateAction) -> Option<SwarmEvent> {
         match event {
             StateAction::Connect { remote_addr, peer_id } => {
-                self.dial_outbound(remote_addr, peer_id);
+                if let Err(DialError::AlreadyDialing(session_id)) =
+                    self.dial_outbound(remote_addr, peer_id)
+                {
+                    // the pending session of the ongoing dial reports the outcome
+                    trace!(target: "net", ?peer_id, ?session_id, "already dialing peer");
+                    return None
+                }
                 return Some(SwarmEvent::OutgoingTcpConnection { remote_addr, peer_id })
             }
             StateAction::Disconnect { peer_id, reason } => {
@@ -397,7 +429,9 @@ pub(crate) enum SwarmEvent {
         peer_id: PeerId,
         remote_addr: SocketAddr,
         /// Whether the session was closed due to an error
-        error: Option<EthStreamError>,
+        error: Option<SessionError>,
+        /// The reason we disconnected the peer with, if we gracefully closed the session.
+        local_reason: Option<DisconnectReason>,
     },
     /// Admin rpc: new peer added
     PeerAdded(PeerId),
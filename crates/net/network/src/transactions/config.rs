@@ -1,6 +1,8 @@
 use derive_more::Constructor;
+use reth_primitives::TxType;
 
 use super::{
+    DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND,
     DEFAULT_MAX_COUNT_TRANSACTIONS_SEEN_BY_PEER,
     DEFAULT_SOFT_LIMIT_BYTE_SIZE_POOLED_TRANSACTIONS_RESP_ON_PACK_GET_POOLED_TRANSACTIONS_REQ,
     SOFT_LIMIT_BYTE_SIZE_POOLED_TRANSACTIONS_RESPONSE,
@@ -18,6 +20,14 @@ pub struct TransactionsManagerConfig {
     pub transaction_fetcher_config: TransactionFetcherConfig,
     /// Max number of seen transactions to store for each peer.
     pub max_transactions_seen_by_peer_history: u32,
+    /// Max number of [`GetPooledTransactions`](reth_eth_wire::GetPooledTransactions) requests
+    /// served per second for each peer. Requests exceeding the limit are answered with an empty
+    /// response.
+    pub max_get_pooled_transactions_requests_per_second: u32,
+    /// How new pending transactions are propagated to connected peers in full.
+    pub propagation_mode: TransactionPropagationMode,
+    /// Which transaction types announced by peers are fetched.
+    pub tx_type_fetch_policy: TxTypeFetchPolicy,
 }
 
 impl Default for TransactionsManagerConfig {
@@ -25,10 +35,70 @@ impl Default for TransactionsManagerConfig {
         Self {
             transaction_fetcher_config: TransactionFetcherConfig::default(),
             max_transactions_seen_by_peer_history: DEFAULT_MAX_COUNT_TRANSACTIONS_SEEN_BY_PEER,
+            max_get_pooled_transactions_requests_per_second:
+                DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND,
+            propagation_mode: TransactionPropagationMode::default(),
+            tx_type_fetch_policy: TxTypeFetchPolicy::default(),
         }
     }
 }
 
+/// Determines to how many of the connected peers new pending transactions are sent in full.
+///
+/// All other peers are only sent announcements of the transaction hashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransactionPropagationMode {
+    /// Send full transactions to the square root of the number of connected peers, like geth.
+    #[default]
+    Sqrt,
+    /// Send full transactions to all connected peers.
+    All,
+    /// Send full transactions to at most the given number of peers.
+    Max(usize),
+}
+
+impl TransactionPropagationMode {
+    /// Returns the number of peers, out of `peer_count`, that full transactions are sent to.
+    pub(crate) fn full_peer_count(self, peer_count: usize) -> usize {
+        match self {
+            Self::Sqrt => (peer_count as f64).sqrt().round() as usize,
+            Self::All => peer_count,
+            Self::Max(max) => max.min(peer_count),
+        }
+    }
+}
+
+/// Determines which announced transactions are fetched, by their [`TxType`].
+///
+/// Only eth/68 announcements carry the transaction type, transactions announced via eth/66 are
+/// always fetched. By default all transaction types are fetched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TxTypeFetchPolicy {
+    /// Bitmask of the transaction types that are not fetched, indexed by the type's byte.
+    skipped: u128,
+}
+
+impl TxTypeFetchPolicy {
+    /// Returns a policy that fetches transactions of all types.
+    pub const fn fetch_all() -> Self {
+        Self { skipped: 0 }
+    }
+
+    /// Skips fetching announced transactions of the given type, e.g. [`TxType::Eip4844`] if the
+    /// blob pool isn't tracked.
+    pub const fn with_skipped(mut self, tx_type: TxType) -> Self {
+        self.skipped |= 1 << tx_type as u8;
+        self
+    }
+
+    /// Returns true if announced transactions of the given type are fetched.
+    pub const fn is_fetched(&self, tx_type: TxType) -> bool {
+        self.skipped & (1 << tx_type as u8) == 0
+    }
+}
+
 /// Configuration for fetching transactions.
 #[derive(Debug, Constructor, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
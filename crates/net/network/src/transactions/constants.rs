@@ -53,6 +53,13 @@ pub mod tx_manager {
     ///
     /// Default is 100 KiB, i.e. 3 200 transaction hashes.
     pub const DEFAULT_MAX_COUNT_BAD_IMPORTS: u32 = 100 * 1024 / 32;
+
+    /// Default limit for number of
+    /// [`GetPooledTransactions`](reth_eth_wire::GetPooledTransactions) requests served per second
+    /// for a single peer.
+    ///
+    /// Default is 50 requests per second, which also bounds the burst a peer can send at once.
+    pub const DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND: u32 = 50;
 }
 
 /// Constants used by [`TransactionFetcher`](super::TransactionFetcher).
@@ -551,13 +551,14 @@ impl TransactionFetcher {
                 // removed in favour of lru dead peers
                 let mut ended_sessions = vec![];
                 for &peer_id in fallback_peers.iter() {
-                    if is_session_active(peer_id) {
+                    if !is_session_active(peer_id) {
                         ended_sessions.push(peer_id);
                     }
                 }
                 for peer_id in ended_sessions {
                     fallback_peers.remove(&peer_id);
                 }
+                fallback_peers.insert(*peer_id);
 
                 return false
             }
@@ -930,7 +931,21 @@ impl TransactionFetcher {
                 }
 
                 //
-                // 2. filter out hashes that we didn't request
+                // 2. reject response if the sidecar of a blob transaction doesn't match the
+                // versioned hashes of the transaction
+                //
+                if !transactions.has_valid_blob_versioned_hashes() {
+                    trace!(target: "net::tx",
+                        peer_id=format!("{peer_id:#}"),
+                        "received `PooledTransactions` response from peer with blob transaction(s) whose commitments don't match their versioned hashes"
+                    );
+
+                    self.try_buffer_hashes_for_retry(requested_hashes, &peer_id);
+                    return FetchEvent::FetchError { peer_id, error: RequestError::BadResponse }
+                }
+
+                //
+                // 3. filter out hashes that we didn't request
                 //
                 let payload = UnverifiedPooledTransactions::new(transactions);
 
@@ -957,7 +972,7 @@ impl TransactionFetcher {
                 }
 
                 //
-                // 3. stateless validation of payload, e.g. dedup
+                // 4. stateless validation of payload, e.g. dedup
                 //
                 let unvalidated_payload_len = verified_payload.len();
 
@@ -982,7 +997,7 @@ impl TransactionFetcher {
                 // to pending pool imports pipeline for validation.
 
                 //
-                // 4. clear received hashes
+                // 5. clear received hashes
                 //
                 let requested_hashes_len = requested_hashes.len();
                 let mut fetched = Vec::with_capacity(valid_payload.len());
@@ -1007,7 +1022,7 @@ impl TransactionFetcher {
                 }
 
                 //
-                // 5. buffer left over hashes
+                // 6. buffer left over hashes
                 //
                 self.try_buffer_hashes_for_retry(requested_hashes, &peer_id);
 
@@ -1338,10 +1353,12 @@ struct TxFetcherSearchDurations {
 mod test {
     use std::{collections::HashSet, str::FromStr};
 
-    use alloy_primitives::{hex, B256};
+    use alloy_consensus::{TxEip4844, TxEip4844WithSidecar};
+    use alloy_eips::eip4844::Bytes48;
+    use alloy_primitives::{hex, Parity, B256, U256};
     use alloy_rlp::Decodable;
     use derive_more::IntoIterator;
-    use reth_primitives::TransactionSigned;
+    use reth_primitives::{BlobTransaction, BlobTransactionSidecar, Signature, TransactionSigned};
 
     use crate::transactions::tests::{default_cache, new_mock_session};
 
@@ -1558,4 +1575,35 @@ mod test {
         assert_eq!(1, verified_payload.len());
         assert!(verified_payload.contains(&signed_tx_1));
     }
+
+    #[test]
+    fn reject_blob_tx_with_mismatching_versioned_hash() {
+        let mut tx_fetcher = TransactionFetcher::default();
+        let peer_id = PeerId::new([1; 64]);
+        tx_fetcher.active_peers.insert(peer_id, 1);
+
+        // the sidecar's commitment doesn't hash to the versioned hash listed in the transaction
+        let tx = PooledTransactionsElement::BlobTransaction(BlobTransaction {
+            hash: B256::random(),
+            signature: Signature::new(U256::from(1), U256::from(1), Parity::Parity(false)),
+            transaction: TxEip4844WithSidecar {
+                tx: TxEip4844 { blob_versioned_hashes: vec![B256::random()], ..Default::default() },
+                sidecar: BlobTransactionSidecar {
+                    blobs: vec![],
+                    commitments: vec![Bytes48::repeat_byte(0xc0)],
+                    proofs: vec![],
+                },
+            },
+        });
+        let requested_hashes = RequestTxHashes::new(HashSet::from([*tx.hash()]));
+        let ev = tx_fetcher.on_resolved_get_pooled_transactions_request_fut(GetPooledTxResponse {
+            peer_id,
+            requested_hashes,
+            result: Ok(Ok(PooledTransactions(vec![tx]))),
+        });
+
+        let FetchEvent::FetchError { peer_id: reported, error } = ev else { unreachable!() };
+        assert_eq!(reported, peer_id);
+        assert_eq!(error, RequestError::BadResponse);
+    }
 }
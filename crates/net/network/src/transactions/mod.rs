@@ -12,7 +12,10 @@ pub use self::constants::{
     tx_fetcher::DEFAULT_SOFT_LIMIT_BYTE_SIZE_POOLED_TRANSACTIONS_RESP_ON_PACK_GET_POOLED_TRANSACTIONS_REQ,
     SOFT_LIMIT_BYTE_SIZE_POOLED_TRANSACTIONS_RESPONSE,
 };
-pub use config::{TransactionFetcherConfig, TransactionsManagerConfig};
+pub use config::{
+    TransactionFetcherConfig, TransactionPropagationMode, TransactionsManagerConfig,
+    TxTypeFetchPolicy,
+};
 pub use validation::*;
 
 pub(crate) use fetcher::{FetchEvent, TransactionFetcher};
@@ -48,12 +51,14 @@ use reth_network_p2p::{
 };
 use reth_network_peers::PeerId;
 use reth_network_types::ReputationChangeKind;
-use reth_primitives::{PooledTransactionsElement, TransactionSigned, TransactionSignedEcRecovered};
+use reth_primitives::{
+    PooledTransactionsElement, TransactionSigned, TransactionSignedEcRecovered, TxType,
+};
 use reth_tokio_util::EventStream;
 use reth_transaction_pool::{
     error::{PoolError, PoolResult},
-    GetPooledTransactionLimit, PoolTransaction, PropagateKind, PropagatedTransactions,
-    TransactionPool, ValidPoolTransaction,
+    BlobSidecarInclusion, GetPooledTransactionLimit, PoolTransaction, PropagateKind,
+    PropagatedTransactions, TransactionPool, ValidPoolTransaction,
 };
 use tokio::sync::{mpsc, oneshot, oneshot::error::RecvError};
 use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
@@ -248,6 +253,12 @@ pub struct TransactionsManager<Pool> {
     transaction_events: UnboundedMeteredReceiver<NetworkTransactionEvent>,
     /// Max number of seen transactions to store for each peer.
     max_transactions_seen_by_peer_history: u32,
+    /// Max number of [`GetPooledTransactions`] requests served per second for each peer.
+    max_get_pooled_transactions_requests_per_second: u32,
+    /// How new pending transactions are propagated to peers in full.
+    propagation_mode: TransactionPropagationMode,
+    /// Which announced transaction types are fetched.
+    tx_type_fetch_policy: TxTypeFetchPolicy,
     /// `TransactionsManager` metrics
     metrics: TransactionsManagerMetrics,
 }
@@ -300,6 +311,10 @@ impl<Pool: TransactionPool> TransactionsManager<Pool> {
             ),
             max_transactions_seen_by_peer_history: transactions_manager_config
                 .max_transactions_seen_by_peer_history,
+            max_get_pooled_transactions_requests_per_second: transactions_manager_config
+                .max_get_pooled_transactions_requests_per_second,
+            propagation_mode: transactions_manager_config.propagation_mode,
+            tx_type_fetch_policy: transactions_manager_config.tx_type_fetch_policy,
             metrics,
         }
     }
@@ -359,11 +374,22 @@ where
                 let _ = response.send(Ok(PooledTransactions::default()));
                 return
             }
-            let transactions = self.pool.get_pooled_transaction_elements(
+            if !peer.get_pooled_transactions_limiter.try_acquire() {
+                trace!(target: "net::tx",
+                    peer_id=format!("{peer_id:#}"),
+                    "peer exceeded rate limit for GetPooledTransactions requests, sending empty response"
+                );
+                self.metrics.rate_limited_get_pooled_transactions_requests.increment(1);
+                let _ = response.send(Ok(PooledTransactions::default()));
+                return
+            }
+            // peers request the pooled form of blob transactions, which includes the sidecar
+            let transactions = self.pool.get_pooled_transaction_elements_with(
                 request.0,
                 GetPooledTransactionLimit::ResponseSizeSoftLimit(
                     self.transaction_fetcher.info.soft_limit_byte_size_pooled_transactions_response,
                 ),
+                BlobSidecarInclusion::Include,
             );
 
             trace!(target: "net::tx::propagation", sent_txs=?transactions.iter().map(|tx| *tx.hash()), "Sending requested transactions to peer");
@@ -424,17 +450,21 @@ where
             return propagated
         }
 
-        // send full transactions to a fraction of the connected peers (square root of the total
-        // number of connected peers)
-        let max_num_full = (self.peers.len() as f64).sqrt().round() as usize;
+        // send full transactions to a fraction of the connected peers, chosen at random for each
+        // batch, and only announce the hashes to the rest
+        let num_peers = self.peers.len();
+        let max_num_full = self.propagation_mode.full_peer_count(num_peers);
+        let full_peer_indices: HashSet<usize> =
+            rand::seq::index::sample(&mut rand::thread_rng(), num_peers, max_num_full)
+                .into_iter()
+                .collect();
 
-        // Note: Assuming ~random~ order due to random state of the peers map hasher
         for (peer_idx, (peer_id, peer)) in self.peers.iter_mut().enumerate() {
             // determine whether to send full tx objects or hashes.
-            let mut builder = if peer_idx > max_num_full {
-                PropagateTransactionsBuilder::pooled(peer.version)
-            } else {
+            let mut builder = if full_peer_indices.contains(&peer_idx) {
                 PropagateTransactionsBuilder::full(peer.version)
+            } else {
+                PropagateTransactionsBuilder::pooled(peer.version)
             };
 
             // Iterate through the transactions to propagate and fill the hashes and full
@@ -724,7 +754,24 @@ where
             return
         }
 
-        // 5. filter out already seen unknown hashes
+        // 5. filter out transaction types that aren't fetched
+        //
+        // only eth68 announcements carry the tx type, tx types have been validated in step 4.
+        //
+        let tx_type_fetch_policy = &self.tx_type_fetch_policy;
+        valid_announcement_data.retain(|_, metadata| {
+            let Some(ty) = metadata.and_then(|(ty, _)| TxType::try_from(ty).ok()) else {
+                return true
+            };
+            tx_type_fetch_policy.is_fetched(ty)
+        });
+
+        if valid_announcement_data.is_empty() {
+            // nothing to request
+            return
+        }
+
+        // 6. filter out already seen unknown hashes
         //
         // seen hashes are already in the tx fetcher, pending fetch.
         //
@@ -914,6 +961,7 @@ where
                     version,
                     client_version,
                     self.max_transactions_seen_by_peer_history,
+                    self.max_get_pooled_transactions_requests_per_second,
                 );
                 let peer = match self.peers.entry(peer_id) {
                     Entry::Occupied(mut entry) => {
@@ -1149,10 +1197,14 @@ where
         let kind = match req_err {
             RequestError::UnsupportedCapability => ReputationChangeKind::BadProtocol,
             RequestError::Timeout => ReputationChangeKind::Timeout,
-            RequestError::ChannelClosed | RequestError::ConnectionDropped => {
+            RequestError::ChannelClosed |
+            RequestError::ConnectionDropped |
+            RequestError::Disconnected(_) => {
                 // peer is already disconnected
                 return
             }
+            // the peer is busy, which is not its fault
+            RequestError::ChannelFull => return,
             RequestError::BadResponse => return self.report_peer_bad_transactions(peer_id),
         };
         self.report_peer(peer_id, kind);
@@ -1582,7 +1634,7 @@ impl PooledTransactionsHashesBuilder {
     fn new(version: EthVersion) -> Self {
         match version {
             EthVersion::Eth66 | EthVersion::Eth67 => Self::Eth66(Default::default()),
-            EthVersion::Eth68 => Self::Eth68(Default::default()),
+            EthVersion::Eth68 | EthVersion::Eth69 => Self::Eth68(Default::default()),
         }
     }
 
@@ -1624,6 +1676,8 @@ pub struct PeerMetadata {
     version: EthVersion,
     /// The peer's client version.
     client_version: Arc<str>,
+    /// Limits the rate at which [`GetPooledTransactions`] requests of the peer are served.
+    get_pooled_transactions_limiter: RequestRateLimiter,
 }
 
 impl PeerMetadata {
@@ -1633,16 +1687,56 @@ impl PeerMetadata {
         version: EthVersion,
         client_version: Arc<str>,
         max_transactions_seen_by_peer: u32,
+        max_get_pooled_transactions_requests_per_second: u32,
     ) -> Self {
         Self {
             seen_transactions: LruCache::new(max_transactions_seen_by_peer),
             request_tx,
             version,
             client_version,
+            get_pooled_transactions_limiter: RequestRateLimiter::new(
+                max_get_pooled_transactions_requests_per_second,
+            ),
         }
     }
 }
 
+/// Token bucket limiting the number of requests served per second.
+///
+/// The bucket holds one second worth of tokens, so a peer may burst up to the configured rate
+/// before its requests are limited.
+#[derive(Debug)]
+struct RequestRateLimiter {
+    /// Max number of tokens, i.e. requests allowed per second.
+    capacity: f64,
+    /// Currently available tokens.
+    tokens: f64,
+    /// Last time the bucket was refilled.
+    last_refill: Instant,
+}
+
+impl RequestRateLimiter {
+    /// Returns a new, full bucket for the given rate.
+    fn new(requests_per_second: u32) -> Self {
+        let capacity = requests_per_second as f64;
+        Self { capacity, tokens: capacity, last_refill: Instant::now() }
+    }
+
+    /// Refills the bucket and takes a token. Returns `false` if the rate limit is exceeded.
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.tokens = elapsed.mul_add(self.capacity, self.tokens).min(self.capacity);
+
+        if self.tokens < 1.0 {
+            return false
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
 /// Commands to send to the [`TransactionsManager`]
 #[derive(Debug)]
 enum TransactionsCommand {
@@ -1803,6 +1897,7 @@ mod tests {
                 version,
                 Arc::from(""),
                 DEFAULT_MAX_COUNT_TRANSACTIONS_SEEN_BY_PEER,
+                DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND,
             ),
             to_mock_session_rx,
         )
@@ -2161,6 +2256,45 @@ mod tests {
         }
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_pooled_transactions_rate_limited() {
+        reth_tracing::init_test_tracing();
+        let (mut tx_manager, _network) = new_tx_manager().await;
+
+        let peer_id = PeerId::new([1; 64]);
+        let (mut peer, _to_mock_session_rx) = new_mock_session(peer_id, EthVersion::Eth68);
+        let max_requests_per_second = 3;
+        peer.get_pooled_transactions_limiter = RequestRateLimiter::new(max_requests_per_second);
+        tx_manager.peers.insert(peer_id, peer);
+
+        let tx = MockTransaction::eip1559();
+        let _ = tx_manager
+            .pool
+            .add_transaction(reth_transaction_pool::TransactionOrigin::External, tx.clone())
+            .await;
+
+        // burst of requests, exceeding the limit
+        let mut responses = Vec::new();
+        for _ in 0..max_requests_per_second + 2 {
+            let (send, receive) = oneshot::channel::<RequestResult<PooledTransactions>>();
+            tx_manager.on_network_tx_event(NetworkTransactionEvent::GetPooledTransactions {
+                peer_id,
+                request: GetPooledTransactions(vec![tx.get_hash()]),
+                response: send,
+            });
+            responses.push(receive);
+        }
+
+        for (i, receive) in responses.into_iter().enumerate() {
+            let PooledTransactions(txns) = receive.await.unwrap().unwrap();
+            if i < max_requests_per_second as usize {
+                assert_eq!(txns.len(), 1);
+            } else {
+                assert!(txns.is_empty());
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_max_retries_tx_request() {
         reth_tracing::init_test_tracing();
@@ -2391,4 +2525,156 @@ mod tests {
         let propagated = tx_manager.propagate_transactions(propagate);
         assert!(propagated.0.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_tx_type_fetch_policy_skips_blob_txs() {
+        reth_tracing::init_test_tracing();
+
+        let (mut tx_manager, network) = new_tx_manager().await;
+        tx_manager.tx_type_fetch_policy =
+            TxTypeFetchPolicy::fetch_all().with_skipped(TxType::Eip4844);
+
+        // ensure not syncing
+        network.handle().update_sync_state(SyncState::Idle);
+
+        let peer_id = PeerId::random();
+        let (peer, mut to_mock_session_rx) = new_mock_session(peer_id, EthVersion::Eth68);
+        tx_manager.peers.insert(peer_id, peer);
+
+        let eip1559_hash = B256::random();
+        let eip4844_hash = B256::random();
+        tx_manager.on_new_pooled_transaction_hashes(
+            peer_id,
+            NewPooledTransactionHashes::Eth68(NewPooledTransactionHashes68 {
+                types: vec![TxType::Eip1559 as u8, TxType::Eip4844 as u8],
+                sizes: vec![200, 131_300],
+                hashes: vec![eip1559_hash, eip4844_hash],
+            }),
+        );
+
+        // only the non-blob transaction is requested
+        let req = to_mock_session_rx.recv().await.unwrap();
+        let PeerRequest::GetPooledTransactions { request, .. } = req else { unreachable!() };
+        assert_eq!(request.0, vec![eip1559_hash]);
+        assert!(!tx_manager.transaction_fetcher.hashes_pending_fetch.contains(&eip4844_hash));
+    }
+
+    #[tokio::test]
+    async fn test_announcements_dedup_across_versions() {
+        reth_tracing::init_test_tracing();
+
+        let (mut tx_manager, network) = new_tx_manager().await;
+
+        // ensure not syncing
+        network.handle().update_sync_state(SyncState::Idle);
+
+        let peer_66 = PeerId::random();
+        let peer_68 = PeerId::random();
+        let (peer, mut peer_66_rx) = new_mock_session(peer_66, EthVersion::Eth66);
+        tx_manager.peers.insert(peer_66, peer);
+        let (peer, mut peer_68_rx) = new_mock_session(peer_68, EthVersion::Eth68);
+        tx_manager.peers.insert(peer_68, peer);
+
+        // the same hash is announced by both peers, in the announcement of their version
+        let hash = B256::random();
+        tx_manager.on_new_pooled_transaction_hashes(
+            peer_66,
+            NewPooledTransactionHashes::Eth66(NewPooledTransactionHashes66(vec![hash])),
+        );
+        tx_manager.on_new_pooled_transaction_hashes(
+            peer_68,
+            NewPooledTransactionHashes::Eth68(NewPooledTransactionHashes68 {
+                types: vec![TxType::Eip1559 as u8],
+                sizes: vec![200],
+                hashes: vec![hash],
+            }),
+        );
+
+        // the hash is only requested once, the second peer is a fallback
+        let req = peer_66_rx.recv().await.unwrap();
+        let PeerRequest::GetPooledTransactions { request, .. } = req else { unreachable!() };
+        assert_eq!(request.0, vec![hash]);
+        assert!(peer_68_rx.try_recv().is_err());
+
+        let fetcher = &mut tx_manager.transaction_fetcher;
+        assert_eq!(fetcher.inflight_requests.len(), 1);
+        let metadata = fetcher.hashes_fetch_inflight_and_pending_fetch.peek_mut(&hash).unwrap();
+        // the metadata of the eth/68 announcement is kept
+        assert_eq!(metadata.tx_encoded_len(), Some(200));
+        assert!(metadata.fallback_peers_mut().contains(&peer_68));
+    }
+
+    #[tokio::test]
+    async fn test_no_announcement_back_to_announcing_peer() {
+        reth_tracing::init_test_tracing();
+
+        let (mut tx_manager, network) = new_tx_manager().await;
+
+        // ensure not syncing
+        network.handle().update_sync_state(SyncState::Idle);
+
+        let announcing_peer = PeerId::random();
+        let other_peer = PeerId::random();
+        let (peer, _announcing_peer_rx) = new_mock_session(announcing_peer, EthVersion::Eth68);
+        tx_manager.peers.insert(announcing_peer, peer);
+        let (peer, _other_peer_rx) = new_mock_session(other_peer, EthVersion::Eth68);
+        tx_manager.peers.insert(other_peer, peer);
+
+        let mut factory = MockTransactionFactory::default();
+        let tx = Arc::new(factory.create_eip1559());
+        let hash = *tx.transaction.hash();
+
+        // the peer announces the transaction to us
+        tx_manager.on_new_pooled_transaction_hashes(
+            announcing_peer,
+            NewPooledTransactionHashes::Eth66(NewPooledTransactionHashes66(vec![hash])),
+        );
+        assert!(tx_manager.peers[&announcing_peer].seen_transactions.contains(&hash));
+
+        // the transaction is only propagated to the peer that doesn't know it yet
+        let propagated = tx_manager.propagate_transactions(vec![PropagateTransaction::new(tx)]);
+        let kinds = propagated.0.get(&hash).unwrap();
+        assert_eq!(kinds.len(), 1);
+        assert_eq!(kinds[0].peer(), &other_peer);
+        assert!(tx_manager.peers[&other_peer].seen_transactions.contains(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_propagate_full_to_sqrt_of_peers() {
+        reth_tracing::init_test_tracing();
+
+        let (mut tx_manager, network) = new_tx_manager().await;
+
+        // ensure not syncing
+        network.handle().update_sync_state(SyncState::Idle);
+
+        let num_peers = 25;
+        for _ in 0..num_peers {
+            let peer_id = PeerId::random();
+            let (peer, _to_mock_session_rx) = new_mock_session(peer_id, EthVersion::Eth68);
+            tx_manager.peers.insert(peer_id, peer);
+        }
+
+        let mut factory = MockTransactionFactory::default();
+        let tx = Arc::new(factory.create_eip1559());
+        let propagated =
+            tx_manager.propagate_transactions(vec![PropagateTransaction::new(tx.clone())]);
+
+        let kinds = propagated.0.get(tx.transaction.hash()).unwrap();
+        assert_eq!(kinds.len(), num_peers);
+        let num_full = kinds.iter().filter(|kind| kind.is_full()).count();
+        let num_hashes = kinds.iter().filter(|kind| kind.is_hash()).count();
+        assert_eq!(num_full, 5);
+        assert_eq!(num_hashes, num_peers - 5);
+    }
+
+    #[test]
+    fn test_propagation_mode_full_peer_count() {
+        assert_eq!(TransactionPropagationMode::Sqrt.full_peer_count(0), 0);
+        assert_eq!(TransactionPropagationMode::Sqrt.full_peer_count(1), 1);
+        assert_eq!(TransactionPropagationMode::Sqrt.full_peer_count(50), 7);
+        assert_eq!(TransactionPropagationMode::All.full_peer_count(50), 50);
+        assert_eq!(TransactionPropagationMode::Max(3).full_peer_count(50), 3);
+        assert_eq!(TransactionPropagationMode::Max(3).full_peer_count(2), 2);
+    }
 }
@@ -13,17 +13,17 @@ use reth_net_banlist::BanList;
 use reth_network::{
     test_utils::{enr_to_peer_id, NetworkEventStream, PeerConfig, Testnet, GETH_TIMEOUT},
     BlockDownloaderProvider, NetworkConfigBuilder, NetworkEvent, NetworkEventListenerProvider,
-    NetworkManager, PeersConfig,
+    NetworkManager, PeersConfig, StaticDiscovery,
 };
 use reth_network_api::{NetworkInfo, Peers, PeersInfo};
 use reth_network_p2p::{
     headers::client::{HeadersClient, HeadersRequest},
     sync::{NetworkSyncUpdater, SyncState},
 };
-use reth_network_peers::{mainnet_nodes, NodeRecord, TrustedPeer};
+use reth_network_peers::{mainnet_nodes, pk2id, NodeRecord, TrustedPeer};
 use reth_provider::test_utils::NoopProvider;
 use reth_transaction_pool::test_utils::testing_pool;
-use secp256k1::SecretKey;
+use secp256k1::{SecretKey, SECP256K1};
 use tokio::task;
 use url::Host;
 
@@ -89,9 +89,14 @@ async fn test_already_connected() {
     reth_tracing::init_test_tracing();
     let mut net = Testnet::default();
 
-    let secret_key = SecretKey::new(&mut rand::thread_rng());
+    // the first peer's id is the smaller one, so it keeps the connection it dialed when the same
+    // identifier connects again
+    let mut secret_keys =
+        [SecretKey::new(&mut rand::thread_rng()), SecretKey::new(&mut rand::thread_rng())];
+    secret_keys.sort_by_key(|key| pk2id(&key.public_key(SECP256K1)));
+    let [first_key, secret_key] = secret_keys;
     let client = NoopProvider::default();
-    let p1 = PeerConfig::default();
+    let p1 = PeerConfig::with_secret_key(client, first_key);
 
     // initialize two peers with the same identifier
     let p2 = PeerConfig::with_secret_key(client, secret_key);
@@ -681,6 +686,30 @@ async fn test_rejected_by_already_connect() {
     assert_eq!(handle.num_connected_peers(), 2);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connect_to_static_discovery_nodes() {
+    reth_tracing::init_test_tracing();
+    let peer = new_random_peer(10, vec![]).await;
+    let peer_handle = peer.handle().clone();
+    tokio::task::spawn(peer);
+
+    // discovery is disabled, so the node only learns about the peer from the static list
+    let node = NodeRecord::new(peer_handle.local_addr(), *peer_handle.peer_id());
+    let config = NetworkConfigBuilder::new(SecretKey::new(&mut rand::thread_rng()))
+        .listener_port(0)
+        .disable_discovery()
+        .add_discovery_backend(StaticDiscovery::new([node]))
+        .build_with_noop_provider(MAINNET.clone());
+    let network = NetworkManager::new(config).await.unwrap();
+    let handle = network.handle().clone();
+    let mut events = NetworkEventStream::new(handle.event_listener());
+    tokio::task::spawn(network);
+
+    let peer_id = events.next_session_established().await.unwrap();
+    assert_eq!(peer_id, *peer_handle.peer_id());
+    assert_eq!(handle.num_connected_peers(), 1);
+}
+
 async fn new_random_peer(max_in_bound: usize, trusted_nodes: Vec<TrustedPeer>) -> NetworkManager {
     let secret_key = SecretKey::new(&mut rand::thread_rng());
     let peers_config =
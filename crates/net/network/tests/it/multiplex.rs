@@ -10,7 +10,8 @@ use std::{
 use alloy_primitives::bytes::BytesMut;
 use futures::{Stream, StreamExt};
 use reth_eth_wire::{
-    capability::SharedCapabilities, multiplex::ProtocolConnection, protocol::Protocol,
+    capability::SharedCapabilities, multiplex::ProtocolConnection, protocol::Protocol, Capability,
+    EthVersion,
 };
 use reth_network::{
     protocol::{ConnectionHandler, OnNotSupported, ProtocolHandler},
@@ -325,3 +326,105 @@ async fn test_proto_multiplex() {
     let response = rx.await.unwrap();
     assert_eq!(response, "hello from peer1!");
 }
+
+/// A `snap/1` handler that keeps the connection open without serving any messages.
+#[derive(Debug)]
+struct SnapProtoHandler;
+
+impl ProtocolHandler for SnapProtoHandler {
+    type ConnectionHandler = SnapConnectionHandler;
+
+    fn on_incoming(&self, _socket_addr: SocketAddr) -> Option<Self::ConnectionHandler> {
+        Some(SnapConnectionHandler)
+    }
+
+    fn on_outgoing(
+        &self,
+        _socket_addr: SocketAddr,
+        _peer_id: PeerId,
+    ) -> Option<Self::ConnectionHandler> {
+        Some(SnapConnectionHandler)
+    }
+}
+
+struct SnapConnectionHandler;
+
+impl ConnectionHandler for SnapConnectionHandler {
+    type Connection = SnapProtoConnection;
+
+    fn protocol(&self) -> Protocol {
+        Protocol::snap_1()
+    }
+
+    fn on_unsupported_by_peer(
+        self,
+        _supported: &SharedCapabilities,
+        _direction: Direction,
+        _peer_id: PeerId,
+    ) -> OnNotSupported {
+        OnNotSupported::KeepAlive
+    }
+
+    fn into_connection(
+        self,
+        _direction: Direction,
+        _peer_id: PeerId,
+        conn: ProtocolConnection,
+    ) -> Self::Connection {
+        SnapProtoConnection { conn }
+    }
+}
+
+struct SnapProtoConnection {
+    conn: ProtocolConnection,
+}
+
+impl Stream for SnapProtoConnection {
+    type Item = BytesMut;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // drop all incoming messages
+        while ready!(this.conn.poll_next_unpin(cx)).is_some() {}
+        Poll::Ready(None)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_peers_with_shared_capability() {
+    reth_tracing::init_test_tracing();
+    let provider = MockEthProvider::default();
+    let mut net = Testnet::create_with(3, provider.clone()).await;
+
+    // only the first two peers support snap
+    net.peers_mut()[0].add_rlpx_sub_protocol(SnapProtoHandler);
+    net.peers_mut()[1].add_rlpx_sub_protocol(SnapProtoHandler);
+
+    let handle = net.spawn();
+    // connect all the peers
+    handle.connect_peers().await;
+
+    let network = handle.peers()[0].network();
+    let snap_peer = *handle.peers()[1].peer_id();
+    let eth_peer = *handle.peers()[2].peer_id();
+
+    // negotiated eth/68 and snap/1 with the snap peer
+    let shared = network.shared_capabilities(snap_peer).await.unwrap().unwrap();
+    assert_eq!(shared.len(), 2);
+    assert_eq!(shared.eth_version().unwrap(), EthVersion::Eth68);
+    assert_eq!(shared.snap().unwrap().capability().into_owned(), Capability::snap_1());
+
+    // only eth/68 with the other peer
+    let shared = network.shared_capabilities(eth_peer).await.unwrap().unwrap();
+    assert_eq!(shared.len(), 1);
+    assert!(shared.snap().is_none());
+
+    // scheduling snap requests only considers the snap peer
+    assert_eq!(network.peers_with_capability(Capability::snap_1()).await.unwrap(), vec![snap_peer]);
+    let mut eth_68_peers = network.peers_with_capability(Capability::eth_68()).await.unwrap();
+    eth_68_peers.sort();
+    let mut expected = vec![snap_peer, eth_peer];
+    expected.sort();
+    assert_eq!(eth_68_peers, expected);
+    assert!(network.peers_with_capability(Capability::eth_66()).await.unwrap().is_empty());
+}
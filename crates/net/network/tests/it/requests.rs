@@ -4,20 +4,21 @@
 use std::sync::Arc;
 
 use alloy_consensus::TxEip2930;
-use alloy_primitives::{Bytes, Parity, TxKind, U256};
+use alloy_primitives::{Bytes, Log, Parity, TxKind, U256};
 use rand::Rng;
-use reth_eth_wire::HeadersDirection;
+use reth_eth_wire::{GetReceipts, HeadersDirection};
 use reth_network::{
     test_utils::{NetworkEventStream, Testnet},
     BlockDownloaderProvider, NetworkEventListenerProvider,
 };
-use reth_network_api::{NetworkInfo, Peers};
+use reth_network_api::{NetworkInfo, PeerRequest, Peers};
 use reth_network_p2p::{
     bodies::client::BodiesClient,
     headers::client::{HeadersClient, HeadersRequest},
 };
-use reth_primitives::{Block, Header, Signature, Transaction, TransactionSigned};
+use reth_primitives::{Block, Header, Receipt, Signature, Transaction, TransactionSigned};
 use reth_provider::test_utils::MockEthProvider;
+use tokio::sync::oneshot;
 
 /// Returns a new [`TransactionSigned`] with some random parameters
 pub fn rng_transaction(rng: &mut impl rand::RngCore) -> TransactionSigned {
@@ -78,6 +79,99 @@ async fn test_get_body() {
     }
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_bodies_omits_missing() {
+    reth_tracing::init_test_tracing();
+    let mut rng = rand::thread_rng();
+    let mock_provider = Arc::new(MockEthProvider::default());
+
+    let mut net = Testnet::create_with(2, mock_provider.clone()).await;
+
+    // install request handlers
+    net.for_each_mut(|peer| peer.install_request_handler());
+
+    let handle0 = net.peers()[0].handle();
+    let mut events0 = NetworkEventStream::new(handle0.event_listener());
+
+    let handle1 = net.peers()[1].handle();
+
+    let _handle = net.spawn();
+
+    let fetch0 = handle0.fetch_client().await.unwrap();
+
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+    let connected = events0.next_session_established().await.unwrap();
+    assert_eq!(connected, *handle1.peer_id());
+
+    // only every other requested block is known
+    let mut hashes = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..10 {
+        let block_hash = rng.gen();
+        hashes.push(block_hash);
+        if i % 2 == 1 {
+            let mut block = Block::default();
+            block.body.transactions.push(rng_transaction(&mut rng));
+            mock_provider.add_block(block_hash, block.clone());
+            expected.push(block.body);
+        }
+    }
+
+    let res = fetch0.get_block_bodies(hashes).await;
+    assert!(res.is_ok(), "{res:?}");
+    assert_eq!(res.unwrap().1, expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_receipts_omits_missing() {
+    reth_tracing::init_test_tracing();
+    let mut rng = rand::thread_rng();
+    let mock_provider = Arc::new(MockEthProvider::default());
+
+    let mut net = Testnet::create_with(2, mock_provider.clone()).await;
+
+    // install request handlers
+    net.for_each_mut(|peer| peer.install_request_handler());
+
+    let handle0 = net.peers()[0].handle();
+    let mut events0 = NetworkEventStream::new(handle0.event_listener());
+
+    let handle1 = net.peers()[1].handle();
+
+    let _handle = net.spawn();
+
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+    let connected = events0.next_session_established().await.unwrap();
+    assert_eq!(connected, *handle1.peer_id());
+
+    // only every other requested block is known, the store doesn't keep the blooms
+    let mut hashes = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..10 {
+        let block_hash = rng.gen();
+        hashes.push(block_hash);
+        if i % 2 == 1 {
+            let receipt = Receipt {
+                success: true,
+                cumulative_gas_used: rng.gen(),
+                logs: vec![Log::new_unchecked(rng.gen(), vec![rng.gen()], Bytes::new())],
+                ..Default::default()
+            };
+            mock_provider.add_receipts(block_hash, vec![receipt.clone()]);
+            expected.push(vec![receipt.with_bloom()]);
+        }
+    }
+
+    let (tx, rx) = oneshot::channel();
+    handle0.send_request(
+        *handle1.peer_id(),
+        PeerRequest::GetReceipts { request: GetReceipts(hashes), response: tx },
+    );
+    let res = rx.await.unwrap();
+    assert!(res.is_ok(), "{res:?}");
+    assert_eq!(res.unwrap().0, expected);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_get_header() {
     reth_tracing::init_test_tracing();
@@ -108,7 +202,7 @@ async fn test_get_header() {
     for idx in 0..100 {
         // Set a new random header to the mock storage and request it via the network
         let header = Header { number: start + idx, parent_hash: hash, ..Default::default() };
-        hash = rng.gen();
+        hash = header.hash_slow();
 
         mock_provider.add_header(hash, header.clone());
 
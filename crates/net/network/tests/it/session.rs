@@ -1,12 +1,14 @@
 //! Session tests
 
 use futures::StreamExt;
-use reth_eth_wire::EthVersion;
+use reth_chainspec::MAINNET;
+use reth_eth_wire::{DisconnectReason, EthVersion, Initiator};
 use reth_network::{
     test_utils::{PeerConfig, Testnet},
     NetworkEvent, NetworkEventListenerProvider,
 };
-use reth_network_api::{NetworkInfo, Peers};
+use reth_network_api::{Direction, NetworkInfo, PeerEvent, PeerId, Peers};
+use reth_primitives::{constants::RETH_CLIENT_VERSION, Head};
 use reth_provider::test_utils::NoopProvider;
 
 #[tokio::test(flavor = "multi_thread")]
@@ -43,6 +45,193 @@ async fn test_session_established_with_highest_version() {
     handle.terminate().await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_peer_info_reflects_session() {
+    reth_tracing::init_test_tracing();
+
+    let mut net = Testnet::create(1).await;
+
+    let p1 = PeerConfig::with_protocols(NoopProvider::default(), Some(EthVersion::Eth67.into()));
+    net.add_peer_with_config(p1).await.unwrap();
+
+    let mut handles = net.handles();
+    let handle0 = handles.next().unwrap();
+    let handle1 = handles.next().unwrap();
+    drop(handles);
+
+    let handle = net.spawn();
+
+    let mut events = handle0.event_listener().take(2);
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+
+    let mut established_status = None;
+    while let Some(event) = events.next().await {
+        if let NetworkEvent::SessionEstablished { status, .. } = event {
+            established_status = Some(status);
+        }
+    }
+    let status = established_status.expect("session established");
+
+    let info = handle0.get_peer_by_id(*handle1.peer_id()).await.unwrap().unwrap();
+    assert_eq!(info.remote_id, *handle1.peer_id());
+    assert_eq!(info.direction, Direction::Outgoing(*handle1.peer_id()));
+    assert_eq!(&*info.client_version, RETH_CLIENT_VERSION);
+    assert_eq!(info.eth_version, EthVersion::Eth67);
+    assert!(info.capabilities.supports_eth_v67());
+    assert!(!info.capabilities.supports_eth_v68());
+    assert_eq!(info.best_block, status.blockhash);
+    assert!(info.session_established.elapsed() < std::time::Duration::from_secs(60));
+
+    handle.terminate().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_peer_events_on_session_established_and_closed() {
+    reth_tracing::init_test_tracing();
+
+    let net = Testnet::create(2).await;
+
+    let mut handles = net.handles();
+    let handle0 = handles.next().unwrap();
+    let handle1 = handles.next().unwrap();
+    drop(handles);
+
+    let handle = net.spawn();
+
+    let mut events0 = handle0.peer_events();
+    let mut events1 = handle1.peer_events();
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+
+    let PeerEvent::SessionEstablished(info) = events0.next().await.unwrap() else {
+        panic!("expected established session")
+    };
+    assert_eq!(info.remote_id, *handle1.peer_id());
+    assert_eq!(info.direction, Direction::Outgoing(*handle1.peer_id()));
+    let PeerEvent::SessionEstablished(info) = events1.next().await.unwrap() else {
+        panic!("expected established session")
+    };
+    assert_eq!(info.remote_id, *handle0.peer_id());
+    assert_eq!(info.direction, Direction::Incoming);
+
+    handle0.disconnect_peer_with_reason(*handle1.peer_id(), DisconnectReason::TooManyPeers);
+
+    match events0.next().await.unwrap() {
+        PeerEvent::SessionClosed { peer_id, reason, initiator } => {
+            assert_eq!(peer_id, *handle1.peer_id());
+            assert_eq!(reason, Some(DisconnectReason::TooManyPeers));
+            assert_eq!(initiator, Initiator::Local);
+        }
+        ev => panic!("unexpected event: {ev:?}"),
+    }
+    match events1.next().await.unwrap() {
+        PeerEvent::SessionClosed { peer_id, reason, initiator } => {
+            assert_eq!(peer_id, *handle0.peer_id());
+            assert_eq!(reason, Some(DisconnectReason::TooManyPeers));
+            assert_eq!(initiator, Initiator::Remote);
+        }
+        ev => panic!("unexpected event: {ev:?}"),
+    }
+
+    handle.terminate().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_disconnect_active_peer() {
+    reth_tracing::init_test_tracing();
+
+    let net = Testnet::create(2).await;
+
+    let mut handles = net.handles();
+    let handle0 = handles.next().unwrap();
+    let handle1 = handles.next().unwrap();
+    drop(handles);
+
+    let handle = net.spawn();
+
+    let mut events0 = handle0.peer_events();
+    let mut events1 = handle1.peer_events();
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+
+    assert!(matches!(events0.next().await.unwrap(), PeerEvent::SessionEstablished(_)));
+    assert!(matches!(events1.next().await.unwrap(), PeerEvent::SessionEstablished(_)));
+
+    // an unknown peer isn't found
+    assert!(!handle0
+        .disconnect_active_peer(PeerId::random(), DisconnectReason::UselessPeer)
+        .await
+        .unwrap());
+
+    assert!(handle0
+        .disconnect_active_peer(*handle1.peer_id(), DisconnectReason::UselessPeer)
+        .await
+        .unwrap());
+
+    match events0.next().await.unwrap() {
+        PeerEvent::SessionClosed { peer_id, reason, initiator } => {
+            assert_eq!(peer_id, *handle1.peer_id());
+            assert_eq!(reason, Some(DisconnectReason::UselessPeer));
+            assert_eq!(initiator, Initiator::Local);
+        }
+        ev => panic!("unexpected event: {ev:?}"),
+    }
+    match events1.next().await.unwrap() {
+        PeerEvent::SessionClosed { peer_id, reason, initiator } => {
+            assert_eq!(peer_id, *handle0.peer_id());
+            assert_eq!(reason, Some(DisconnectReason::UselessPeer));
+            assert_eq!(initiator, Initiator::Remote);
+        }
+        ev => panic!("unexpected event: {ev:?}"),
+    }
+
+    // the session is gone
+    assert!(!handle0
+        .disconnect_active_peer(*handle1.peer_id(), DisconnectReason::UselessPeer)
+        .await
+        .unwrap());
+
+    handle.terminate().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_handshake_uses_updated_fork_id() {
+    reth_tracing::init_test_tracing();
+
+    let net = Testnet::create(2).await;
+
+    let mut handles = net.handles();
+    let handle0 = handles.next().unwrap();
+    let handle1 = handles.next().unwrap();
+    drop(handles);
+
+    let handle = net.spawn();
+
+    // advance past the homestead activation block
+    let head = Head { number: 1_150_000, ..Default::default() };
+    let fork_id = MAINNET.fork_id(&head);
+    assert_ne!(fork_id, MAINNET.fork_id(&Head::default()));
+    handle0.update_status(head);
+
+    let mut events = handle1.event_listener().take(2);
+    handle0.add_peer(*handle1.peer_id(), handle1.local_addr());
+
+    while let Some(event) = events.next().await {
+        match event {
+            NetworkEvent::PeerAdded(peer_id) => {
+                assert_eq!(handle0.peer_id(), &peer_id);
+            }
+            NetworkEvent::SessionEstablished { peer_id, status, .. } => {
+                assert_eq!(handle0.peer_id(), &peer_id);
+                assert_eq!(status.forkid, fork_id);
+            }
+            ev => {
+                panic!("unexpected event: {ev:?}")
+            }
+        }
+    }
+
+    handle.terminate().await;
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_session_established_with_different_capability() {
     reth_tracing::init_test_tracing();
@@ -8,6 +8,14 @@ pub trait DownloadClient: Send + Sync + Debug {
     /// that violates validation rules
     fn report_bad_message(&self, peer_id: PeerId);
 
+    /// Penalize the peer for an incorrect response to a request, e.g. headers that don't chain
+    /// or bodies that don't match their headers.
+    ///
+    /// By default, this is reported as a bad message.
+    fn report_bad_response(&self, peer_id: PeerId) {
+        self.report_bad_message(peer_id)
+    }
+
     /// Returns how many peers the network is currently connected to.
     fn num_connected_peers(&self) -> usize;
 }
@@ -1,10 +1,11 @@
-use std::ops::RangeInclusive;
+use std::{fmt, ops::RangeInclusive};
 
 use super::headers::client::HeadersRequest;
 use alloy_eips::BlockHashOrNumber;
 use alloy_primitives::{BlockNumber, B256};
 use derive_more::{Display, Error};
 use reth_consensus::ConsensusError;
+use reth_eth_wire_types::{DisconnectReason, Initiator};
 use reth_network_peers::WithPeerId;
 use reth_network_types::ReputationChangeKind;
 use reth_primitives::{GotExpected, GotExpectedBoxed, Header};
@@ -53,8 +54,10 @@ impl EthResponseValidator for RequestResult<Vec<Header>> {
     /// [`RequestError::ChannelClosed`] is not possible here since these errors are mapped to
     /// `ConnectionDropped`, which will be handled when the dropped connection is cleaned up.
     ///
-    /// [`RequestError::ConnectionDropped`] should be ignored here because this is already handled
-    /// when the dropped connection is handled.
+    /// [`RequestError::ChannelFull`] means the request was never sent to the peer.
+    ///
+    /// [`RequestError::ConnectionDropped`] and [`RequestError::Disconnected`] should be ignored
+    /// here because this is already handled when the dropped connection is handled.
     ///
     /// [`RequestError::UnsupportedCapability`] is not used yet because we only support active
     /// session for eth protocol.
@@ -62,7 +65,9 @@ impl EthResponseValidator for RequestResult<Vec<Header>> {
         if let Err(err) = self {
             match err {
                 RequestError::ChannelClosed |
+                RequestError::ChannelFull |
                 RequestError::ConnectionDropped |
+                RequestError::Disconnected(_) |
                 RequestError::UnsupportedCapability |
                 RequestError::BadResponse => None,
                 RequestError::Timeout => Some(ReputationChangeKind::Timeout),
@@ -82,6 +87,10 @@ pub enum RequestError {
     #[display("closed channel to the peer")]
     /// Indicates the channel to the peer is closed.
     ChannelClosed,
+    /// The channel to the peer is full.
+    #[display("channel to the peer is full")]
+    /// Indicates the peer has too many queued requests to accept another one.
+    ChannelFull,
     /// Connection to a peer dropped while handling the request.
     #[display("connection to a peer dropped while handling the request")]
     /// Represents a dropped connection while handling the request.
@@ -98,6 +107,9 @@ pub enum RequestError {
     #[display("received bad response")]
     /// Indicates a bad response was received.
     BadResponse,
+    /// The session ended before the peer responded to the request.
+    #[display("session ended before the peer responded to the request: {_0}")]
+    Disconnected(#[error(not(source))] SessionEnd),
 }
 
 // === impl RequestError ===
@@ -105,7 +117,26 @@ pub enum RequestError {
 impl RequestError {
     /// Indicates whether this error is retryable or fatal.
     pub const fn is_retryable(&self) -> bool {
-        matches!(self, Self::Timeout | Self::ConnectionDropped)
+        matches!(
+            self,
+            Self::Timeout | Self::ConnectionDropped | Self::ChannelFull | Self::Disconnected(_)
+        )
+    }
+
+    /// Returns the [`DisconnectReason`] if the session ended before the peer responded.
+    pub const fn as_disconnected(&self) -> Option<DisconnectReason> {
+        match self {
+            Self::Disconnected(end) => Some(end.reason),
+            _ => None,
+        }
+    }
+
+    /// Returns how the session ended if it ended before the peer responded.
+    pub const fn as_session_end(&self) -> Option<SessionEnd> {
+        match self {
+            Self::Disconnected(end) => Some(*end),
+            _ => None,
+        }
     }
 
     /// Whether the error happened because the channel was closed.
@@ -114,6 +145,41 @@ impl RequestError {
     }
 }
 
+/// How a session ended, attached to the requests that were still pending on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionEnd {
+    /// The side that disconnected.
+    pub initiator: Initiator,
+    /// The reason of the disconnect.
+    pub reason: DisconnectReason,
+}
+
+impl SessionEnd {
+    /// Creates a new [`SessionEnd`] for a disconnect by the given side.
+    pub const fn new(initiator: Initiator, reason: DisconnectReason) -> Self {
+        Self { initiator, reason }
+    }
+
+    /// Creates a new [`SessionEnd`] for a disconnect sent by the local node.
+    pub const fn local(reason: DisconnectReason) -> Self {
+        Self::new(Initiator::Local, reason)
+    }
+
+    /// Creates a new [`SessionEnd`] for a disconnect sent by the peer.
+    pub const fn remote(reason: DisconnectReason) -> Self {
+        Self::new(Initiator::Remote, reason)
+    }
+}
+
+impl fmt::Display for SessionEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.initiator {
+            Initiator::Local => write!(f, "disconnected the peer: {}", self.reason),
+            Initiator::Remote => write!(f, "peer disconnected us: {}", self.reason),
+        }
+    }
+}
+
 impl<T> From<mpsc::error::SendError<T>> for RequestError {
     fn from(_: mpsc::error::SendError<T>) -> Self {
         Self::ChannelClosed
@@ -126,6 +192,25 @@ impl From<oneshot::error::RecvError> for RequestError {
     }
 }
 
+/// Reasons why a headers response does not satisfy the originating [`HeadersRequest`].
+#[derive(Clone, Debug, PartialEq, Eq, Display, Error)]
+pub enum HeadersResponseError {
+    /// Received more headers than requested.
+    #[display("received more headers than requested: {_0}")]
+    TooManyHeaders(GotExpected<u64>),
+    /// The first header is not the requested start block.
+    #[display("headers response does not start at the requested block {_0:?}")]
+    WrongStart(#[error(not(source))] BlockHashOrNumber),
+    /// A header does not follow its predecessor in the requested direction.
+    #[display("header {number} does not follow header {parent} in the requested direction")]
+    WrongDirection {
+        /// Number of the preceding header.
+        parent: BlockNumber,
+        /// Number of the header.
+        number: BlockNumber,
+    },
+}
+
 /// The download result type
 pub type DownloadResult<T> = Result<T, DownloadError>;
 
@@ -230,4 +315,18 @@ mod tests {
         let headers: Vec<Header> = vec![];
         assert!(Ok(headers).is_likely_bad_headers_response(&request));
     }
+
+    #[test]
+    fn test_session_end_display() {
+        let err = RequestError::Disconnected(SessionEnd::remote(DisconnectReason::UselessPeer));
+        assert_eq!(
+            err.to_string(),
+            "session ended before the peer responded to the request: peer disconnected us: useless peer"
+        );
+        assert_eq!(err.as_disconnected(), Some(DisconnectReason::UselessPeer));
+        assert_eq!(
+            SessionEnd::local(DisconnectReason::TooManyPeers).to_string(),
+            "disconnected the peer: too many peers"
+        );
+    }
 }
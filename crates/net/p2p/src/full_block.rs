@@ -144,7 +144,7 @@ where
                 // ensure the block is valid, else retry
                 if let Err(err) = ensure_valid_body_response(&header, resp.data()) {
                     debug!(target: "downloaders", %err, hash=?header.hash(), "Received wrong body");
-                    self.client.report_bad_message(resp.peer_id());
+                    self.client.report_bad_response(resp.peer_id());
                     self.header = Some(header);
                     self.request.body = Some(self.client.get_block_body(self.hash));
                     return None
@@ -158,7 +158,7 @@ where
         if let Some(ref header) = self.header {
             if let Err(err) = ensure_valid_body_response(header, resp.data()) {
                 debug!(target: "downloaders", %err, hash=?header.hash(), "Received wrong body");
-                self.client.report_bad_message(resp.peer_id());
+                self.client.report_bad_response(resp.peer_id());
                 return
             }
             self.body = Some(BodyResponse::Validated(resp.into_data()));
@@ -197,7 +197,7 @@ where
                                 } else {
                                     debug!(target: "downloaders", expected=?this.hash, received=?header.hash(), "Received wrong header");
                                     // received a different header than requested
-                                    this.client.report_bad_message(peer)
+                                    this.client.report_bad_response(peer)
                                 }
                             }
                         }
@@ -453,7 +453,7 @@ where
                         // ensure the block is valid, else retry
                         if let Err(err) = ensure_valid_body_response(header, resp.data()) {
                             debug!(target: "downloaders", %err, hash=?header.hash(), "Received wrong body in range response");
-                            self.client.report_bad_message(resp.peer_id());
+                            self.client.report_bad_response(resp.peer_id());
 
                             // get body that doesn't match, put back into vecdeque, and retry it
                             self.pending_headers.push_back(header.clone());
@@ -513,7 +513,7 @@ where
                 // check if the downloaded headers are valid
                 if let Err(err) = self.consensus.validate_header_range(&headers_rising) {
                     debug!(target: "downloaders", %err, ?self.start_hash, "Received bad header response");
-                    self.client.report_bad_message(peer);
+                    self.client.report_bad_response(peer);
                 }
 
                 // get the bodies request so it can be polled later
@@ -532,7 +532,7 @@ where
                 self.headers = Some(headers_falling);
             } else {
                 // received a different header than requested
-                self.client.report_bad_message(peer);
+                self.client.report_bad_response(peer);
             }
         }
     }
@@ -1,8 +1,12 @@
-use crate::{download::DownloadClient, error::PeerRequestResult, priority::Priority};
+use crate::{
+    download::DownloadClient,
+    error::{HeadersResponseError, PeerRequestResult},
+    priority::Priority,
+};
 use alloy_eips::BlockHashOrNumber;
 use futures::{Future, FutureExt};
 pub use reth_eth_wire_types::{BlockHeaders, HeadersDirection};
-use reth_primitives::Header;
+use reth_primitives::{GotExpected, Header};
 use std::{
     fmt::Debug,
     pin::Pin,
@@ -21,6 +25,44 @@ pub struct HeadersRequest {
     pub direction: HeadersDirection,
 }
 
+impl HeadersRequest {
+    /// Validates that the headers of a response satisfy this request.
+    ///
+    /// A response may contain fewer headers than requested, but the headers must begin at the
+    /// requested start block, follow each other in the requested direction without gaps, and not
+    /// exceed the limit.
+    pub fn validate_response(&self, headers: &[Header]) -> Result<(), HeadersResponseError> {
+        if headers.len() as u64 > self.limit {
+            return Err(HeadersResponseError::TooManyHeaders(GotExpected {
+                got: headers.len() as u64,
+                expected: self.limit,
+            }))
+        }
+
+        let Some(first) = headers.first() else { return Ok(()) };
+        let is_start = match self.start {
+            BlockHashOrNumber::Number(number) => first.number == number,
+            BlockHashOrNumber::Hash(hash) => first.hash_slow() == hash,
+        };
+        if !is_start {
+            return Err(HeadersResponseError::WrongStart(self.start))
+        }
+
+        for pair in headers.windows(2) {
+            let (parent, number) = (pair[0].number, pair[1].number);
+            let expected = match self.direction {
+                HeadersDirection::Rising => parent.checked_add(1),
+                HeadersDirection::Falling => parent.checked_sub(1),
+            };
+            if expected != Some(number) {
+                return Err(HeadersResponseError::WrongDirection { parent, number })
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// The headers future type
 pub type HeadersFut = Pin<Box<dyn Future<Output = PeerRequestResult<Vec<Header>>> + Send + Sync>>;
 
@@ -85,3 +127,52 @@ where
         Poll::Ready(resp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(numbers: &[u64]) -> Vec<Header> {
+        numbers.iter().map(|&number| Header { number, ..Default::default() }).collect()
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let request =
+            HeadersRequest { start: 10u64.into(), limit: 3, direction: HeadersDirection::Falling };
+        assert_eq!(request.validate_response(&[]), Ok(()));
+        assert_eq!(request.validate_response(&headers(&[10, 9])), Ok(()));
+        assert_eq!(request.validate_response(&headers(&[10, 9, 8])), Ok(()));
+        assert_eq!(
+            request.validate_response(&headers(&[10, 9, 8, 7])),
+            Err(HeadersResponseError::TooManyHeaders(GotExpected { got: 4, expected: 3 }))
+        );
+        assert_eq!(
+            request.validate_response(&headers(&[9, 8])),
+            Err(HeadersResponseError::WrongStart(10u64.into()))
+        );
+        assert_eq!(
+            request.validate_response(&headers(&[10, 11])),
+            Err(HeadersResponseError::WrongDirection { parent: 10, number: 11 })
+        );
+        assert_eq!(
+            request.validate_response(&headers(&[10, 8])),
+            Err(HeadersResponseError::WrongDirection { parent: 10, number: 8 })
+        );
+    }
+
+    #[test]
+    fn test_validate_response_by_hash() {
+        let response = headers(&[10, 11]);
+        let request = HeadersRequest {
+            start: response[0].hash_slow().into(),
+            limit: 2,
+            direction: HeadersDirection::Rising,
+        };
+        assert_eq!(request.validate_response(&response), Ok(()));
+        assert_eq!(
+            request.validate_response(&headers(&[11, 12])),
+            Err(HeadersResponseError::WrongStart(request.start))
+        );
+    }
+}
@@ -23,7 +23,9 @@ use reth_network::{
                 DEFAULT_MAX_COUNT_CONCURRENT_REQUESTS_PER_PEER,
             },
             tx_manager::{
-                DEFAULT_MAX_COUNT_PENDING_POOL_IMPORTS, DEFAULT_MAX_COUNT_TRANSACTIONS_SEEN_BY_PEER,
+                DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND,
+                DEFAULT_MAX_COUNT_PENDING_POOL_IMPORTS,
+                DEFAULT_MAX_COUNT_TRANSACTIONS_SEEN_BY_PEER,
             },
         },
         TransactionFetcherConfig, TransactionsManagerConfig,
@@ -226,6 +228,10 @@ impl NetworkArgs {
                 self.max_capacity_cache_txns_pending_fetch,
             ),
             max_transactions_seen_by_peer_history: self.max_seen_tx_history,
+            max_get_pooled_transactions_requests_per_second:
+                DEFAULT_MAX_COUNT_GET_POOLED_TRANSACTIONS_REQUESTS_PER_SECOND,
+            propagation_mode: Default::default(),
+            tx_type_fetch_policy: Default::default(),
         };
 
         // Configure basic network stack
@@ -50,7 +50,7 @@ where
         client.get_headers_with_priority(request, Priority::High).await?.split();
 
     if response.len() != 1 {
-        client.report_bad_message(peer_id);
+        client.report_bad_response(peer_id);
         eyre::bail!("Invalid number of headers received. Expected: 1. Received: {}", response.len())
     }
 
@@ -64,7 +64,7 @@ where
     };
 
     if !valid {
-        client.report_bad_message(peer_id);
+        client.report_bad_response(peer_id);
         eyre::bail!(
             "Received invalid header. Received: {:?}. Expected: {:?}",
             header.num_hash(),
@@ -87,7 +87,7 @@ where
     let (peer_id, response) = client.get_block_body(header.hash()).await?.split();
 
     if response.is_none() {
-        client.report_bad_message(peer_id);
+        client.report_bad_response(peer_id);
         eyre::bail!("Invalid number of bodies received. Expected: 1. Received: 0")
     }
 
@@ -47,6 +47,8 @@ pub struct MockEthProvider {
     pub blocks: Arc<Mutex<HashMap<B256, Block>>>,
     /// Local header store
     pub headers: Arc<Mutex<HashMap<B256, Header>>>,
+    /// Local receipt store, keyed by block hash
+    pub receipts: Arc<Mutex<HashMap<B256, Vec<Receipt>>>>,
     /// Local account store
     pub accounts: Arc<Mutex<HashMap<Address, ExtendedAccount>>>,
     /// Local chain spec
@@ -60,6 +62,7 @@ impl Default for MockEthProvider {
         Self {
             blocks: Default::default(),
             headers: Default::default(),
+            receipts: Default::default(),
             accounts: Default::default(),
             chain_spec: Arc::new(reth_chainspec::ChainSpecBuilder::mainnet().build()),
             state_roots: Default::default(),
@@ -131,6 +134,11 @@ impl MockEthProvider {
         }
     }
 
+    /// Add the receipts of a block to local receipt store
+    pub fn add_receipts(&self, hash: B256, receipts: Vec<Receipt>) {
+        self.receipts.lock().insert(hash, receipts);
+    }
+
     /// Add account to local account store
     pub fn add_account(&self, address: Address, account: ExtendedAccount) {
         self.accounts.lock().insert(address, account);
@@ -390,8 +398,15 @@ impl ReceiptProvider for MockEthProvider {
         Ok(None)
     }
 
-    fn receipts_by_block(&self, _block: BlockHashOrNumber) -> ProviderResult<Option<Vec<Receipt>>> {
-        Ok(None)
+    fn receipts_by_block(&self, block: BlockHashOrNumber) -> ProviderResult<Option<Vec<Receipt>>> {
+        let hash = match block {
+            BlockHashOrNumber::Hash(hash) => hash,
+            BlockHashOrNumber::Number(number) => match self.block_hash(number)? {
+                Some(hash) => hash,
+                None => return Ok(None),
+            },
+        };
+        Ok(self.receipts.lock().get(&hash).cloned())
     }
 
     fn receipts_by_tx_range(
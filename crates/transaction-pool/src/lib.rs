@@ -420,6 +420,15 @@ where
         self.pool.get_pooled_transaction_elements(tx_hashes, limit)
     }
 
+    fn get_pooled_transaction_elements_with(
+        &self,
+        tx_hashes: Vec<TxHash>,
+        limit: GetPooledTransactionLimit,
+        blob_sidecars: BlobSidecarInclusion,
+    ) -> Vec<PooledTransactionsElement> {
+        self.pool.get_pooled_transaction_elements_with(tx_hashes, limit, blob_sidecars)
+    }
+
     fn get_pooled_transaction_element(&self, tx_hash: TxHash) -> Option<PooledTransactionsElement> {
         self.pool.get_pooled_transaction_element(tx_hash)
     }
@@ -81,6 +81,7 @@ use crate::{
     CanonicalStateUpdate, PoolConfig, TransactionOrdering, TransactionValidator,
 };
 use alloy_primitives::{Address, TxHash, B256};
+use alloy_rlp::Encodable;
 use best::BestTransactions;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 use reth_eth_wire_types::HandleMempoolData;
@@ -103,7 +104,9 @@ use crate::{
     blobstore::BlobStore,
     metrics::BlobStoreMetrics,
     pool::txpool::UpdateOutcome,
-    traits::{GetPooledTransactionLimit, NewBlobSidecar, TransactionListenerKind},
+    traits::{
+        BlobSidecarInclusion, GetPooledTransactionLimit, NewBlobSidecar, TransactionListenerKind,
+    },
     validate::ValidTransaction,
 };
 pub use best::BestTransactionFilter;
@@ -320,6 +323,21 @@ where
         tx_hashes: Vec<TxHash>,
         limit: GetPooledTransactionLimit,
     ) -> Vec<PooledTransactionsElement>
+    where
+        <V as TransactionValidator>::Transaction:
+            PoolTransaction<Consensus: Into<TransactionSignedEcRecovered>>,
+    {
+        self.get_pooled_transaction_elements_with(tx_hashes, limit, BlobSidecarInclusion::Include)
+    }
+
+    /// Returns converted [`PooledTransactionsElement`] for the given transaction hashes, blob
+    /// transactions are only included with their sidecar if requested.
+    pub(crate) fn get_pooled_transaction_elements_with(
+        &self,
+        tx_hashes: Vec<TxHash>,
+        limit: GetPooledTransactionLimit,
+        blob_sidecars: BlobSidecarInclusion,
+    ) -> Vec<PooledTransactionsElement>
     where
         <V as TransactionValidator>::Transaction:
             PoolTransaction<Consensus: Into<TransactionSignedEcRecovered>>,
@@ -328,14 +346,21 @@ where
         let mut elements = Vec::with_capacity(transactions.len());
         let mut size = 0;
         for transaction in transactions {
-            let encoded_len = transaction.encoded_length();
+            let mut encoded_len = transaction.encoded_length();
             let recovered: TransactionSignedEcRecovered =
                 transaction.transaction.clone().into_consensus().into();
             let tx = recovered.into_signed();
             let pooled = if tx.is_eip4844() {
+                if !blob_sidecars.is_include() {
+                    continue
+                }
                 // for EIP-4844 transactions, we need to fetch the blob sidecar from the blob store
                 if let Some(blob) = self.get_blob_transaction(tx) {
-                    PooledTransactionsElement::BlobTransaction(blob)
+                    let blob = PooledTransactionsElement::BlobTransaction(blob);
+                    // the pooled transaction may not account for the sidecar, which makes up
+                    // most of the response size
+                    encoded_len = encoded_len.max(blob.length());
+                    blob
                 } else {
                     continue
                 }
@@ -1163,9 +1188,16 @@ mod tests {
         blobstore::{BlobStore, InMemoryBlobStore},
         test_utils::{MockTransaction, TestPoolBuilder},
         validate::ValidTransaction,
-        BlockInfo, PoolConfig, SubPoolLimit, TransactionOrigin, TransactionValidationOutcome, U256,
+        BlobSidecarInclusion, BlockInfo, GetPooledTransactionLimit, PoolConfig, SubPoolLimit,
+        TransactionOrigin, TransactionValidationOutcome, U256,
+    };
+    use alloy_primitives::Address;
+    use alloy_rlp::Encodable;
+    use reth_primitives::{
+        kzg::{Blob, BYTES_PER_BLOB},
+        transaction::generate_blob_sidecar,
+        PooledTransactionsElement,
     };
-    use reth_primitives::{kzg::Blob, transaction::generate_blob_sidecar};
     use std::{fs, path::PathBuf};
 
     #[test]
@@ -1247,4 +1279,68 @@ mod tests {
         // Assert that the pool's blob store matches the expected blob store.
         assert_eq!(*test_pool.blob_store(), blob_store);
     }
+
+    #[test]
+    fn test_get_pooled_transaction_elements_blob_sidecars() {
+        let test_pool = &TestPoolBuilder::default().pool;
+        test_pool
+            .set_block_info(BlockInfo { pending_blob_fee: Some(10_000_000), ..Default::default() });
+
+        let sidecar = generate_blob_sidecar(vec![Blob::new([0; BYTES_PER_BLOB])]);
+        let blob_tx = MockTransaction::eip4844_with_sidecar(sidecar.clone());
+        let tx = MockTransaction::eip1559().with_sender(Address::random());
+        let hashes = vec![blob_tx.get_hash(), tx.get_hash()];
+
+        for (transaction, sidecar) in [(blob_tx.clone(), Some(sidecar)), (tx.clone(), None)] {
+            let transaction = match sidecar {
+                Some(sidecar) => ValidTransaction::ValidWithSidecar { transaction, sidecar },
+                None => ValidTransaction::Valid(transaction),
+            };
+            test_pool
+                .add_transaction(
+                    TransactionOrigin::External,
+                    TransactionValidationOutcome::Valid {
+                        balance: U256::from(1_000),
+                        state_nonce: 0,
+                        transaction,
+                        propagate: true,
+                    },
+                )
+                .unwrap();
+        }
+
+        // with sidecars the blob transaction is returned in its network form
+        let elements = test_pool.get_pooled_transaction_elements_with(
+            hashes.clone(),
+            GetPooledTransactionLimit::None,
+            BlobSidecarInclusion::Include,
+        );
+        assert_eq!(elements.len(), 2);
+        let PooledTransactionsElement::BlobTransaction(blob) = &elements[0] else {
+            panic!("expected blob transaction, got {:?}", elements[0])
+        };
+        assert_eq!(blob.hash, blob_tx.get_hash());
+        assert_eq!(*elements[1].hash(), tx.get_hash());
+
+        // the size of the sidecar counts against the limit, so the soft limit is exceeded by the
+        // blob transaction alone
+        let blob_len = elements[0].length();
+        assert!(blob_len > BYTES_PER_BLOB);
+        let elements = test_pool.get_pooled_transaction_elements_with(
+            hashes.clone(),
+            GetPooledTransactionLimit::ResponseSizeSoftLimit(blob_len - 1),
+            BlobSidecarInclusion::Include,
+        );
+        assert_eq!(elements.len(), 1);
+        assert_eq!(*elements[0].hash(), blob_tx.get_hash());
+
+        // without sidecars only the non-blob transaction is returned
+        let elements = test_pool.get_pooled_transaction_elements_with(
+            hashes,
+            GetPooledTransactionLimit::ResponseSizeSoftLimit(blob_len - 1),
+            BlobSidecarInclusion::Exclude,
+        );
+        assert_eq!(elements.len(), 1);
+        assert_eq!(*elements[0].hash(), tx.get_hash());
+    }
 }
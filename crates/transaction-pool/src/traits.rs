@@ -229,6 +229,29 @@ pub trait TransactionPool: Send + Sync + Clone {
         limit: GetPooledTransactionLimit,
     ) -> Vec<PooledTransactionsElement>;
 
+    /// Returns converted [`PooledTransactionsElement`] for the given transaction hashes, like
+    /// [`Self::get_pooled_transaction_elements`], but lets the caller choose whether blob
+    /// transactions are included.
+    ///
+    /// Blob transactions are only included with their sidecar, whose size is counted against the
+    /// `limit`. With [`BlobSidecarInclusion::Exclude`] blob transactions are skipped, for example
+    /// if only non-blob transactions are requested.
+    ///
+    /// By default this filters the result of [`Self::get_pooled_transaction_elements`], so skipped
+    /// blob transactions still count against the `limit`.
+    fn get_pooled_transaction_elements_with(
+        &self,
+        tx_hashes: Vec<TxHash>,
+        limit: GetPooledTransactionLimit,
+        blob_sidecars: BlobSidecarInclusion,
+    ) -> Vec<PooledTransactionsElement> {
+        let mut elements = self.get_pooled_transaction_elements(tx_hashes, limit);
+        if !blob_sidecars.is_include() {
+            elements.retain(|tx| !matches!(tx, PooledTransactionsElement::BlobTransaction(_)));
+        }
+        elements
+    }
+
     /// Returns converted [PooledTransactionsElement] for the given transaction hash.
     ///
     /// This adheres to the expected behavior of
@@ -1329,6 +1352,25 @@ impl GetPooledTransactionLimit {
     }
 }
 
+/// Whether blob transactions are included, with their sidecars, in
+/// [`TransactionPool::get_pooled_transaction_elements_with`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum BlobSidecarInclusion {
+    /// Include blob transactions with their sidecars.
+    #[default]
+    Include,
+    /// Skip blob transactions.
+    Exclude,
+}
+
+impl BlobSidecarInclusion {
+    /// Returns true if blob transactions should be included.
+    #[inline]
+    pub const fn is_include(&self) -> bool {
+        matches!(self, Self::Include)
+    }
+}
+
 /// A Stream that yields full transactions the subpool
 #[must_use = "streams do nothing unless polled"]
 #[derive(Debug)]